@@ -23,4 +23,23 @@ pub struct Cli {
 
 	#[arg(long, num_args = 1..)]
 	pub apply: Vec<String>,
+
+	#[arg(long)]
+	pub faststart: bool,
+
+	/// Write fragmented mp4, cutting fragments of about this many seconds.
+	#[arg(long)]
+	pub fragment: Option<f64>,
+
+	/// Write each mp4 fragment to its own file, `%d` being its number.
+	#[arg(long)]
+	pub segments: Option<String>,
+
+	/// Pad ts output with null packets to this many bits per second.
+	#[arg(long)]
+	pub muxrate: Option<u64>,
+
+	/// Seconds between ts program table repetitions.
+	#[arg(long)]
+	pub pat_period: Option<f64>,
 }
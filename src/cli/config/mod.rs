@@ -10,7 +10,7 @@ pub use track::Track;
 pub use audio::{AudioConfig, parse_audio};
 pub use subtitle::{SubtitleConfig, parse_subtitle};
 pub use transform::{TransformConfig, parse_transform};
-pub use video::{VideoConfig, parse_video};
+pub use video::{VideoConfig, parse_frame_rate, parse_video};
 
 pub fn parse_flags(tokens: Vec<String>, boolean_value: bool) -> FxHashMap<String, String> {
 	let mut map = FxHashMap::default();
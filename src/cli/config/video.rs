@@ -1,20 +1,36 @@
+use rustc_hash::FxHashMap;
+
 use super::track::parse_track_id;
 use crate::cli::config::parse_flags;
-use crate::message::Result;
+use crate::core::time::Time;
+use crate::{error, message::Result};
 
 #[derive(Debug, Default)]
 pub struct VideoConfig {
 	pub track: Option<usize>,
 	pub codec: Option<String>,
 	pub scale: Option<String>,
+	pub scaler: Option<String>,
 	pub width: Option<String>,
 	pub height: Option<String>,
 	pub fps: Option<String>,
+	pub fps_mode: Option<String>,
+	pub pix_fmt: Option<String>,
+	pub matrix: Option<String>,
+	pub range: Option<String>,
 	pub bitrate: Option<String>,
 	pub aspect_ratio: Option<String>,
+	pub crop: Option<String>,
+	pub pad: Option<String>,
+	pub fill: Option<String>,
 	pub rotate: Option<String>,
-	pub brightness: Option<String>,
-	pub contrast: Option<String>,
+	pub brightness: Option<f32>,
+	pub contrast: Option<f32>,
+	pub saturation: Option<f32>,
+	pub hue: Option<f32>,
+	pub gamma: Option<f32>,
+	pub lut: Option<String>,
+	pub lut_interp: Option<String>,
 }
 
 pub fn parse_video(tokens: Vec<String>) -> Result<VideoConfig> {
@@ -25,13 +41,66 @@ pub fn parse_video(tokens: Vec<String>) -> Result<VideoConfig> {
 		track,
 		codec: map.get("codec").cloned(),
 		scale: map.get("scale").cloned(),
+		scaler: map.get("scaler").cloned(),
 		width: map.get("width").cloned(),
 		height: map.get("height").cloned(),
 		fps: map.get("fps").cloned(),
+		fps_mode: map.get("fps_mode").cloned(),
+		pix_fmt: map.get("pix_fmt").cloned(),
+		matrix: map.get("matrix").cloned(),
+		range: map.get("range").cloned(),
 		bitrate: map.get("bitrate").cloned(),
 		aspect_ratio: map.get("aspect_ratio").cloned(),
+		crop: map.get("crop").cloned(),
+		pad: map.get("pad").cloned(),
+		fill: map.get("fill").cloned(),
 		rotate: map.get("rotate").cloned(),
-		brightness: map.get("brightness").cloned(),
-		contrast: map.get("contrast").cloned(),
+		brightness: parse_ranged(&map, "brightness", -1.0, 1.0)?,
+		contrast: parse_ranged(&map, "contrast", -1000.0, 1000.0)?,
+		saturation: parse_ranged(&map, "saturation", 0.0, 3.0)?,
+		hue: parse_ranged(&map, "hue", -360.0, 360.0)?,
+		gamma: parse_ranged(&map, "gamma", 0.1, 10.0)?,
+		lut: map.get("lut").cloned(),
+		lut_interp: map.get("lut_interp").cloned(),
 	})
 }
+
+fn parse_ranged(
+	map: &FxHashMap<String, String>,
+	key: &str,
+	min: f32,
+	max: f32,
+) -> Result<Option<f32>> {
+	let Some(value) = map.get(key) else {
+		return Ok(None);
+	};
+	match value.parse::<f32>() {
+		Ok(parsed) if (min..=max).contains(&parsed) => Ok(Some(parsed)),
+		_ => Err(error!("invalid {}: {} (expected {} to {})", key, value, min, max)),
+	}
+}
+
+/// Frame rate as `num/den`, a whole number or a decimal such as `29.97`.
+pub fn parse_frame_rate(value: &str) -> Result<(u32, u32)> {
+	let invalid = || error!("invalid frame rate: {}", value);
+	let (num, den) = match value.split_once('/') {
+		Some((num, den)) => {
+			(num.parse::<u32>().map_err(|_| invalid())?, den.parse::<u32>().map_err(|_| invalid())?)
+		}
+		None => match value.parse::<u32>() {
+			Ok(rate) => (rate, 1),
+			Err(_) => {
+				let rate = value.parse::<f64>().map_err(|_| invalid())?;
+				if !rate.is_finite() || rate <= 0.0 || rate > u32::MAX as f64 / 1000.0 {
+					return Err(invalid());
+				}
+				((rate * 1000.0).round() as u32, 1000)
+			}
+		},
+	};
+	if num == 0 || den == 0 {
+		return Err(invalid());
+	}
+	let rate = Time::new(num, den).simplify();
+	Ok((rate.num, rate.den))
+}
@@ -11,6 +11,9 @@ pub fn execute(cli: cli::Cli) -> message::Result<()> {
 	let subtitle = config::parse_subtitle(cli.subtitle)?;
 	let transform = config::parse_transform(cli.apply)?;
 	pipe.with_transform(transform);
+	pipe.with_faststart(cli.faststart);
+	pipe.with_fragments(cli.fragment, cli.segments);
+	pipe.with_ts_options(cli.muxrate, cli.pat_period);
 
 	let input_ext = utils::get_extension(&cli.input)?;
 	let output_ext = utils::get_extension(&cli.output)?;
@@ -20,14 +23,16 @@ pub fn execute(cli: cli::Cli) -> message::Result<()> {
 	compat.assert_container_supported(&output_ext)?;
 
 	if let Some(codec) = &audio.codec {
-		compat.assert_audio_supported(&input_ext, codec)?;
-		pipe.with_audio(audio);
+		compat.assert_audio_supported(&output_ext, codec)?;
 	}
+	// the dsd output rate applies without a codec too
+	pipe.with_audio(audio);
 
 	if let Some(codec) = &video.codec {
 		compat.assert_video_supported(&input_ext, codec)?;
-		pipe.with_video(video);
 	}
+	// geometry and pixel format options apply without a codec too
+	pipe.with_video(video);
 
 	if let Some(codec) = &subtitle.codec {
 		compat.assert_subtitle_supported(&input_ext, codec)?;
@@ -37,7 +42,18 @@ pub fn execute(cli: cli::Cli) -> message::Result<()> {
 	// Route based on output format first for clarity
 	match output_ext.as_str() {
 		container::WAV => pipeline::wav::run(pipe),
+		container::W64 => pipeline::w64::run(pipe),
+		container::CAF => pipeline::caf::run(pipe),
 		container::RAW | container::PCM => pipeline::raw::run(pipe),
+		container::Y4M => pipeline::y4m::run(pipe),
+		container::YUV | container::RGB => pipeline::rawvideo::run(pipe),
+		container::MKV => pipeline::mkv::run(pipe),
+		container::WEBM => pipeline::webm::run(pipe),
+		container::MP4 | container::MOV | container::M4A => pipeline::mp4::run(pipe),
+		container::TS => pipeline::ts::run(pipe),
+		container::AVI => pipeline::avi::run(pipe),
+		container::FLV => pipeline::flv::run(pipe),
+		container::NUT => pipeline::nut::run(pipe),
 		_ => {
 			// Fall back to input-based routing
 			match input_ext.as_str() {
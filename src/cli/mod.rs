@@ -6,3 +6,4 @@ pub mod pipeline;
 pub mod transcoder;
 mod utils;
 pub use args::Cli;
+pub use utils::STDIO_PATH;
@@ -0,0 +1,60 @@
+use super::common::Pipeline;
+use super::video::{VideoInput, VideoPlan};
+use crate::cli::utils;
+use crate::container::avi::{AviDemuxer, AviMuxer, AviStream};
+use crate::container::{self, wav};
+use crate::core::{Demuxer, Muxer};
+use crate::io::File;
+use crate::{error, message::Result};
+
+pub fn run(pipeline: Pipeline) -> Result<()> {
+	let input_extension = utils::get_extension(&pipeline.input)?;
+	match input_extension.as_str() {
+		container::AVI => {
+			let mut demuxer = AviDemuxer::new(File::open(&pipeline.input)?)?;
+			let streams = demuxer.avi_streams().to_vec();
+			let mut muxer = AviMuxer::new(File::create(&pipeline.output)?, streams)?;
+			copy(&mut demuxer, &mut muxer)
+		}
+		container::WAV => {
+			let mut demuxer = wav::WavDemuxer::new(File::open(&pipeline.input)?)?;
+			let format = demuxer.format();
+			let codec = format.to_codec_string();
+			let stream = AviStream::audio(codec, format.channels.count() as u16, format.sample_rate)?;
+			let mut muxer = AviMuxer::new(File::create(&pipeline.output)?, vec![stream])?;
+			copy(&mut demuxer, &mut muxer)
+		}
+		container::Y4M | container::YUV | container::RGB => raw_video(pipeline, &input_extension),
+		_ => Err(error!("'{}' cannot be muxed into avi", input_extension)),
+	}
+}
+
+fn copy(demuxer: &mut impl Demuxer, muxer: &mut impl Muxer) -> Result<()> {
+	while let Some(packet) = demuxer.read_packet()? {
+		muxer.write(packet)?;
+	}
+	muxer.finalize()
+}
+
+/// Uncompressed pictures through the video plan, stored as planar YUV.
+fn raw_video(pipeline: Pipeline, input_extension: &str) -> Result<()> {
+	let mut input = VideoInput::open(&pipeline, input_extension)?;
+	let source = input.format;
+	let chroma_location = input.chroma_location();
+	let plan = VideoPlan::new(&pipeline, input_extension, container::AVI, source, chroma_location)?;
+	let target = plan.target;
+
+	let stream = AviStream::raw_video(target.format, target.width, target.height, target.time())?;
+	let mut muxer = AviMuxer::new(File::create(&pipeline.output)?, vec![stream])?;
+	let mut transcoder = plan.into_transcoder(source);
+
+	while let Some(packet) = input.demuxer.read_packet()? {
+		for output_packet in transcoder.transcode(packet)? {
+			muxer.write(output_packet)?;
+		}
+	}
+	for packet in transcoder.flush()? {
+		muxer.write(packet)?;
+	}
+	muxer.finalize()
+}
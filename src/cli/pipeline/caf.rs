@@ -0,0 +1,75 @@
+use super::common::Pipeline;
+use crate::cli::transcoder::media;
+use crate::cli::utils;
+use crate::codecs::audio::pcm::{PcmDecoder, PcmEncoder};
+use crate::container::{self, caf, raw, wav};
+use crate::core::{Demuxer, Muxer};
+use crate::io::{Error, File};
+use crate::{error, message::Result};
+
+pub fn run(pipeline: Pipeline) -> Result<()> {
+	let input_extension = utils::get_extension(&pipeline.input)?;
+	let mut format = caf::CafFormat::default();
+	let mut metadata = None;
+
+	if input_extension == container::CAF {
+		let file = File::open(&pipeline.input)?;
+		let demuxer = caf::CafDemuxer::new(file)?;
+		format = demuxer.format();
+		metadata = Some(demuxer.metadata().clone());
+	} else if input_extension == container::WAV {
+		let file = File::open(&pipeline.input)?;
+		let demuxer = wav::WavDemuxer::new(file)?;
+		format = caf::CafFormat::from_raw_format(demuxer.format().to_raw_format());
+	}
+
+	if !format.is_pcm() {
+		return Err(error!("caf codec '{}' cannot be decoded", format.to_codec_string()));
+	}
+
+	let mut target_format = format;
+	if let Some(codec) = &pipeline.audio.codec {
+		target_format.apply_codec(codec).map_err(Error::invalid_data)?;
+	}
+
+	let output_file = File::create(&pipeline.output)?;
+	let mut muxer = caf::CafMuxer::new(output_file, target_format)?;
+	muxer.with_metadata(metadata);
+
+	let mut demuxer = create_demuxer(&pipeline.input, &input_extension, format)?;
+	let mut transcoder = create_transcoder(format, target_format);
+
+	while let Some(packet) = demuxer.read_packet()? {
+		for output_packet in transcoder.transcode(packet)? {
+			muxer.write(output_packet)?;
+		}
+	}
+
+	for packet in transcoder.flush()? {
+		muxer.write(packet)?;
+	}
+
+	muxer.finalize()
+}
+
+fn create_demuxer(path: &str, extension: &str, format: caf::CafFormat) -> Result<Box<dyn Demuxer>> {
+	let file = File::open(path)?;
+	match extension {
+		container::CAF => Ok(Box::new(caf::CafDemuxer::new(file)?)),
+		container::WAV => Ok(Box::new(wav::WavDemuxer::new(file)?)),
+		_ => Ok(Box::new(raw::RawPcmDemuxer::new(file, format.to_raw_format())?)),
+	}
+}
+
+fn create_transcoder(format: caf::CafFormat, target_format: caf::CafFormat) -> media::Transcoder {
+	let decoder = PcmDecoder::new(format.sample_rate, format.channels, format.bytes_per_sample());
+
+	if format.audio_format() != target_format.audio_format() {
+		let encoder = PcmEncoder::new(target_format.sample_rate);
+		let encoder = encoder.with_target_format(target_format.audio_format());
+		return media::Transcoder::new(Box::new(decoder), Box::new(encoder));
+	}
+
+	let encoder = PcmEncoder::new(target_format.sample_rate);
+	media::Transcoder::new(Box::new(decoder), Box::new(encoder))
+}
@@ -8,6 +8,16 @@ pub struct Pipeline {
 	pub video: config::VideoConfig,
 	pub subtitle: config::SubtitleConfig,
 	pub transform: config::TransformConfig,
+	/// Place the mp4 movie header before the media data.
+	pub faststart: bool,
+	/// Target fragment length in seconds for fragmented mp4.
+	pub fragment: Option<f64>,
+	/// File name pattern for fragments written as separate segments.
+	pub segments: Option<String>,
+	/// Constant ts mux rate in bits per second.
+	pub mux_rate: Option<u64>,
+	/// Seconds between ts PAT/PMT repetitions.
+	pub psi_interval: Option<f64>,
 }
 
 impl Pipeline {
@@ -30,4 +40,18 @@ impl Pipeline {
 	pub fn with_transform(&mut self, transform: config::TransformConfig) {
 		self.transform = transform;
 	}
+
+	pub fn with_faststart(&mut self, faststart: bool) {
+		self.faststart = faststart;
+	}
+
+	pub fn with_fragments(&mut self, fragment: Option<f64>, segments: Option<String>) {
+		self.fragment = fragment;
+		self.segments = segments;
+	}
+
+	pub fn with_ts_options(&mut self, mux_rate: Option<u64>, psi_interval: Option<f64>) {
+		self.mux_rate = mux_rate;
+		self.psi_interval = psi_interval;
+	}
 }
@@ -0,0 +1,45 @@
+use super::common::Pipeline;
+use crate::cli::utils;
+use crate::container::flv::{FlvDemuxer, FlvMuxer};
+use crate::container::mkv::MkvDemuxer;
+use crate::container::mp4::Mp4Demuxer;
+use crate::container::{self, Metadata};
+use crate::core::{Demuxer, Muxer};
+use crate::io::File;
+use crate::{error, message::Result};
+
+pub fn run(pipeline: Pipeline) -> Result<()> {
+	let input_extension = utils::get_extension(&pipeline.input)?;
+	let input = File::open(&pipeline.input)?;
+	match input_extension.as_str() {
+		container::FLV => {
+			let mut demuxer = FlvDemuxer::new(input)?;
+			let metadata = demuxer.metadata().clone();
+			remux(&pipeline, metadata, &mut demuxer)
+		}
+		container::MP4 | container::MOV | container::M4A => {
+			let mut demuxer = Mp4Demuxer::new(input)?;
+			let metadata = demuxer.metadata().clone();
+			remux(&pipeline, metadata, &mut demuxer)
+		}
+		container::MKV | container::WEBM => {
+			let mut demuxer = MkvDemuxer::new(input)?;
+			demuxer.load_seek_head()?;
+			let metadata = demuxer.metadata().clone();
+			remux(&pipeline, metadata, &mut demuxer)
+		}
+		_ => Err(error!("'{}' cannot be muxed into flv", input_extension)),
+	}
+}
+
+/// Copy every stream's packets; AVC and AAC setup comes from codec private data.
+fn remux(pipeline: &Pipeline, metadata: Metadata, demuxer: &mut impl Demuxer) -> Result<()> {
+	let streams = demuxer.streams().all().to_vec();
+	let mut muxer = FlvMuxer::new(File::create(&pipeline.output)?, &streams)?;
+	muxer.with_metadata(Some(metadata));
+
+	while let Some(packet) = demuxer.read_packet()? {
+		muxer.write(packet)?;
+	}
+	muxer.finalize()
+}
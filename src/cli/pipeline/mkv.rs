@@ -0,0 +1,132 @@
+use super::common::Pipeline;
+use crate::cli::transcoder::media;
+use crate::cli::utils;
+use crate::codecs::audio::pcm::{PcmDecoder, PcmEncoder};
+use crate::container::mkv::{MkvAudio, MkvDemuxer, MkvMuxer, MkvProfile, MkvTrack};
+use crate::container::nut::NutDemuxer;
+use crate::container::{self, Metadata, wav};
+use crate::core::Muxer;
+use crate::core::stream::StreamKind;
+use crate::io::{Error, File};
+use crate::{error, message::Result};
+
+pub fn run(pipeline: Pipeline) -> Result<()> {
+	run_with_profile(pipeline, MkvProfile::Matroska)
+}
+
+pub fn run_with_profile(pipeline: Pipeline, profile: MkvProfile) -> Result<()> {
+	let input_extension = utils::get_extension(&pipeline.input)?;
+	match input_extension.as_str() {
+		container::MKV | container::WEBM => remux(pipeline, profile),
+		container::WAV => from_wav(pipeline, profile),
+		container::NUT => from_nut(pipeline, profile),
+		_ => Err(error!("'{}' cannot be muxed into {}", input_extension, profile.doc_type())),
+	}
+}
+
+fn remux(pipeline: Pipeline, profile: MkvProfile) -> Result<()> {
+	let mut demuxer = MkvDemuxer::new(File::open(&pipeline.input)?)?;
+	demuxer.load_seek_head()?;
+
+	let output_file = File::create(&pipeline.output)?;
+	let mut muxer = MkvMuxer::new(output_file, demuxer.tracks().to_vec(), profile)?;
+	muxer.with_metadata(Some(demuxer.metadata().clone()));
+
+	while let Some(packet) = demuxer.read_packet()? {
+		muxer.write(packet)?;
+	}
+	muxer.finalize()
+}
+
+/// PCM and text subtitle streams of a nut input, which may be standard input.
+fn from_nut(pipeline: Pipeline, profile: MkvProfile) -> Result<()> {
+	let mut demuxer = NutDemuxer::new(utils::open_input(&pipeline.input)?)?;
+	let mut tracks = Vec::new();
+	let mut ids = Vec::new();
+	for stream in demuxer.nut_streams() {
+		let Some(kind) = stream.kind() else {
+			continue;
+		};
+		let number = tracks.len() as u64 + 1;
+		let mut track = MkvTrack::for_codec(number, kind, stream.codec(), profile)?;
+		match (kind, stream.audio) {
+			(StreamKind::Audio, Some(audio)) => {
+				track = track.with_audio(MkvAudio {
+					sample_rate: audio.sample_rate() as f64,
+					channels: audio.channels as u8,
+					..Default::default()
+				});
+			}
+			(StreamKind::Subtitle, _) => track = track.with_codec_private(stream.codec_private.clone()),
+			_ => {
+				return Err(error!("nut stream {} cannot be muxed into {}", stream.id, profile.doc_type()));
+			}
+		}
+		tracks.push(track);
+		ids.push(stream.id);
+	}
+
+	let output_file = File::create(&pipeline.output)?;
+	let mut muxer = MkvMuxer::new(output_file, tracks, profile)?;
+	muxer.with_metadata(Some(demuxer.metadata().clone()));
+
+	while let Some(mut packet) = demuxer.read_packet()? {
+		let Some(index) = ids.iter().position(|id| *id == packet.stream_id) else {
+			continue;
+		};
+		packet.stream_id = index as u32;
+		muxer.write(packet)?;
+	}
+	muxer.finalize()
+}
+
+fn from_wav(pipeline: Pipeline, profile: MkvProfile) -> Result<()> {
+	let mut demuxer = wav::WavDemuxer::new(File::open(&pipeline.input)?)?;
+	let format = demuxer.format();
+	let metadata = to_mkv_metadata(demuxer.metadata());
+
+	let mut target_format = format;
+	if let Some(codec) = &pipeline.audio.codec {
+		target_format.apply_codec(codec).map_err(Error::invalid_data)?;
+	}
+
+	let codec = target_format.to_codec_string();
+	let audio = MkvAudio {
+		sample_rate: target_format.sample_rate as f64,
+		channels: target_format.channels.count(),
+		bit_depth: Some(target_format.bit_depth),
+		..Default::default()
+	};
+	let track = MkvTrack::for_codec(1, StreamKind::Audio, codec, profile)?.with_audio(audio);
+
+	let output_file = File::create(&pipeline.output)?;
+	let mut muxer = MkvMuxer::new(output_file, vec![track], profile)?;
+	muxer.with_metadata(Some(metadata));
+
+	let decoder = PcmDecoder::new(format.sample_rate, format.channels, format.bytes_per_sample());
+	let mut encoder = PcmEncoder::new(target_format.sample_rate);
+	if format.audio_format() != target_format.audio_format() {
+		encoder = encoder.with_target_format(target_format.audio_format());
+	}
+	let mut transcoder = media::Transcoder::new(Box::new(decoder), Box::new(encoder));
+
+	while let Some(packet) = demuxer.read_packet()? {
+		for output_packet in transcoder.transcode(packet)? {
+			muxer.write(output_packet)?;
+		}
+	}
+	for packet in transcoder.flush()? {
+		muxer.write(packet)?;
+	}
+	muxer.finalize()
+}
+
+fn to_mkv_metadata(metadata: &wav::WavMetadata) -> Metadata {
+	let mut fields: Vec<_> = metadata.all_fields().iter().collect();
+	fields.sort();
+	let mut mkv_metadata = Metadata::new();
+	for (key, value) in fields {
+		mkv_metadata.set(key, value.clone());
+	}
+	mkv_metadata
+}
@@ -1,7 +1,17 @@
 pub mod aac;
+pub mod avi;
+pub mod caf;
 mod common;
-// pub mod mkv;
+pub mod flv;
+pub mod mkv;
+pub mod mp4;
+pub mod nut;
 pub mod raw;
+pub mod rawvideo;
+pub mod ts;
+mod video;
+pub mod w64;
 pub mod wav;
 pub mod webm;
+pub mod y4m;
 pub use common::Pipeline;
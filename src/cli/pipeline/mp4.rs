@@ -0,0 +1,92 @@
+use super::common::Pipeline;
+use crate::cli::utils;
+use crate::container;
+use crate::container::Metadata;
+use crate::container::mkv::{MkvDemuxer, MkvTrack};
+use crate::container::mp4::{CmafMuxer, Mp4Demuxer, Mp4Muxer, Mp4Profile, Mp4Track, cmaf};
+use crate::core::time::Time;
+use crate::core::{Demuxer, Muxer};
+use crate::io::File;
+use crate::{error, message::Result};
+
+pub fn run(pipeline: Pipeline) -> Result<()> {
+	let input_extension = utils::get_extension(&pipeline.input)?;
+	let output_extension = utils::get_extension(&pipeline.output)?;
+	let profile = Mp4Profile::from_extension(&output_extension);
+	match input_extension.as_str() {
+		container::MP4 | container::MOV | container::M4A => remux(pipeline, profile),
+		container::MKV | container::WEBM => from_mkv(pipeline, profile),
+		_ => Err(error!("'{}' cannot be muxed into {}", input_extension, profile.extension())),
+	}
+}
+
+/// Copy every track's packets, keeping codec setup and metadata.
+fn remux(pipeline: Pipeline, profile: Mp4Profile) -> Result<()> {
+	let mut demuxer = Mp4Demuxer::new(File::open(&pipeline.input)?)?;
+
+	let tracks = demuxer.tracks().to_vec();
+	let metadata = demuxer.metadata().clone();
+	mux(&pipeline, profile, tracks, metadata, &mut demuxer)
+}
+
+fn from_mkv(pipeline: Pipeline, profile: Mp4Profile) -> Result<()> {
+	let mut demuxer = MkvDemuxer::new(File::open(&pipeline.input)?)?;
+	demuxer.load_seek_head()?;
+
+	let time = demuxer.time();
+	let mut tracks = Vec::new();
+	for (index, track) in demuxer.tracks().iter().enumerate() {
+		tracks.push(to_mp4_track(index as u32 + 1, track, time)?);
+	}
+
+	let metadata = demuxer.metadata().clone();
+	mux(&pipeline, profile, tracks, metadata, &mut demuxer)
+}
+
+/// Plain mp4, or fragmented when a fragment length or segment files are asked for.
+fn mux(
+	pipeline: &Pipeline,
+	profile: Mp4Profile,
+	tracks: Vec<Mp4Track>,
+	metadata: Metadata,
+	demuxer: &mut impl Demuxer,
+) -> Result<()> {
+	let output_file = File::create(&pipeline.output)?;
+	if pipeline.fragment.is_none() && pipeline.segments.is_none() {
+		let mut muxer = Mp4Muxer::new(output_file, tracks, profile)?;
+		muxer.with_metadata(Some(metadata));
+		muxer.with_faststart(pipeline.faststart);
+		return copy(demuxer, &mut muxer);
+	}
+
+	let duration = pipeline.fragment.unwrap_or(cmaf::DEFAULT_FRAGMENT_DURATION);
+	let mut muxer = CmafMuxer::new(output_file, tracks, profile, duration)?;
+	muxer.with_metadata(Some(metadata));
+	if let Some(pattern) = &pipeline.segments {
+		muxer.with_segment_files(pattern)?;
+	}
+	copy(demuxer, &mut muxer)
+}
+
+/// Matroska codec private data already has the layout mp4 config boxes use.
+fn to_mp4_track(id: u32, track: &MkvTrack, time: Time) -> Result<Mp4Track> {
+	let timescale = (time.den / time.num).max(1);
+	let mut mp4_track = Mp4Track::for_codec(id, track.kind, &track.codec_name(), timescale)?
+		.with_codec_private(track.codec_private.clone())
+		.with_language(&track.language);
+	if let Some(video) = &track.video {
+		mp4_track = mp4_track.with_video(video.pixel_width as u16, video.pixel_height as u16);
+	}
+	if let Some(audio) = &track.audio {
+		let sample_size = audio.bit_depth.unwrap_or(16);
+		mp4_track = mp4_track.with_audio(audio.sample_rate, audio.channels as u16, sample_size);
+	}
+	Ok(mp4_track)
+}
+
+fn copy(demuxer: &mut impl Demuxer, muxer: &mut impl Muxer) -> Result<()> {
+	while let Some(packet) = demuxer.read_packet()? {
+		muxer.write(packet)?;
+	}
+	muxer.finalize()
+}
@@ -0,0 +1,135 @@
+use super::common::Pipeline;
+use super::video::{VideoInput, VideoPlan};
+use crate::cli::transcoder::media;
+use crate::cli::utils;
+use crate::codecs::audio::pcm::{PcmDecoder, PcmEncoder};
+use crate::container::mkv::MkvDemuxer;
+use crate::container::nut::{NutDemuxer, NutMuxer, NutStream};
+use crate::container::{self, Metadata, wav};
+use crate::core::stream::StreamKind;
+use crate::core::{Demuxer, Muxer};
+use crate::io::{Error, File};
+use crate::{error, message::Result};
+
+pub fn run(pipeline: Pipeline) -> Result<()> {
+	let input_extension = utils::get_extension(&pipeline.input)?;
+	match input_extension.as_str() {
+		container::NUT => remux(pipeline),
+		container::WAV => from_wav(pipeline),
+		container::MKV | container::WEBM => from_mkv(pipeline),
+		container::Y4M | container::YUV | container::RGB => raw_video(pipeline, &input_extension),
+		_ => Err(error!("'{}' cannot be muxed into nut", input_extension)),
+	}
+}
+
+fn copy(demuxer: &mut impl Demuxer, muxer: &mut impl Muxer) -> Result<()> {
+	while let Some(packet) = demuxer.read_packet()? {
+		muxer.write(packet)?;
+	}
+	muxer.finalize()
+}
+
+/// Every stream and the global information, user data streams dropped.
+fn remux(pipeline: Pipeline) -> Result<()> {
+	let mut demuxer = NutDemuxer::new(utils::open_input(&pipeline.input)?)?;
+	let streams: Vec<NutStream> =
+		demuxer.nut_streams().iter().filter(|stream| stream.kind().is_some()).cloned().collect();
+	let ids: Vec<u32> = streams.iter().map(|stream| stream.id).collect();
+	let mut muxer = NutMuxer::new(utils::create_output(&pipeline.output)?, streams)?;
+	muxer.with_metadata(Some(demuxer.metadata().clone()));
+
+	// the muxer numbers the streams it keeps from zero
+	while let Some(mut packet) = demuxer.read_packet()? {
+		let Some(index) = ids.iter().position(|id| *id == packet.stream_id) else {
+			continue;
+		};
+		packet.stream_id = index as u32;
+		muxer.write(packet)?;
+	}
+	muxer.finalize()
+}
+
+fn from_wav(pipeline: Pipeline) -> Result<()> {
+	let mut demuxer = wav::WavDemuxer::new(File::open(&pipeline.input)?)?;
+	let format = demuxer.format();
+	let mut fields: Vec<_> = demuxer.metadata().all_fields().iter().collect();
+	fields.sort();
+	let mut metadata = Metadata::new();
+	for (key, value) in fields {
+		metadata.set(key, value.clone());
+	}
+
+	let mut target_format = format;
+	if let Some(codec) = &pipeline.audio.codec {
+		target_format.apply_codec(codec).map_err(Error::invalid_data)?;
+	}
+	let codec = target_format.to_codec_string();
+	let stream = NutStream::audio(codec, target_format.channels.count(), target_format.sample_rate)?;
+	let mut muxer = NutMuxer::new(utils::create_output(&pipeline.output)?, vec![stream])?;
+	muxer.with_metadata(Some(metadata));
+
+	if format.audio_format() == target_format.audio_format() {
+		return copy(&mut demuxer, &mut muxer);
+	}
+	let decoder = PcmDecoder::new(format.sample_rate, format.channels, format.bytes_per_sample());
+	let encoder =
+		PcmEncoder::new(target_format.sample_rate).with_target_format(target_format.audio_format());
+	let mut transcoder = media::Transcoder::new(Box::new(decoder), Box::new(encoder));
+
+	while let Some(packet) = demuxer.read_packet()? {
+		for output_packet in transcoder.transcode(packet)? {
+			muxer.write(output_packet)?;
+		}
+	}
+	for packet in transcoder.flush()? {
+		muxer.write(packet)?;
+	}
+	muxer.finalize()
+}
+
+/// PCM and text subtitle tracks, copied; compressed tracks have no nut
+/// codec here.
+fn from_mkv(pipeline: Pipeline) -> Result<()> {
+	let mut demuxer = MkvDemuxer::new(File::open(&pipeline.input)?)?;
+	demuxer.load_seek_head()?;
+	let mut streams = Vec::new();
+	for track in demuxer.tracks() {
+		let codec = track.codec_name();
+		let stream = match (track.kind, track.audio) {
+			(StreamKind::Audio, Some(audio)) => {
+				NutStream::audio(&codec, audio.channels, audio.sample_rate as u32)?
+			}
+			(StreamKind::Subtitle, _) => {
+				NutStream::subtitle(&codec, demuxer.time(), track.codec_private.clone())?
+			}
+			_ => return Err(error!("mkv track {} ('{}') cannot be muxed into nut", track.number, codec)),
+		};
+		streams.push(stream);
+	}
+	let mut muxer = NutMuxer::new(utils::create_output(&pipeline.output)?, streams)?;
+	muxer.with_metadata(Some(demuxer.metadata().clone()));
+	copy(&mut demuxer, &mut muxer)
+}
+
+/// Uncompressed pictures through the video plan.
+fn raw_video(pipeline: Pipeline, input_extension: &str) -> Result<()> {
+	let mut input = VideoInput::open(&pipeline, input_extension)?;
+	let source = input.format;
+	let chroma_location = input.chroma_location();
+	let plan = VideoPlan::new(&pipeline, input_extension, container::NUT, source, chroma_location)?;
+	let target = plan.target;
+
+	let stream = NutStream::raw_video(target.format, target.width, target.height, target.time())?;
+	let mut muxer = NutMuxer::new(utils::create_output(&pipeline.output)?, vec![stream])?;
+	let mut transcoder = plan.into_transcoder(source);
+
+	while let Some(packet) = input.demuxer.read_packet()? {
+		for output_packet in transcoder.transcode(packet)? {
+			muxer.write(output_packet)?;
+		}
+	}
+	for packet in transcoder.flush()? {
+		muxer.write(packet)?;
+	}
+	muxer.finalize()
+}
@@ -0,0 +1,35 @@
+use super::common::Pipeline;
+use super::video::{VideoInput, VideoPlan};
+use crate::cli::utils;
+use crate::container::rawvideo;
+use crate::core::Muxer;
+use crate::io::File;
+use crate::message::Result;
+
+pub fn run(pipeline: Pipeline) -> Result<()> {
+	let input_extension = utils::get_extension(&pipeline.input)?;
+	let output_extension = utils::get_extension(&pipeline.output)?;
+
+	let mut input = VideoInput::open(&pipeline, &input_extension)?;
+	let source = input.format;
+	let chroma_location = input.chroma_location();
+	let plan =
+		VideoPlan::new(&pipeline, &input_extension, &output_extension, source, chroma_location)?;
+
+	let output_file = File::create(&pipeline.output)?;
+	let mut muxer = rawvideo::RawVideoMuxer::new(output_file, plan.target)?;
+
+	let mut transcoder = plan.into_transcoder(source);
+
+	while let Some(packet) = input.demuxer.read_packet()? {
+		for output_packet in transcoder.transcode(packet)? {
+			muxer.write(output_packet)?;
+		}
+	}
+
+	for packet in transcoder.flush()? {
+		muxer.write(packet)?;
+	}
+
+	muxer.finalize()
+}
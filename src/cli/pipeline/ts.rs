@@ -0,0 +1,42 @@
+use super::common::Pipeline;
+use crate::cli::utils;
+use crate::container;
+use crate::container::mkv::MkvDemuxer;
+use crate::container::mp4::Mp4Demuxer;
+use crate::container::ts::{TsDemuxer, TsMuxer};
+use crate::core::{Demuxer, Muxer};
+use crate::io::File;
+use crate::{error, message::Result};
+
+pub fn run(pipeline: Pipeline) -> Result<()> {
+	let input_extension = utils::get_extension(&pipeline.input)?;
+	let input = File::open(&pipeline.input)?;
+	match input_extension.as_str() {
+		container::TS | container::M2TS | container::MTS => {
+			remux(&pipeline, &mut TsDemuxer::new(input)?)
+		}
+		container::MP4 | container::MOV | container::M4A => {
+			remux(&pipeline, &mut Mp4Demuxer::new(input)?)
+		}
+		container::MKV | container::WEBM => {
+			let mut demuxer = MkvDemuxer::new(input)?;
+			demuxer.load_seek_head()?;
+			remux(&pipeline, &mut demuxer)
+		}
+		_ => Err(error!("'{}' cannot be muxed into ts", input_extension)),
+	}
+}
+
+fn remux(pipeline: &Pipeline, demuxer: &mut impl Demuxer) -> Result<()> {
+	let streams = demuxer.streams().all().to_vec();
+	let mut muxer = TsMuxer::new(File::create(&pipeline.output)?, &streams)?;
+	if let Some(interval) = pipeline.psi_interval {
+		muxer.with_psi_interval(interval)?;
+	}
+	muxer.with_mux_rate(pipeline.mux_rate)?;
+
+	while let Some(packet) = demuxer.read_packet()? {
+		muxer.write(packet)?;
+	}
+	muxer.finalize()
+}
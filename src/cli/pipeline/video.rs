@@ -0,0 +1,513 @@
+use super::common::Pipeline;
+use crate::cli::config::{self, VideoConfig};
+use crate::cli::transcoder::media;
+use crate::cli::utils;
+use crate::container::nut::NutDemuxer;
+use crate::container::rawvideo::{RawVideoDemuxer, RawVideoFormat};
+use crate::container::{self, y4m};
+use crate::core::frame::{ChromaLocation, VideoFormat};
+use crate::core::time::Time;
+use crate::core::{Decoder, Demuxer, Transform};
+use crate::io::File;
+use crate::transform::video::{
+	ColorLut, ColorMatrix, ColorRange, ColorSpace, Crop, CropDetector, CubeLut, Equalizer,
+	FrameRateConverter, FrameRateMode, LutInterpolation, Orientation, Pad, PixelConverter, Rotate,
+	ScaleKernel, ScaleMode, Scaler, parse_rgb,
+};
+use crate::{error, message::Result};
+
+/// Frames scanned by `crop=auto` when no count is given.
+const CROP_DETECT_FRAMES: usize = 30;
+/// Mean luma above black, as a fraction of the range, that still counts as
+/// a black border.
+const CROP_DETECT_LIMIT: f32 = 24.0 / 255.0;
+
+pub struct VideoInput {
+	pub demuxer: Box<dyn Demuxer>,
+	pub format: RawVideoFormat,
+	/// Header of a y4m input, kept so its tags survive a y4m round trip.
+	pub header: Option<y4m::Y4mFormat>,
+}
+
+impl VideoInput {
+	pub fn open(pipeline: &Pipeline, input_extension: &str) -> Result<Self> {
+		if input_extension == container::NUT {
+			return Self::open_nut(&pipeline.input);
+		}
+		let file = File::open(&pipeline.input)?;
+		match input_extension {
+			container::Y4M => {
+				let demuxer = y4m::Y4mDemuxer::new(file)?;
+				let header = demuxer.format().clone();
+				let format = RawVideoFormat::new(header.width, header.height, header.colorspace)
+					.with_frame_rate(header.framerate_num, header.framerate_den);
+				Ok(Self { demuxer: Box::new(demuxer), format, header: Some(header) })
+			}
+			container::YUV | container::RGB => {
+				let format = raw_video_format(&pipeline.video, input_extension)?;
+				let demuxer = RawVideoDemuxer::new(file, format)?;
+				Ok(Self { demuxer: Box::new(demuxer), format, header: None })
+			}
+			_ => Err(error!("'{}' input is not uncompressed video", input_extension)),
+		}
+	}
+
+	/// The first uncompressed video stream of a nut input, which may be
+	/// standard input.
+	fn open_nut(path: &str) -> Result<Self> {
+		let mut demuxer = NutDemuxer::new(utils::open_input(path)?)?;
+		let stream = demuxer.nut_streams().iter().find(|stream| stream.video_format().is_some());
+		let Some(stream) = stream else {
+			return Err(error!("nut input has no uncompressed video stream"));
+		};
+		let (Some(video), Some(pixel_format)) = (stream.video, stream.video_format()) else {
+			return Err(error!("nut video stream {} has no picture size", stream.id));
+		};
+		let format = RawVideoFormat::new(video.width as u32, video.height as u32, pixel_format)
+			.with_frame_rate(stream.time.den, stream.time.num);
+		format.validate()?;
+		let id = stream.id;
+		demuxer.select_stream(id)?;
+		Ok(Self { demuxer: Box::new(demuxer), format, header: None })
+	}
+
+	pub fn chroma_location(&self) -> ChromaLocation {
+		self.header.as_ref().map(|header| header.chroma_location).unwrap_or_default()
+	}
+}
+
+pub struct VideoPlan {
+	pub target: RawVideoFormat,
+	transforms: Vec<Box<dyn Transform>>,
+	frame_rate: Option<FrameRateConverter>,
+}
+
+impl VideoPlan {
+	pub fn new(
+		pipeline: &Pipeline,
+		input_extension: &str,
+		output_extension: &str,
+		source: RawVideoFormat,
+		chroma_location: ChromaLocation,
+	) -> Result<Self> {
+		let video = &pipeline.video;
+		let color = color_space(video, chroma_location)?;
+		let requested = output_pixel_format(video, input_extension)?;
+		let format = target_pixel_format(source.format, requested, output_extension)?;
+
+		let mut transforms: Vec<Box<dyn Transform>> = Vec::new();
+		if format != source.format {
+			transforms.push(Box::new(PixelConverter::new(format).with_color(color)));
+		}
+
+		let equalizer = create_equalizer(video, color);
+		if !equalizer.is_identity() {
+			transforms.push(Box::new(equalizer));
+		}
+
+		if let Some(path) = &video.lut {
+			let mut lut = ColorLut::new(CubeLut::load(path)?).with_color(color);
+			if let Some(name) = &video.lut_interp {
+				let interpolation = LutInterpolation::from_name(name)
+					.ok_or_else(|| error!("unsupported LUT interpolation '{}'", name))?;
+				lut = lut.with_interpolation(interpolation);
+			}
+			transforms.push(Box::new(lut));
+		}
+
+		let (mut width, mut height) = (source.width, source.height);
+		let crop = match &video.crop {
+			Some(spec) => create_crop(pipeline, input_extension, source, spec, color.range)?,
+			None => None,
+		};
+		if let Some(crop) = crop {
+			(width, height) = crop.output_size(width, height, format);
+			transforms.push(Box::new(crop));
+		}
+
+		let fill = match &video.fill {
+			Some(fill) => parse_rgb(fill).ok_or_else(|| error!("invalid fill colour: {}", fill))?,
+			None => [0, 0, 0],
+		};
+		let specs = [video.rotate.as_deref(), pipeline.transform.rotate.as_deref()];
+		for spec in specs.into_iter().flatten() {
+			for rotate in parse_rotate(spec)? {
+				let rotate = rotate.with_fill(fill).with_color(color);
+				(width, height) = rotate.output_size(width, height, format);
+				transforms.push(Box::new(rotate));
+			}
+		}
+
+		let aspect = video.aspect_ratio.as_deref().map(parse_aspect).transpose()?;
+		let (box_width, box_height) = scale_box(video, input_extension, width, height)?;
+		if box_width.is_some() || box_height.is_some() {
+			let scaler = create_scaler(video, box_width, box_height, aspect)?;
+			(width, height) = scaler.output_size(width, height, format);
+			transforms.push(Box::new(scaler));
+		}
+
+		let mut pads = Vec::new();
+		if let Some(spec) = &video.pad {
+			pads.push(parse_pad(spec)?);
+		}
+		match (aspect, box_width, box_height) {
+			(Some(Aspect::Letterbox), Some(box_width), Some(box_height)) => {
+				pads.push(Pad::new(box_width, box_height))
+			}
+			(Some(Aspect::Letterbox), _, _) => {
+				return Err(error!("aspect_ratio=letterbox needs a width and a height"));
+			}
+			(Some(Aspect::Ratio(ratio)), None, None) => pads.push(Pad::letterbox(width, height, ratio)),
+			_ => {}
+		}
+		for pad in pads {
+			let pad = pad.with_fill(fill).with_color(color);
+			(width, height) = pad.output_size(width, height, format);
+			transforms.push(Box::new(pad));
+		}
+
+		let frame_rate = create_frame_rate(video, input_extension, source)?;
+		let (framerate_num, framerate_den) = match &frame_rate {
+			Some((rate, _)) => *rate,
+			None => (source.framerate_num, source.framerate_den),
+		};
+		let target = RawVideoFormat { width, height, format, framerate_num, framerate_den };
+		let frame_rate = frame_rate.map(|(_, converter)| converter);
+		Ok(Self { target, transforms, frame_rate })
+	}
+
+	pub fn into_transcoder(self, source: RawVideoFormat) -> media::Transcoder {
+		let decoder = source.create_decoder();
+		let encoder = self.target.create_encoder();
+		let mut transcoder = media::Transcoder::new(Box::new(decoder), Box::new(encoder));
+		for transform in self.transforms {
+			transcoder = transcoder.with_transform(transform);
+		}
+		if let Some(frame_rate) = self.frame_rate {
+			transcoder = transcoder.with_frame_rate(frame_rate);
+		}
+		transcoder
+	}
+}
+
+pub fn raw_video_format(video: &VideoConfig, extension: &str) -> Result<RawVideoFormat> {
+	let width = parse_dimension(video.width.as_deref(), "width")?;
+	let height = parse_dimension(video.height.as_deref(), "height")?;
+
+	let pixel_format = match &video.pix_fmt {
+		Some(name) => parse_pixel_format(name)?,
+		None => RawVideoFormat::default_format(extension),
+	};
+
+	let mut format = RawVideoFormat::new(width, height, pixel_format);
+	if let Some(fps) = &video.fps {
+		let (num, den) = config::parse_frame_rate(fps)?;
+		format = format.with_frame_rate(num, den);
+	}
+	format.validate()?;
+	Ok(format)
+}
+
+/// `pix_fmt` describes a headerless input, so it only selects the output
+/// format when the input carries its own.
+fn output_pixel_format(video: &VideoConfig, input_extension: &str) -> Result<Option<VideoFormat>> {
+	if matches!(input_extension, container::YUV | container::RGB) {
+		return Ok(None);
+	}
+	video.pix_fmt.as_deref().map(parse_pixel_format).transpose()
+}
+
+/// Picks the output pixel format: the requested one, or the source format
+/// moved to RGB or YCbCr when the output extension demands it.
+fn target_pixel_format(
+	source: VideoFormat,
+	requested: Option<VideoFormat>,
+	output_extension: &str,
+) -> Result<VideoFormat> {
+	let wants_rgb = output_extension == container::RGB;
+	match requested {
+		Some(format) if format.is_rgb() != wants_rgb => {
+			Err(error!("'{}' pixel format cannot be written as {}", format.name(), output_extension))
+		}
+		Some(format) => Ok(format),
+		None if source.is_rgb() && !wants_rgb => Ok(VideoFormat::YUV420),
+		None if !source.is_rgb() && wants_rgb => Ok(VideoFormat::RGB24),
+		None => Ok(source),
+	}
+}
+
+fn color_space(video: &VideoConfig, chroma_location: ChromaLocation) -> Result<ColorSpace> {
+	let matrix = match &video.matrix {
+		Some(name) => {
+			ColorMatrix::from_name(name).ok_or_else(|| error!("unsupported color matrix '{}'", name))?
+		}
+		None => ColorMatrix::default(),
+	};
+	let range = match &video.range {
+		Some(name) => {
+			ColorRange::from_name(name).ok_or_else(|| error!("unsupported color range '{}'", name))?
+		}
+		None => ColorRange::default(),
+	};
+	Ok(ColorSpace::new(matrix, range).with_chroma_location(chroma_location))
+}
+
+#[derive(Clone, Copy)]
+enum Aspect {
+	Mode(ScaleMode),
+	/// Fit inside the scale box and pad the rest.
+	Letterbox,
+	/// Display aspect ratio: derives a missing dimension, or pads the
+	/// picture to it when no size is requested.
+	Ratio(f64),
+}
+
+fn parse_aspect(value: &str) -> Result<Aspect> {
+	if let Some(mode) = ScaleMode::from_name(value) {
+		return Ok(Aspect::Mode(mode));
+	}
+	if matches!(value, "letterbox" | "pad") {
+		return Ok(Aspect::Letterbox);
+	}
+	parse_aspect_ratio(value).map(Aspect::Ratio)
+}
+
+/// Requested size from `scale=WxH|W:H|factor`, `width` and `height`. With
+/// a headerless input `width` and `height` describe the input instead.
+fn scale_box(
+	video: &VideoConfig,
+	input_extension: &str,
+	source_width: u32,
+	source_height: u32,
+) -> Result<(Option<u32>, Option<u32>)> {
+	let (mut width, mut height) = match &video.scale {
+		Some(scale) => parse_scale(scale, source_width, source_height)?,
+		None => (None, None),
+	};
+	if !matches!(input_extension, container::YUV | container::RGB) {
+		if let Some(value) = &video.width {
+			width = Some(parse_dimension(Some(value), "width")?);
+		}
+		if let Some(value) = &video.height {
+			height = Some(parse_dimension(Some(value), "height")?);
+		}
+	}
+	Ok((width, height))
+}
+
+fn create_scaler(
+	video: &VideoConfig,
+	width: Option<u32>,
+	height: Option<u32>,
+	aspect: Option<Aspect>,
+) -> Result<Scaler> {
+	let mut scaler = Scaler::new(width, height);
+	if let Some(name) = &video.scaler {
+		let kernel =
+			ScaleKernel::from_name(name).ok_or_else(|| error!("unsupported scaler '{}'", name))?;
+		scaler = scaler.with_kernel(kernel);
+	}
+	scaler = match aspect {
+		Some(Aspect::Mode(mode)) => scaler.with_mode(mode),
+		Some(Aspect::Letterbox) => scaler.with_mode(ScaleMode::Fit),
+		Some(Aspect::Ratio(ratio)) => scaler.with_aspect_ratio(ratio),
+		None => scaler,
+	};
+	Ok(scaler)
+}
+
+/// Parses `crop=W:H[:X:Y]`, centred when no position is given, or
+/// `crop=auto[:frames]` which scans the start of the input for black
+/// borders.
+fn create_crop(
+	pipeline: &Pipeline,
+	input_extension: &str,
+	source: RawVideoFormat,
+	spec: &str,
+	range: ColorRange,
+) -> Result<Option<Crop>> {
+	let invalid = || error!("invalid crop: {}", spec);
+	let parts: Vec<&str> = spec.split(':').collect();
+	if parts[0] == "auto" {
+		let frames = match parts.get(1) {
+			Some(count) => count.parse::<usize>().map_err(|_| invalid())?,
+			None => CROP_DETECT_FRAMES,
+		};
+		return detect_crop(pipeline, input_extension, source, frames, range);
+	}
+
+	let numbers = parts
+		.iter()
+		.map(|part| part.parse::<u32>().map_err(|_| invalid()))
+		.collect::<Result<Vec<_>>>()?;
+	let (width, height, x, y) = match numbers[..] {
+		[width, height] => (
+			width,
+			height,
+			source.width.saturating_sub(width) / 2,
+			source.height.saturating_sub(height) / 2,
+		),
+		[width, height, x, y] => (width, height, x, y),
+		_ => return Err(invalid()),
+	};
+	if width == 0 || height == 0 || x >= source.width || y >= source.height {
+		return Err(invalid());
+	}
+	Ok(Some(Crop::new(x, y, width, height)))
+}
+
+fn detect_crop(
+	pipeline: &Pipeline,
+	input_extension: &str,
+	source: RawVideoFormat,
+	frames: usize,
+	range: ColorRange,
+) -> Result<Option<Crop>> {
+	// the scan opens the input a second time, which a pipe cannot replay
+	if pipeline.input == utils::STDIO_PATH {
+		return Err(error!("crop=auto cannot read standard input twice, give the crop size instead"));
+	}
+	let mut input = VideoInput::open(pipeline, input_extension)?;
+	let mut decoder = source.create_decoder();
+	let mut detector = CropDetector::new(CROP_DETECT_LIMIT, range);
+	for _ in 0..frames {
+		let Some(packet) = input.demuxer.read_packet()? else {
+			break;
+		};
+		let frame = decoder.decode(packet)?;
+		if let Some(video) = frame.as_ref().and_then(|frame| frame.video()) {
+			detector.push(video);
+		}
+	}
+	Ok(detector.result())
+}
+
+/// Parses `pad=W:H[:X:Y]` or `pad=WxH`.
+fn parse_pad(spec: &str) -> Result<Pad> {
+	let invalid = || error!("invalid pad: {}", spec);
+	let numbers = spec
+		.split([':', 'x'])
+		.map(|part| part.parse::<u32>().map_err(|_| invalid()))
+		.collect::<Result<Vec<_>>>()?;
+	match numbers[..] {
+		[width, height] if width > 0 && height > 0 => Ok(Pad::new(width, height)),
+		[width, height, x, y] if width > 0 && height > 0 => {
+			Ok(Pad::new(width, height).with_position(x, y))
+		}
+		_ => Err(invalid()),
+	}
+}
+
+fn create_frame_rate(
+	video: &VideoConfig,
+	input_extension: &str,
+	source: RawVideoFormat,
+) -> Result<Option<((u32, u32), FrameRateConverter)>> {
+	if matches!(input_extension, container::YUV | container::RGB) {
+		return Ok(None);
+	}
+	let Some(fps) = &video.fps else {
+		return Ok(None);
+	};
+	let (num, den) = config::parse_frame_rate(fps)?;
+	if num as u64 * source.framerate_den as u64 == source.framerate_num as u64 * den as u64 {
+		return Ok(None);
+	}
+
+	let mut converter = FrameRateConverter::new(source.time(), Time::new(den, num));
+	if let Some(name) = &video.fps_mode {
+		let mode =
+			FrameRateMode::from_name(name).ok_or_else(|| error!("unsupported fps mode '{}'", name))?;
+		converter = converter.with_mode(mode);
+	}
+	Ok(Some(((num, den), converter)))
+}
+
+fn create_equalizer(video: &VideoConfig, color: ColorSpace) -> Equalizer {
+	let mut equalizer = Equalizer::new().with_color(color);
+	if let Some(brightness) = video.brightness {
+		equalizer = equalizer.with_brightness(brightness);
+	}
+	if let Some(contrast) = video.contrast {
+		equalizer = equalizer.with_contrast(contrast);
+	}
+	if let Some(saturation) = video.saturation {
+		equalizer = equalizer.with_saturation(saturation);
+	}
+	if let Some(hue) = video.hue {
+		equalizer = equalizer.with_hue(hue);
+	}
+	if let Some(gamma) = video.gamma {
+		equalizer = equalizer.with_gamma(gamma);
+	}
+	equalizer
+}
+
+fn parse_rotate(spec: &str) -> Result<Vec<Rotate>> {
+	let mut rotations = Vec::new();
+	for operation in spec.split(',').map(str::trim).filter(|operation| !operation.is_empty()) {
+		if let Some(orientation) = Orientation::from_name(operation) {
+			rotations.push(Rotate::new(orientation));
+			continue;
+		}
+		let (angle, fill) = match operation.split_once(':') {
+			Some((angle, fill)) => (angle, Some(fill)),
+			None => (operation, None),
+		};
+		let degrees = angle.parse::<f64>().map_err(|_| error!("invalid rotation: {}", operation))?;
+		if !degrees.is_finite() {
+			return Err(error!("invalid rotation: {}", operation));
+		}
+		let mut rotate = Rotate::angle(degrees);
+		if let Some(fill) = fill {
+			rotate =
+				rotate.with_fill(parse_rgb(fill).ok_or_else(|| error!("invalid fill colour: {}", fill))?);
+		}
+		rotations.push(rotate);
+	}
+	Ok(rotations)
+}
+
+fn parse_scale(value: &str, width: u32, height: u32) -> Result<(Option<u32>, Option<u32>)> {
+	let invalid = || error!("invalid scale: {}", value);
+	let dimension = |part: &str| -> Result<Option<u32>> {
+		match part.trim() {
+			"-1" | "" => Ok(None),
+			part => part.parse::<u32>().map(Some).map_err(|_| invalid()),
+		}
+	};
+	if let Some((w, h)) = value.split_once(['x', ':']) {
+		return Ok((dimension(w)?, dimension(h)?));
+	}
+	let factor = value.parse::<f64>().map_err(|_| invalid())?;
+	if !factor.is_finite() || factor <= 0.0 {
+		return Err(invalid());
+	}
+	let scaled = |size: u32| ((size as f64 * factor).round() as u32).max(1);
+	Ok((Some(scaled(width)), Some(scaled(height))))
+}
+
+fn parse_aspect_ratio(value: &str) -> Result<f64> {
+	let invalid = || error!("invalid aspect ratio: {}", value);
+	let ratio = match value.split_once([':', '/']) {
+		Some((num, den)) => {
+			let num = num.parse::<f64>().map_err(|_| invalid())?;
+			let den = den.parse::<f64>().map_err(|_| invalid())?;
+			num / den
+		}
+		None => value.parse::<f64>().map_err(|_| invalid())?,
+	};
+	if !ratio.is_finite() || ratio <= 0.0 {
+		return Err(invalid());
+	}
+	Ok(ratio)
+}
+
+fn parse_pixel_format(name: &str) -> Result<VideoFormat> {
+	VideoFormat::from_name(name).ok_or_else(|| error!("unsupported pixel format '{}'", name))
+}
+
+fn parse_dimension(value: Option<&str>, name: &str) -> Result<u32> {
+	let value = value.ok_or_else(|| error!("raw video input requires --video {}=...", name))?;
+	value.parse::<u32>().map_err(|_| error!("invalid {}: {}", name, value))
+}
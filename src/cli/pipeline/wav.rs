@@ -2,21 +2,108 @@ use super::common::Pipeline;
 use crate::cli::transcoder::media;
 use crate::cli::utils;
 use crate::codecs::audio::pcm::{PcmDecoder, PcmEncoder};
-use crate::container::{self, raw, wav};
-use crate::core::{Demuxer, Muxer};
+use crate::container::{self, ape, caf, dsd, mxf, nut, raw, tta, w64, wav, wavpack};
+use crate::core::frame::{AudioFormat, Channels};
+use crate::core::{Decoder, Demuxer, Muxer};
 use crate::io::{Error, File};
-use crate::message::Result;
+use crate::{error, message::Result};
 
 pub fn run(pipeline: Pipeline) -> Result<()> {
 	let input_extension = utils::get_extension(&pipeline.input)?;
 	let mut format = wav::WavFormat::default();
 	let mut metadata = None;
+	// compressed inputs bring their own decoder and the sample format it produces
+	let mut decoder: Option<(Box<dyn Decoder>, AudioFormat)> = None;
+	// inputs that can only be read once, like standard input, open here
+	let mut opened: Option<Box<dyn Demuxer>> = None;
 
 	if input_extension == container::WAV {
 		let file = File::open(&pipeline.input)?;
 		let demuxer = wav::WavDemuxer::new(file)?;
 		format = demuxer.format();
 		metadata = Some(demuxer.metadata().clone())
+	} else if input_extension == container::W64 {
+		let file = File::open(&pipeline.input)?;
+		let demuxer = w64::W64Demuxer::new(file)?;
+		format = demuxer.format();
+		metadata = Some(demuxer.metadata().clone())
+	} else if input_extension == container::CAF {
+		let file = File::open(&pipeline.input)?;
+		let demuxer = caf::CafDemuxer::new(file)?;
+		if !demuxer.format().is_pcm() {
+			let codec = demuxer.format().to_codec_string();
+			return Err(error!("caf codec '{}' cannot be decoded", codec));
+		}
+		format = demuxer.format().to_wav_format();
+		metadata = Some(to_wav_metadata(demuxer.metadata()));
+	} else if input_extension == container::DSF || input_extension == container::DFF {
+		let file = File::open(&pipeline.input)?;
+		let dsd_format = match input_extension.as_str() {
+			container::DSF => dsd::DsfDemuxer::new(file)?.format(),
+			_ => {
+				let demuxer = dsd::DffDemuxer::new(file)?;
+				metadata = Some(to_wav_metadata(demuxer.metadata()));
+				demuxer.format()
+			}
+		};
+		let output_rate = match &pipeline.audio.sample_rate {
+			Some(rate) => rate.parse::<u32>().map_err(|_| error!("invalid sample rate: {}", rate))?,
+			None => dsd_format.default_output_rate(),
+		};
+		format = dsd_format.to_wav_format(output_rate);
+		// the dsd decoder always produces float samples
+		decoder = Some((Box::new(dsd_format.create_decoder(output_rate)?), AudioFormat::PCM32));
+	} else if input_extension == container::WV {
+		let file = File::open(&pipeline.input)?;
+		let demuxer = wavpack::WavPackDemuxer::new(file)?;
+		let wavpack_decoder = demuxer.format().create_decoder()?;
+		let decoded_format = wavpack_decoder.audio_format();
+		format = demuxer.format().to_wav_format();
+		metadata = Some(to_wav_metadata(demuxer.metadata()));
+		decoder = Some((Box::new(wavpack_decoder), decoded_format));
+	} else if input_extension == container::TTA {
+		let file = File::open(&pipeline.input)?;
+		let demuxer = tta::TtaDemuxer::new(file)?;
+		let tta_decoder = demuxer.format().create_decoder()?;
+		let decoded_format = tta_decoder.audio_format();
+		format = demuxer.format().to_wav_format();
+		metadata = Some(to_wav_metadata(demuxer.metadata()));
+		decoder = Some((Box::new(tta_decoder), decoded_format));
+	} else if input_extension == container::APE {
+		let file = File::open(&pipeline.input)?;
+		let demuxer = ape::ApeDemuxer::new(file)?;
+		let ape_decoder = demuxer.format().create_decoder()?;
+		let decoded_format = ape_decoder.audio_format();
+		format = demuxer.format().to_wav_format();
+		metadata = Some(to_wav_metadata(demuxer.metadata()));
+		decoder = Some((Box::new(ape_decoder), decoded_format));
+	} else if input_extension == container::MXF {
+		let file = File::open(&pipeline.input)?;
+		let demuxer = mxf::MxfDemuxer::new(file)?;
+		let (_, descriptor) = first_mxf_sound(&demuxer)?;
+		format = wav::WavFormat {
+			channels: Channels::from_count(descriptor.channels as u8),
+			sample_rate: descriptor.audio_sampling_rate.map_or(48000, |(num, den)| (num / den) as u32),
+			bit_depth: descriptor.quantization_bits as u16,
+			format_code: 1,
+		};
+		metadata = Some(to_wav_metadata(demuxer.metadata()));
+	} else if input_extension == container::NUT {
+		let mut demuxer = nut::NutDemuxer::new(utils::open_input(&pipeline.input)?)?;
+		let stream = demuxer.nut_streams().iter().find(|stream| stream.audio.is_some());
+		let Some(stream) = stream else {
+			return Err(error!("nut input has no audio stream"));
+		};
+		let Some(audio) = stream.audio else {
+			return Err(error!("nut audio stream {} has no sample rate", stream.id));
+		};
+		format.channels = Channels::from_count(audio.channels as u8);
+		format.sample_rate = audio.sample_rate();
+		format.apply_codec(stream.codec()).map_err(Error::invalid_data)?;
+		let id = stream.id;
+		demuxer.select_stream(id)?;
+		metadata = Some(to_wav_metadata(demuxer.metadata()));
+		opened = Some(Box::new(demuxer));
 	}
 
 	let mut target_format = format;
@@ -28,8 +115,16 @@ pub fn run(pipeline: Pipeline) -> Result<()> {
 	let mut muxer = wav::WavMuxer::new(output_file, target_format)?;
 	muxer.with_metadata(metadata);
 
-	let mut demuxer = create_demuxer(&pipeline.input, &input_extension, format)?;
-	let mut transcoder = create_transcoder(format, target_format);
+	let mut demuxer = match opened {
+		Some(demuxer) => demuxer,
+		None => create_demuxer(&pipeline.input, &input_extension, format)?,
+	};
+	let mut transcoder = match decoder {
+		Some((decoder, decoded_format)) => {
+			create_decoding_transcoder(decoder, decoded_format, target_format)
+		}
+		None => create_transcoder(format, target_format),
+	};
 
 	while let Some(packet) = demuxer.read_packet()? {
 		for output_packet in transcoder.transcode(packet)? {
@@ -49,10 +144,55 @@ fn create_demuxer(path: &str, extension: &str, format: wav::WavFormat) -> Result
 	if extension == container::WAV {
 		return Ok(Box::new(wav::WavDemuxer::new(file)?));
 	}
+	if extension == container::W64 {
+		return Ok(Box::new(w64::W64Demuxer::new(file)?));
+	}
+	if extension == container::CAF {
+		return Ok(Box::new(caf::CafDemuxer::new(file)?));
+	}
+	if extension == container::DSF {
+		return Ok(Box::new(dsd::DsfDemuxer::new(file)?));
+	}
+	if extension == container::DFF {
+		return Ok(Box::new(dsd::DffDemuxer::new(file)?));
+	}
+	if extension == container::WV {
+		return Ok(Box::new(wavpack::WavPackDemuxer::new(file)?));
+	}
+	if extension == container::TTA {
+		return Ok(Box::new(tta::TtaDemuxer::new(file)?));
+	}
+	if extension == container::APE {
+		return Ok(Box::new(ape::ApeDemuxer::new(file)?));
+	}
+	if extension == container::MXF {
+		let mut demuxer = mxf::MxfDemuxer::new(file)?;
+		let (stream_id, _) = first_mxf_sound(&demuxer)?;
+		demuxer.select_stream(stream_id)?;
+		return Ok(Box::new(demuxer));
+	}
 	let demuxer = raw::RawPcmDemuxer::new(file, format.to_raw_format())?;
 	Ok(Box::new(demuxer))
 }
 
+/// The first PCM sound track of an mxf file, the one extracted; broadcast
+/// deliverables often carry one mono track per channel.
+fn first_mxf_sound(demuxer: &mxf::MxfDemuxer<File>) -> Result<(u32, mxf::MxfDescriptor)> {
+	for stream in demuxer.streams().audio() {
+		let Some(track) = demuxer.stream_track(stream.id) else {
+			continue;
+		};
+		let Some(descriptor) = track.descriptor.as_ref() else {
+			continue;
+		};
+		if descriptor.channels == 0 {
+			return Err(error!("mxf sound track {} has no channel count", track.id));
+		}
+		return Ok((stream.id, descriptor.clone()));
+	}
+	Err(error!("mxf file has no pcm sound track"))
+}
+
 fn create_transcoder(format: wav::WavFormat, target_format: wav::WavFormat) -> media::Transcoder {
 	let decoder = PcmDecoder::new_from_metadata(&format);
 
@@ -65,3 +205,24 @@ fn create_transcoder(format: wav::WavFormat, target_format: wav::WavFormat) -> m
 	let encoder = PcmEncoder::new(target_format.sample_rate);
 	media::Transcoder::new(Box::new(decoder), Box::new(encoder))
 }
+
+fn create_decoding_transcoder(
+	decoder: Box<dyn Decoder>,
+	decoded_format: AudioFormat,
+	target_format: wav::WavFormat,
+) -> media::Transcoder {
+	let encoder = PcmEncoder::new(target_format.sample_rate);
+	if decoded_format != target_format.audio_format() {
+		let encoder = encoder.with_target_format(target_format.audio_format());
+		return media::Transcoder::new(decoder, Box::new(encoder));
+	}
+	media::Transcoder::new(decoder, Box::new(encoder))
+}
+
+fn to_wav_metadata(metadata: &container::Metadata) -> wav::WavMetadata {
+	let mut wav_metadata = wav::WavMetadata::new();
+	for (key, value) in metadata.iter() {
+		wav_metadata.set(key, value.to_string());
+	}
+	wav_metadata
+}
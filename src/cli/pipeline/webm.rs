@@ -1,6 +1,8 @@
 use super::common::Pipeline;
-use crate::{error, message::Result};
+use super::mkv;
+use crate::container::mkv::MkvProfile;
+use crate::message::Result;
 
-pub fn run(_pipeline: Pipeline) -> Result<()> {
-	Err(error!("webm pipeline not implemented"))
+pub fn run(pipeline: Pipeline) -> Result<()> {
+	mkv::run_with_profile(pipeline, MkvProfile::WebM)
 }
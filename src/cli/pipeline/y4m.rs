@@ -0,0 +1,46 @@
+use super::common::Pipeline;
+use super::video::{VideoInput, VideoPlan};
+use crate::cli::utils;
+use crate::container::{self, y4m};
+use crate::core::Muxer;
+use crate::io::File;
+use crate::message::Result;
+
+pub fn run(pipeline: Pipeline) -> Result<()> {
+	let input_extension = utils::get_extension(&pipeline.input)?;
+
+	let mut input = VideoInput::open(&pipeline, &input_extension)?;
+	let source = input.format;
+	let chroma_location = input.chroma_location();
+	let plan = VideoPlan::new(&pipeline, &input_extension, container::Y4M, source, chroma_location)?;
+	let target = plan.target;
+
+	let header = match input.header.take() {
+		Some(header) => header,
+		None => y4m::Y4mFormat::new(source.width, source.height, source.format, source.time()),
+	};
+	let output_file = File::create(&pipeline.output)?;
+	let output_header = y4m::Y4mFormat {
+		width: target.width,
+		height: target.height,
+		framerate_num: target.framerate_num,
+		framerate_den: target.framerate_den,
+		colorspace: target.format,
+		..header
+	};
+	let mut muxer = y4m::Y4mMuxer::new(output_file, output_header)?;
+
+	let mut transcoder = plan.into_transcoder(source);
+
+	while let Some(packet) = input.demuxer.read_packet()? {
+		for output_packet in transcoder.transcode(packet)? {
+			muxer.write(output_packet)?;
+		}
+	}
+
+	for packet in transcoder.flush()? {
+		muxer.write(packet)?;
+	}
+
+	muxer.finalize()
+}
@@ -1,24 +1,63 @@
+use crate::core::frame::Frame;
 use crate::core::packet::Packet;
-use crate::core::{Decoder, Encoder};
+use crate::core::{Decoder, Encoder, Transform};
 use crate::message::Result;
+use crate::transform::video::FrameRateConverter;
 
 pub struct Transcoder {
 	pub decoder: Box<dyn Decoder>,
 	pub encoder: Box<dyn Encoder>,
+	pub transforms: Vec<Box<dyn Transform>>,
+	pub frame_rate: Option<FrameRateConverter>,
 }
 
 impl Transcoder {
 	pub fn new(decoder: Box<dyn Decoder>, encoder: Box<dyn Encoder>) -> Self {
-		Self { decoder, encoder }
+		Self { decoder, encoder, transforms: Vec::new(), frame_rate: None }
 	}
 
-	pub fn transcode(&mut self, packet: Packet) -> Result<Vec<Packet>> {
-		let mut packets = Vec::new();
-		if let Some(frame) = self.decoder.decode(packet)? {
+	/// Applies `transform` to every decoded frame, after those added before it.
+	pub fn with_transform(mut self, transform: Box<dyn Transform>) -> Self {
+		self.transforms.push(transform);
+		self
+	}
+
+	/// Retimes transformed frames to a constant rate before encoding.
+	pub fn with_frame_rate(mut self, frame_rate: FrameRateConverter) -> Self {
+		self.frame_rate = Some(frame_rate);
+		self
+	}
+
+	fn apply_transforms(&mut self, mut frame: Frame) -> Result<Frame> {
+		for transform in &mut self.transforms {
+			frame = transform.apply(frame)?;
+		}
+		Ok(frame)
+	}
+
+	fn retime(&mut self, frame: Frame) -> Result<Vec<Frame>> {
+		match &mut self.frame_rate {
+			Some(frame_rate) => frame_rate.push(frame),
+			None => Ok(vec![frame]),
+		}
+	}
+
+	fn encode_all(&mut self, frames: Vec<Frame>, packets: &mut Vec<Packet>) -> Result<()> {
+		for frame in frames {
 			if let Some(encoded_packet) = self.encoder.encode(frame)? {
 				packets.push(encoded_packet);
 			}
 		}
+		Ok(())
+	}
+
+	pub fn transcode(&mut self, packet: Packet) -> Result<Vec<Packet>> {
+		let mut packets = Vec::new();
+		if let Some(frame) = self.decoder.decode(packet)? {
+			let frame = self.apply_transforms(frame)?;
+			let frames = self.retime(frame)?;
+			self.encode_all(frames, &mut packets)?;
+		}
 		Ok(packets)
 	}
 
@@ -26,9 +65,14 @@ impl Transcoder {
 		let mut packets = Vec::new();
 
 		while let Some(frame) = self.decoder.flush()? {
-			if let Some(encoded_packet) = self.encoder.encode(frame)? {
-				packets.push(encoded_packet);
-			}
+			let frame = self.apply_transforms(frame)?;
+			let frames = self.retime(frame)?;
+			self.encode_all(frames, &mut packets)?;
+		}
+
+		if let Some(frame_rate) = &mut self.frame_rate {
+			let frames = frame_rate.flush()?;
+			self.encode_all(frames, &mut packets)?;
 		}
 
 		while let Some(packet) = self.encoder.flush()? {
@@ -1,9 +1,36 @@
+use crate::container;
+use crate::io::stdio::{StdinAdapter, StdioSink, StdioSource, StdoutAdapter};
+use crate::io::{BufferedReader, mapper_error};
 use crate::{error, message};
 
+/// Input or output path that stands for standard input or output, which
+/// always carry nut.
+pub const STDIO_PATH: &str = "-";
+
 pub fn get_extension(path: &str) -> message::Result<String> {
+	if path == STDIO_PATH {
+		return Ok(container::NUT.to_string());
+	}
 	std::path::Path::new(path)
 		.extension()
 		.and_then(|e| e.to_str())
 		.map(|s| s.to_lowercase())
 		.ok_or_else(|| error!("no file extension"))
 }
+
+/// Opens an input that may be standard input, buffered.
+pub fn open_input(path: &str) -> message::Result<BufferedReader<StdioSource>> {
+	if path == STDIO_PATH {
+		return Ok(BufferedReader::new(StdioSource::Stdin(StdinAdapter::new())));
+	}
+	let file = mapper_error(std::fs::File::open(path), path)?;
+	Ok(BufferedReader::new(StdioSource::File(file)))
+}
+
+/// Creates an output that may be standard output.
+pub fn create_output(path: &str) -> message::Result<StdioSink> {
+	if path == STDIO_PATH {
+		return Ok(StdioSink::Stdout(StdoutAdapter::new()));
+	}
+	Ok(StdioSink::File(mapper_error(std::fs::File::create(path), path)?))
+}
@@ -0,0 +1,205 @@
+use super::entropy::{RangeDecoder, Rice};
+use super::filter::{LEVELS, NnFilter};
+use super::predictor::Predictor;
+use crate::core::crc::Crc32;
+use crate::core::frame::{AudioFormat, Channels, Frame, FrameAudio};
+use crate::core::packet::Packet;
+use crate::core::traits::Decoder;
+use crate::{error, message::Result};
+
+pub const MIN_VERSION: u16 = 3950;
+pub const MAX_VERSION: u16 = 3990;
+
+// frame flags
+const FLAG_MONO_SILENCE: u32 = 0x1;
+const FLAG_STEREO_SILENCE: u32 = 0x3;
+const FLAG_PSEUDO_STEREO: u32 = 0x4;
+/// Set in the stored CRC when a frame flags word follows it.
+const HAS_FLAGS: u32 = 0x8000_0000;
+
+/// Monkey's Audio decoder for files written by version 3.95 and later.
+/// Each packet is one frame in stream byte order; every frame restarts the
+/// entropy coder, filters and predictor. 8 and 16-bit streams produce
+/// PCM16, 24-bit streams PCM24.
+pub struct ApeDecoder {
+	version: u16,
+	/// Index into the filter levels: compression level / 1000 - 1.
+	level: usize,
+	sample_rate: u32,
+	channels: Channels,
+	bits_per_sample: u16,
+	blocks_per_frame: u64,
+	total_blocks: u64,
+}
+
+impl ApeDecoder {
+	pub fn new(
+		version: u16,
+		compression_level: u16,
+		sample_rate: u32,
+		channels: Channels,
+		bits_per_sample: u16,
+		blocks_per_frame: u64,
+		total_blocks: u64,
+	) -> Result<Self> {
+		if !(MIN_VERSION..=MAX_VERSION).contains(&version) {
+			return Err(error!("monkey's audio version {} is not supported", version));
+		}
+		if !compression_level.is_multiple_of(1000) || !(1000..=5000).contains(&compression_level) {
+			return Err(error!("monkey's audio compression level {} is invalid", compression_level));
+		}
+		if !matches!(bits_per_sample, 8 | 16 | 24) {
+			return Err(error!(
+				"monkey's audio with {} bits per sample is not supported",
+				bits_per_sample
+			));
+		}
+		if !matches!(channels.count(), 1 | 2) {
+			return Err(error!("monkey's audio with {} channels is not supported", channels.count()));
+		}
+		if sample_rate == 0 || blocks_per_frame == 0 {
+			return Err(error!("monkey's audio stream has no samples"));
+		}
+		Ok(Self {
+			version,
+			level: compression_level as usize / 1000 - 1,
+			sample_rate,
+			channels,
+			bits_per_sample,
+			blocks_per_frame,
+			total_blocks,
+		})
+	}
+
+	pub fn audio_format(&self) -> AudioFormat {
+		match self.bits_per_sample {
+			24 => AudioFormat::PCM24,
+			_ => AudioFormat::PCM16,
+		}
+	}
+
+	fn filters(&self) -> Vec<NnFilter> {
+		let levels = LEVELS[self.level].iter();
+		levels.map(|(order, bits)| NnFilter::new(*order, *bits, self.version)).collect()
+	}
+
+	fn entropy_value(&self, range: &mut RangeDecoder, rice: &mut Rice) -> Result<i32> {
+		match self.version >= 3990 {
+			true => range.value_3990(rice),
+			false => range.value_3900(rice),
+		}
+	}
+
+	fn apply_filters(&self, samples: &mut [i32]) {
+		for mut filter in self.filters() {
+			for sample in samples.iter_mut() {
+				*sample = filter.process(*sample);
+			}
+		}
+	}
+
+	fn decode_frame(&self, data: &[u8], blocks: usize) -> Result<Vec<i32>> {
+		let mut stored_crc = read_be32(data, 0)?;
+		let mut offset = 4;
+		let mut flags = 0;
+		if stored_crc & HAS_FLAGS != 0 {
+			stored_crc &= !HAS_FLAGS;
+			flags = read_be32(data, 4)?;
+			offset += 4;
+		}
+
+		// the first byte of the range coded data is unused
+		let payload = data.get(offset + 1..).unwrap_or_default();
+		let stereo = self.channels.count() == 2;
+
+		let output = if !stereo || flags & FLAG_PSEUDO_STEREO != 0 {
+			let mut samples = vec![0i32; blocks];
+			if flags & FLAG_MONO_SILENCE == 0 {
+				let mut range = RangeDecoder::new(payload)?;
+				let mut rice = Rice::new();
+				for sample in samples.iter_mut() {
+					*sample = self.entropy_value(&mut range, &mut rice)?;
+				}
+				self.apply_filters(&mut samples);
+				Predictor::new().decode_mono(&mut samples);
+			}
+			match stereo {
+				true => samples.iter().flat_map(|sample| [*sample, *sample]).collect(),
+				false => samples,
+			}
+		} else {
+			let mut y = vec![0i32; blocks];
+			let mut x = vec![0i32; blocks];
+			if flags & FLAG_STEREO_SILENCE != FLAG_STEREO_SILENCE {
+				let mut range = RangeDecoder::new(payload)?;
+				let (mut rice_y, mut rice_x) = (Rice::new(), Rice::new());
+				// interleaved from 3930 on; only older files store every Y value first
+				for (y, x) in y.iter_mut().zip(x.iter_mut()) {
+					*y = self.entropy_value(&mut range, &mut rice_y)?;
+					*x = self.entropy_value(&mut range, &mut rice_x)?;
+				}
+				self.apply_filters(&mut y);
+				self.apply_filters(&mut x);
+				Predictor::new().decode_stereo(&mut y, &mut x);
+			}
+
+			let mut output = Vec::with_capacity(blocks * 2);
+			for (y, x) in y.iter().zip(&x) {
+				let left = x.wrapping_sub(y / 2);
+				output.extend_from_slice(&[left, left.wrapping_add(*y)]);
+			}
+			output
+		};
+
+		let mut crc = Crc32::new();
+		for sample in &output {
+			match self.bits_per_sample {
+				8 => crc.update(&[(*sample as u8).wrapping_add(0x80)]),
+				16 => crc.update(&(*sample as i16).to_le_bytes()),
+				_ => crc.update(&sample.to_le_bytes()[..3]),
+			}
+		}
+		if crc.finish() >> 1 != stored_crc {
+			return Err(error!("ape frame crc mismatch"));
+		}
+		Ok(output)
+	}
+}
+
+fn read_be32(data: &[u8], offset: usize) -> Result<u32> {
+	match data.get(offset..offset + 4) {
+		Some(bytes) => Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+		None => Err(error!("ape frame is too small")),
+	}
+}
+
+impl Decoder for ApeDecoder {
+	fn decode(&mut self, packet: Packet) -> Result<Option<Frame>> {
+		if packet.is_empty() {
+			return Ok(None);
+		}
+
+		let position = packet.pts.max(0) as u64;
+		let blocks = self.blocks_per_frame.min(self.total_blocks.saturating_sub(position)) as usize;
+		let decoded = self.decode_frame(&packet.data, blocks)?;
+
+		let mut data = Vec::with_capacity(decoded.len() * 3);
+		for value in decoded {
+			match self.bits_per_sample {
+				8 => data.extend_from_slice(&((value << 8) as i16).to_le_bytes()),
+				16 => data.extend_from_slice(&(value as i16).to_le_bytes()),
+				_ => data.extend_from_slice(&value.to_le_bytes()[..3]),
+			}
+		}
+		if data.is_empty() {
+			return Ok(None);
+		}
+
+		let audio = FrameAudio::new(data, self.sample_rate, self.channels, self.audio_format());
+		Ok(Some(Frame::new_audio(audio, packet.stream_id).with_pts(packet.pts)))
+	}
+
+	fn flush(&mut self) -> Result<Option<Frame>> {
+		Ok(None)
+	}
+}
@@ -0,0 +1,182 @@
+use crate::{error, message::Result};
+
+const BOTTOM: u32 = 1 << 23;
+/// Escape symbol of the overflow models.
+const ESCAPE: u32 = 63;
+
+/// Cumulative frequencies of the overflow model before version 3.98.
+const COUNTS_3970: [u32; 22] = [
+	0, 14824, 28224, 39348, 47855, 53994, 58171, 60926, 62682, 63786, 64463, 64878, 65126, 65276,
+	65365, 65419, 65450, 65469, 65480, 65487, 65491, 65493,
+];
+const DIFFS_3970: [u32; 21] = [
+	14824, 13400, 11124, 8507, 6139, 4177, 2755, 1756, 1104, 677, 415, 248, 150, 89, 54, 31, 19, 11,
+	7, 4, 2,
+];
+const COUNTS_3980: [u32; 22] = [
+	0, 19578, 36160, 48417, 56323, 60899, 63265, 64435, 64971, 65232, 65351, 65416, 65447, 65466,
+	65476, 65482, 65485, 65488, 65490, 65491, 65492, 65493,
+];
+const DIFFS_3980: [u32; 21] =
+	[19578, 16582, 12257, 7906, 4576, 2366, 1170, 536, 261, 119, 65, 31, 19, 10, 6, 3, 3, 2, 1, 1, 1];
+
+#[derive(Debug, Clone, Copy)]
+pub struct Rice {
+	k: u32,
+	sum: u32,
+}
+
+impl Rice {
+	pub fn new() -> Self {
+		Self { k: 10, sum: 1 << 14 }
+	}
+
+	fn update(&mut self, value: u32) {
+		let limit = if self.k != 0 { 1 << (self.k + 4) } else { 0 };
+		self.sum =
+			self.sum.wrapping_add((value.wrapping_add(1) / 2).wrapping_sub((self.sum + 16) >> 5));
+		if self.sum < limit {
+			self.k -= 1;
+		} else if self.sum >= 1 << (self.k + 5) && self.k < 24 {
+			self.k += 1;
+		}
+	}
+}
+
+impl Default for Rice {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+pub struct RangeDecoder<'a> {
+	data: &'a [u8],
+	position: usize,
+	low: u32,
+	range: u32,
+	buffer: u32,
+	help: u32,
+}
+
+impl<'a> RangeDecoder<'a> {
+	pub fn new(data: &'a [u8]) -> Result<Self> {
+		let Some(&first) = data.first() else {
+			return Err(error!("ape frame has no entropy data"));
+		};
+		let buffer = first as u32;
+		Ok(Self { data, position: 1, low: buffer >> 1, range: 1 << 7, buffer, help: 0 })
+	}
+
+	fn normalize(&mut self) -> Result<()> {
+		while self.range <= BOTTOM {
+			let Some(&byte) = self.data.get(self.position) else {
+				return Err(error!("ape frame ended early"));
+			};
+			self.position += 1;
+			self.buffer = (self.buffer << 8) | byte as u32;
+			self.low = (self.low << 8) | ((self.buffer >> 1) & 0xff);
+			self.range <<= 8;
+		}
+		Ok(())
+	}
+
+	fn frequency(&mut self, total: u32) -> Result<u32> {
+		self.normalize()?;
+		self.help = self.range / total;
+		Ok(self.low / self.help)
+	}
+
+	fn shifted(&mut self, shift: u32) -> Result<u32> {
+		self.normalize()?;
+		self.help = self.range >> shift;
+		Ok(self.low / self.help)
+	}
+
+	fn update(&mut self, size: u32, start: u32) {
+		self.low = self.low.wrapping_sub(self.help.wrapping_mul(start));
+		self.range = self.help.wrapping_mul(size);
+	}
+
+	fn bits(&mut self, count: u32) -> Result<u32> {
+		let value = self.shifted(count)?;
+		self.update(1, value);
+		Ok(value)
+	}
+
+	fn symbol(&mut self, counts: &[u32; 22], diffs: &[u32; 21]) -> Result<u32> {
+		let frequency = self.shifted(16)?;
+		if frequency >= counts[21] {
+			// rare symbols sit one per slot past the table
+			if frequency > u16::MAX as u32 {
+				return Err(error!("ape overflow symbol is out of range"));
+			}
+			self.update(1, frequency);
+			return Ok(frequency + ESCAPE - u16::MAX as u32);
+		}
+
+		let symbol = counts[1..].iter().position(|count| *count > frequency).unwrap_or(20);
+		self.update(diffs[symbol], counts[symbol]);
+		Ok(symbol as u32)
+	}
+
+	/// Residual coding before version 3.99: an overflow symbol plus `k - 1`
+	/// raw bits.
+	pub fn value_3900(&mut self, rice: &mut Rice) -> Result<i32> {
+		let mut overflow = self.symbol(&COUNTS_3970, &DIFFS_3970)?;
+		let bits = match overflow {
+			ESCAPE => {
+				overflow = 0;
+				self.bits(5)?
+			}
+			_ => rice.k.saturating_sub(1),
+		};
+
+		let mut value = match bits {
+			0..=16 => self.bits(bits)?,
+			17..=31 => self.bits(16)? | (self.bits(bits - 16)? << 16),
+			_ => return Err(error!("ape residual width {} is invalid", bits)),
+		};
+		value = value.wrapping_add(overflow.wrapping_shl(bits));
+		rice.update(value);
+		Ok(Self::signed(value))
+	}
+
+	/// Residual coding from version 3.99: an overflow symbol times a pivot
+	/// derived from the running average, plus a uniformly coded remainder.
+	pub fn value_3990(&mut self, rice: &mut Rice) -> Result<i32> {
+		let pivot = (rice.sum >> 5).max(1);
+
+		let mut overflow = self.symbol(&COUNTS_3980, &DIFFS_3980)?;
+		if overflow == ESCAPE {
+			overflow = self.bits(16)? << 16;
+			overflow |= self.bits(16)?;
+		}
+
+		let base = if pivot < 1 << 16 {
+			let base = self.frequency(pivot)?;
+			self.update(1, base);
+			base
+		} else {
+			let mut high = pivot;
+			let mut shift = 0;
+			while high & !0xffff != 0 {
+				high >>= 1;
+				shift += 1;
+			}
+			let high = self.frequency(high + 1)?;
+			self.update(1, high);
+			let low = self.frequency(1 << shift)?;
+			self.update(1, low);
+			(high << shift).wrapping_add(low)
+		};
+
+		let value = base.wrapping_add(overflow.wrapping_mul(pivot));
+		rice.update(value);
+		Ok(Self::signed(value))
+	}
+
+	fn signed(value: u32) -> i32 {
+		let value = value as i32;
+		((value >> 1) ^ ((value & 1) - 1)).wrapping_add(1)
+	}
+}
@@ -0,0 +1,93 @@
+const HISTORY_SIZE: usize = 512;
+
+/// Filter orders and fraction bits per compression level, applied in order
+/// after entropy decoding.
+pub const LEVELS: [&[(usize, u32)]; 5] =
+	[&[], &[(16, 11)], &[(64, 11)], &[(32, 10), (256, 13)], &[(16, 11), (256, 13), (1280, 15)]];
+
+/// Inverted sign, matching the reference implementation.
+pub fn sign(value: i32) -> i32 {
+	(value < 0) as i32 - (value > 0) as i32
+}
+
+/// Sign-sign adaptive FIR stage ("neural network" filter). Past outputs and
+/// their adaption steps are kept in rolling windows of `order` entries.
+pub struct NnFilter {
+	order: usize,
+	fraction_bits: u32,
+	/// Version 3.98 changed how the adaption steps are derived.
+	scaled_adaption: bool,
+	coeffs: Vec<i16>,
+	delay: Vec<i16>,
+	adapt: Vec<i16>,
+	position: usize,
+	average: i32,
+}
+
+impl NnFilter {
+	pub fn new(order: usize, fraction_bits: u32, version: u16) -> Self {
+		Self {
+			order,
+			fraction_bits,
+			scaled_adaption: version >= 3980,
+			coeffs: vec![0; order],
+			delay: vec![0; HISTORY_SIZE + order],
+			adapt: vec![0; HISTORY_SIZE + order],
+			position: 0,
+			average: 0,
+		}
+	}
+
+	pub fn process(&mut self, input: i32) -> i32 {
+		let window = self.position..self.position + self.order;
+		let direction = sign(input) as i16;
+
+		let mut sum = 0i32;
+		let delay = &self.delay[window.clone()];
+		let adapt = &self.adapt[window];
+		for ((coeff, delay), adapt) in self.coeffs.iter_mut().zip(delay).zip(adapt) {
+			sum = sum.wrapping_add(*coeff as i32 * *delay as i32);
+			*coeff = coeff.wrapping_add(direction.wrapping_mul(*adapt));
+		}
+
+		let rounded = (sum as i64 + (1 << (self.fraction_bits - 1))) >> self.fraction_bits;
+		let output = (rounded as i32).wrapping_add(input);
+
+		let end = self.position + self.order;
+		self.delay[end] = output.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+
+		if self.scaled_adaption {
+			let magnitude = output.unsigned_abs();
+			let average = self.average as i64;
+			self.adapt[end] = match magnitude {
+				0 => 0,
+				_ => {
+					let scale = (magnitude as i64 > average * 3) as u32
+						+ (magnitude as i64 > average + average / 3) as u32;
+					(sign(output) * (8 << scale)) as i16
+				}
+			};
+			let step = magnitude.wrapping_sub(self.average as u32) as i32 / 16;
+			self.average = self.average.wrapping_add(step);
+			for back in [1, 2, 8] {
+				self.adapt[end - back] >>= 1;
+			}
+		} else {
+			self.adapt[end] = match output {
+				0 => 0,
+				_ => (((output >> 28) & 8) - 4) as i16,
+			};
+			for back in [4, 8] {
+				self.adapt[end - back] >>= 1;
+			}
+		}
+
+		self.position += 1;
+		if self.position == HISTORY_SIZE {
+			self.delay.copy_within(HISTORY_SIZE.., 0);
+			self.adapt.copy_within(HISTORY_SIZE.., 0);
+			self.position = 0;
+		}
+		output
+	}
+}
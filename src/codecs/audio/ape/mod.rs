@@ -0,0 +1,6 @@
+pub mod decoder;
+pub mod entropy;
+pub mod filter;
+pub mod predictor;
+
+pub use decoder::ApeDecoder;
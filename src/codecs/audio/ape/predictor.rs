@@ -0,0 +1,149 @@
+use super::filter::sign;
+
+const HISTORY_SIZE: usize = 512;
+const WINDOW_SIZE: usize = 50;
+
+const Y_DELAY_A: usize = 50;
+const Y_DELAY_B: usize = 42;
+const X_DELAY_A: usize = 34;
+const X_DELAY_B: usize = 26;
+const Y_ADAPT_A: usize = 18;
+const X_ADAPT_A: usize = 14;
+const Y_ADAPT_B: usize = 10;
+const X_ADAPT_B: usize = 5;
+
+const INITIAL_COEFFS_A: [i32; 4] = [360, 317, -109, 98];
+
+struct Layout {
+	delay_a: usize,
+	delay_b: usize,
+	adapt_a: usize,
+	adapt_b: usize,
+}
+
+const Y: Layout =
+	Layout { delay_a: Y_DELAY_A, delay_b: Y_DELAY_B, adapt_a: Y_ADAPT_A, adapt_b: Y_ADAPT_B };
+const X: Layout =
+	Layout { delay_a: X_DELAY_A, delay_b: X_DELAY_B, adapt_a: X_ADAPT_A, adapt_b: X_ADAPT_B };
+
+/// Prediction stage of version 3.95 and later. Stage A predicts a channel
+/// from its own past, stage B from the other channel.
+pub struct Predictor {
+	history: Vec<i32>,
+	position: usize,
+	last_a: [i32; 2],
+	filter_a: [i32; 2],
+	filter_b: [i32; 2],
+	coeffs_a: [[i32; 4]; 2],
+	coeffs_b: [[i32; 5]; 2],
+}
+
+impl Predictor {
+	pub fn new() -> Self {
+		Self {
+			history: vec![0; HISTORY_SIZE + WINDOW_SIZE],
+			position: 0,
+			last_a: [0; 2],
+			filter_a: [0; 2],
+			filter_b: [0; 2],
+			coeffs_a: [INITIAL_COEFFS_A; 2],
+			coeffs_b: [[0; 5]; 2],
+		}
+	}
+
+	fn advance(&mut self) {
+		self.position += 1;
+		if self.position == HISTORY_SIZE {
+			self.history.copy_within(HISTORY_SIZE.., 0);
+			self.position = 0;
+		}
+	}
+
+	fn scale(value: i32) -> i32 {
+		value.wrapping_mul(31) >> 5
+	}
+
+	fn update(&mut self, value: i32, channel: usize, layout: &Layout) -> i32 {
+		let base = self.position;
+		let buffer = &mut self.history;
+		let (delay_a, delay_b) = (base + layout.delay_a, base + layout.delay_b);
+		let (adapt_a, adapt_b) = (base + layout.adapt_a, base + layout.adapt_b);
+
+		buffer[delay_a] = self.last_a[channel];
+		buffer[adapt_a] = sign(buffer[delay_a]);
+		buffer[delay_a - 1] = buffer[delay_a].wrapping_sub(buffer[delay_a - 1]);
+		buffer[adapt_a - 1] = sign(buffer[delay_a - 1]);
+
+		let prediction_a = (0..4).fold(0i32, |sum, index| {
+			sum.wrapping_add(buffer[delay_a - index].wrapping_mul(self.coeffs_a[channel][index]))
+		});
+
+		let other = self.filter_a[channel ^ 1];
+		buffer[delay_b] = other.wrapping_sub(Self::scale(self.filter_b[channel]));
+		buffer[adapt_b] = sign(buffer[delay_b]);
+		buffer[delay_b - 1] = buffer[delay_b].wrapping_sub(buffer[delay_b - 1]);
+		buffer[adapt_b - 1] = sign(buffer[delay_b - 1]);
+		self.filter_b[channel] = other;
+
+		let prediction_b = (0..5).fold(0i32, |sum, index| {
+			sum.wrapping_add(buffer[delay_b - index].wrapping_mul(self.coeffs_b[channel][index]))
+		});
+
+		let prediction = prediction_a.wrapping_add(prediction_b >> 1) >> 10;
+		self.last_a[channel] = value.wrapping_add(prediction);
+		self.filter_a[channel] = self.last_a[channel].wrapping_add(Self::scale(self.filter_a[channel]));
+
+		let direction = sign(value);
+		for (index, coeff) in self.coeffs_a[channel].iter_mut().enumerate() {
+			*coeff = coeff.wrapping_add(buffer[adapt_a - index].wrapping_mul(direction));
+		}
+		for (index, coeff) in self.coeffs_b[channel].iter_mut().enumerate() {
+			*coeff = coeff.wrapping_add(buffer[adapt_b - index].wrapping_mul(direction));
+		}
+		self.filter_a[channel]
+	}
+
+	pub fn decode_stereo(&mut self, y: &mut [i32], x: &mut [i32]) {
+		for (y, x) in y.iter_mut().zip(x.iter_mut()) {
+			*y = self.update(*y, 0, &Y);
+			*x = self.update(*x, 1, &X);
+			self.advance();
+		}
+	}
+
+	pub fn decode_mono(&mut self, samples: &mut [i32]) {
+		let mut current = self.last_a[0];
+		for sample in samples.iter_mut() {
+			let value = *sample;
+			let base = self.position;
+			let buffer = &mut self.history;
+			let delay = base + Y_DELAY_A;
+
+			buffer[delay] = current;
+			buffer[delay - 1] = buffer[delay].wrapping_sub(buffer[delay - 1]);
+			let prediction = (0..4).fold(0i32, |sum, index| {
+				sum.wrapping_add(buffer[delay - index].wrapping_mul(self.coeffs_a[0][index]))
+			});
+			current = value.wrapping_add(prediction >> 10);
+
+			let adapt = base + Y_ADAPT_A;
+			buffer[adapt] = sign(buffer[delay]);
+			buffer[adapt - 1] = sign(buffer[delay - 1]);
+			let direction = sign(value);
+			for (index, coeff) in self.coeffs_a[0].iter_mut().enumerate() {
+				*coeff = coeff.wrapping_add(buffer[adapt - index].wrapping_mul(direction));
+			}
+
+			self.advance();
+			self.filter_a[0] = current.wrapping_add(Self::scale(self.filter_a[0]));
+			*sample = self.filter_a[0];
+		}
+		self.last_a[0] = current;
+	}
+}
+
+impl Default for Predictor {
+	fn default() -> Self {
+		Self::new()
+	}
+}
@@ -0,0 +1,42 @@
+/// Little-endian bit reader: bits are taken from the least significant end
+/// of each byte first. Reading past the end yields zeros and leaves
+/// `bits_left` negative, so callers check for overruns once per sample.
+pub struct BitReader<'a> {
+	data: &'a [u8],
+	position: usize,
+}
+
+impl<'a> BitReader<'a> {
+	pub fn new(data: &'a [u8]) -> Self {
+		Self { data, position: 0 }
+	}
+
+	pub fn bits_left(&self) -> isize {
+		(self.data.len() * 8) as isize - self.position as isize
+	}
+
+	pub fn read_bit(&mut self) -> u32 {
+		let byte = self.data.get(self.position / 8).copied().unwrap_or(0);
+		let bit = (byte >> (self.position % 8)) & 1;
+		self.position += 1;
+		bit as u32
+	}
+
+	/// Read up to 32 bits; the first bit read is the least significant.
+	pub fn read_bits(&mut self, count: u32) -> u32 {
+		let mut value = 0u32;
+		for index in 0..count {
+			value |= self.read_bit() << index;
+		}
+		value
+	}
+
+	/// Count one bits up to a terminating zero, giving up after `limit` ones.
+	pub fn read_unary(&mut self, limit: u32) -> u32 {
+		let mut count = 0;
+		while count < limit && self.read_bit() == 1 {
+			count += 1;
+		}
+		count
+	}
+}
@@ -0,0 +1,202 @@
+use super::filter::{self, ByteFilter};
+use crate::codecs;
+use crate::core::frame::{AudioFormat, Channels, Frame, FrameAudio};
+use crate::core::packet::Packet;
+use crate::core::traits::Decoder;
+use crate::{error, message::Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DsdLayout {
+	/// The oldest sample is in the least significant bit (DSF with 1 bit per sample).
+	pub lsb_first: bool,
+	/// Each packet holds one contiguous block per channel (DSF) instead of
+	/// interleaving channels byte by byte (DSDIFF).
+	pub planar: bool,
+}
+
+impl DsdLayout {
+	pub fn from_codec(codec: &str) -> Result<Self> {
+		match codec {
+			codecs::audio::DSD_LSBF => Ok(Self { lsb_first: true, planar: false }),
+			codecs::audio::DSD_MSBF => Ok(Self { lsb_first: false, planar: false }),
+			codecs::audio::DSD_LSBF_PLANAR => Ok(Self { lsb_first: true, planar: true }),
+			codecs::audio::DSD_MSBF_PLANAR => Ok(Self { lsb_first: false, planar: true }),
+			_ => Err(error!("codec '{}' is not dsd", codec)),
+		}
+	}
+
+	pub fn to_codec_string(&self) -> &'static str {
+		match (self.lsb_first, self.planar) {
+			(true, false) => codecs::audio::DSD_LSBF,
+			(false, false) => codecs::audio::DSD_MSBF,
+			(true, true) => codecs::audio::DSD_LSBF_PLANAR,
+			(false, true) => codecs::audio::DSD_MSBF_PLANAR,
+		}
+	}
+}
+
+struct ChannelState {
+	bytes: [u8; ByteFilter::BYTES],
+	samples: Vec<f32>,
+}
+
+pub struct DsdDecoder {
+	channels: Channels,
+	layout: DsdLayout,
+	output_rate: u32,
+	byte_filter: ByteFilter,
+	coefficients: Vec<f32>,
+	decimation: usize,
+	states: Vec<ChannelState>,
+	position: i64,
+	flushed: bool,
+}
+
+impl DsdDecoder {
+	/// Byte value of DSD silence (alternating ones and zeros).
+	const SILENCE: u8 = 0x69;
+	const TAPS_PER_PHASE: usize = 48;
+	const CUTOFF: f64 = 0.4;
+
+	pub fn new(
+		dsd_rate: u32,
+		channels: Channels,
+		layout: DsdLayout,
+		output_rate: u32,
+	) -> Result<Self> {
+		if dsd_rate == 0 || !dsd_rate.is_multiple_of(8) {
+			return Err(error!("dsd rate {} is not a multiple of 8", dsd_rate));
+		}
+		let byte_rate = dsd_rate / 8;
+		if output_rate == 0 || output_rate > byte_rate || !byte_rate.is_multiple_of(output_rate) {
+			return Err(error!("cannot decimate dsd rate {} to {} Hz", dsd_rate, output_rate));
+		}
+
+		let decimation = (byte_rate / output_rate) as usize;
+		let coefficients = match decimation {
+			1 => vec![1.0],
+			m => {
+				let taps = Self::TAPS_PER_PHASE * m;
+				filter::lowpass(taps, Self::CUTOFF / m as f64).into_iter().map(|c| c as f32).collect()
+			}
+		};
+
+		let delay = (coefficients.len() - 1) / 2;
+		let states = (0..channels.count())
+			.map(|_| ChannelState {
+				bytes: [Self::SILENCE; ByteFilter::BYTES],
+				samples: vec![0.0; delay],
+			})
+			.collect();
+
+		Ok(Self {
+			channels,
+			layout,
+			output_rate,
+			byte_filter: ByteFilter::new(layout.lsb_first),
+			coefficients,
+			decimation,
+			states,
+			position: 0,
+			flushed: false,
+		})
+	}
+
+	/// Default output rate: dsd rate / 32, i.e. 88.2 kHz for DSD64 and 176.4 kHz for DSD128.
+	pub fn default_output_rate(dsd_rate: u32) -> u32 {
+		dsd_rate / 32
+	}
+
+	pub fn output_rate(&self) -> u32 {
+		self.output_rate
+	}
+
+	fn split_channels(&self, data: &[u8]) -> Result<Vec<Vec<u8>>> {
+		let count = self.channels.count() as usize;
+		if !data.len().is_multiple_of(count) {
+			return Err(error!("dsd packet is not a whole number of channel bytes"));
+		}
+
+		if self.layout.planar {
+			let block = data.len() / count;
+			return Ok(data.chunks_exact(block).map(|chunk| chunk.to_vec()).collect());
+		}
+
+		let mut channels = vec![Vec::with_capacity(data.len() / count); count];
+		for group in data.chunks_exact(count) {
+			for (channel, byte) in channels.iter_mut().zip(group) {
+				channel.push(*byte);
+			}
+		}
+		Ok(channels)
+	}
+
+	fn filter_channel(&mut self, channel: usize, bytes: &[u8]) -> Vec<f32> {
+		let state = &mut self.states[channel];
+		for byte in bytes {
+			state.bytes.copy_within(0..ByteFilter::BYTES - 1, 1);
+			state.bytes[0] = *byte;
+			state.samples.push(self.byte_filter.apply(&state.bytes));
+		}
+		Self::decimate(&mut state.samples, &self.coefficients, self.decimation)
+	}
+
+	fn decimate(samples: &mut Vec<f32>, coefficients: &[f32], decimation: usize) -> Vec<f32> {
+		let taps = coefficients.len();
+		let mut output = Vec::new();
+		let mut offset = 0;
+		while offset + taps <= samples.len() {
+			let window = &samples[offset..offset + taps];
+			output.push(window.iter().zip(coefficients).map(|(s, c)| s * c).sum());
+			offset += decimation;
+		}
+		samples.drain(..offset);
+		output
+	}
+
+	fn interleave(&mut self, planes: Vec<Vec<f32>>, stream_id: u32) -> Option<Frame> {
+		let nb_samples = planes.iter().map(|plane| plane.len()).min().unwrap_or(0);
+		if nb_samples == 0 {
+			return None;
+		}
+
+		let mut data = Vec::with_capacity(nb_samples * planes.len() * 4);
+		for index in 0..nb_samples {
+			for plane in &planes {
+				data.extend_from_slice(&plane[index].to_le_bytes());
+			}
+		}
+
+		let audio = FrameAudio::new(data, self.output_rate, self.channels, AudioFormat::PCM32);
+		let frame = Frame::new_audio(audio, stream_id).with_pts(self.position);
+		self.position += nb_samples as i64;
+		Some(frame)
+	}
+}
+
+impl Decoder for DsdDecoder {
+	fn decode(&mut self, packet: Packet) -> Result<Option<Frame>> {
+		if packet.is_empty() {
+			return Ok(None);
+		}
+
+		let channels = self.split_channels(&packet.data)?;
+		let planes: Vec<Vec<f32>> =
+			channels.iter().enumerate().map(|(index, bytes)| self.filter_channel(index, bytes)).collect();
+
+		Ok(self.interleave(planes, packet.stream_id))
+	}
+
+	fn flush(&mut self) -> Result<Option<Frame>> {
+		if self.flushed {
+			return Ok(None);
+		}
+		self.flushed = true;
+
+		// push the delay of both filter stages out with silence
+		let delay = (self.coefficients.len() - 1) / 2 + ByteFilter::BYTES / 2;
+		let silence = vec![Self::SILENCE; delay];
+		let planes = (0..self.states.len()).map(|index| self.filter_channel(index, &silence)).collect();
+		Ok(self.interleave(planes, 0))
+	}
+}
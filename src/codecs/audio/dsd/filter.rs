@@ -0,0 +1,65 @@
+use std::f64::consts::PI;
+
+/// Windowed-sinc low-pass FIR with unity DC gain. `cutoff` is relative to the
+/// input sample rate (0.5 is Nyquist).
+pub fn lowpass(taps: usize, cutoff: f64) -> Vec<f64> {
+	let center = (taps - 1) as f64 / 2.0;
+	let mut coefficients: Vec<f64> = (0..taps)
+		.map(|n| {
+			let x = n as f64 - center;
+			let sinc = if x == 0.0 { 2.0 * cutoff } else { (2.0 * PI * cutoff * x).sin() / (PI * x) };
+			sinc * blackman(n, taps)
+		})
+		.collect();
+
+	let sum: f64 = coefficients.iter().sum();
+	coefficients.iter_mut().for_each(|c| *c /= sum);
+	coefficients
+}
+
+fn blackman(n: usize, taps: usize) -> f64 {
+	let phase = 2.0 * PI * n as f64 / (taps - 1) as f64;
+	0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos()
+}
+
+/// First decimation stage: a low-pass over the one-bit stream that produces
+/// one sample per input byte. Each byte's contribution to the convolution is
+/// precomputed for all 256 values, so filtering costs one lookup per byte.
+pub struct ByteFilter {
+	tables: Vec<[f32; 256]>,
+}
+
+impl ByteFilter {
+	pub const BYTES: usize = 20;
+	/// Pass band edge relative to the one-bit rate, below the Nyquist of the
+	/// byte-rate output (1/16) so the first stage does not alias.
+	const CUTOFF: f64 = 0.045;
+
+	pub fn new(lsb_first: bool) -> Self {
+		let coefficients = lowpass(Self::BYTES * 8, Self::CUTOFF);
+		let tables = (0..Self::BYTES)
+			.map(|position| {
+				let mut table = [0f32; 256];
+				for (value, entry) in table.iter_mut().enumerate() {
+					// t counts bits in time order, the newest bit has age 0
+					let sum: f64 = (0..8)
+						.map(|t| {
+							let shift = if lsb_first { t } else { 7 - t };
+							let level = if (value >> shift) & 1 == 1 { 1.0 } else { -1.0 };
+							coefficients[position * 8 + 7 - t] * level
+						})
+						.sum();
+					*entry = sum as f32;
+				}
+				table
+			})
+			.collect();
+		Self { tables }
+	}
+
+	/// `history[0]` is the newest byte.
+	#[inline]
+	pub fn apply(&self, history: &[u8]) -> f32 {
+		self.tables.iter().zip(history).map(|(table, byte)| table[*byte as usize]).sum()
+	}
+}
@@ -1,3 +1,8 @@
+pub mod ape;
+pub mod bitstream;
 mod constants;
+pub mod dsd;
 pub mod pcm;
+pub mod tta;
+pub mod wavpack;
 pub use constants::*;
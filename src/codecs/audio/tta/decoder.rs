@@ -0,0 +1,201 @@
+use super::filter::HybridFilter;
+use crate::codecs::audio::bitstream::BitReader;
+use crate::core::crc;
+use crate::core::frame::{AudioFormat, Channels, Frame, FrameAudio};
+use crate::core::packet::Packet;
+use crate::core::traits::Decoder;
+use crate::{error, message::Result};
+
+/// Adaptive Rice parameters; `k1` codes values that overflow the `k0` range.
+struct Rice {
+	k0: u32,
+	k1: u32,
+	sum0: u32,
+	sum1: u32,
+}
+
+impl Rice {
+	const INITIAL_K: u32 = 10;
+
+	fn new() -> Self {
+		let sum = Self::threshold(Self::INITIAL_K);
+		Self { k0: Self::INITIAL_K, k1: Self::INITIAL_K, sum0: sum, sum1: sum }
+	}
+
+	/// 2^k, saturating at the top bit like the reference tables.
+	fn power(k: u32) -> u32 {
+		if k < 32 { 1 << k } else { 1 << 31 }
+	}
+
+	fn threshold(k: u32) -> u32 {
+		Self::power(k + 4)
+	}
+
+	fn adapt(k: &mut u32, sum: &mut u32, value: u32) {
+		*sum = sum.wrapping_add(value.wrapping_sub(*sum >> 4));
+		if *k > 0 && *sum < Self::threshold(*k) {
+			*k -= 1;
+		} else if *sum > Self::threshold(*k + 1) {
+			*k += 1;
+		}
+	}
+
+	fn read(&mut self, bits: &mut BitReader) -> Result<i32> {
+		let limit = bits.bits_left().max(0) as u32;
+		let unary = bits.read_unary(limit);
+		let (escaped, k, unary) = match unary {
+			0 => (false, self.k0, 0),
+			n => (true, self.k1, n - 1),
+		};
+		if k > 31 || bits.bits_left() < k as isize {
+			return Err(error!("tta frame ended early"));
+		}
+
+		let mut value = match k {
+			0 => unary,
+			k => (unary << k).wrapping_add(bits.read_bits(k)),
+		};
+		if escaped {
+			Self::adapt(&mut self.k1, &mut self.sum1, value);
+			value = value.wrapping_add(Self::power(self.k0));
+		}
+		Self::adapt(&mut self.k0, &mut self.sum0, value);
+
+		// zigzag back to signed
+		let value = value as i32;
+		Ok(((value >> 1) ^ ((value & 1) - 1)).wrapping_add(1))
+	}
+}
+
+struct ChannelState {
+	predictor: i32,
+	filter: HybridFilter,
+	rice: Rice,
+}
+
+/// TTA (True Audio) decoder. Each packet is one frame followed by its
+/// CRC-32; every frame restarts the adaptive state. 8 and 16-bit streams
+/// produce PCM16, 24-bit streams PCM24.
+pub struct TtaDecoder {
+	sample_rate: u32,
+	channels: Channels,
+	bytes_per_sample: usize,
+	frame_length: u64,
+	total_samples: u64,
+}
+
+impl TtaDecoder {
+	pub fn new(
+		sample_rate: u32,
+		channels: Channels,
+		bits_per_sample: u16,
+		total_samples: u64,
+	) -> Result<Self> {
+		if !matches!(bits_per_sample, 8 | 16 | 24) {
+			return Err(error!("tta with {} bits per sample is not supported", bits_per_sample));
+		}
+		if sample_rate == 0 || channels.count() == 0 {
+			return Err(error!("tta stream has no samples"));
+		}
+		Ok(Self {
+			sample_rate,
+			channels,
+			bytes_per_sample: bits_per_sample as usize / 8,
+			frame_length: Self::frame_length(sample_rate),
+			total_samples,
+		})
+	}
+
+	pub fn frame_length(sample_rate: u32) -> u64 {
+		256 * sample_rate as u64 / 245
+	}
+
+	pub fn audio_format(&self) -> AudioFormat {
+		match self.bytes_per_sample {
+			3 => AudioFormat::PCM24,
+			_ => AudioFormat::PCM16,
+		}
+	}
+
+	fn predict(&self, previous: i32) -> i32 {
+		let scale = |k: u32| ((previous as i64 * ((1 << k) - 1)) >> k) as i32;
+		match self.bytes_per_sample {
+			1 => scale(4),
+			2 | 3 => scale(5),
+			_ => previous,
+		}
+	}
+
+	fn decode_frame(&self, data: &[u8], samples: usize) -> Result<Vec<i32>> {
+		if data.len() < 4 {
+			return Err(error!("tta frame is too small"));
+		}
+		let (payload, stored) = data.split_at(data.len() - 4);
+		if crc::crc32(payload) != u32::from_le_bytes([stored[0], stored[1], stored[2], stored[3]]) {
+			return Err(error!("tta frame crc mismatch"));
+		}
+
+		let count = self.channels.count() as usize;
+		let mut states: Vec<_> = (0..count)
+			.map(|_| ChannelState {
+				predictor: 0,
+				filter: HybridFilter::new(self.bytes_per_sample),
+				rice: Rice::new(),
+			})
+			.collect();
+
+		let mut bits = BitReader::new(payload);
+		let mut output = Vec::with_capacity(samples * count);
+		for _ in 0..samples {
+			let group = output.len();
+			for state in states.iter_mut() {
+				let value = state.rice.read(&mut bits)?;
+				let value = state.filter.process(value);
+				let value = value.wrapping_add(self.predict(state.predictor));
+				state.predictor = value;
+				output.push(value);
+			}
+
+			// undo the inter-channel difference coding
+			if count > 1 {
+				let samples = &mut output[group..];
+				samples[count - 1] = samples[count - 1].wrapping_add(samples[count - 2] / 2);
+				for index in (0..count - 1).rev() {
+					samples[index] = samples[index + 1].wrapping_sub(samples[index]);
+				}
+			}
+		}
+		Ok(output)
+	}
+}
+
+impl Decoder for TtaDecoder {
+	fn decode(&mut self, packet: Packet) -> Result<Option<Frame>> {
+		if packet.is_empty() {
+			return Ok(None);
+		}
+
+		let position = packet.pts.max(0) as u64;
+		let samples = self.frame_length.min(self.total_samples.saturating_sub(position)) as usize;
+		let decoded = self.decode_frame(&packet.data, samples)?;
+
+		let mut data = Vec::with_capacity(decoded.len() * 3);
+		for value in decoded {
+			match self.bytes_per_sample {
+				1 => data.extend_from_slice(&((value << 8) as i16).to_le_bytes()),
+				2 => data.extend_from_slice(&(value as i16).to_le_bytes()),
+				_ => data.extend_from_slice(&value.to_le_bytes()[..3]),
+			}
+		}
+		if data.is_empty() {
+			return Ok(None);
+		}
+
+		let audio = FrameAudio::new(data, self.sample_rate, self.channels, self.audio_format());
+		Ok(Some(Frame::new_audio(audio, packet.stream_id).with_pts(packet.pts)))
+	}
+
+	fn flush(&mut self) -> Result<Option<Frame>> {
+		Ok(None)
+	}
+}
@@ -0,0 +1,57 @@
+/// Adaptive eight-tap filter run over every residual before the fixed
+/// predictor. `dl` holds recent outputs and their differences, `dx` the
+/// sign-derived steps applied to the weights `qm`.
+#[derive(Debug, Clone)]
+pub struct HybridFilter {
+	shift: u32,
+	round: i32,
+	error: i32,
+	qm: [i32; 8],
+	dx: [i32; 9],
+	dl: [i32; 9],
+}
+
+impl HybridFilter {
+	/// Filter shift per bytes per sample (1 to 4).
+	const SHIFTS: [u32; 4] = [10, 9, 10, 12];
+
+	pub fn new(bytes_per_sample: usize) -> Self {
+		let shift = Self::SHIFTS[bytes_per_sample - 1];
+		Self { shift, round: 1 << (shift - 1), error: 0, qm: [0; 8], dx: [0; 9], dl: [0; 9] }
+	}
+
+	pub fn process(&mut self, value: i32) -> i32 {
+		if self.error < 0 {
+			for (weight, step) in self.qm.iter_mut().zip(&self.dx) {
+				*weight = weight.wrapping_sub(*step);
+			}
+		} else if self.error > 0 {
+			for (weight, step) in self.qm.iter_mut().zip(&self.dx) {
+				*weight = weight.wrapping_add(*step);
+			}
+		}
+
+		let sum = self
+			.dl
+			.iter()
+			.zip(&self.qm)
+			.fold(self.round, |sum, (sample, weight)| sum.wrapping_add(sample.wrapping_mul(*weight)));
+
+		self.dx[8] = ((self.dl[7] >> 30) | 1) << 2;
+		self.dx[7] = ((self.dl[6] >> 30) | 1) << 1;
+		self.dx[6] = ((self.dl[5] >> 30) | 1) << 1;
+		self.dx[5] = (self.dl[4] >> 30) | 1;
+
+		self.error = value;
+		let value = value.wrapping_add(sum >> self.shift);
+
+		self.dl[8] = value;
+		self.dl[7] = self.dl[8].wrapping_sub(self.dl[7]);
+		self.dl[6] = self.dl[7].wrapping_sub(self.dl[6]);
+		self.dl[5] = self.dl[6].wrapping_sub(self.dl[5]);
+
+		self.dl.copy_within(1.., 0);
+		self.dx.copy_within(1.., 0);
+		value
+	}
+}
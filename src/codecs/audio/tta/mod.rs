@@ -0,0 +1,4 @@
+pub mod decoder;
+pub mod filter;
+
+pub use decoder::TtaDecoder;
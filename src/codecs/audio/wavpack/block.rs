@@ -0,0 +1,186 @@
+use crate::{error, message::Result};
+
+pub const HEADER_SIZE: usize = 32;
+pub const MIN_VERSION: u16 = 0x402;
+pub const MAX_VERSION: u16 = 0x410;
+
+// header flags
+pub const BYTES_STORED: u32 = 0x3;
+pub const MONO: u32 = 0x4;
+pub const HYBRID: u32 = 0x8;
+pub const JOINT_STEREO: u32 = 0x10;
+pub const FLOAT_DATA: u32 = 0x80;
+pub const INITIAL_BLOCK: u32 = 0x800;
+pub const FINAL_BLOCK: u32 = 0x1000;
+pub const SHIFT_LSB: u32 = 13;
+pub const SHIFT_MASK: u32 = 0x1f << SHIFT_LSB;
+pub const SRATE_LSB: u32 = 23;
+pub const SRATE_MASK: u32 = 0xf << SRATE_LSB;
+pub const FALSE_STEREO: u32 = 0x4000_0000;
+pub const DSD: u32 = 0x8000_0000;
+
+// metadata sub-block ids
+pub const ID_DECORR_TERMS: u8 = 0x2;
+pub const ID_DECORR_WEIGHTS: u8 = 0x3;
+pub const ID_DECORR_SAMPLES: u8 = 0x4;
+pub const ID_ENTROPY_VARS: u8 = 0x5;
+pub const ID_FLOAT_INFO: u8 = 0x8;
+pub const ID_INT32_INFO: u8 = 0x9;
+pub const ID_WV_BITSTREAM: u8 = 0xa;
+pub const ID_WVX_BITSTREAM: u8 = 0xc;
+pub const ID_CHANNEL_INFO: u8 = 0xd;
+pub const ID_SAMPLE_RATE: u8 = 0x27;
+
+const ID_MASK: u8 = 0x3f;
+const ID_ODD_SIZE: u8 = 0x40;
+const ID_LARGE: u8 = 0x80;
+
+const SAMPLE_RATES: [u32; 15] = [
+	6000, 8000, 9600, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 64000, 88200, 96000,
+	192000,
+];
+
+#[derive(Debug, Clone, Copy)]
+pub struct BlockHeader {
+	/// Block size minus the 8 bytes of id and size.
+	pub size: u32,
+	pub version: u16,
+	pub total_samples: Option<u64>,
+	pub block_index: u64,
+	pub block_samples: u32,
+	pub flags: u32,
+	pub crc: u32,
+}
+
+impl BlockHeader {
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		if data.len() < HEADER_SIZE || &data[..4] != b"wvpk" {
+			return Err(error!("missing wavpack block header"));
+		}
+
+		let u32_at = |offset: usize| {
+			u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+		};
+		let size = u32_at(4);
+		let version = u16::from_le_bytes([data[8], data[9]]);
+		if !(MIN_VERSION..=MAX_VERSION).contains(&version) {
+			return Err(error!("wavpack version {:#x} is not supported", version));
+		}
+		if (size as usize) < HEADER_SIZE - 8 {
+			return Err(error!("wavpack block size {} is invalid", size));
+		}
+
+		let total_samples = match u32_at(12) {
+			u32::MAX => None,
+			low => Some(((data[11] as u64) << 32) | low as u64),
+		};
+
+		Ok(Self {
+			size,
+			version,
+			total_samples,
+			block_index: ((data[10] as u64) << 32) | u32_at(16) as u64,
+			block_samples: u32_at(20),
+			flags: u32_at(24),
+			crc: u32_at(28),
+		})
+	}
+
+	pub fn block_size(&self) -> usize {
+		self.size as usize + 8
+	}
+
+	pub fn bytes_stored(&self) -> usize {
+		(self.flags & BYTES_STORED) as usize + 1
+	}
+
+	/// Low bits that were zero in every sample and are shifted back in.
+	pub fn shift(&self) -> u32 {
+		(self.flags & SHIFT_MASK) >> SHIFT_LSB
+	}
+
+	pub fn channel_count(&self) -> usize {
+		if self.flags & MONO != 0 { 1 } else { 2 }
+	}
+
+	/// Two output channels coded once because they are identical.
+	pub fn is_false_stereo(&self) -> bool {
+		self.flags & FALSE_STEREO != 0
+	}
+
+	pub fn is_float(&self) -> bool {
+		self.flags & FLOAT_DATA != 0
+	}
+
+	pub fn is_initial(&self) -> bool {
+		self.flags & INITIAL_BLOCK != 0
+	}
+
+	pub fn is_final(&self) -> bool {
+		self.flags & FINAL_BLOCK != 0
+	}
+
+	/// Sample rate from the header table; `None` means the block carries an
+	/// explicit sample rate sub-block.
+	pub fn sample_rate(&self) -> Option<u32> {
+		SAMPLE_RATES.get(((self.flags & SRATE_MASK) >> SRATE_LSB) as usize).copied()
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SubBlock<'a> {
+	/// Id without the size flags; bit 0x20 still marks optional ids.
+	pub id: u8,
+	pub data: &'a [u8],
+}
+
+pub fn sub_blocks(body: &[u8]) -> Result<Vec<SubBlock<'_>>> {
+	let mut blocks = Vec::new();
+	let mut position = 0;
+
+	while position + 2 <= body.len() {
+		let id = body[position];
+		let mut size = body[position + 1] as usize;
+		position += 2;
+
+		if id & ID_LARGE != 0 {
+			if position + 2 > body.len() {
+				return Err(error!("wavpack sub-block header is truncated"));
+			}
+			size |= (body[position] as usize) << 8 | (body[position + 1] as usize) << 16;
+			position += 2;
+		}
+
+		// sizes are counted in 16-bit words
+		let stored = size * 2;
+		if position + stored > body.len() {
+			return Err(error!("wavpack sub-block {:#x} is truncated", id & ID_MASK));
+		}
+		let length = if id & ID_ODD_SIZE != 0 { stored.saturating_sub(1) } else { stored };
+		blocks.push(SubBlock { id: id & ID_MASK, data: &body[position..position + length] });
+		position += stored;
+	}
+	Ok(blocks)
+}
+
+/// Sample rate and channel count a block declares beyond its header flags.
+pub fn stream_info(header: &BlockHeader, body: &[u8]) -> Result<(u32, Option<u8>)> {
+	let mut sample_rate = header.sample_rate();
+	let mut channels = None;
+
+	for block in sub_blocks(body)? {
+		match block.id {
+			ID_SAMPLE_RATE if block.data.len() >= 3 => {
+				let data = block.data;
+				sample_rate = Some(u32::from_le_bytes([data[0], data[1], data[2], 0]));
+			}
+			ID_CHANNEL_INFO if !block.data.is_empty() => channels = Some(block.data[0]),
+			_ => {}
+		}
+	}
+
+	match sample_rate {
+		Some(rate) if rate > 0 => Ok((rate, channels)),
+		_ => Err(error!("wavpack block has no sample rate")),
+	}
+}
@@ -0,0 +1,740 @@
+use super::block::{self, BlockHeader, SubBlock};
+use crate::codecs::audio::bitstream::BitReader;
+use crate::core::frame::{AudioFormat, Channels, Frame, FrameAudio};
+use crate::core::packet::Packet;
+use crate::core::traits::Decoder;
+use crate::{error, message::Result};
+
+const MAX_TERMS: usize = 16;
+
+// float info flags
+const FLOAT_SHIFT_ONES: u8 = 0x01;
+const FLOAT_SHIFT_SAME: u8 = 0x02;
+const FLOAT_SHIFT_SENT: u8 = 0x04;
+const FLOAT_ZERO_SENT: u8 = 0x08;
+const FLOAT_ZERO_SIGN: u8 = 0x10;
+
+/// One decorrelation pass. `samples_a`/`samples_b` hold the history the
+/// pass predicts from, for the first and second channel.
+#[derive(Debug, Clone, Copy, Default)]
+struct Decorr {
+	term: i32,
+	delta: i32,
+	weight_a: i32,
+	weight_b: i32,
+	samples_a: [i32; 8],
+	samples_b: [i32; 8],
+}
+
+impl Decorr {
+	fn predict(weight: i32, sample: i32) -> i32 {
+		((weight as i64 * sample as i64 + 512) >> 10) as i32
+	}
+
+	fn update_weight(&mut self, sample: i32, residual: i32, second: bool) {
+		if sample == 0 || residual == 0 {
+			return;
+		}
+		let weight = if second { &mut self.weight_b } else { &mut self.weight_a };
+		if (sample ^ residual) < 0 {
+			*weight -= self.delta;
+		} else {
+			*weight += self.delta;
+		}
+	}
+
+	fn update_weight_clip(&mut self, sample: i32, residual: i32, second: bool) {
+		self.update_weight(sample, residual, second);
+		let weight = if second { &mut self.weight_b } else { &mut self.weight_a };
+		*weight = (*weight).clamp(-1024, 1024);
+	}
+
+	/// Prediction for passes that look back `term` samples (1..=8) or
+	/// extrapolate from the last two (17, 18).
+	fn history(samples: &mut [i32; 8], term: i32, position: usize) -> (i32, usize) {
+		match term {
+			17 => {
+				let value = samples[0].wrapping_mul(2).wrapping_sub(samples[1]);
+				samples[1] = samples[0];
+				(value, 0)
+			}
+			18 => {
+				let value = samples[0].wrapping_mul(3).wrapping_sub(samples[1]) >> 1;
+				samples[1] = samples[0];
+				(value, 0)
+			}
+			_ => (samples[position], (position + term as usize) & 7),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FloatInfo {
+	flags: u8,
+	shift: u32,
+	max_exp: i32,
+}
+
+/// Adaptive Golomb-like residual decoder state shared by the channels of a block.
+struct Entropy {
+	medians: [[u32; 3]; 2],
+	zero: bool,
+	one: bool,
+	zeroes: u32,
+}
+
+impl Entropy {
+	fn get_med(&self, channel: usize, index: usize) -> u32 {
+		(self.medians[channel][index] >> 4) + 1
+	}
+
+	fn inc_med(&mut self, channel: usize, index: usize) {
+		let median = &mut self.medians[channel][index];
+		let divisor = 128 >> index;
+		*median = median.wrapping_add((median.wrapping_add(divisor) / divisor) * 5);
+	}
+
+	fn dec_med(&mut self, channel: usize, index: usize) {
+		let median = &mut self.medians[channel][index];
+		let divisor = 128 >> index;
+		*median = median.wrapping_sub((median.wrapping_add(divisor - 2) / divisor) * 2);
+	}
+
+	/// Elias-gamma style count used by zero runs and long unary escapes.
+	fn read_count(bits: &mut BitReader) -> Result<u32> {
+		let count = bits.read_unary(33);
+		if count < 2 {
+			return Ok(count);
+		}
+		if count >= 32 {
+			return Err(error!("wavpack bitstream has an invalid escape code"));
+		}
+		Ok(bits.read_bits(count - 1) | (1 << (count - 1)))
+	}
+
+	fn read_tail(bits: &mut BitReader, k: u32) -> u32 {
+		if k < 1 {
+			return 0;
+		}
+		let p = 31 - k.leading_zeros();
+		let e = (1u32 << (p + 1)) - k - 1;
+		let value = bits.read_bits(p);
+		if value >= e { (value << 1) - e + bits.read_bit() } else { value }
+	}
+
+	fn read_value(&mut self, bits: &mut BitReader, channel: usize) -> Result<i32> {
+		if self.medians[0][0] < 2 && self.medians[1][0] < 2 && !self.zero && !self.one {
+			if self.zeroes > 0 {
+				self.zeroes -= 1;
+				if self.zeroes > 0 {
+					return Ok(0);
+				}
+			} else {
+				self.zeroes = Self::read_count(bits)?;
+				if self.zeroes > 0 {
+					self.medians = [[0; 3]; 2];
+					return Ok(0);
+				}
+			}
+		}
+
+		let band = if self.zero {
+			self.zero = false;
+			0
+		} else {
+			let mut code = bits.read_unary(33);
+			if code == 16 {
+				code += Self::read_count(bits)?;
+			}
+			let band = if self.one { (code >> 1) + 1 } else { code >> 1 };
+			self.one = code & 1 != 0;
+			self.zero = !self.one;
+			band
+		};
+
+		let (base, add) = match band {
+			0 => {
+				let add = self.get_med(channel, 0) - 1;
+				self.dec_med(channel, 0);
+				(0, add)
+			}
+			1 => {
+				let base = self.get_med(channel, 0);
+				let add = self.get_med(channel, 1) - 1;
+				self.inc_med(channel, 0);
+				self.dec_med(channel, 1);
+				(base, add)
+			}
+			2 => {
+				let base = self.get_med(channel, 0) + self.get_med(channel, 1);
+				let add = self.get_med(channel, 2) - 1;
+				self.inc_med(channel, 0);
+				self.inc_med(channel, 1);
+				self.dec_med(channel, 2);
+				(base, add)
+			}
+			_ => {
+				let base = self
+					.get_med(channel, 0)
+					.wrapping_add(self.get_med(channel, 1))
+					.wrapping_add(self.get_med(channel, 2).wrapping_mul(band - 2));
+				let add = self.get_med(channel, 2) - 1;
+				self.inc_med(channel, 0);
+				self.inc_med(channel, 1);
+				self.inc_med(channel, 2);
+				(base, add)
+			}
+		};
+
+		if add >= 0x200_0000 {
+			return Err(error!("wavpack residual range {} is too large", add));
+		}
+		let magnitude = base.wrapping_add(Self::read_tail(bits, add));
+		let negative = bits.read_bit() == 1;
+		if bits.bits_left() < 0 {
+			return Err(error!("wavpack bitstream ended early"));
+		}
+		Ok(if negative { !magnitude as i32 } else { magnitude as i32 })
+	}
+}
+
+/// Correction data for samples wider than the main bitstream carries:
+/// the low bits of 32-bit integers and the exact mantissa of floats.
+struct ExtraBits<'a> {
+	bits: BitReader<'a>,
+	crc: u32,
+}
+
+struct BlockDecoder<'a> {
+	header: BlockHeader,
+	stereo: bool,
+	terms: Vec<Decorr>,
+	entropy: Entropy,
+	bitstream: Option<BitReader<'a>>,
+	extra: Option<ExtraBits<'a>>,
+	float: Option<FloatInfo>,
+	extra_bits: u32,
+	shift: u32,
+	and: i32,
+	or: i32,
+}
+
+impl<'a> BlockDecoder<'a> {
+	fn new(header: BlockHeader, body: &'a [u8]) -> Result<Self> {
+		if header.flags & block::HYBRID != 0 {
+			return Err(error!("wavpack hybrid (lossy) blocks are not supported"));
+		}
+		if header.flags & block::DSD != 0 {
+			return Err(error!("wavpack dsd blocks are not supported"));
+		}
+
+		let mut decoder = Self {
+			header,
+			stereo: header.channel_count() == 2 && !header.is_false_stereo(),
+			terms: Vec::new(),
+			entropy: Entropy { medians: [[0; 3]; 2], zero: false, one: false, zeroes: 0 },
+			bitstream: None,
+			extra: None,
+			float: None,
+			extra_bits: 0,
+			shift: 0,
+			and: 0,
+			or: 0,
+		};
+
+		let mut got_entropy = false;
+		for sub_block in block::sub_blocks(body)? {
+			match sub_block.id {
+				block::ID_DECORR_TERMS => decoder.read_terms(sub_block)?,
+				block::ID_DECORR_WEIGHTS => decoder.read_weights(sub_block)?,
+				block::ID_DECORR_SAMPLES => decoder.read_samples(sub_block)?,
+				block::ID_ENTROPY_VARS => {
+					decoder.read_entropy(sub_block)?;
+					got_entropy = true;
+				}
+				block::ID_INT32_INFO => decoder.read_int32_info(sub_block)?,
+				block::ID_FLOAT_INFO => decoder.read_float_info(sub_block)?,
+				block::ID_WV_BITSTREAM => decoder.bitstream = Some(BitReader::new(sub_block.data)),
+				block::ID_WVX_BITSTREAM => decoder.read_extra_bits(sub_block)?,
+				_ => {}
+			}
+		}
+
+		if !got_entropy {
+			return Err(error!("wavpack block has no entropy variables"));
+		}
+		if decoder.bitstream.is_none() {
+			return Err(error!("wavpack block has no bitstream"));
+		}
+		if header.is_float() && decoder.float.is_none() {
+			return Err(error!("wavpack float block has no float info"));
+		}
+		Ok(decoder)
+	}
+
+	fn read_terms(&mut self, sub_block: SubBlock) -> Result<()> {
+		if sub_block.data.len() > MAX_TERMS {
+			return Err(error!("wavpack block has too many decorrelation terms"));
+		}
+		// terms are stored in reverse order of application
+		for byte in sub_block.data.iter().rev() {
+			let term = (byte & 0x1f) as i32 - 5;
+			let valid = match term {
+				1..=8 | 17 | 18 => true,
+				-3..=-1 => self.stereo,
+				_ => false,
+			};
+			if !valid {
+				return Err(error!("wavpack decorrelation term {} is invalid", term));
+			}
+			self.terms.push(Decorr { term, delta: (byte >> 5) as i32, ..Decorr::default() });
+		}
+		Ok(())
+	}
+
+	fn restore_weight(byte: u8) -> i32 {
+		let weight = (byte as i8 as i32) * 8;
+		if weight > 0 { weight + ((weight + 64) >> 7) } else { weight }
+	}
+
+	fn read_weights(&mut self, sub_block: SubBlock) -> Result<()> {
+		let per_term = if self.stereo { 2 } else { 1 };
+		let count = sub_block.data.len() / per_term;
+		if count > self.terms.len() {
+			return Err(error!("wavpack block has more weights than terms"));
+		}
+
+		let terms = self.terms.len();
+		for (index, weights) in sub_block.data.chunks_exact(per_term).enumerate() {
+			let term = &mut self.terms[terms - index - 1];
+			term.weight_a = Self::restore_weight(weights[0]);
+			if self.stereo {
+				term.weight_b = Self::restore_weight(weights[1]);
+			}
+		}
+		Ok(())
+	}
+
+	fn read_samples(&mut self, sub_block: SubBlock) -> Result<()> {
+		let words = sub_block.data.chunks_exact(2);
+		let mut words = words.map(|word| exp2(i16::from_le_bytes([word[0], word[1]]))).peekable();
+
+		// history is stored for the last applied terms first, as far as the data goes
+		for term in self.terms.iter_mut().rev() {
+			if words.peek().is_none() {
+				break;
+			}
+			let mut next =
+				|| words.next().ok_or_else(|| error!("wavpack decorrelation samples are truncated"));
+			if term.term > 8 {
+				term.samples_a[0] = next()?;
+				term.samples_a[1] = next()?;
+				if self.stereo {
+					term.samples_b[0] = next()?;
+					term.samples_b[1] = next()?;
+				}
+			} else if term.term < 0 {
+				term.samples_a[0] = next()?;
+				term.samples_b[0] = next()?;
+			} else {
+				for index in 0..term.term as usize {
+					term.samples_a[index] = next()?;
+					if self.stereo {
+						term.samples_b[index] = next()?;
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+
+	fn read_entropy(&mut self, sub_block: SubBlock) -> Result<()> {
+		let channels = if self.stereo { 2 } else { 1 };
+		if sub_block.data.len() != 6 * channels {
+			return Err(error!("wavpack entropy variables have invalid size"));
+		}
+		for (index, word) in sub_block.data.chunks_exact(2).enumerate() {
+			let value = exp2(i16::from_le_bytes([word[0], word[1]]));
+			self.entropy.medians[index / 3][index % 3] = value as u32;
+		}
+		Ok(())
+	}
+
+	fn read_int32_info(&mut self, sub_block: SubBlock) -> Result<()> {
+		let &[sent_bits, zeros, ones, dups] = sub_block.data else {
+			return Err(error!("wavpack int32 info has invalid size"));
+		};
+		if sent_bits > 30 {
+			return Err(error!("wavpack int32 info sends {} extra bits", sent_bits));
+		}
+		if sent_bits > 0 {
+			self.extra_bits = sent_bits as u32;
+		} else if zeros > 0 {
+			self.shift = zeros as u32;
+		} else if ones > 0 {
+			(self.and, self.or, self.shift) = (1, 1, ones as u32);
+		} else if dups > 0 {
+			(self.and, self.shift) = (1, dups as u32);
+		}
+		if self.shift > 31 {
+			return Err(error!("wavpack int32 shift {} is invalid", self.shift));
+		}
+		Ok(())
+	}
+
+	fn read_float_info(&mut self, sub_block: SubBlock) -> Result<()> {
+		let &[flags, shift, max_exp, _norm_exp] = sub_block.data else {
+			return Err(error!("wavpack float info has invalid size"));
+		};
+		if shift > 31 {
+			return Err(error!("wavpack float shift {} is invalid", shift));
+		}
+		self.float = Some(FloatInfo { flags, shift: shift as u32, max_exp: max_exp as i32 });
+		Ok(())
+	}
+
+	fn read_extra_bits(&mut self, sub_block: SubBlock<'a>) -> Result<()> {
+		let data = sub_block.data;
+		if data.len() <= 4 {
+			return Err(error!("wavpack extra bits sub-block is too small"));
+		}
+		let crc = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+		self.extra = Some(ExtraBits { bits: BitReader::new(&data[4..]), crc });
+		Ok(())
+	}
+
+	/// Decode the block into one sample buffer per output channel. Integer
+	/// samples are returned right-aligned to the stored width, float samples
+	/// as their IEEE bit patterns.
+	fn decode(mut self) -> Result<Vec<Vec<u32>>> {
+		let count = self.header.block_samples as usize;
+		let mut bits = self.bitstream.take().ok_or_else(|| error!("wavpack block has no bitstream"))?;
+		let mut crc = u32::MAX;
+		let mut extra_crc = u32::MAX;
+
+		let channels = if self.stereo {
+			let (left, right) = self.unpack_stereo(&mut bits, count, &mut crc)?;
+			let mut outputs = vec![Vec::with_capacity(count), Vec::with_capacity(count)];
+			// extra bits are interleaved like the samples they belong to
+			for (left, right) in left.into_iter().zip(right) {
+				outputs[0].push(self.output(left, &mut extra_crc));
+				outputs[1].push(self.output(right, &mut extra_crc));
+			}
+			outputs
+		} else {
+			let mono = self.unpack_mono(&mut bits, count, &mut crc)?;
+			let mono: Vec<u32> =
+				mono.into_iter().map(|value| self.output(value, &mut extra_crc)).collect();
+			match self.header.channel_count() {
+				2 => vec![mono.clone(), mono],
+				_ => vec![mono],
+			}
+		};
+
+		if crc != self.header.crc {
+			return Err(error!("wavpack block crc mismatch"));
+		}
+		if let Some(extra) = &self.extra
+			&& extra_crc != extra.crc
+		{
+			return Err(error!("wavpack extra bits crc mismatch"));
+		}
+		Ok(channels)
+	}
+
+	fn unpack_mono(&mut self, bits: &mut BitReader, count: usize, crc: &mut u32) -> Result<Vec<i32>> {
+		let mut output = Vec::with_capacity(count);
+		let mut position = 0;
+
+		for _ in 0..count {
+			let mut sample = self.entropy.read_value(bits, 0)?;
+			for term in self.terms.iter_mut() {
+				let (predicted, slot) = Decorr::history(&mut term.samples_a, term.term, position);
+				let decoded = sample.wrapping_add(Decorr::predict(term.weight_a, predicted));
+				term.update_weight(predicted, sample, false);
+				term.samples_a[slot] = decoded;
+				sample = decoded;
+			}
+			position = (position + 1) & 7;
+			*crc = crc.wrapping_mul(3).wrapping_add(sample as u32);
+			output.push(sample);
+		}
+		Ok(output)
+	}
+
+	fn unpack_stereo(
+		&mut self,
+		bits: &mut BitReader,
+		count: usize,
+		crc: &mut u32,
+	) -> Result<(Vec<i32>, Vec<i32>)> {
+		let mut left_output = Vec::with_capacity(count);
+		let mut right_output = Vec::with_capacity(count);
+		let mut position = 0;
+
+		for _ in 0..count {
+			let mut left = self.entropy.read_value(bits, 0)?;
+			let mut right = self.entropy.read_value(bits, 1)?;
+
+			for term in self.terms.iter_mut() {
+				match term.term {
+					1.. => {
+						let (a, slot) = Decorr::history(&mut term.samples_a, term.term, position);
+						let (b, _) = Decorr::history(&mut term.samples_b, term.term, position);
+						let decoded_left = left.wrapping_add(Decorr::predict(term.weight_a, a));
+						let decoded_right = right.wrapping_add(Decorr::predict(term.weight_b, b));
+						term.update_weight(a, left, false);
+						term.update_weight(b, right, true);
+						term.samples_a[slot] = decoded_left;
+						term.samples_b[slot] = decoded_right;
+						(left, right) = (decoded_left, decoded_right);
+					}
+					-1 => {
+						let decoded_left = left.wrapping_add(Decorr::predict(term.weight_a, term.samples_a[0]));
+						term.update_weight_clip(term.samples_a[0], left, false);
+						let decoded_right = right.wrapping_add(Decorr::predict(term.weight_b, decoded_left));
+						term.update_weight_clip(decoded_left, right, true);
+						term.samples_a[0] = decoded_right;
+						(left, right) = (decoded_left, decoded_right);
+					}
+					_ => {
+						let decoded_right =
+							right.wrapping_add(Decorr::predict(term.weight_b, term.samples_b[0]));
+						term.update_weight_clip(term.samples_b[0], right, true);
+						let mut source = decoded_right;
+						if term.term == -3 {
+							source = term.samples_a[0];
+							term.samples_a[0] = decoded_right;
+						}
+						let decoded_left = left.wrapping_add(Decorr::predict(term.weight_a, source));
+						term.update_weight_clip(source, left, false);
+						term.samples_b[0] = decoded_left;
+						(left, right) = (decoded_left, decoded_right);
+					}
+				}
+			}
+
+			position = (position + 1) & 7;
+			if self.header.flags & block::JOINT_STEREO != 0 {
+				right = right.wrapping_sub(left >> 1);
+				left = left.wrapping_add(right);
+			}
+			*crc =
+				crc.wrapping_mul(3).wrapping_add(left as u32).wrapping_mul(3).wrapping_add(right as u32);
+			left_output.push(left);
+			right_output.push(right);
+		}
+		Ok((left_output, right_output))
+	}
+
+	fn output(&mut self, value: i32, crc: &mut u32) -> u32 {
+		match self.float {
+			Some(info) if self.header.is_float() => self.float_value(info, value, crc),
+			_ => self.integer_value(value, crc) as u32,
+		}
+	}
+
+	fn integer_value(&mut self, value: i32, crc: &mut u32) -> i32 {
+		let mut value = value;
+		if self.extra_bits > 0 {
+			value = value.wrapping_shl(self.extra_bits);
+			if let Some(extra) = self.extra.as_mut()
+				&& extra.bits.bits_left() >= self.extra_bits as isize
+			{
+				value |= extra.bits.read_bits(self.extra_bits) as i32;
+				*crc = crc
+					.wrapping_mul(9)
+					.wrapping_add((value as u32 & 0xffff) * 3)
+					.wrapping_add(value as u32 >> 16);
+			}
+		}
+
+		let bit = (value & self.and) | self.or;
+		let value = value.wrapping_add(bit).wrapping_shl(self.shift).wrapping_sub(bit);
+		value.wrapping_shl(self.header.shift())
+	}
+
+	fn float_value(&mut self, info: FloatInfo, value: i32, crc: &mut u32) -> u32 {
+		let mut extra = self.extra.as_mut().map(|extra| &mut extra.bits);
+		let mut exponent = info.max_exp;
+		let mut sign = 0u32;
+		let mut mantissa: u32;
+
+		if value != 0 {
+			let value = value.wrapping_shl(info.shift);
+			sign = (value < 0) as u32;
+			mantissa = value.unsigned_abs();
+
+			if mantissa >= 0x100_0000 {
+				let sent = extra.as_mut().is_some_and(|bits| bits.read_bit() == 1);
+				mantissa = match extra.as_mut() {
+					Some(bits) if sent => bits.read_bits(23),
+					_ => 0,
+				};
+				exponent = 255;
+			} else if exponent != 0 {
+				let mut shift = 23 - (31 - mantissa.leading_zeros() as i32);
+				if exponent <= shift {
+					exponent -= 1;
+					shift = exponent;
+				}
+				exponent -= shift;
+
+				if shift > 0 {
+					mantissa <<= shift;
+					let fill = (1u32 << shift) - 1;
+					let same = info.flags & FLOAT_SHIFT_SAME != 0;
+					let sent = info.flags & FLOAT_SHIFT_SENT != 0;
+					if info.flags & FLOAT_SHIFT_ONES != 0
+						|| (same && extra.as_mut().is_some_and(|bits| bits.read_bit() == 1))
+					{
+						mantissa |= fill;
+					} else if let Some(bits) = extra.as_mut().filter(|_| sent) {
+						mantissa |= bits.read_bits(shift as u32);
+					}
+				}
+			}
+			mantissa &= 0x7f_ffff;
+		} else {
+			exponent = 0;
+			mantissa = 0;
+			if let Some(bits) = extra.as_mut()
+				&& info.flags & FLOAT_ZERO_SENT != 0
+			{
+				if bits.read_bit() == 1 {
+					mantissa = bits.read_bits(23);
+					if info.max_exp >= 25 {
+						exponent = bits.read_bits(8) as i32;
+					}
+					sign = bits.read_bit();
+				} else if info.flags & FLOAT_ZERO_SIGN != 0 {
+					sign = bits.read_bit();
+				}
+			}
+		}
+
+		let exponent = exponent as u32;
+		*crc =
+			crc.wrapping_mul(27).wrapping_add(mantissa * 9).wrapping_add(exponent * 3).wrapping_add(sign);
+		(sign << 31) | (exponent << 23) | mantissa
+	}
+}
+
+/// Inverse of the encoder's fixed-point log2, used for stored medians and
+/// decorrelation history.
+fn exp2(value: i16) -> i32 {
+	let negative = value < 0;
+	let value = (value as i32).unsigned_abs();
+	let exponent = value >> 8;
+	if exponent > 31 {
+		return i32::MIN;
+	}
+
+	let mantissa = ((256.0 * 2f64.powf((value & 0xff) as f64 / 256.0)).round() as u32 - 256) | 0x100;
+	let result = match exponent {
+		e if e > 9 => (mantissa << (e - 9)) as i32,
+		e => (mantissa >> (9 - e)) as i32,
+	};
+	if negative { -result } else { result }
+}
+
+/// Lossless WavPack decoder. Each packet holds the blocks of one frame; each
+/// block carries one or two channels and the decoded channels are
+/// interleaved in block order. 8 and 16-bit streams produce PCM16, 24-bit
+/// PCM24, and 32-bit integer or float streams produce float PCM32.
+pub struct WavPackDecoder {
+	sample_rate: u32,
+	channels: Channels,
+	bytes_per_sample: usize,
+	float: bool,
+}
+
+impl WavPackDecoder {
+	pub fn new(
+		sample_rate: u32,
+		channels: Channels,
+		bytes_per_sample: usize,
+		float: bool,
+	) -> Result<Self> {
+		if !(1..=4).contains(&bytes_per_sample) || (float && bytes_per_sample != 4) {
+			return Err(error!("wavpack sample size of {} bytes is invalid", bytes_per_sample));
+		}
+		Ok(Self { sample_rate, channels, bytes_per_sample, float })
+	}
+
+	pub fn audio_format(&self) -> AudioFormat {
+		match (self.bytes_per_sample, self.float) {
+			(1 | 2, _) => AudioFormat::PCM16,
+			(3, _) => AudioFormat::PCM24,
+			_ => AudioFormat::PCM32,
+		}
+	}
+
+	fn decode_frame(&self, data: &[u8]) -> Result<Vec<Vec<u32>>> {
+		let mut channels = Vec::new();
+		let mut data = data;
+
+		while !data.is_empty() {
+			let header = BlockHeader::parse(data)?;
+			if header.block_size() > data.len() {
+				return Err(error!("wavpack block is truncated"));
+			}
+			if header.bytes_stored() != self.bytes_per_sample || header.is_float() != self.float {
+				return Err(error!("wavpack sample format changes mid-stream"));
+			}
+
+			let body = &data[block::HEADER_SIZE..header.block_size()];
+			channels.extend(BlockDecoder::new(header, body)?.decode()?);
+			data = &data[header.block_size()..];
+		}
+
+		if channels.len() != self.channels.count() as usize {
+			let count = self.channels.count();
+			return Err(error!("wavpack frame has {} channels, expected {}", channels.len(), count));
+		}
+		Ok(channels)
+	}
+
+	fn interleave(&self, channels: &[Vec<u32>]) -> Vec<u8> {
+		let samples = channels.iter().map(|channel| channel.len()).min().unwrap_or(0);
+		let mut data = Vec::with_capacity(samples * channels.len() * 4);
+
+		for index in 0..samples {
+			for channel in channels {
+				let value = channel[index];
+				match (self.bytes_per_sample, self.float) {
+					(1, _) => data.extend_from_slice(&((value as i32) << 8).to_le_bytes()[..2]),
+					(2, _) => data.extend_from_slice(&value.to_le_bytes()[..2]),
+					(3, _) => data.extend_from_slice(&value.to_le_bytes()[..3]),
+					(_, true) => data.extend_from_slice(&value.to_le_bytes()),
+					(_, false) => {
+						let sample = value as i32 as f32 / 2_147_483_648.0;
+						data.extend_from_slice(&sample.to_le_bytes());
+					}
+				}
+			}
+		}
+		data
+	}
+}
+
+impl Decoder for WavPackDecoder {
+	fn decode(&mut self, packet: Packet) -> Result<Option<Frame>> {
+		if packet.is_empty() {
+			return Ok(None);
+		}
+
+		let channels = self.decode_frame(&packet.data)?;
+		let data = self.interleave(&channels);
+		if data.is_empty() {
+			return Ok(None);
+		}
+
+		let audio = FrameAudio::new(data, self.sample_rate, self.channels, self.audio_format());
+		Ok(Some(Frame::new_audio(audio, packet.stream_id).with_pts(packet.pts)))
+	}
+
+	fn flush(&mut self) -> Result<Option<Frame>> {
+		Ok(None)
+	}
+}
@@ -0,0 +1,5 @@
+pub mod block;
+pub mod decoder;
+
+pub use block::BlockHeader;
+pub use decoder::WavPackDecoder;
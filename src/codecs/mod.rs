@@ -2,5 +2,7 @@ pub mod audio;
 pub mod subtitle;
 pub mod video;
 
+pub use video::raw::{RawVideoDecoder, RawVideoEncoder};
+
 pub const UNKNOWN: &str = "unknown";
 pub const RAW: &str = "raw";
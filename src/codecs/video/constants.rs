@@ -28,3 +28,6 @@ pub const DNXHR: &str = "dnxhr";
 
 // experimental / emerging
 pub const VP10: &str = "vp10";
+
+// uncompressed
+pub const RAWVIDEO: &str = "rawvideo";
@@ -1,4 +1,5 @@
 // pub mod h264;
+pub mod raw;
 
 mod constants;
 pub use constants::*;
@@ -0,0 +1,41 @@
+use crate::core::frame::{Frame, FrameVideo, VideoFormat};
+use crate::core::packet::Packet;
+use crate::core::traits::Decoder;
+use crate::{error, message::Result};
+
+pub struct RawVideoDecoder {
+	width: u32,
+	height: u32,
+	format: VideoFormat,
+}
+
+impl RawVideoDecoder {
+	pub fn new(width: u32, height: u32, format: VideoFormat) -> Self {
+		Self { width, height, format }
+	}
+
+	pub fn frame_size(&self) -> usize {
+		self.format.expected_size(self.width, self.height)
+	}
+}
+
+impl Decoder for RawVideoDecoder {
+	fn decode(&mut self, packet: Packet) -> Result<Option<Frame>> {
+		if packet.is_empty() {
+			return Ok(None);
+		}
+
+		if packet.data.len() != self.frame_size() {
+			let (expected, actual) = (self.frame_size(), packet.data.len());
+			return Err(error!("raw video packet has {} bytes, expected {}", actual, expected));
+		}
+
+		let video = FrameVideo::new(packet.data, self.width, self.height, self.format, true);
+		let frame = Frame::new_video(video, packet.stream_id);
+		Ok(Some(frame.with_pts(packet.pts)))
+	}
+
+	fn flush(&mut self) -> Result<Option<Frame>> {
+		Ok(None)
+	}
+}
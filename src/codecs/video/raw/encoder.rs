@@ -0,0 +1,52 @@
+use crate::core::Encoder;
+use crate::core::frame::{Frame, FrameData, VideoFormat};
+use crate::core::packet::Packet;
+use crate::core::time::Time;
+use crate::{error, message::Result};
+
+/// Writes video frames out unchanged, one picture per packet. Frames must
+/// already have the size and pixel format the output was configured with.
+pub struct RawVideoEncoder {
+	width: u32,
+	height: u32,
+	format: VideoFormat,
+	time: Time,
+}
+
+impl RawVideoEncoder {
+	pub fn new(width: u32, height: u32, format: VideoFormat, time: Time) -> Self {
+		Self { width, height, format, time }
+	}
+}
+
+impl Encoder for RawVideoEncoder {
+	fn encode(&mut self, frame: Frame) -> Result<Option<Packet>> {
+		let (stream_id, pts) = (frame.stream_id, frame.pts);
+		let video = match frame.data {
+			FrameData::Video(video) => video,
+			_ => return Ok(None),
+		};
+
+		if video.width != self.width || video.height != self.height {
+			let (width, height) = (video.width, video.height);
+			return Err(error!(
+				"raw video frame is {}x{}, expected {}x{}",
+				width, height, self.width, self.height
+			));
+		}
+		if video.format != self.format {
+			return Err(error!("raw video frame is {:?}, expected {:?}", video.format, self.format));
+		}
+		if !video.is_valid() {
+			let (expected, actual) = (video.expected_size(), video.data.len());
+			return Err(error!("raw video frame has {} bytes, expected {}", actual, expected));
+		}
+
+		let packet = Packet::new(video.data, stream_id, self.time);
+		Ok(Some(packet.with_pts(pts).with_dts(pts).with_keyframe(true)))
+	}
+
+	fn flush(&mut self) -> Result<Option<Packet>> {
+		Ok(None)
+	}
+}
@@ -0,0 +1,5 @@
+pub mod decoder;
+pub mod encoder;
+
+pub use decoder::RawVideoDecoder;
+pub use encoder::RawVideoEncoder;
@@ -0,0 +1,264 @@
+use super::ApeFormat;
+use crate::codecs;
+use crate::codecs::audio::ape::decoder::{MAX_VERSION, MIN_VERSION};
+use crate::container::Metadata;
+use crate::container::apev2::ApeTag;
+use crate::container::id3v2;
+use crate::core::frame::Channels;
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream, time};
+use crate::io::{MediaRead, MediaSeek, ReadPrimitives, SeekFrom};
+use crate::{error, message::Result};
+
+/// Descriptor and header layout introduced with version 3.98.
+const DESCRIPTOR_VERSION: u16 = 3980;
+const DESCRIPTOR_SIZE: u32 = 52;
+const OLD_BLOCKS_PER_FRAME: u32 = 73728 * 4;
+
+// format flags of the old header
+const FLAG_8_BIT: u16 = 0x1;
+const FLAG_PEAK_LEVEL: u16 = 0x4;
+const FLAG_24_BIT: u16 = 0x8;
+const FLAG_SEEK_ELEMENTS: u16 = 0x10;
+const FLAG_CREATE_WAV_HEADER: u16 = 0x20;
+
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+	seek_table_size: u32,
+	wav_header_size: u32,
+	wav_tail_size: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FrameEntry {
+	position: u64,
+	size: u64,
+	/// Bytes before the frame start inside the first word.
+	skip: usize,
+}
+
+/// Demuxer for Monkey's Audio (`.ape`) files from version 3.95 on. A
+/// leading ID3v2 tag is skipped and a trailing APEv2 tag provides the
+/// metadata. Frames are stored as little-endian 32-bit words relative to
+/// the first frame; packets carry them byte swapped into stream order.
+pub struct ApeDemuxer<R: MediaRead + MediaSeek> {
+	reader: R,
+	format: ApeFormat,
+	streams: stream::Streams,
+	metadata: Metadata,
+	frames: Vec<FrameEntry>,
+	frame_index: usize,
+	/// The last frame may be padded past the end of the file to a whole word.
+	file_size: u64,
+}
+
+impl<R: MediaRead + MediaSeek> ApeDemuxer<R> {
+	pub fn new(mut reader: R) -> Result<Self> {
+		let file_size = reader.seek(SeekFrom::End(0))?;
+		let (metadata, data_end) = match ApeTag::read(&mut reader)? {
+			Some(tag) => (tag.metadata, tag.start),
+			None => (Metadata::new(), file_size),
+		};
+
+		let junk = id3v2::tag_size(&mut reader)?;
+		reader.seek(SeekFrom::Start(junk))?;
+
+		let mut id = [0u8; 4];
+		reader.read_exact(&mut id)?;
+		if &id != b"MAC " {
+			return Err(error!("missing 'MAC ' header"));
+		}
+		let version = reader.read_u16_le()?;
+		if !(MIN_VERSION..=MAX_VERSION).contains(&version) {
+			return Err(error!("monkey's audio version {} is not supported", version));
+		}
+
+		let (format, layout) = match version >= DESCRIPTOR_VERSION {
+			true => Self::read_descriptor(&mut reader, version)?,
+			false => Self::read_old_header(&mut reader, version)?,
+		};
+		format.validate()?;
+		if format.total_frames == 0 {
+			return Err(error!("monkey's audio file has no frames"));
+		}
+
+		let seek_table_start = reader.stream_position()?;
+		let first_frame =
+			seek_table_start + layout.seek_table_size as u64 + layout.wav_header_size as u64;
+		let entries = layout.seek_table_size as usize / 4;
+		if entries < format.total_frames as usize {
+			return Err(error!("monkey's audio seek table is too short"));
+		}
+		let mut seek_table = Vec::with_capacity(entries);
+		for _ in 0..entries {
+			seek_table.push(reader.read_u32_le()? as u64 + junk);
+		}
+
+		let audio_end = data_end.saturating_sub(layout.wav_tail_size as u64);
+		let frames = Self::frame_entries(&seek_table, &format, first_frame, audio_end)?;
+
+		let time = time::Time::new(1, format.sample_rate);
+		let codec_name = codecs::audio::APE.to_string();
+		let stream = stream::Stream::new(0, 0, stream::StreamKind::Audio, codec_name, time);
+		let streams = stream::Streams::new(vec![stream]);
+
+		Ok(Self { reader, format, streams, metadata, frames, frame_index: 0, file_size })
+	}
+
+	/// Descriptor and header of version 3.98 and later; the reader is left at
+	/// the seek table.
+	fn read_descriptor(reader: &mut R, version: u16) -> Result<(ApeFormat, Layout)> {
+		let _padding = reader.read_u16_le()?;
+		let descriptor_size = reader.read_u32_le()?;
+		let header_size = reader.read_u32_le()?;
+		let seek_table_size = reader.read_u32_le()?;
+		let wav_header_size = reader.read_u32_le()?;
+		let _audio_size = reader.read_u32_le()?;
+		let _audio_size_high = reader.read_u32_le()?;
+		let wav_tail_size = reader.read_u32_le()?;
+		let mut _md5 = [0u8; 16];
+		reader.read_exact(&mut _md5)?;
+		if descriptor_size < DESCRIPTOR_SIZE {
+			return Err(error!("monkey's audio descriptor is too small"));
+		}
+		reader.seek(SeekFrom::Current((descriptor_size - DESCRIPTOR_SIZE) as i64))?;
+
+		let header_start = reader.stream_position()?;
+		let compression_level = reader.read_u16_le()?;
+		let _flags = reader.read_u16_le()?;
+		let blocks_per_frame = reader.read_u32_le()?;
+		let final_frame_blocks = reader.read_u32_le()?;
+		let total_frames = reader.read_u32_le()?;
+		let bits_per_sample = reader.read_u16_le()?;
+		let channels = reader.read_u16_le()?;
+		let sample_rate = reader.read_u32_le()?;
+		reader.seek(SeekFrom::Start(header_start + header_size as u64))?;
+
+		let format = ApeFormat {
+			version,
+			compression_level,
+			channels: Self::channels(channels)?,
+			sample_rate,
+			bits_per_sample,
+			blocks_per_frame,
+			final_frame_blocks,
+			total_frames,
+		};
+		Ok((format, Layout { seek_table_size, wav_header_size, wav_tail_size }))
+	}
+
+	/// Header of versions before 3.98. The stored WAV header comes before the
+	/// seek table here and is skipped.
+	fn read_old_header(reader: &mut R, version: u16) -> Result<(ApeFormat, Layout)> {
+		let compression_level = reader.read_u16_le()?;
+		let flags = reader.read_u16_le()?;
+		let channels = reader.read_u16_le()?;
+		let sample_rate = reader.read_u32_le()?;
+		let wav_header_size = reader.read_u32_le()?;
+		let wav_tail_size = reader.read_u32_le()?;
+		let total_frames = reader.read_u32_le()?;
+		let final_frame_blocks = reader.read_u32_le()?;
+
+		if flags & FLAG_PEAK_LEVEL != 0 {
+			let _peak = reader.read_u32_le()?;
+		}
+		let seek_table_size = match flags & FLAG_SEEK_ELEMENTS != 0 {
+			true => reader.read_u32_le()?.saturating_mul(4),
+			false => total_frames.saturating_mul(4),
+		};
+		if flags & FLAG_CREATE_WAV_HEADER == 0 {
+			reader.seek(SeekFrom::Current(wav_header_size as i64))?;
+		}
+
+		let bits_per_sample = match flags {
+			flags if flags & FLAG_8_BIT != 0 => 8,
+			flags if flags & FLAG_24_BIT != 0 => 24,
+			_ => 16,
+		};
+		let format = ApeFormat {
+			version,
+			compression_level,
+			channels: Self::channels(channels)?,
+			sample_rate,
+			bits_per_sample,
+			blocks_per_frame: OLD_BLOCKS_PER_FRAME,
+			final_frame_blocks,
+			total_frames,
+		};
+		Ok((format, Layout { seek_table_size, wav_header_size: 0, wav_tail_size }))
+	}
+
+	fn channels(count: u16) -> Result<Channels> {
+		match count {
+			1 | 2 => Ok(Channels::from_count(count as u8)),
+			count => Err(error!("monkey's audio with {} channels is not supported", count)),
+		}
+	}
+
+	fn frame_entries(
+		seek_table: &[u64],
+		format: &ApeFormat,
+		first_frame: u64,
+		audio_end: u64,
+	) -> Result<Vec<FrameEntry>> {
+		let count = format.total_frames as usize;
+		let mut frames = Vec::with_capacity(count);
+		for index in 0..count {
+			let start = if index == 0 { first_frame } else { seek_table[index] };
+			let end = match index + 1 < count {
+				true => seek_table[index + 1],
+				false => audio_end,
+			};
+			if end <= start {
+				return Err(error!("monkey's audio frame {} has an invalid size", index));
+			}
+
+			// frames are addressed in words counted from the first frame
+			let skip = ((start - first_frame) & 3) as usize;
+			let size = (end - start + skip as u64 + 3) & !3;
+			frames.push(FrameEntry { position: start - skip as u64, size, skip });
+		}
+		Ok(frames)
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		let Some(entry) = self.frames.get(self.frame_index).copied() else {
+			return Ok(None);
+		};
+
+		self.reader.seek(SeekFrom::Start(entry.position))?;
+		let mut data = vec![0u8; entry.size as usize];
+		let available = entry.size.min(self.file_size.saturating_sub(entry.position)) as usize;
+		self.reader.read_exact(&mut data[..available])?;
+		for word in data.chunks_exact_mut(4) {
+			word.reverse();
+		}
+		data.drain(..entry.skip.min(data.len()));
+
+		let pts = self.frame_index as u64 * self.format.blocks_per_frame as u64;
+		self.frame_index += 1;
+
+		let time = time::Time::new(1, self.format.sample_rate);
+		let mut packet = Packet::new(data, 0, time).with_pts(pts as i64);
+		packet.dts = packet.pts;
+		packet.keyframe = true;
+		Ok(Some(packet))
+	}
+
+	pub fn format(&self) -> ApeFormat {
+		self.format
+	}
+
+	pub fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+}
+
+impl<R: MediaRead + MediaSeek> Demuxer for ApeDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
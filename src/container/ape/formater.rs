@@ -0,0 +1,63 @@
+use crate::codecs::audio::ape::ApeDecoder;
+use crate::container::wav;
+use crate::core::frame::Channels;
+use crate::{error, message::Result};
+
+#[derive(Debug, Clone, Copy)]
+pub struct ApeFormat {
+	/// Encoder version times 1000, e.g. 3990 for 3.99.
+	pub version: u16,
+	/// 1000 (fast) to 5000 (insane).
+	pub compression_level: u16,
+	pub channels: Channels,
+	pub sample_rate: u32,
+	pub bits_per_sample: u16,
+	pub blocks_per_frame: u32,
+	pub final_frame_blocks: u32,
+	pub total_frames: u32,
+}
+
+impl ApeFormat {
+	/// PCM format the decoded stream is written with by default. 8-bit
+	/// streams widen to 16 bits.
+	pub fn to_wav_format(&self) -> wav::WavFormat {
+		wav::WavFormat {
+			channels: self.channels,
+			sample_rate: self.sample_rate,
+			bit_depth: self.bits_per_sample.max(16),
+			format_code: 1,
+		}
+	}
+
+	pub fn total_blocks(&self) -> u64 {
+		match self.total_frames {
+			0 => 0,
+			frames => (frames as u64 - 1) * self.blocks_per_frame as u64 + self.final_frame_blocks as u64,
+		}
+	}
+
+	pub fn create_decoder(&self) -> Result<ApeDecoder> {
+		ApeDecoder::new(
+			self.version,
+			self.compression_level,
+			self.sample_rate,
+			self.channels,
+			self.bits_per_sample,
+			self.blocks_per_frame as u64,
+			self.total_blocks(),
+		)
+	}
+
+	pub fn validate(&self) -> Result<()> {
+		if self.channels.count() == 0 {
+			return Err(error!("channels must be non-zero"));
+		}
+		if self.sample_rate == 0 {
+			return Err(error!("sample rate must be non-zero"));
+		}
+		if self.blocks_per_frame == 0 || self.final_frame_blocks > self.blocks_per_frame {
+			return Err(error!("monkey's audio frame size is invalid"));
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,4 @@
+pub mod demuxer;
+pub mod formater;
+pub use demuxer::ApeDemuxer;
+pub use formater::ApeFormat;
@@ -0,0 +1,110 @@
+use super::Metadata;
+use crate::io::{MediaRead, MediaSeek, ReadPrimitives, SeekFrom};
+use crate::{error, message::Result};
+
+const PREAMBLE: &[u8; 8] = b"APETAGEX";
+const FOOTER_SIZE: u64 = 32;
+const ID3V1_SIZE: u64 = 128;
+const FLAG_HAS_HEADER: u32 = 1 << 31;
+const ITEM_TYPE_TEXT: u32 = 0;
+
+#[derive(Debug, Clone)]
+pub struct ApeTag {
+	pub metadata: Metadata,
+	/// File offset of the first tag byte (its header when present), i.e. where
+	/// the audio data ends.
+	pub start: u64,
+}
+
+impl ApeTag {
+	/// Look for a tag footer at the end of the stream, also behind a trailing
+	/// ID3v1 tag. The stream is rewound to the start afterwards.
+	pub fn read<R: MediaRead + MediaSeek>(reader: &mut R) -> Result<Option<Self>> {
+		let file_size = reader.seek(SeekFrom::End(0))?;
+		let mut end = file_size;
+		if file_size >= ID3V1_SIZE && Self::has_id3v1(reader, file_size)? {
+			end -= ID3V1_SIZE;
+		}
+
+		let tag = match end >= FOOTER_SIZE {
+			true => Self::read_footer(reader, end)?,
+			false => None,
+		};
+		reader.seek(SeekFrom::Start(0))?;
+		Ok(tag)
+	}
+
+	fn has_id3v1<R: MediaRead + MediaSeek>(reader: &mut R, file_size: u64) -> Result<bool> {
+		reader.seek(SeekFrom::Start(file_size - ID3V1_SIZE))?;
+		let mut id = [0u8; 3];
+		reader.read_exact(&mut id)?;
+		Ok(&id == b"TAG")
+	}
+
+	fn read_footer<R: MediaRead + MediaSeek>(reader: &mut R, end: u64) -> Result<Option<Self>> {
+		reader.seek(SeekFrom::Start(end - FOOTER_SIZE))?;
+		let mut preamble = [0u8; 8];
+		reader.read_exact(&mut preamble)?;
+		if &preamble != PREAMBLE {
+			return Ok(None);
+		}
+
+		let version = reader.read_u32_le()?;
+		let size = reader.read_u32_le()? as u64;
+		let count = reader.read_u32_le()?;
+		let flags = reader.read_u32_le()?;
+		if version != 1000 && version != 2000 {
+			return Err(error!("ape tag version {} is not supported", version));
+		}
+		if size < FOOTER_SIZE || size > end {
+			return Err(error!("ape tag has invalid size {}", size));
+		}
+
+		let items_start = end - size;
+		let start = match flags & FLAG_HAS_HEADER != 0 {
+			true => items_start.saturating_sub(FOOTER_SIZE),
+			false => items_start,
+		};
+
+		reader.seek(SeekFrom::Start(items_start))?;
+		let mut items = vec![0u8; (size - FOOTER_SIZE) as usize];
+		reader.read_exact(&mut items)?;
+
+		let metadata = Self::parse_items(&items, count)?;
+		Ok(Some(Self { metadata, start }))
+	}
+
+	fn parse_items(data: &[u8], count: u32) -> Result<Metadata> {
+		let mut metadata = Metadata::new();
+		let mut data = data;
+
+		for _ in 0..count {
+			if data.len() < 8 {
+				return Err(error!("ape tag item is truncated"));
+			}
+			let value_size = (&data[0..4]).read_u32_le()? as usize;
+			let flags = (&data[4..8]).read_u32_le()?;
+			data = &data[8..];
+
+			let key_end = data.iter().position(|byte| *byte == 0);
+			let key_end = key_end.ok_or_else(|| error!("ape tag item key is not terminated"))?;
+			let key = String::from_utf8_lossy(&data[..key_end]).to_lowercase();
+			data = &data[key_end + 1..];
+
+			if value_size > data.len() {
+				return Err(error!("ape tag item '{}' is truncated", key));
+			}
+			let (value, rest) = data.split_at(value_size);
+			data = rest;
+
+			// binary items (cover art) and external links are not kept
+			if (flags >> 1) & 3 != ITEM_TYPE_TEXT {
+				continue;
+			}
+			// lists are stored as null separated values
+			let values: Vec<_> = value.split(|byte| *byte == 0).map(String::from_utf8_lossy).collect();
+			metadata.set(&key, values.join("; "));
+		}
+		Ok(metadata)
+	}
+}
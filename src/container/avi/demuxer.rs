@@ -0,0 +1,288 @@
+use super::formater::{self, AviMainHeader, AviStream};
+use super::index;
+use crate::container::riff::{self, ChunkHeader};
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream};
+use crate::io::{MediaRead, MediaSeek, ReadPrimitives, SeekFrom};
+use crate::{error, message::Result};
+
+#[derive(Debug, Clone, Copy)]
+struct AviSample {
+	stream: usize,
+	offset: u64,
+	size: u32,
+	keyframe: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Movi {
+	start: u64,
+	end: u64,
+}
+
+pub struct AviDemuxer<R: MediaRead + MediaSeek> {
+	reader: R,
+	main_header: AviMainHeader,
+	avi_streams: Vec<AviStream>,
+	streams: stream::Streams,
+	samples: Vec<AviSample>,
+	positions: Vec<i64>,
+	next: usize,
+}
+
+impl<R: MediaRead + MediaSeek> AviDemuxer<R> {
+	pub fn new(mut reader: R) -> Result<Self> {
+		let file_size = reader.stream_len()?;
+		reader.seek(SeekFrom::Start(0))?;
+		let riff_size = riff::read_form(&mut reader, formater::FORM_AVI)?;
+		// writers that never patched the size leave it at zero
+		let riff_end = match riff_size {
+			0 => file_size,
+			size => (12 + size as u64).min(file_size),
+		};
+
+		let mut hdrl = None;
+		let mut idx1 = None;
+		let mut movi = Vec::new();
+		let mut position = 12;
+		while position + 8 <= riff_end {
+			reader.seek(SeekFrom::Start(position))?;
+			let header = ChunkHeader::read(&mut reader)?;
+			match (header.id, header.is_list()) {
+				(_, true) if header.size >= 4 => match riff::read_fourcc(&mut reader)? {
+					formater::HDRL => {
+						let chunks = ChunkHeader { id: header.id, size: header.size - 4 };
+						hdrl = Some(riff::read_body(&mut reader, &chunks)?);
+					}
+					formater::MOVI => movi.push(Movi::new(position, &header, file_size)),
+					_ => {}
+				},
+				(formater::IDX1, _) => idx1 = Some(riff::read_body(&mut reader, &header)?),
+				_ => {}
+			}
+			position += 8 + header.padded_size();
+		}
+		Self::find_extensions(&mut reader, riff_end + riff_end % 2, file_size, &mut movi)?;
+
+		let hdrl = hdrl.ok_or_else(|| error!("avi file has no header list"))?;
+		let main_header = riff::find(&hdrl, formater::AVIH)
+			.ok_or_else(|| error!("avi file has no main header"))
+			.and_then(AviMainHeader::parse)?;
+
+		// data chunks are numbered by `strl` order, including stream types
+		// that are not read
+		let mut avi_streams = Vec::new();
+		let mut numbers = Vec::new();
+		let mut super_indexes = Vec::new();
+		for (number, chunks) in Self::stream_lists(&hdrl).enumerate() {
+			let Some(avi_stream) = AviStream::parse(chunks)? else {
+				continue;
+			};
+			let super_index =
+				riff::find(chunks, formater::INDX).map(index::parse_super_index).transpose()?;
+			super_indexes.push(super_index.filter(|entries| !entries.is_empty()));
+			numbers.push(number);
+			avi_streams.push(avi_stream);
+		}
+		if avi_streams.is_empty() {
+			return Err(error!("avi file has no audio or video streams"));
+		}
+
+		let mut samples = Vec::new();
+		if super_indexes.iter().all(Option::is_some) {
+			for (stream, entries) in super_indexes.iter().flatten().enumerate() {
+				Self::read_standard_indexes(&mut reader, stream, entries, file_size, &mut samples)?;
+			}
+		} else {
+			let mut scanned = movi.as_slice();
+			if let (Some(idx1), Some(first)) = (&idx1, movi.first()) {
+				Self::read_idx1(idx1, first, &numbers, file_size, &mut samples);
+				scanned = &movi[1..];
+			}
+			for list in scanned {
+				Self::scan_movi(&mut reader, list, &numbers, &mut samples)?;
+			}
+		}
+		samples.sort_by_key(|sample| sample.offset);
+
+		let mut streams = stream::Streams::new_empty();
+		for (index, avi_stream) in avi_streams.iter().enumerate() {
+			streams.add(avi_stream.to_stream(index));
+		}
+		let positions = avi_streams.iter().map(|stream| stream.header.start as i64).collect();
+		Ok(Self { reader, main_header, avi_streams, streams, samples, positions, next: 0 })
+	}
+
+	fn find_extensions(
+		reader: &mut R,
+		start: u64,
+		file_size: u64,
+		movi: &mut Vec<Movi>,
+	) -> Result<()> {
+		let mut position = start;
+		while position + 12 <= file_size {
+			reader.seek(SeekFrom::Start(position))?;
+			let header = ChunkHeader::read(reader)?;
+			if header.id != riff::RIFF || riff::read_fourcc(reader)? != formater::FORM_AVIX {
+				break;
+			}
+			let end = (position + 8 + header.size as u64).min(file_size);
+			let mut child = position + 12;
+			while child + 12 <= end {
+				reader.seek(SeekFrom::Start(child))?;
+				let list = ChunkHeader::read(reader)?;
+				if list.id == riff::LIST && riff::read_fourcc(reader)? == formater::MOVI {
+					movi.push(Movi::new(child, &list, file_size));
+				}
+				child += 8 + list.padded_size();
+			}
+			position = end + end % 2;
+		}
+		Ok(())
+	}
+
+	fn stream_lists(hdrl: &[u8]) -> impl Iterator<Item = &[u8]> {
+		riff::Chunks::new(hdrl)
+			.filter(|(id, _)| *id == riff::LIST)
+			.filter_map(|(_, body)| riff::split_list(body))
+			.filter(|(form, _)| *form == formater::STRL)
+			.map(|(_, chunks)| chunks)
+	}
+
+	fn read_standard_indexes(
+		reader: &mut R,
+		stream: usize,
+		entries: &[index::SuperIndexEntry],
+		file_size: u64,
+		samples: &mut Vec<AviSample>,
+	) -> Result<()> {
+		for entry in entries {
+			if entry.offset + 8 > file_size {
+				continue;
+			}
+			reader.seek(SeekFrom::Start(entry.offset))?;
+			let header = ChunkHeader::read(reader)?;
+			let body = riff::read_body(reader, &header)?;
+			for chunk in index::parse_standard_index(&body)? {
+				if chunk.offset + chunk.size as u64 <= file_size {
+					samples.push(AviSample {
+						stream,
+						offset: chunk.offset,
+						size: chunk.size,
+						keyframe: chunk.keyframe,
+					});
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// `idx1` offsets point at chunk headers, from the `movi` form type in most
+	/// files and from the start of the file in some.
+	fn read_idx1(
+		body: &[u8],
+		movi: &Movi,
+		numbers: &[usize],
+		file_size: u64,
+		samples: &mut Vec<AviSample>,
+	) {
+		let entries = index::parse_idx1(body);
+		let relative = entries
+			.iter()
+			.find(|entry| formater::stream_number(&entry.id).is_some())
+			.is_none_or(|entry| (entry.offset as u64) < movi.start);
+		let base = if relative { movi.start } else { 0 };
+		for entry in entries {
+			let Some(stream) = Self::stream_of(&entry.id, numbers) else {
+				continue;
+			};
+			let offset = base + entry.offset as u64 + 8;
+			if offset + entry.size as u64 > file_size {
+				continue;
+			}
+			let keyframe = entry.flags & formater::AVIIF_KEYFRAME != 0;
+			samples.push(AviSample { stream, offset, size: entry.size, keyframe });
+		}
+	}
+
+	fn scan_movi(
+		reader: &mut R,
+		movi: &Movi,
+		numbers: &[usize],
+		samples: &mut Vec<AviSample>,
+	) -> Result<()> {
+		let mut position = movi.start + 4;
+		while position + 8 <= movi.end {
+			reader.seek(SeekFrom::Start(position))?;
+			let header = ChunkHeader::read(reader)?;
+			if header.id == riff::LIST {
+				position += 12;
+				continue;
+			}
+			if let Some(stream) = Self::stream_of(&header.id, numbers) {
+				let size = header.size.min((movi.end - position - 8) as u32);
+				samples.push(AviSample { stream, offset: position + 8, size, keyframe: true });
+			}
+			position += 8 + header.padded_size();
+		}
+		Ok(())
+	}
+
+	fn stream_of(id: &riff::FourCC, numbers: &[usize]) -> Option<usize> {
+		if id.starts_with(b"ix") {
+			return None;
+		}
+		let number = formater::stream_number(id)?;
+		numbers.iter().position(|candidate| *candidate == number)
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		while let Some(sample) = self.samples.get(self.next).copied() {
+			self.next += 1;
+			let avi_stream = &self.avi_streams[sample.stream];
+			let position = self.positions[sample.stream];
+			self.positions[sample.stream] += match avi_stream.header.sample_size {
+				0 => 1,
+				sample_size => (sample.size / sample_size) as i64,
+			};
+			// empty chunks stand for dropped frames
+			if sample.size == 0 {
+				continue;
+			}
+
+			self.reader.seek(SeekFrom::Start(sample.offset))?;
+			let mut data = vec![0u8; sample.size as usize];
+			self.reader.read_exact(&mut data)?;
+			let keyframe = sample.keyframe || avi_stream.kind() == stream::StreamKind::Audio;
+			let packet = Packet::new(data, sample.stream as u32, avi_stream.time())
+				.with_pts(position)
+				.with_dts(position)
+				.with_keyframe(keyframe);
+			return Ok(Some(packet));
+		}
+		Ok(None)
+	}
+
+	pub fn main_header(&self) -> &AviMainHeader {
+		&self.main_header
+	}
+
+	pub fn avi_streams(&self) -> &[AviStream] {
+		&self.avi_streams
+	}
+}
+
+impl Movi {
+	fn new(position: u64, header: &ChunkHeader, file_size: u64) -> Self {
+		Self { start: position + 8, end: (position + 8 + header.size as u64).min(file_size) }
+	}
+}
+
+impl<R: MediaRead + MediaSeek> Demuxer for AviDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
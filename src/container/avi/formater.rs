@@ -0,0 +1,515 @@
+use crate::codecs::{self, audio, video};
+use crate::container::riff::{self, FourCC};
+use crate::core::frame::VideoFormat;
+use crate::core::stream::{Stream, StreamKind};
+use crate::core::time::Time;
+use crate::io::{Cursor, ReadPrimitives};
+use crate::{error, message::Result};
+
+pub const FORM_AVI: FourCC = *b"AVI ";
+pub const FORM_AVIX: FourCC = *b"AVIX";
+pub const HDRL: FourCC = *b"hdrl";
+pub const AVIH: FourCC = *b"avih";
+pub const STRL: FourCC = *b"strl";
+pub const STRH: FourCC = *b"strh";
+pub const STRF: FourCC = *b"strf";
+pub const STRN: FourCC = *b"strn";
+pub const INDX: FourCC = *b"indx";
+pub const ODML: FourCC = *b"odml";
+pub const DMLH: FourCC = *b"dmlh";
+pub const MOVI: FourCC = *b"movi";
+pub const IDX1: FourCC = *b"idx1";
+pub const VIDS: FourCC = *b"vids";
+pub const AUDS: FourCC = *b"auds";
+
+pub const AVIF_HASINDEX: u32 = 0x10;
+pub const AVIF_ISINTERLEAVED: u32 = 0x100;
+pub const AVIIF_KEYFRAME: u32 = 0x10;
+
+pub const COMPRESSION_RGB: FourCC = [0; 4];
+
+const FORMAT_PCM: u16 = 0x0001;
+const FORMAT_FLOAT: u16 = 0x0003;
+const FORMAT_MP2: u16 = 0x0050;
+const FORMAT_MP3: u16 = 0x0055;
+const FORMAT_AAC: u16 = 0x00FF;
+const FORMAT_AC3: u16 = 0x2000;
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AviMainHeader {
+	pub micro_sec_per_frame: u32,
+	pub max_bytes_per_sec: u32,
+	pub flags: u32,
+	/// Frames in the first `RIFF` chunk only; `dmlh` holds the whole count.
+	pub total_frames: u32,
+	pub streams: u32,
+	pub suggested_buffer_size: u32,
+	pub width: u32,
+	pub height: u32,
+}
+
+impl AviMainHeader {
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		if data.len() < 40 {
+			return Err(error!("avi main header is truncated"));
+		}
+		let mut data = Cursor::new(data);
+		let micro_sec_per_frame = data.read_u32_le()?;
+		let max_bytes_per_sec = data.read_u32_le()?;
+		let _padding_granularity = data.read_u32_le()?;
+		let flags = data.read_u32_le()?;
+		let total_frames = data.read_u32_le()?;
+		let _initial_frames = data.read_u32_le()?;
+		Ok(Self {
+			micro_sec_per_frame,
+			max_bytes_per_sec,
+			flags,
+			total_frames,
+			streams: data.read_u32_le()?,
+			suggested_buffer_size: data.read_u32_le()?,
+			width: data.read_u32_le()?,
+			height: data.read_u32_le()?,
+		})
+	}
+
+	pub fn write(&self) -> Vec<u8> {
+		let mut out = Vec::with_capacity(56);
+		for value in [
+			self.micro_sec_per_frame,
+			self.max_bytes_per_sec,
+			0,
+			self.flags,
+			self.total_frames,
+			0,
+			self.streams,
+			self.suggested_buffer_size,
+			self.width,
+			self.height,
+		] {
+			out.extend_from_slice(&value.to_le_bytes());
+		}
+		out.extend_from_slice(&[0; 16]);
+		out
+	}
+}
+
+/// The `strh` chunk. Stream time runs in units of `scale / rate` seconds;
+/// a unit is a chunk when `sample_size` is zero, else `sample_size` bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct AviStreamHeader {
+	pub kind: FourCC,
+	pub handler: FourCC,
+	pub flags: u32,
+	pub scale: u32,
+	pub rate: u32,
+	pub start: u32,
+	pub length: u32,
+	pub suggested_buffer_size: u32,
+	pub sample_size: u32,
+	pub frame: [i16; 4],
+}
+
+impl AviStreamHeader {
+	fn new(kind: FourCC, handler: FourCC, scale: u32, rate: u32, sample_size: u32) -> Self {
+		Self {
+			kind,
+			handler,
+			flags: 0,
+			scale,
+			rate,
+			start: 0,
+			length: 0,
+			suggested_buffer_size: 0,
+			sample_size,
+			frame: [0; 4],
+		}
+	}
+
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		if data.len() < 48 {
+			return Err(error!("avi stream header is truncated"));
+		}
+		let mut cursor = Cursor::new(data);
+		let kind = riff::read_fourcc(&mut cursor)?;
+		let handler = riff::read_fourcc(&mut cursor)?;
+		let flags = cursor.read_u32_le()?;
+		let _priority_and_language = cursor.read_u32_le()?;
+		let _initial_frames = cursor.read_u32_le()?;
+		let mut header = Self::new(kind, handler, cursor.read_u32_le()?, cursor.read_u32_le()?, 0);
+		header.flags = flags;
+		header.start = cursor.read_u32_le()?;
+		header.length = cursor.read_u32_le()?;
+		header.suggested_buffer_size = cursor.read_u32_le()?;
+		let _quality = cursor.read_u32_le()?;
+		header.sample_size = cursor.read_u32_le()?;
+		if data.len() >= 56 {
+			for value in header.frame.iter_mut() {
+				*value = cursor.read_i16_le()?;
+			}
+		}
+		if header.scale == 0 || header.rate == 0 {
+			return Err(error!("avi stream header has a zero rate"));
+		}
+		Ok(header)
+	}
+
+	pub fn write(&self) -> Vec<u8> {
+		let mut out = Vec::with_capacity(56);
+		out.extend_from_slice(&self.kind);
+		out.extend_from_slice(&self.handler);
+		for value in [
+			self.flags,
+			0,
+			0,
+			self.scale,
+			self.rate,
+			self.start,
+			self.length,
+			self.suggested_buffer_size,
+			u32::MAX,
+			self.sample_size,
+		] {
+			out.extend_from_slice(&value.to_le_bytes());
+		}
+		for value in self.frame {
+			out.extend_from_slice(&value.to_le_bytes());
+		}
+		out
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct BitmapInfo {
+	pub width: i32,
+	pub height: i32,
+	pub bit_count: u16,
+	pub compression: FourCC,
+	pub size_image: u32,
+	pub extra: Vec<u8>,
+}
+
+impl BitmapInfo {
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		if data.len() < 40 {
+			return Err(error!("avi bitmap info header is truncated"));
+		}
+		let mut cursor = Cursor::new(data);
+		let size = (cursor.read_u32_le()? as usize).clamp(40, data.len());
+		let width = cursor.read_i32_le()?;
+		let height = cursor.read_i32_le()?;
+		let _planes = cursor.read_u16_le()?;
+		let bit_count = cursor.read_u16_le()?;
+		let compression = riff::read_fourcc(&mut cursor)?;
+		let size_image = cursor.read_u32_le()?;
+		Ok(Self { width, height, bit_count, compression, size_image, extra: data[size..].to_vec() })
+	}
+
+	pub fn write(&self) -> Vec<u8> {
+		let mut out = Vec::with_capacity(40 + self.extra.len());
+		out.extend_from_slice(&(40 + self.extra.len() as u32).to_le_bytes());
+		out.extend_from_slice(&self.width.to_le_bytes());
+		out.extend_from_slice(&self.height.to_le_bytes());
+		out.extend_from_slice(&1u16.to_le_bytes());
+		out.extend_from_slice(&self.bit_count.to_le_bytes());
+		out.extend_from_slice(&self.compression);
+		out.extend_from_slice(&self.size_image.to_le_bytes());
+		out.extend_from_slice(&[0; 16]);
+		out.extend_from_slice(&self.extra);
+		out
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct WaveFormat {
+	pub format_tag: u16,
+	pub channels: u16,
+	pub sample_rate: u32,
+	pub avg_bytes_per_sec: u32,
+	pub block_align: u16,
+	pub bits_per_sample: u16,
+	pub extra: Vec<u8>,
+}
+
+impl WaveFormat {
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		if data.len() < 14 {
+			return Err(error!("avi wave format is truncated"));
+		}
+		let mut cursor = Cursor::new(data);
+		let mut format = Self {
+			format_tag: cursor.read_u16_le()?,
+			channels: cursor.read_u16_le()?,
+			sample_rate: cursor.read_u32_le()?,
+			avg_bytes_per_sec: cursor.read_u32_le()?,
+			block_align: cursor.read_u16_le()?,
+			bits_per_sample: 0,
+			extra: Vec::new(),
+		};
+		if data.len() >= 16 {
+			format.bits_per_sample = cursor.read_u16_le()?;
+		}
+		if data.len() >= 18 {
+			let size = cursor.read_u16_le()? as usize;
+			format.extra = data[18..(18 + size).min(data.len())].to_vec();
+		}
+		Ok(format)
+	}
+
+	pub fn write(&self) -> Vec<u8> {
+		let mut out = Vec::with_capacity(18 + self.extra.len());
+		out.extend_from_slice(&self.format_tag.to_le_bytes());
+		out.extend_from_slice(&self.channels.to_le_bytes());
+		out.extend_from_slice(&self.sample_rate.to_le_bytes());
+		out.extend_from_slice(&self.avg_bytes_per_sec.to_le_bytes());
+		out.extend_from_slice(&self.block_align.to_le_bytes());
+		out.extend_from_slice(&self.bits_per_sample.to_le_bytes());
+		out.extend_from_slice(&(self.extra.len() as u16).to_le_bytes());
+		out.extend_from_slice(&self.extra);
+		out
+	}
+
+	fn tag(&self) -> u16 {
+		match self.format_tag {
+			FORMAT_EXTENSIBLE if self.extra.len() >= 10 => {
+				u16::from_le_bytes([self.extra[6], self.extra[7]])
+			}
+			tag => tag,
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub enum AviFormat {
+	Video(BitmapInfo),
+	Audio(WaveFormat),
+}
+
+#[derive(Debug, Clone)]
+pub struct AviStream {
+	pub header: AviStreamHeader,
+	pub format: AviFormat,
+	pub name: Option<String>,
+}
+
+impl AviStream {
+	pub fn parse(chunks: &[u8]) -> Result<Option<Self>> {
+		let header = riff::find(chunks, STRH).ok_or_else(|| error!("avi stream list has no header"))?;
+		let header = AviStreamHeader::parse(header)?;
+		let format = riff::find(chunks, STRF).ok_or_else(|| error!("avi stream list has no format"))?;
+		let format = match header.kind {
+			VIDS => AviFormat::Video(BitmapInfo::parse(format)?),
+			AUDS => AviFormat::Audio(WaveFormat::parse(format)?),
+			_ => return Ok(None),
+		};
+		let name = riff::find(chunks, STRN)
+			.map(|name| String::from_utf8_lossy(name).trim_end_matches('\0').to_string());
+		Ok(Some(Self { header, format, name }))
+	}
+
+	pub fn video(codec: &str, width: u32, height: u32, time: Time) -> Result<Self> {
+		let compression = match codec {
+			video::MJPEG => *b"MJPG",
+			video::MPEG4 => *b"FMP4",
+			video::H264 => *b"H264",
+			_ => return Err(error!("'{}' video cannot be written to avi", codec)),
+		};
+		let bitmap = BitmapInfo {
+			width: width as i32,
+			height: height as i32,
+			bit_count: 24,
+			compression,
+			size_image: width * height * 3,
+			extra: Vec::new(),
+		};
+		Ok(Self::video_with(bitmap, time))
+	}
+
+	pub fn raw_video(format: VideoFormat, width: u32, height: u32, time: Time) -> Result<Self> {
+		let (compression, bit_count) = match format {
+			VideoFormat::YUV420 => (*b"I420", 12),
+			VideoFormat::YUV422 => (*b"Y42B", 16),
+			VideoFormat::YUV444 => (*b"444P", 24),
+			VideoFormat::GRAY8 => (*b"Y800", 8),
+			_ => return Err(error!("'{}' pixel format cannot be written to avi", format.name())),
+		};
+		let bitmap = BitmapInfo {
+			width: width as i32,
+			height: height as i32,
+			bit_count,
+			compression,
+			size_image: format.expected_size(width, height) as u32,
+			extra: Vec::new(),
+		};
+		Ok(Self::video_with(bitmap, time))
+	}
+
+	fn video_with(bitmap: BitmapInfo, time: Time) -> Self {
+		let mut header = AviStreamHeader::new(VIDS, bitmap.compression, time.num, time.den, 0);
+		header.frame = [0, 0, bitmap.width as i16, bitmap.height.unsigned_abs() as i16];
+		Self { header, format: AviFormat::Video(bitmap), name: None }
+	}
+
+	pub fn audio(codec: &str, channels: u16, sample_rate: u32) -> Result<Self> {
+		let (format_tag, bits_per_sample, frame_size) = match codec {
+			audio::PCM_S16LE => (FORMAT_PCM, 16, 0),
+			audio::PCM_S24LE => (FORMAT_PCM, 24, 0),
+			audio::PCM_F32LE => (FORMAT_FLOAT, 32, 0),
+			audio::MP2 => (FORMAT_MP2, 0, 1152),
+			audio::MP3 => (FORMAT_MP3, 0, 1152),
+			audio::AAC => (FORMAT_AAC, 0, 1024),
+			audio::AC3 => (FORMAT_AC3, 0, 1536),
+			_ => return Err(error!("'{}' audio cannot be written to avi", codec)),
+		};
+		if channels == 0 || sample_rate == 0 {
+			return Err(error!("avi audio needs channels and a sample rate"));
+		}
+		// PCM counts in sample frames; compressed audio in codec frames, which
+		// readers take as variable bitrate when the block align matches the scale
+		let (block_align, avg_bytes_per_sec, sample_size) = match frame_size {
+			0 => {
+				let block_align = channels * bits_per_sample / 8;
+				(block_align, block_align as u32 * sample_rate, block_align as u32)
+			}
+			frame_size => (frame_size, 0, 0),
+		};
+		let wave = WaveFormat {
+			format_tag,
+			channels,
+			sample_rate,
+			avg_bytes_per_sec,
+			block_align,
+			bits_per_sample,
+			extra: Vec::new(),
+		};
+		let (scale, rate) = match frame_size {
+			0 => (block_align as u32, avg_bytes_per_sec),
+			frame_size => (frame_size as u32, sample_rate),
+		};
+		let header = AviStreamHeader::new(AUDS, [0; 4], scale, rate, sample_size);
+		Ok(Self { header, format: AviFormat::Audio(wave), name: None })
+	}
+
+	pub fn with_codec_private(mut self, data: Vec<u8>) -> Self {
+		match &mut self.format {
+			AviFormat::Video(bitmap) => bitmap.extra = data,
+			AviFormat::Audio(wave) => wave.extra = data,
+		}
+		self
+	}
+
+	pub fn with_name(mut self, name: Option<String>) -> Self {
+		self.name = name;
+		self
+	}
+
+	pub fn kind(&self) -> StreamKind {
+		match self.format {
+			AviFormat::Video(_) => StreamKind::Video,
+			AviFormat::Audio(_) => StreamKind::Audio,
+		}
+	}
+
+	pub fn codec(&self) -> &'static str {
+		match &self.format {
+			AviFormat::Video(bitmap) => video_codec(bitmap.compression),
+			AviFormat::Audio(wave) => match (wave.tag(), wave.bits_per_sample) {
+				(FORMAT_PCM, 16) => audio::PCM_S16LE,
+				(FORMAT_PCM, 24) => audio::PCM_S24LE,
+				(FORMAT_FLOAT, 32) => audio::PCM_F32LE,
+				(FORMAT_MP2, _) => audio::MP2,
+				(FORMAT_MP3, _) => audio::MP3,
+				(FORMAT_AAC | 0x1610 | 0x706D, _) => audio::AAC,
+				(FORMAT_AC3, _) => audio::AC3,
+				_ => codecs::UNKNOWN,
+			},
+		}
+	}
+
+	pub fn codec_private(&self) -> &[u8] {
+		match &self.format {
+			AviFormat::Video(bitmap) => &bitmap.extra,
+			AviFormat::Audio(wave) => &wave.extra,
+		}
+	}
+
+	pub fn time(&self) -> Time {
+		Time::new(self.header.scale, self.header.rate).simplify()
+	}
+
+	pub fn pixel_format(&self) -> Option<VideoFormat> {
+		let AviFormat::Video(bitmap) = &self.format else {
+			return None;
+		};
+		match &bitmap.compression.map(|byte| byte.to_ascii_uppercase()) {
+			b"I420" | b"IYUV" => Some(VideoFormat::YUV420),
+			b"Y42B" => Some(VideoFormat::YUV422),
+			b"444P" => Some(VideoFormat::YUV444),
+			b"Y800" | b"Y8  " | b"GREY" => Some(VideoFormat::GRAY8),
+			_ => None,
+		}
+	}
+
+	pub fn to_stream(&self, index: usize) -> Stream {
+		let stream =
+			Stream::new(index as u32, index, self.kind(), self.codec().to_string(), self.time());
+		stream.with_codec_private(self.codec_private().to_vec())
+	}
+
+	pub fn chunk_id(&self, index: usize) -> FourCC {
+		let suffix = match &self.format {
+			AviFormat::Video(bitmap) if bitmap.compression == COMPRESSION_RGB => *b"db",
+			AviFormat::Video(_) => *b"dc",
+			AviFormat::Audio(_) => *b"wb",
+		};
+		let digits = format!("{:02}", index % 100).into_bytes();
+		[digits[0], digits[1], suffix[0], suffix[1]]
+	}
+
+	pub fn write_headers(&self) -> Vec<u8> {
+		let mut out = Vec::new();
+		riff::write_chunk(&mut out, STRH, &self.header.write());
+		let format = match &self.format {
+			AviFormat::Video(bitmap) => bitmap.write(),
+			AviFormat::Audio(wave) => wave.write(),
+		};
+		riff::write_chunk(&mut out, STRF, &format);
+		if let Some(name) = &self.name {
+			riff::write_chunk(&mut out, STRN, format!("{}\0", name).as_bytes());
+		}
+		out
+	}
+}
+
+pub fn stream_number(id: &FourCC) -> Option<usize> {
+	let digits = match id {
+		[b'i', b'x', a, b] => [*a, *b],
+		[a, b, ..] => [*a, *b],
+	};
+	match digits {
+		[a @ b'0'..=b'9', b @ b'0'..=b'9'] => Some(((a - b'0') * 10 + (b - b'0')) as usize),
+		_ => None,
+	}
+}
+
+fn video_codec(compression: FourCC) -> &'static str {
+	match &compression.map(|byte| byte.to_ascii_uppercase()) {
+		b"MJPG" | b"AVRN" | b"LJPG" | b"JPGL" | b"DMB1" => video::MJPEG,
+		b"H264" | b"X264" | b"AVC1" | b"DAVC" => video::H264,
+		b"HEVC" | b"H265" | b"HVC1" => video::H265,
+		b"FMP4" | b"XVID" | b"DIVX" | b"DX50" | b"MP4V" | b"M4S2" => video::MPEG4,
+		[0, 0, 0, 0]
+		| b"I420"
+		| b"IYUV"
+		| b"YV12"
+		| b"Y42B"
+		| b"444P"
+		| b"Y800"
+		| b"Y8  "
+		| b"GREY"
+		| b"YUY2"
+		| b"UYVY"
+		| b"RAW " => video::RAWVIDEO,
+		_ => codecs::UNKNOWN,
+	}
+}
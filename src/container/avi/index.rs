@@ -0,0 +1,152 @@
+use crate::container::riff::FourCC;
+use crate::io::{Cursor, ReadPrimitives};
+use crate::{error, message::Result};
+
+const INDEX_OF_INDEXES: u8 = 0x00;
+const INDEX_OF_CHUNKS: u8 = 0x01;
+const NOT_KEYFRAME: u32 = 0x8000_0000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct IndexEntry {
+	pub id: FourCC,
+	pub flags: u32,
+	pub offset: u32,
+	pub size: u32,
+}
+
+pub fn parse_idx1(body: &[u8]) -> Vec<IndexEntry> {
+	body
+		.chunks_exact(16)
+		.map(|entry| {
+			let word =
+				|at: usize| u32::from_le_bytes([entry[at], entry[at + 1], entry[at + 2], entry[at + 3]]);
+			IndexEntry {
+				id: [entry[0], entry[1], entry[2], entry[3]],
+				flags: word(4),
+				offset: word(8),
+				size: word(12),
+			}
+		})
+		.collect()
+}
+
+pub fn write_idx1(entries: &[IndexEntry]) -> Vec<u8> {
+	let mut out = Vec::with_capacity(entries.len() * 16);
+	for entry in entries {
+		out.extend_from_slice(&entry.id);
+		out.extend_from_slice(&entry.flags.to_le_bytes());
+		out.extend_from_slice(&entry.offset.to_le_bytes());
+		out.extend_from_slice(&entry.size.to_le_bytes());
+	}
+	out
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SuperIndexEntry {
+	pub offset: u64,
+	pub size: u32,
+	pub duration: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkEntry {
+	pub offset: u64,
+	pub size: u32,
+	pub keyframe: bool,
+}
+
+fn read_index_header(cursor: &mut Cursor<&[u8]>, expected_type: u8) -> Result<(u16, u32)> {
+	let longs_per_entry = cursor.read_u16_le()?;
+	let _sub_type = cursor.read_u8()?;
+	let index_type = cursor.read_u8()?;
+	let entries = cursor.read_u32_le()?;
+	let _chunk_id = cursor.read_u32_le()?;
+	if index_type != expected_type {
+		return Err(error!("avi index has type {}, expected {}", index_type, expected_type));
+	}
+	Ok((longs_per_entry, entries))
+}
+
+pub fn parse_super_index(body: &[u8]) -> Result<Vec<SuperIndexEntry>> {
+	if body.len() < 24 {
+		return Err(error!("avi super index is truncated"));
+	}
+	let mut cursor = Cursor::new(body);
+	let (longs_per_entry, count) = read_index_header(&mut cursor, INDEX_OF_INDEXES)?;
+	if longs_per_entry != 4 || 24 + count as usize * 16 > body.len() {
+		return Err(error!("avi super index has a bad entry layout"));
+	}
+	cursor.set_position(24);
+	let mut entries = Vec::with_capacity(count as usize);
+	for _ in 0..count {
+		let offset = cursor.read_u64_le()?;
+		let size = cursor.read_u32_le()?;
+		let duration = cursor.read_u32_le()?;
+		entries.push(SuperIndexEntry { offset, size, duration });
+	}
+	Ok(entries)
+}
+
+/// An `indx` body with room for `capacity` entries, so that it can be
+/// rewritten in place as standard indexes are added.
+pub fn write_super_index(
+	chunk_id: FourCC,
+	entries: &[SuperIndexEntry],
+	capacity: usize,
+) -> Vec<u8> {
+	let mut out = Vec::with_capacity(24 + capacity * 16);
+	out.extend_from_slice(&4u16.to_le_bytes());
+	out.extend_from_slice(&[0, INDEX_OF_INDEXES]);
+	out.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+	out.extend_from_slice(&chunk_id);
+	out.extend_from_slice(&[0; 12]);
+	for entry in entries {
+		out.extend_from_slice(&entry.offset.to_le_bytes());
+		out.extend_from_slice(&entry.size.to_le_bytes());
+		out.extend_from_slice(&entry.duration.to_le_bytes());
+	}
+	out.resize(24 + capacity * 16, 0);
+	out
+}
+
+pub fn parse_standard_index(body: &[u8]) -> Result<Vec<ChunkEntry>> {
+	if body.len() < 24 {
+		return Err(error!("avi standard index is truncated"));
+	}
+	let mut cursor = Cursor::new(body);
+	let (longs_per_entry, count) = read_index_header(&mut cursor, INDEX_OF_CHUNKS)?;
+	if longs_per_entry != 2 || 24 + count as usize * 8 > body.len() {
+		return Err(error!("avi standard index has a bad entry layout"));
+	}
+	let base = cursor.read_u64_le()?;
+	let _reserved = cursor.read_u32_le()?;
+	let mut entries = Vec::with_capacity(count as usize);
+	for _ in 0..count {
+		let offset = cursor.read_u32_le()?;
+		let size = cursor.read_u32_le()?;
+		entries.push(ChunkEntry {
+			offset: base + offset as u64,
+			size: size & !NOT_KEYFRAME,
+			keyframe: size & NOT_KEYFRAME == 0,
+		});
+	}
+	Ok(entries)
+}
+
+/// An `ix##` body; entry offsets are stored relative to `base`, which must
+/// lie less than 4 GiB before each of them.
+pub fn write_standard_index(chunk_id: FourCC, base: u64, entries: &[ChunkEntry]) -> Vec<u8> {
+	let mut out = Vec::with_capacity(24 + entries.len() * 8);
+	out.extend_from_slice(&2u16.to_le_bytes());
+	out.extend_from_slice(&[0, INDEX_OF_CHUNKS]);
+	out.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+	out.extend_from_slice(&chunk_id);
+	out.extend_from_slice(&base.to_le_bytes());
+	out.extend_from_slice(&[0; 4]);
+	for entry in entries {
+		let flags = if entry.keyframe { 0 } else { NOT_KEYFRAME };
+		out.extend_from_slice(&((entry.offset - base) as u32).to_le_bytes());
+		out.extend_from_slice(&(entry.size | flags).to_le_bytes());
+	}
+	out
+}
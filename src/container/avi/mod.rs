@@ -0,0 +1,7 @@
+pub mod demuxer;
+pub mod formater;
+pub mod index;
+pub mod muxer;
+pub use demuxer::AviDemuxer;
+pub use formater::{AviFormat, AviMainHeader, AviStream, AviStreamHeader, BitmapInfo, WaveFormat};
+pub use muxer::AviMuxer;
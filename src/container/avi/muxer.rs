@@ -0,0 +1,246 @@
+use super::formater::{self, AviMainHeader, AviStream};
+use super::index::{self, ChunkEntry, IndexEntry, SuperIndexEntry};
+use crate::container;
+use crate::container::riff::{self, FourCC, OpenChunk};
+use crate::core::Muxer;
+use crate::core::compatible::Compatible;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, StreamKind};
+use crate::core::time::Time;
+use crate::io::{MediaSeek, MediaWrite, SeekFrom, WritePrimitives};
+use crate::{error, message::Result};
+
+/// Size a `RIFF` chunk is kept under; the `movi` data continues in `RIFF AVIX`
+/// chunks past it, which only OpenDML readers follow.
+pub const RIFF_LIMIT: u64 = 1 << 30;
+pub const SUPER_INDEX_ENTRIES: usize = 256;
+
+struct Output {
+	stream: AviStream,
+	chunk_id: FourCC,
+	index_id: FourCC,
+	units: u64,
+	riff_units: u64,
+	first_riff_chunks: u32,
+	max_size: u32,
+	entries: Vec<ChunkEntry>,
+	super_index: Vec<SuperIndexEntry>,
+}
+
+pub struct AviMuxer<W: MediaWrite + MediaSeek> {
+	writer: W,
+	outputs: Vec<Output>,
+	streams: stream::Streams,
+	hdrl_start: u64,
+	riff: OpenChunk,
+	movi: OpenChunk,
+	idx1: Option<Vec<IndexEntry>>,
+}
+
+impl<W: MediaWrite + MediaSeek> AviMuxer<W> {
+	pub fn new(mut writer: W, avi_streams: Vec<AviStream>) -> Result<Self> {
+		if avi_streams.is_empty() {
+			return Err(error!("avi muxer needs at least one stream"));
+		}
+		if avi_streams.len() > 100 {
+			return Err(error!("avi files hold at most 100 streams"));
+		}
+		let compatible = Compatible::new();
+		let Some(entry) = compatible.container(container::AVI) else {
+			return Err(error!("avi is not a known container"));
+		};
+
+		let mut outputs = Vec::new();
+		let mut streams = stream::Streams::new_empty();
+		for (index, avi_stream) in avi_streams.into_iter().enumerate() {
+			match avi_stream.kind() {
+				StreamKind::Video => entry.assert_video_codec(avi_stream.codec())?,
+				StreamKind::Audio => entry.assert_audio_codec(avi_stream.codec())?,
+				StreamKind::Subtitle => unreachable!("avi streams are video or audio"),
+			}
+			streams.add(avi_stream.to_stream(index));
+			let chunk_id = avi_stream.chunk_id(index);
+			outputs.push(Output {
+				index_id: [b'i', b'x', chunk_id[0], chunk_id[1]],
+				chunk_id,
+				stream: avi_stream,
+				units: 0,
+				riff_units: 0,
+				first_riff_chunks: 0,
+				max_size: 0,
+				entries: Vec::new(),
+				super_index: Vec::new(),
+			});
+		}
+
+		let riff = OpenChunk::begin(&mut writer, riff::RIFF, Some(formater::FORM_AVI))?;
+		let hdrl_start = writer.stream_position()?;
+		let mut muxer =
+			Self { writer, outputs, streams, hdrl_start, riff, movi: riff, idx1: Some(Vec::new()) };
+		let hdrl = muxer.header_list();
+		muxer.writer.write_all(&hdrl)?;
+		muxer.movi = OpenChunk::begin(&mut muxer.writer, riff::LIST, Some(formater::MOVI))?;
+		Ok(muxer)
+	}
+
+	fn header_list(&self) -> Vec<u8> {
+		let video = self.outputs.iter().find(|output| output.stream.kind() == StreamKind::Video);
+		let main = video.unwrap_or(&self.outputs[0]);
+		let (width, height) = match &main.stream.format {
+			formater::AviFormat::Video(bitmap) => (bitmap.width as u32, bitmap.height.unsigned_abs()),
+			formater::AviFormat::Audio(_) => (0, 0),
+		};
+		let header = &main.stream.header;
+		let main_header = AviMainHeader {
+			micro_sec_per_frame: (header.scale as u64 * 1_000_000 / header.rate as u64) as u32,
+			max_bytes_per_sec: 0,
+			flags: formater::AVIF_HASINDEX | formater::AVIF_ISINTERLEAVED,
+			total_frames: main.first_riff_chunks,
+			streams: self.outputs.len() as u32,
+			suggested_buffer_size: self.outputs.iter().map(|output| output.max_size).max().unwrap_or(0),
+			width,
+			height,
+		};
+
+		let mut chunks = Vec::new();
+		riff::write_chunk(&mut chunks, formater::AVIH, &main_header.write());
+		for output in &self.outputs {
+			let mut stream = output.stream.clone();
+			stream.header.length = output.units as u32;
+			stream.header.suggested_buffer_size = output.max_size;
+			let mut strl = stream.write_headers();
+			let super_index =
+				index::write_super_index(output.chunk_id, &output.super_index, SUPER_INDEX_ENTRIES);
+			riff::write_chunk(&mut strl, formater::INDX, &super_index);
+			riff::write_list(&mut chunks, formater::STRL, &strl);
+		}
+		// the frame count of all `RIFF` chunks, where `avih` has the first's
+		let mut dmlh = (main.units as u32).to_le_bytes().to_vec();
+		dmlh.resize(248, 0);
+		let mut odml = Vec::new();
+		riff::write_chunk(&mut odml, formater::DMLH, &dmlh);
+		riff::write_list(&mut chunks, formater::ODML, &odml);
+
+		let mut hdrl = Vec::new();
+		riff::write_list(&mut hdrl, formater::HDRL, &chunks);
+		hdrl
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		let index = self
+			.streams
+			.all()
+			.iter()
+			.position(|stream| stream.id == packet.stream_id)
+			.ok_or_else(|| error!("avi muxer has no stream {}", packet.stream_id))?;
+
+		let header = self.outputs[index].stream.header;
+		if header.sample_size == 0 {
+			// one chunk per frame: gaps in the timestamps become empty chunks
+			let target = Self::to_units(packet.dts, packet.time, &header);
+			while (self.outputs[index].units as i64) < target {
+				self.write_chunk(index, &[], false)?;
+			}
+		}
+		let keyframe = packet.keyframe || header.kind == formater::AUDS;
+		self.write_chunk(index, &packet.data, keyframe)
+	}
+
+	fn to_units(value: i64, time: Time, header: &formater::AviStreamHeader) -> i64 {
+		let numerator = value as i128 * time.num as i128 * header.rate as i128;
+		let denominator = time.den as i128 * header.scale as i128;
+		((numerator + denominator / 2).div_euclid(denominator)) as i64
+	}
+
+	fn write_chunk(&mut self, index: usize, data: &[u8], keyframe: bool) -> Result<()> {
+		let size = 8 + data.len() as u64 + data.len() as u64 % 2;
+		let position = self.writer.stream_position()?;
+		let written = position - self.riff.start;
+		if written + size + self.pending_index_size() > RIFF_LIMIT && position > self.movi.body() + 4 {
+			self.close_riff()?;
+			self.riff = OpenChunk::begin(&mut self.writer, riff::RIFF, Some(formater::FORM_AVIX))?;
+			self.movi = OpenChunk::begin(&mut self.writer, riff::LIST, Some(formater::MOVI))?;
+		}
+
+		let position = self.writer.stream_position()?;
+		let output = &mut self.outputs[index];
+		let mut chunk = Vec::with_capacity(size as usize);
+		riff::write_chunk(&mut chunk, output.chunk_id, data);
+		self.writer.write_all(&chunk)?;
+
+		let size = data.len() as u32;
+		output.entries.push(ChunkEntry { offset: position + 8, size, keyframe });
+		if let Some(idx1) = &mut self.idx1 {
+			let flags = if keyframe { formater::AVIIF_KEYFRAME } else { 0 };
+			let offset = (position - self.movi.body()) as u32;
+			idx1.push(IndexEntry { id: output.chunk_id, flags, offset, size });
+			output.first_riff_chunks += 1;
+		}
+		let units = match output.stream.header.sample_size {
+			0 => 1,
+			sample_size => (size / sample_size) as u64,
+		};
+		output.units += units;
+		output.riff_units += units;
+		output.max_size = output.max_size.max(size);
+		Ok(())
+	}
+
+	fn pending_index_size(&self) -> u64 {
+		let standard: usize = self.outputs.iter().map(|output| 32 + output.entries.len() * 8).sum();
+		let legacy = self.idx1.as_ref().map_or(0, |idx1| 8 + idx1.len() * 16);
+		(standard + legacy) as u64
+	}
+
+	fn close_riff(&mut self) -> Result<()> {
+		let base = self.movi.body();
+		for output in &mut self.outputs {
+			if output.entries.is_empty() {
+				continue;
+			}
+			if output.super_index.len() == SUPER_INDEX_ENTRIES {
+				return Err(error!("avi super index is full after {} riff chunks", SUPER_INDEX_ENTRIES));
+			}
+			let offset = self.writer.stream_position()?;
+			let body = index::write_standard_index(output.chunk_id, base, &output.entries);
+			let mut chunk = Vec::with_capacity(body.len() + 8);
+			riff::write_chunk(&mut chunk, output.index_id, &body);
+			self.writer.write_all(&chunk)?;
+			let duration = output.riff_units as u32;
+			output.super_index.push(SuperIndexEntry { offset, size: chunk.len() as u32, duration });
+			output.entries.clear();
+			output.riff_units = 0;
+		}
+		self.movi.end(&mut self.writer)?;
+
+		if let Some(idx1) = self.idx1.take() {
+			let mut chunk = Vec::with_capacity(idx1.len() * 16 + 8);
+			riff::write_chunk(&mut chunk, formater::IDX1, &index::write_idx1(&idx1));
+			self.writer.write_all(&chunk)?;
+		}
+		self.riff.end(&mut self.writer)?;
+		Ok(())
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		self.close_riff()?;
+
+		let hdrl = self.header_list();
+		self.writer.seek(SeekFrom::Start(self.hdrl_start))?;
+		self.writer.write_all(&hdrl)?;
+		self.writer.seek(SeekFrom::End(0))?;
+		self.writer.flush()
+	}
+}
+
+impl<W: MediaWrite + MediaSeek> Muxer for AviMuxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+}
@@ -0,0 +1,327 @@
+use super::formater::{CafFormat, FLAG_FLOAT, FLAG_LITTLE_ENDIAN};
+use super::layout::CafChannelLayout;
+use super::packet_table::CafPacketTable;
+use crate::container::Metadata;
+use crate::core::endian;
+use crate::core::frame::Channels;
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream, time};
+use crate::io::{MediaRead, ReadPrimitives};
+use crate::{error, message::Result};
+
+pub struct CafDemuxer<R: MediaRead> {
+	reader: R,
+	format: CafFormat,
+	source_flags: u32,
+	streams: stream::Streams,
+	metadata: Metadata,
+	layout: Option<CafChannelLayout>,
+	packet_table: Option<CafPacketTable>,
+	/// `None` when the `data` chunk size is -1 and runs to the end of the file.
+	data_remaining: Option<u64>,
+	packet_index: usize,
+	frame_position: u64,
+}
+
+impl<R: MediaRead> CafDemuxer<R> {
+	const CHUNK_SIZE_LIMIT: usize = 65536;
+	const UNKNOWN_SIZE: i64 = -1;
+
+	pub fn new(mut reader: R) -> Result<Self> {
+		Self::check_fourcc(&mut reader, "caff")?;
+		let version = reader.read_u16_be()?;
+		if version != 1 {
+			return Err(error!("caf version {} is not supported", version));
+		}
+		let _flags = reader.read_u16_be()?;
+
+		let mut format = None;
+		let mut metadata = Metadata::new();
+		let mut layout = None;
+		let mut packet_table = None;
+		let mut cookie = Vec::new();
+
+		let data_size = loop {
+			let chunk_id = Self::read_fourcc(&mut reader)?;
+			let chunk_size = reader.read_i64_be()?;
+
+			if chunk_id == "data" {
+				break Self::read_data_chunk(&mut reader, chunk_size)?;
+			}
+			if chunk_size < 0 {
+				return Err(error!("caf chunk '{}' has invalid size", chunk_id));
+			}
+
+			let chunk_size = chunk_size as u64;
+			match chunk_id.as_str() {
+				"desc" => format = Some(Self::read_desc_chunk(&mut reader, chunk_size)?),
+				"chan" => layout = Some(Self::read_chan_chunk(&mut reader, chunk_size)?),
+				"info" => Self::read_info_chunk(&mut reader, chunk_size, &mut metadata)?,
+				"kuki" => cookie = Self::read_bytes(&mut reader, chunk_size)?,
+				"pakt" => {
+					let desc = format.as_ref().ok_or_else(|| error!("caf pakt chunk before desc"))?;
+					let data = Self::read_bytes(&mut reader, chunk_size)?;
+					let table = CafPacketTable::parse(&data, desc.bytes_per_packet, desc.frames_per_packet)?;
+					packet_table = Some(table);
+				}
+				_ => Self::skip_bytes(&mut reader, chunk_size)?,
+			}
+		};
+
+		let (mut format, source_flags) = match format {
+			Some(format) => (format, format.format_flags),
+			None => return Err(error!("caf file has no desc chunk")),
+		};
+		format.validate()?;
+
+		if format.is_variable() && packet_table.is_none() {
+			return Err(error!("caf file with variable packets needs a pakt chunk before data"));
+		}
+
+		if format.is_pcm() {
+			// packets are handed out little-endian so they fit the pcm codecs
+			format.format_flags |= FLAG_LITTLE_ENDIAN;
+		}
+
+		let codec_name = format.to_codec_string().to_string();
+		let time = time::Time::new(1, format.sample_rate);
+		let stream = stream::Stream::new(0, 0, stream::StreamKind::Audio, codec_name, time);
+		let streams = stream::Streams::new(vec![stream.with_codec_private(cookie)]);
+
+		Ok(Self {
+			reader,
+			format,
+			source_flags,
+			streams,
+			metadata,
+			layout,
+			packet_table,
+			data_remaining: data_size,
+			packet_index: 0,
+			frame_position: 0,
+		})
+	}
+
+	fn read_desc_chunk(reader: &mut R, chunk_size: u64) -> Result<CafFormat> {
+		if chunk_size < 32 {
+			return Err(error!("desc chunk too small"));
+		}
+
+		let sample_rate = reader.read_f64_be()?;
+		let mut format_id = [0u8; 4];
+		reader.read_exact(&mut format_id)?;
+		let format_flags = reader.read_u32_be()?;
+		let bytes_per_packet = reader.read_u32_be()?;
+		let frames_per_packet = reader.read_u32_be()?;
+		let channel_count = reader.read_u32_be()?;
+		let bits_per_channel = reader.read_u32_be()?;
+
+		if !(sample_rate.is_finite() && sample_rate >= 1.0 && sample_rate <= u32::MAX as f64) {
+			return Err(error!("caf sample rate {} is not supported", sample_rate));
+		}
+		if channel_count > u8::MAX as u32 {
+			return Err(error!("caf channel count {} is not supported", channel_count));
+		}
+
+		let remaining = chunk_size - 32;
+		if remaining > 0 {
+			Self::skip_bytes(reader, remaining)?;
+		}
+
+		Ok(CafFormat {
+			channels: Channels::from_count(channel_count as u8),
+			sample_rate: sample_rate.round() as u32,
+			format_id,
+			format_flags: format_flags & (FLAG_FLOAT | FLAG_LITTLE_ENDIAN),
+			bytes_per_packet,
+			frames_per_packet,
+			bit_depth: bits_per_channel as u16,
+		})
+	}
+
+	fn read_chan_chunk(reader: &mut R, chunk_size: u64) -> Result<CafChannelLayout> {
+		if chunk_size < 12 {
+			return Err(error!("chan chunk too small"));
+		}
+		let data = Self::read_bytes(reader, chunk_size)?;
+		CafChannelLayout::read(&mut data.as_slice())
+	}
+
+	fn read_info_chunk(reader: &mut R, chunk_size: u64, metadata: &mut Metadata) -> Result<()> {
+		if chunk_size < 4 {
+			return Self::skip_bytes(reader, chunk_size);
+		}
+
+		let data = Self::read_bytes(reader, chunk_size)?;
+		let count = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+		let mut strings = data[4..].split(|byte| *byte == 0);
+
+		for _ in 0..count {
+			let (Some(key), Some(value)) = (strings.next(), strings.next()) else {
+				break;
+			};
+			let key = String::from_utf8_lossy(key).to_string();
+			metadata.set(&key, String::from_utf8_lossy(value).to_string());
+		}
+		Ok(())
+	}
+
+	fn read_data_chunk(reader: &mut R, chunk_size: i64) -> Result<Option<u64>> {
+		let _edit_count = reader.read_u32_be()?;
+		match chunk_size {
+			Self::UNKNOWN_SIZE => Ok(None),
+			size if size >= 4 => Ok(Some(size as u64 - 4)),
+			_ => Err(error!("caf data chunk has invalid size")),
+		}
+	}
+
+	fn read_fourcc(reader: &mut R) -> Result<String> {
+		let mut buf = [0u8; 4];
+		reader.read_exact(&mut buf)?;
+		Ok(String::from_utf8_lossy(&buf).to_string())
+	}
+
+	fn check_fourcc(reader: &mut R, expected: &str) -> Result<()> {
+		let actual = Self::read_fourcc(reader)?;
+		if actual != expected {
+			return Err(error!("expected {}, found {}", expected, actual));
+		}
+		Ok(())
+	}
+
+	fn read_bytes(reader: &mut R, size: u64) -> Result<Vec<u8>> {
+		let mut buf = vec![0u8; size as usize];
+		reader.read_exact(&mut buf)?;
+		Ok(buf)
+	}
+
+	fn skip_bytes(reader: &mut R, size: u64) -> Result<()> {
+		let mut buf = vec![0u8; size as usize];
+		reader.read_exact(&mut buf)?;
+		Ok(())
+	}
+
+	fn read_up_to(&mut self, size: usize) -> Result<Vec<u8>> {
+		let mut data = vec![0u8; size];
+		let mut filled = 0;
+		while filled < size {
+			let bytes_read = self.reader.read(&mut data[filled..])?;
+			if bytes_read == 0 {
+				break;
+			}
+			filled += bytes_read;
+		}
+		data.truncate(filled);
+		if let Some(remaining) = self.data_remaining.as_mut() {
+			*remaining -= filled as u64;
+		}
+		Ok(data)
+	}
+
+	fn limit(&self, size: u64) -> usize {
+		match self.data_remaining {
+			Some(remaining) => std::cmp::min(remaining, size) as usize,
+			None => size as usize,
+		}
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		if self.data_remaining == Some(0) {
+			return Ok(None);
+		}
+
+		let (data, frames) = if self.format.is_pcm() {
+			match self.read_pcm_chunk()? {
+				Some(chunk) => chunk,
+				None => return Ok(None),
+			}
+		} else {
+			match self.read_codec_packet()? {
+				Some(chunk) => chunk,
+				None => return Ok(None),
+			}
+		};
+
+		let time = time::Time::new(1, self.format.sample_rate);
+		let mut packet = Packet::new(data, 0, time).with_pts(self.frame_position as i64);
+		packet.dts = packet.pts;
+		packet.keyframe = true;
+
+		if let Some(table) = &self.packet_table {
+			let priming = table.priming_frames.max(0) as u64;
+			packet.discard = self.frame_position + frames <= priming;
+		}
+
+		self.frame_position += frames;
+		self.packet_index += 1;
+		Ok(Some(packet))
+	}
+
+	fn read_pcm_chunk(&mut self) -> Result<Option<(Vec<u8>, u64)>> {
+		let bytes_per_frame = self.format.bytes_per_frame();
+		let max_chunk = (Self::CHUNK_SIZE_LIMIT / bytes_per_frame) * bytes_per_frame;
+		let chunk_size = self.limit(max_chunk as u64);
+
+		let mut data = self.read_up_to(chunk_size)?;
+		data.truncate((data.len() / bytes_per_frame) * bytes_per_frame);
+		if data.is_empty() {
+			return Ok(None);
+		}
+
+		if self.source_flags & FLAG_LITTLE_ENDIAN == 0 {
+			endian::swap_samples(&mut data, self.format.bytes_per_sample());
+		}
+
+		let frames = (data.len() / bytes_per_frame) as u64;
+		Ok(Some((data, frames)))
+	}
+
+	fn read_codec_packet(&mut self) -> Result<Option<(Vec<u8>, u64)>> {
+		let entry = match &self.packet_table {
+			Some(table) => match table.entries.get(self.packet_index) {
+				Some(entry) => *entry,
+				None => return Ok(None),
+			},
+			None => super::CafPacketEntry {
+				size: self.format.bytes_per_packet,
+				frames: self.format.frames_per_packet,
+			},
+		};
+
+		let size = self.limit(entry.size as u64);
+		let data = self.read_up_to(size)?;
+		if data.len() < entry.size as usize {
+			return Ok(None);
+		}
+		Ok(Some((data, entry.frames as u64)))
+	}
+
+	pub fn read_audio_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+
+	pub fn format(&self) -> CafFormat {
+		self.format
+	}
+
+	pub fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+
+	pub fn channel_layout(&self) -> Option<&CafChannelLayout> {
+		self.layout.as_ref()
+	}
+
+	pub fn packet_table(&self) -> Option<&CafPacketTable> {
+		self.packet_table.as_ref()
+	}
+}
+
+impl<R: MediaRead> Demuxer for CafDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
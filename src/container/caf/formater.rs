@@ -0,0 +1,170 @@
+use crate::codecs;
+use crate::container::{raw, wav};
+use crate::core::frame::{AudioFormat, Channels};
+use crate::{error, message::Result};
+
+pub const FORMAT_LPCM: [u8; 4] = *b"lpcm";
+pub const FORMAT_AAC: [u8; 4] = *b"aac ";
+pub const FORMAT_ALAC: [u8; 4] = *b"alac";
+pub const FORMAT_FLAC: [u8; 4] = *b"flac";
+pub const FORMAT_OPUS: [u8; 4] = *b"opus";
+pub const FORMAT_MP3: [u8; 4] = *b".mp3";
+pub const FORMAT_AC3: [u8; 4] = *b"ac-3";
+
+pub const FLAG_FLOAT: u32 = 1;
+pub const FLAG_LITTLE_ENDIAN: u32 = 2;
+
+#[derive(Debug, Clone, Copy)]
+pub struct CafFormat {
+	pub channels: Channels,
+	pub sample_rate: u32,
+	pub format_id: [u8; 4],
+	pub format_flags: u32,
+	pub bytes_per_packet: u32,
+	pub frames_per_packet: u32,
+	pub bit_depth: u16,
+}
+
+impl Default for CafFormat {
+	fn default() -> Self {
+		// default is little-endian pcm_16
+		Self {
+			channels: Channels::Stereo,
+			sample_rate: 44100,
+			format_id: FORMAT_LPCM,
+			format_flags: FLAG_LITTLE_ENDIAN,
+			bytes_per_packet: 4,
+			frames_per_packet: 1,
+			bit_depth: 16,
+		}
+	}
+}
+
+impl CafFormat {
+	pub fn new_for_codec(codec: &str) -> std::result::Result<Self, String> {
+		let mut format = Self::default();
+		format.apply_codec(codec)?;
+		Ok(format)
+	}
+
+	pub fn from_raw_format(format: raw::RawPcmFormat) -> Self {
+		let mut caf =
+			Self { channels: format.channels, sample_rate: format.sample_rate, ..Self::default() };
+		caf.set_pcm(format.bit_depth, format.bit_depth == 32);
+		caf
+	}
+
+	pub fn to_raw_format(&self) -> raw::RawPcmFormat {
+		raw::RawPcmFormat {
+			channels: self.channels,
+			sample_rate: self.sample_rate,
+			bit_depth: self.bit_depth,
+		}
+	}
+
+	pub fn to_wav_format(&self) -> wav::WavFormat {
+		let format_code = if self.is_float() { 3 } else { 1 };
+		wav::WavFormat {
+			channels: self.channels,
+			sample_rate: self.sample_rate,
+			bit_depth: self.bit_depth,
+			format_code,
+		}
+	}
+
+	pub fn is_pcm(&self) -> bool {
+		self.format_id == FORMAT_LPCM
+	}
+
+	pub fn is_float(&self) -> bool {
+		self.format_flags & FLAG_FLOAT != 0
+	}
+
+	pub fn is_little_endian(&self) -> bool {
+		self.format_flags & FLAG_LITTLE_ENDIAN != 0
+	}
+
+	/// Packets have a variable byte size or frame count and need a `pakt` table.
+	pub fn is_variable(&self) -> bool {
+		self.bytes_per_packet == 0 || self.frames_per_packet == 0
+	}
+
+	pub fn bytes_per_sample(&self) -> usize {
+		(self.bit_depth / 8) as usize
+	}
+
+	pub fn bytes_per_frame(&self) -> usize {
+		self.bytes_per_sample() * self.channels.count() as usize
+	}
+
+	pub fn block_align(&self) -> u16 {
+		self.channels.count() as u16 * (self.bit_depth / 8)
+	}
+
+	pub fn audio_format(&self) -> AudioFormat {
+		match self.bit_depth {
+			16 => AudioFormat::PCM16,
+			24 => AudioFormat::PCM24,
+			32 => AudioFormat::PCM32,
+			_ => AudioFormat::PCM16,
+		}
+	}
+
+	pub fn to_codec_string(&self) -> &'static str {
+		match self.format_id {
+			FORMAT_LPCM => match (self.bit_depth, self.is_float()) {
+				(24, false) => codecs::audio::PCM_S24LE,
+				(32, true) => codecs::audio::PCM_F32LE,
+				_ => codecs::audio::PCM_S16LE,
+			},
+			FORMAT_AAC => codecs::audio::AAC,
+			FORMAT_ALAC => codecs::audio::ALAC,
+			FORMAT_FLAC => codecs::audio::FLAC,
+			FORMAT_OPUS => codecs::audio::OPUS,
+			FORMAT_MP3 => codecs::audio::MP3,
+			FORMAT_AC3 => codecs::audio::AC3,
+			_ => codecs::UNKNOWN,
+		}
+	}
+
+	pub fn apply_codec(&mut self, codec: &str) -> std::result::Result<(), String> {
+		match codec {
+			codecs::audio::PCM_S16LE => self.set_pcm(16, false),
+			codecs::audio::PCM_S24LE => self.set_pcm(24, false),
+			codecs::audio::PCM_F32LE => self.set_pcm(32, true),
+			_ => return Err(format!("caf codec '{}' is not supported", codec)),
+		}
+		Ok(())
+	}
+
+	fn set_pcm(&mut self, bit_depth: u16, float: bool) {
+		self.format_id = FORMAT_LPCM;
+		self.bit_depth = bit_depth;
+		self.format_flags = FLAG_LITTLE_ENDIAN | if float { FLAG_FLOAT } else { 0 };
+		self.bytes_per_packet = self.bytes_per_frame() as u32;
+		self.frames_per_packet = 1;
+	}
+
+	pub fn validate(&self) -> Result<()> {
+		if self.channels.count() == 0 {
+			return Err(error!("channels must be non-zero"));
+		}
+		if self.sample_rate == 0 {
+			return Err(error!("sample rate must be non-zero"));
+		}
+		if !self.is_pcm() {
+			return Ok(());
+		}
+
+		match (self.bit_depth, self.is_float()) {
+			(16, false) | (24, false) | (32, true) => {}
+			(bits, true) => return Err(error!("caf float pcm with {} bits is not supported", bits)),
+			(bits, false) => return Err(error!("caf integer pcm with {} bits is not supported", bits)),
+		}
+
+		if self.bytes_per_packet as usize != self.bytes_per_frame() || self.frames_per_packet != 1 {
+			return Err(error!("caf pcm must have one frame per packet"));
+		}
+		Ok(())
+	}
+}
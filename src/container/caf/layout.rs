@@ -0,0 +1,82 @@
+use crate::core::frame::Channels;
+use crate::io::{MediaRead, MediaWrite, ReadPrimitives, WritePrimitives};
+use crate::message::Result;
+
+pub const TAG_USE_CHANNEL_DESCRIPTIONS: u32 = 0;
+pub const TAG_USE_CHANNEL_BITMAP: u32 = 1 << 16;
+pub const TAG_MONO: u32 = (100 << 16) | 1;
+pub const TAG_STEREO: u32 = (101 << 16) | 2;
+pub const TAG_QUADRAPHONIC: u32 = (108 << 16) | 4;
+pub const TAG_MPEG_5_1_A: u32 = (121 << 16) | 6;
+pub const TAG_MPEG_7_1_A: u32 = (126 << 16) | 8;
+pub const TAG_DISCRETE_IN_ORDER: u32 = 147 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CafChannelDescription {
+	pub label: u32,
+	pub flags: u32,
+	pub coordinates: [f32; 3],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CafChannelLayout {
+	pub tag: u32,
+	pub bitmap: u32,
+	pub descriptions: Vec<CafChannelDescription>,
+}
+
+impl CafChannelLayout {
+	pub fn from_channels(channels: Channels) -> Self {
+		let tag = match channels {
+			Channels::Mono => TAG_MONO,
+			Channels::Stereo => TAG_STEREO,
+			Channels::Quad => TAG_QUADRAPHONIC,
+			Channels::Surround => TAG_MPEG_5_1_A,
+			Channels::SevenPointOne => TAG_MPEG_7_1_A,
+			Channels::Custom(count) => TAG_DISCRETE_IN_ORDER | count as u32,
+		};
+		Self { tag, bitmap: 0, descriptions: Vec::new() }
+	}
+
+	pub fn channel_count(&self) -> Option<u32> {
+		match self.tag {
+			TAG_USE_CHANNEL_DESCRIPTIONS => Some(self.descriptions.len() as u32),
+			TAG_USE_CHANNEL_BITMAP => Some(self.bitmap.count_ones()),
+			tag => Some(tag & 0xFFFF).filter(|count| *count > 0),
+		}
+	}
+
+	pub fn size(&self) -> u64 {
+		12 + self.descriptions.len() as u64 * 20
+	}
+
+	pub fn read<R: MediaRead>(reader: &mut R) -> Result<Self> {
+		let tag = reader.read_u32_be()?;
+		let bitmap = reader.read_u32_be()?;
+		let count = reader.read_u32_be()?;
+
+		let mut descriptions = Vec::with_capacity(count.min(64) as usize);
+		for _ in 0..count {
+			let label = reader.read_u32_be()?;
+			let flags = reader.read_u32_be()?;
+			let coordinates = [reader.read_f32_be()?, reader.read_f32_be()?, reader.read_f32_be()?];
+			descriptions.push(CafChannelDescription { label, flags, coordinates });
+		}
+
+		Ok(Self { tag, bitmap, descriptions })
+	}
+
+	pub fn write<W: MediaWrite>(&self, writer: &mut W) -> Result<()> {
+		writer.write_u32_be(self.tag)?;
+		writer.write_u32_be(self.bitmap)?;
+		writer.write_u32_be(self.descriptions.len() as u32)?;
+		for description in &self.descriptions {
+			writer.write_u32_be(description.label)?;
+			writer.write_u32_be(description.flags)?;
+			for coordinate in description.coordinates {
+				writer.write_f32_be(coordinate)?;
+			}
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,10 @@
+pub mod demuxer;
+pub mod formater;
+pub mod layout;
+pub mod muxer;
+pub mod packet_table;
+pub use demuxer::CafDemuxer;
+pub use formater::CafFormat;
+pub use layout::{CafChannelDescription, CafChannelLayout};
+pub use muxer::CafMuxer;
+pub use packet_table::{CafPacketEntry, CafPacketTable};
@@ -0,0 +1,128 @@
+use super::formater::CafFormat;
+use super::layout::CafChannelLayout;
+use crate::container::Metadata;
+use crate::core::Muxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream, StreamKind};
+use crate::core::time::Time;
+use crate::io::{MediaSeek, MediaWrite, SeekFrom, WritePrimitives};
+use crate::{error, message::Result};
+
+pub struct CafMuxer<W: MediaWrite + MediaSeek> {
+	writer: W,
+	format: CafFormat,
+	streams: stream::Streams,
+	metadata: Option<Metadata>,
+	data_size: u64,
+	/// Set once the header is out, which waits for the metadata so that the
+	/// `info` chunk lands before `data`.
+	data_size_pos: Option<u64>,
+}
+
+impl<W: MediaWrite + MediaSeek> CafMuxer<W> {
+	pub fn new(writer: W, format: CafFormat) -> Result<Self> {
+		format.validate()?;
+		if !format.is_pcm() || !format.is_little_endian() {
+			return Err(error!("caf muxer only writes little-endian pcm"));
+		}
+
+		let codec_name = format.to_codec_string().to_string();
+		let time = Time::new(1, format.sample_rate);
+		let mut streams = stream::Streams::new_empty();
+		streams.add(Stream::new(0, 0, StreamKind::Audio, codec_name, time));
+
+		Ok(Self { writer, format, streams, metadata: None, data_size: 0, data_size_pos: None })
+	}
+
+	pub fn with_metadata(&mut self, metadata: Option<Metadata>) {
+		self.metadata = metadata;
+	}
+
+	fn write_header(&mut self) -> Result<u64> {
+		if let Some(data_size_pos) = self.data_size_pos {
+			return Ok(data_size_pos);
+		}
+
+		let writer = &mut self.writer;
+		let format = &self.format;
+		writer.write_all(b"caff")?;
+		writer.write_u16_be(1)?;
+		writer.write_u16_be(0)?;
+
+		writer.write_all(b"desc")?;
+		writer.write_i64_be(32)?;
+		writer.write_f64_be(format.sample_rate as f64)?;
+		writer.write_all(&format.format_id)?;
+		writer.write_u32_be(format.format_flags)?;
+		writer.write_u32_be(format.bytes_per_packet)?;
+		writer.write_u32_be(format.frames_per_packet)?;
+		writer.write_u32_be(format.channels.count() as u32)?;
+		writer.write_u32_be(format.bit_depth as u32)?;
+
+		let layout = CafChannelLayout::from_channels(format.channels);
+		writer.write_all(b"chan")?;
+		writer.write_i64_be(layout.size() as i64)?;
+		layout.write(writer)?;
+
+		if let Some(meta) = &self.metadata {
+			Self::write_info_chunk(writer, meta)?;
+		}
+
+		// size stays -1 (streaming) until finalize knows the real length
+		writer.write_all(b"data")?;
+		let data_size_pos = writer.stream_position()?;
+		writer.write_i64_be(-1)?;
+		writer.write_u32_be(0)?;
+		self.data_size_pos = Some(data_size_pos);
+		Ok(data_size_pos)
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		if !packet.data.len().is_multiple_of(self.format.bytes_per_frame()) {
+			return Err(error!("caf packet is not a whole number of frames"));
+		}
+		self.write_header()?;
+		self.writer.write_all(&packet.data)?;
+		self.data_size += packet.data.len() as u64;
+		Ok(())
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		let data_size_pos = self.write_header()?;
+		self.writer.seek(SeekFrom::Start(data_size_pos))?;
+		self.writer.write_i64_be(self.data_size as i64 + 4)?;
+		self.writer.seek(SeekFrom::End(0))?;
+		self.writer.flush()?;
+		Ok(())
+	}
+
+	fn write_info_chunk(writer: &mut W, metadata: &Metadata) -> Result<()> {
+		if metadata.is_empty() {
+			return Ok(());
+		}
+
+		let size = metadata.iter().fold(4, |acc, (k, v)| acc + k.len() + v.len() + 2);
+		writer.write_all(b"info")?;
+		writer.write_i64_be(size as i64)?;
+		writer.write_u32_be(metadata.len() as u32)?;
+		for (key, value) in metadata.iter() {
+			writer.write_all(key.as_bytes())?;
+			writer.write_u8(0)?;
+			writer.write_all(value.as_bytes())?;
+			writer.write_u8(0)?;
+		}
+		Ok(())
+	}
+}
+
+impl<W: MediaWrite + MediaSeek> Muxer for CafMuxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+}
@@ -0,0 +1,62 @@
+use crate::io::ReadPrimitives;
+use crate::{error, message::Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CafPacketEntry {
+	pub size: u32,
+	pub frames: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CafPacketTable {
+	pub valid_frames: i64,
+	pub priming_frames: i32,
+	pub remainder_frames: i32,
+	pub entries: Vec<CafPacketEntry>,
+}
+
+impl CafPacketTable {
+	/// Parse a `pakt` chunk body. Sizes and frame counts are only stored in the
+	/// table when the `desc` chunk leaves them unspecified (zero).
+	pub fn parse(mut data: &[u8], bytes_per_packet: u32, frames_per_packet: u32) -> Result<Self> {
+		let packet_count = data.read_i64_be()?;
+		let valid_frames = data.read_i64_be()?;
+		let priming_frames = data.read_i32_be()?;
+		let remainder_frames = data.read_i32_be()?;
+
+		if packet_count < 0 {
+			return Err(error!("caf packet table has negative packet count"));
+		}
+
+		let mut entries = Vec::with_capacity((packet_count as usize).min(data.len()));
+		for _ in 0..packet_count {
+			let size = match bytes_per_packet {
+				0 => Self::read_vlq(&mut data)?,
+				size => size,
+			};
+			let frames = match frames_per_packet {
+				0 => Self::read_vlq(&mut data)?,
+				frames => frames,
+			};
+			entries.push(CafPacketEntry { size, frames });
+		}
+
+		Ok(Self { valid_frames, priming_frames, remainder_frames, entries })
+	}
+
+	fn read_vlq(data: &mut &[u8]) -> Result<u32> {
+		let mut value = 0u32;
+		for _ in 0..5 {
+			let byte = data.read_u8()?;
+			value = (value << 7) | (byte & 0x7F) as u32;
+			if byte & 0x80 == 0 {
+				return Ok(value);
+			}
+		}
+		Err(error!("caf packet table entry is too large"))
+	}
+
+	pub fn total_frames(&self) -> u64 {
+		self.entries.iter().map(|entry| entry.frames as u64).sum()
+	}
+}
@@ -7,6 +7,9 @@ pub const OGV: &str = "ogv";
 pub const FLV: &str = "flv";
 pub const MXF: &str = "mxf";
 pub const TS: &str = "ts";
+pub const M2TS: &str = "m2ts";
+pub const MTS: &str = "mts";
+pub const NUT: &str = "nut";
 
 //
 pub const MP3: &str = "mp3";
@@ -19,3 +22,13 @@ pub const PCM: &str = "pcm";
 pub const M4A: &str = "m4a";
 pub const ALAC: &str = "alac";
 pub const OGG: &str = "ogg";
+pub const CAF: &str = "caf";
+pub const W64: &str = "w64";
+pub const DSF: &str = "dsf";
+pub const DFF: &str = "dff";
+pub const WV: &str = "wv";
+pub const TTA: &str = "tta";
+pub const APE: &str = "ape";
+pub const Y4M: &str = "y4m";
+pub const YUV: &str = "yuv";
+pub const RGB: &str = "rgb";
@@ -0,0 +1,191 @@
+use super::DsdFormat;
+use crate::codecs::audio::dsd::DsdLayout;
+use crate::container::Metadata;
+use crate::core::frame::Channels;
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream, time};
+use crate::io::{MediaRead, ReadPrimitives};
+use crate::{error, message::Result};
+
+pub struct DffDemuxer<R: MediaRead> {
+	reader: R,
+	format: DsdFormat,
+	streams: stream::Streams,
+	metadata: Metadata,
+	data_remaining: u64,
+	sample_position: u64,
+}
+
+impl<R: MediaRead> DffDemuxer<R> {
+	const CHUNK_SIZE_LIMIT: usize = 65536;
+
+	pub fn new(mut reader: R) -> Result<Self> {
+		Self::check_fourcc(&mut reader, "FRM8")?;
+		let _form_size = reader.read_u64_be()?;
+		Self::check_fourcc(&mut reader, "DSD ")?;
+
+		let mut sample_rate = 0;
+		let mut channel_count = 0;
+		let mut metadata = Metadata::new();
+
+		let data_size = loop {
+			let chunk_id = Self::read_fourcc(&mut reader)?;
+			let chunk_size = reader.read_u64_be()?;
+
+			match chunk_id.as_str() {
+				"PROP" => {
+					let data = Self::read_chunk(&mut reader, chunk_size)?;
+					(sample_rate, channel_count) = Self::parse_prop(&data)?;
+				}
+				"DIIN" => {
+					let data = Self::read_chunk(&mut reader, chunk_size)?;
+					Self::parse_diin(&data, &mut metadata);
+				}
+				"DSD " => break chunk_size,
+				"DST " => return Err(error!("dst compressed dff is not supported")),
+				_ => {
+					Self::read_chunk(&mut reader, chunk_size)?;
+				}
+			}
+		};
+
+		if channel_count == 0 || sample_rate == 0 {
+			return Err(error!("dff file has no sound properties"));
+		}
+
+		let channels = Channels::from_count(channel_count as u8);
+		let sample_count = data_size / channel_count as u64 * 8;
+		let layout = DsdLayout { lsb_first: false, planar: false };
+		let format = DsdFormat { channels, sample_rate, layout, sample_count: Some(sample_count) };
+		format.validate()?;
+
+		let codec_name = format.to_codec_string().to_string();
+		let time = time::Time::new(1, sample_rate);
+		let stream = stream::Stream::new(0, 0, stream::StreamKind::Audio, codec_name, time);
+		let streams = stream::Streams::new(vec![stream]);
+
+		Ok(Self { reader, format, streams, metadata, data_remaining: data_size, sample_position: 0 })
+	}
+
+	fn parse_prop(mut data: &[u8]) -> Result<(u32, u16)> {
+		let mut prop_type = [0u8; 4];
+		data.read_exact(&mut prop_type)?;
+		if &prop_type != b"SND " {
+			return Ok((0, 0));
+		}
+
+		let mut sample_rate = 0;
+		let mut channel_count = 0;
+		while data.len() >= 12 {
+			let (id, body) = Self::next_local_chunk(&mut data)?;
+			match &id {
+				b"FS  " => sample_rate = body.as_slice().read_u32_be()?,
+				b"CHNL" => channel_count = body.as_slice().read_u16_be()?,
+				b"CMPR" if body.len() >= 4 && &body[..4] != b"DSD " => {
+					let name = String::from_utf8_lossy(&body[..4]).to_string();
+					return Err(error!("dff compression '{}' is not supported", name.trim()));
+				}
+				_ => {}
+			}
+		}
+		Ok((sample_rate, channel_count))
+	}
+
+	fn parse_diin(mut data: &[u8], metadata: &mut Metadata) {
+		while data.len() >= 12 {
+			let Ok((id, body)) = Self::next_local_chunk(&mut data) else {
+				return;
+			};
+			let key = match &id {
+				b"DIAR" => "artist",
+				b"DITI" => "title",
+				_ => continue,
+			};
+			if body.len() < 4 {
+				continue;
+			}
+			let count = u32::from_be_bytes([body[0], body[1], body[2], body[3]]) as usize;
+			let text = &body[4..std::cmp::min(4 + count, body.len())];
+			metadata.set(key, String::from_utf8_lossy(text).to_string());
+		}
+	}
+
+	fn next_local_chunk(data: &mut &[u8]) -> Result<([u8; 4], Vec<u8>)> {
+		let mut id = [0u8; 4];
+		data.read_exact(&mut id)?;
+		let size = data.read_u64_be()?;
+		if size > data.len() as u64 {
+			return Err(error!("dff chunk '{}' is truncated", String::from_utf8_lossy(&id)));
+		}
+		let mut body = vec![0u8; size as usize];
+		data.read_exact(&mut body)?;
+		if size % 2 == 1 && !data.is_empty() {
+			data.read_u8()?;
+		}
+		Ok((id, body))
+	}
+
+	fn read_fourcc(reader: &mut R) -> Result<String> {
+		let mut buf = [0u8; 4];
+		reader.read_exact(&mut buf)?;
+		Ok(String::from_utf8_lossy(&buf).to_string())
+	}
+
+	fn check_fourcc(reader: &mut R, expected: &str) -> Result<()> {
+		let actual = Self::read_fourcc(reader)?;
+		if actual != expected {
+			return Err(error!("expected {}, found {}", expected, actual));
+		}
+		Ok(())
+	}
+
+	fn read_chunk(reader: &mut R, size: u64) -> Result<Vec<u8>> {
+		let mut buf = vec![0u8; size as usize];
+		reader.read_exact(&mut buf)?;
+		if size % 2 == 1 {
+			reader.read_u8()?;
+		}
+		Ok(buf)
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		if self.data_remaining == 0 {
+			return Ok(None);
+		}
+
+		let channels = self.format.channels.count() as u64;
+		let max_chunk = (Self::CHUNK_SIZE_LIMIT as u64 / channels) * channels;
+		let chunk_size = std::cmp::min(self.data_remaining, max_chunk) as usize;
+		let mut data = vec![0u8; chunk_size];
+		self.reader.read_exact(&mut data)?;
+		self.data_remaining -= chunk_size as u64;
+
+		data.truncate((data.len() / channels as usize) * channels as usize);
+		if data.is_empty() {
+			return Ok(None);
+		}
+
+		let time = time::Time::new(1, self.format.sample_rate);
+		let packet = Packet::new(data, 0, time).with_pts(self.sample_position as i64);
+		self.sample_position += (chunk_size as u64 / channels) * 8;
+
+		Ok(Some(packet.with_keyframe(true)))
+	}
+
+	pub fn format(&self) -> DsdFormat {
+		self.format
+	}
+
+	pub fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+}
+
+impl<R: MediaRead> Demuxer for DffDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
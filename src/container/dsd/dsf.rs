@@ -0,0 +1,145 @@
+use super::DsdFormat;
+use crate::codecs::audio::dsd::DsdLayout;
+use crate::core::frame::Channels;
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream, time};
+use crate::io::{MediaRead, ReadPrimitives};
+use crate::{error, message::Result};
+
+pub struct DsfDemuxer<R: MediaRead> {
+	reader: R,
+	format: DsdFormat,
+	streams: stream::Streams,
+	block_size: usize,
+	/// Bytes per channel that still carry samples; the last block is zero padded.
+	bytes_remaining: u64,
+	data_remaining: u64,
+	sample_position: u64,
+}
+
+impl<R: MediaRead> DsfDemuxer<R> {
+	pub fn new(mut reader: R) -> Result<Self> {
+		Self::check_fourcc(&mut reader, "DSD ")?;
+		let header_size = reader.read_u64_le()?;
+		let _file_size = reader.read_u64_le()?;
+		let _metadata_offset = reader.read_u64_le()?;
+		Self::skip_bytes(&mut reader, header_size.saturating_sub(28))?;
+
+		Self::check_fourcc(&mut reader, "fmt ")?;
+		let fmt_size = reader.read_u64_le()?;
+		if fmt_size < 52 {
+			return Err(error!("dsf fmt chunk too small"));
+		}
+		let version = reader.read_u32_le()?;
+		let format_id = reader.read_u32_le()?;
+		let _channel_type = reader.read_u32_le()?;
+		let channel_count = reader.read_u32_le()?;
+		let sample_rate = reader.read_u32_le()?;
+		let bits_per_sample = reader.read_u32_le()?;
+		let sample_count = reader.read_u64_le()?;
+		let block_size = reader.read_u32_le()? as usize;
+		let _reserved = reader.read_u32_le()?;
+		Self::skip_bytes(&mut reader, fmt_size - 52)?;
+
+		if version != 1 || format_id != 0 {
+			return Err(error!("dsf format {} version {} is not supported", format_id, version));
+		}
+		if channel_count == 0 || channel_count > 6 {
+			return Err(error!("dsf channel count {} is not supported", channel_count));
+		}
+		if block_size == 0 {
+			return Err(error!("dsf block size must be non-zero"));
+		}
+
+		let lsb_first = match bits_per_sample {
+			1 => true,
+			8 => false,
+			bits => return Err(error!("dsf bits per sample {} is not supported", bits)),
+		};
+
+		Self::check_fourcc(&mut reader, "data")?;
+		let data_size = reader.read_u64_le()?.saturating_sub(12);
+
+		let format = DsdFormat {
+			channels: Channels::from_count(channel_count as u8),
+			sample_rate,
+			layout: DsdLayout { lsb_first, planar: true },
+			sample_count: Some(sample_count),
+		};
+		format.validate()?;
+
+		let codec_name = format.to_codec_string().to_string();
+		let time = time::Time::new(1, sample_rate);
+		let stream = stream::Stream::new(0, 0, stream::StreamKind::Audio, codec_name, time);
+		let streams = stream::Streams::new(vec![stream]);
+
+		Ok(Self {
+			reader,
+			format,
+			streams,
+			block_size,
+			bytes_remaining: sample_count.div_ceil(8),
+			data_remaining: data_size,
+			sample_position: 0,
+		})
+	}
+
+	fn read_fourcc(reader: &mut R) -> Result<String> {
+		let mut buf = [0u8; 4];
+		reader.read_exact(&mut buf)?;
+		Ok(String::from_utf8_lossy(&buf).to_string())
+	}
+
+	fn check_fourcc(reader: &mut R, expected: &str) -> Result<()> {
+		let actual = Self::read_fourcc(reader)?;
+		if actual != expected {
+			return Err(error!("expected {}, found {}", expected, actual));
+		}
+		Ok(())
+	}
+
+	fn skip_bytes(reader: &mut R, size: u64) -> Result<()> {
+		let mut buf = vec![0u8; size as usize];
+		reader.read_exact(&mut buf)?;
+		Ok(())
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		let channels = self.format.channels.count() as usize;
+		let group_size = (self.block_size * channels) as u64;
+		if self.bytes_remaining == 0 || self.data_remaining < group_size {
+			return Ok(None);
+		}
+
+		let mut data = vec![0u8; group_size as usize];
+		self.reader.read_exact(&mut data)?;
+		self.data_remaining -= group_size;
+
+		// drop the zero padding of the final block of every channel
+		let valid = std::cmp::min(self.bytes_remaining, self.block_size as u64) as usize;
+		if valid < self.block_size {
+			let blocks = data.chunks_exact(self.block_size);
+			data = blocks.flat_map(|block| block[..valid].to_vec()).collect();
+		}
+		self.bytes_remaining -= valid as u64;
+
+		let time = time::Time::new(1, self.format.sample_rate);
+		let packet = Packet::new(data, 0, time).with_pts(self.sample_position as i64);
+		self.sample_position += valid as u64 * 8;
+
+		Ok(Some(packet.with_keyframe(true)))
+	}
+
+	pub fn format(&self) -> DsdFormat {
+		self.format
+	}
+}
+
+impl<R: MediaRead> Demuxer for DsfDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
@@ -0,0 +1,46 @@
+use crate::codecs::audio::dsd::{DsdDecoder, DsdLayout};
+use crate::container::wav;
+use crate::core::frame::Channels;
+use crate::{error, message::Result};
+
+#[derive(Debug, Clone, Copy)]
+pub struct DsdFormat {
+	pub channels: Channels,
+	/// One-bit sample rate per channel, e.g. 2822400 for DSD64.
+	pub sample_rate: u32,
+	pub layout: DsdLayout,
+	pub sample_count: Option<u64>,
+}
+
+impl DsdFormat {
+	pub fn to_codec_string(&self) -> &'static str {
+		self.layout.to_codec_string()
+	}
+
+	pub fn default_output_rate(&self) -> u32 {
+		DsdDecoder::default_output_rate(self.sample_rate)
+	}
+
+	pub fn to_wav_format(&self, output_rate: u32) -> wav::WavFormat {
+		wav::WavFormat {
+			channels: self.channels,
+			sample_rate: output_rate,
+			bit_depth: 24,
+			format_code: 1,
+		}
+	}
+
+	pub fn create_decoder(&self, output_rate: u32) -> Result<DsdDecoder> {
+		DsdDecoder::new(self.sample_rate, self.channels, self.layout, output_rate)
+	}
+
+	pub fn validate(&self) -> Result<()> {
+		if self.channels.count() == 0 {
+			return Err(error!("channels must be non-zero"));
+		}
+		if self.sample_rate == 0 || !self.sample_rate.is_multiple_of(44100) {
+			return Err(error!("dsd sample rate {} is not supported", self.sample_rate));
+		}
+		Ok(())
+	}
+}
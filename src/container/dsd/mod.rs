@@ -0,0 +1,6 @@
+pub mod dff;
+pub mod dsf;
+pub mod formater;
+pub use dff::DffDemuxer;
+pub use dsf::DsfDemuxer;
+pub use formater::DsdFormat;
@@ -0,0 +1,203 @@
+use crate::{error, message::Result};
+
+const NUMBER: u8 = 0x00;
+const BOOLEAN: u8 = 0x01;
+const STRING: u8 = 0x02;
+const OBJECT: u8 = 0x03;
+const NULL: u8 = 0x05;
+const UNDEFINED: u8 = 0x06;
+const REFERENCE: u8 = 0x07;
+pub const ECMA_ARRAY: u8 = 0x08;
+pub const OBJECT_END: u8 = 0x09;
+const STRICT_ARRAY: u8 = 0x0A;
+const DATE: u8 = 0x0B;
+const LONG_STRING: u8 = 0x0C;
+
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AmfValue {
+	Number(f64),
+	Boolean(bool),
+	String(String),
+	Object(Vec<(String, AmfValue)>),
+	Null,
+	Undefined,
+	Reference(u16),
+	EcmaArray(Vec<(String, AmfValue)>),
+	StrictArray(Vec<AmfValue>),
+	/// Milliseconds since the epoch; the time zone is ignored.
+	Date(f64),
+}
+
+impl AmfValue {
+	pub fn read(data: &mut &[u8]) -> Result<Self> {
+		Self::read_nested(data, 0)
+	}
+
+	fn read_nested(data: &mut &[u8], depth: usize) -> Result<Self> {
+		if depth > MAX_DEPTH {
+			return Err(error!("amf value is nested too deeply"));
+		}
+		let marker = take(data, 1)?[0];
+		let value = match marker {
+			NUMBER => Self::Number(read_f64(data)?),
+			BOOLEAN => Self::Boolean(take(data, 1)?[0] != 0),
+			STRING => Self::String(read_string(data)?),
+			OBJECT => Self::Object(read_properties(data, depth)?),
+			NULL => Self::Null,
+			UNDEFINED => Self::Undefined,
+			REFERENCE => Self::Reference(read_u16(data)?),
+			ECMA_ARRAY => {
+				// the count is only a hint, the end marker is what counts
+				take(data, 4)?;
+				Self::EcmaArray(read_properties(data, depth)?)
+			}
+			STRICT_ARRAY => {
+				let count = read_u32(data)? as usize;
+				let mut values = Vec::with_capacity(count.min(1024));
+				for _ in 0..count {
+					values.push(Self::read_nested(data, depth + 1)?);
+				}
+				Self::StrictArray(values)
+			}
+			DATE => {
+				let value = read_f64(data)?;
+				take(data, 2)?;
+				Self::Date(value)
+			}
+			LONG_STRING => {
+				let size = read_u32(data)? as usize;
+				Self::String(String::from_utf8_lossy(take(data, size)?).to_string())
+			}
+			marker => return Err(error!("amf type 0x{:02X} is not supported", marker)),
+		};
+		Ok(value)
+	}
+
+	pub fn write(&self, out: &mut Vec<u8>) {
+		match self {
+			Self::Number(value) => {
+				out.push(NUMBER);
+				out.extend_from_slice(&value.to_be_bytes());
+			}
+			Self::Boolean(value) => out.extend_from_slice(&[BOOLEAN, *value as u8]),
+			Self::String(value) if value.len() > u16::MAX as usize => {
+				out.push(LONG_STRING);
+				out.extend_from_slice(&(value.len() as u32).to_be_bytes());
+				out.extend_from_slice(value.as_bytes());
+			}
+			Self::String(value) => {
+				out.push(STRING);
+				write_string(out, value);
+			}
+			Self::Object(properties) => {
+				out.push(OBJECT);
+				write_properties(out, properties);
+			}
+			Self::Null => out.push(NULL),
+			Self::Undefined => out.push(UNDEFINED),
+			Self::Reference(index) => {
+				out.push(REFERENCE);
+				out.extend_from_slice(&index.to_be_bytes());
+			}
+			Self::EcmaArray(properties) => {
+				out.push(ECMA_ARRAY);
+				out.extend_from_slice(&(properties.len() as u32).to_be_bytes());
+				write_properties(out, properties);
+			}
+			Self::StrictArray(values) => {
+				out.push(STRICT_ARRAY);
+				out.extend_from_slice(&(values.len() as u32).to_be_bytes());
+				for value in values {
+					value.write(out);
+				}
+			}
+			Self::Date(value) => {
+				out.push(DATE);
+				out.extend_from_slice(&value.to_be_bytes());
+				out.extend_from_slice(&[0, 0]);
+			}
+		}
+	}
+
+	pub fn as_number(&self) -> Option<f64> {
+		match self {
+			Self::Number(value) => Some(*value),
+			_ => None,
+		}
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match self {
+			Self::String(value) => Some(value),
+			_ => None,
+		}
+	}
+
+	pub fn properties(&self) -> Option<&[(String, AmfValue)]> {
+		match self {
+			Self::Object(properties) | Self::EcmaArray(properties) => Some(properties),
+			_ => None,
+		}
+	}
+}
+
+fn take<'a>(data: &mut &'a [u8], size: usize) -> Result<&'a [u8]> {
+	if data.len() < size {
+		return Err(error!("amf value is truncated"));
+	}
+	let (head, rest) = data.split_at(size);
+	*data = rest;
+	Ok(head)
+}
+
+fn read_u16(data: &mut &[u8]) -> Result<u16> {
+	let bytes = take(data, 2)?;
+	Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &mut &[u8]) -> Result<u32> {
+	let bytes = take(data, 4)?;
+	Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_f64(data: &mut &[u8]) -> Result<f64> {
+	let bytes = take(data, 8)?;
+	Ok(f64::from_be_bytes(bytes.try_into().unwrap_or_default()))
+}
+
+pub fn read_string(data: &mut &[u8]) -> Result<String> {
+	let size = read_u16(data)? as usize;
+	Ok(String::from_utf8_lossy(take(data, size)?).to_string())
+}
+
+fn read_properties(data: &mut &[u8], depth: usize) -> Result<Vec<(String, AmfValue)>> {
+	let mut properties = Vec::new();
+	loop {
+		// some writers end the last array without its marker
+		if data.is_empty() {
+			return Ok(properties);
+		}
+		let key = read_string(data)?;
+		if key.is_empty() && data.first() == Some(&OBJECT_END) {
+			take(data, 1)?;
+			return Ok(properties);
+		}
+		properties.push((key, AmfValue::read_nested(data, depth + 1)?));
+	}
+}
+
+pub fn write_string(out: &mut Vec<u8>, value: &str) {
+	let bytes = &value.as_bytes()[..value.len().min(u16::MAX as usize)];
+	out.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+	out.extend_from_slice(bytes);
+}
+
+fn write_properties(out: &mut Vec<u8>, properties: &[(String, AmfValue)]) {
+	for (key, value) in properties {
+		write_string(out, key);
+		value.write(out);
+	}
+	out.extend_from_slice(&[0, 0, OBJECT_END]);
+}
@@ -0,0 +1,303 @@
+use super::amf::AmfValue;
+use super::formater::{self, FlvHeader, FlvTagHeader};
+use crate::container::Metadata;
+use crate::core::Demuxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream, StreamKind};
+use crate::io::MediaRead;
+use crate::{error, message::Result};
+use std::collections::VecDeque;
+
+const PROBE_TAGS: usize = 200;
+
+struct Track {
+	codec: &'static str,
+	codec_private: Vec<u8>,
+	/// AVC and AAC streams are ready once their sequence header is seen.
+	ready: bool,
+	stream_id: u32,
+}
+
+pub struct FlvDemuxer<R: MediaRead> {
+	reader: R,
+	header: FlvHeader,
+	streams: stream::Streams,
+	video: Option<Track>,
+	audio: Option<Track>,
+	probed: VecDeque<(FlvTagHeader, Vec<u8>)>,
+	on_metadata: Vec<(String, AmfValue)>,
+	metadata: Metadata,
+	eof: bool,
+}
+
+impl<R: MediaRead> FlvDemuxer<R> {
+	pub fn new(mut reader: R) -> Result<Self> {
+		let header = FlvHeader::parse(&mut reader)?;
+		let mut demuxer = Self {
+			reader,
+			header,
+			streams: stream::Streams::new_empty(),
+			video: None,
+			audio: None,
+			probed: VecDeque::new(),
+			on_metadata: Vec::new(),
+			metadata: Metadata::new(),
+			eof: false,
+		};
+		// whatever sits between the header and the first tag, then PreviousTagSize0
+		let mut remaining = (header.data_offset - formater::HEADER_SIZE + 4) as usize;
+		let mut skip = vec![0u8; remaining.min(65536)];
+		while remaining > 0 {
+			let size = remaining.min(skip.len());
+			if demuxer.fill(&mut skip[..size])? < size {
+				return Err(error!("flv file ends before its first tag"));
+			}
+			remaining -= size;
+		}
+
+		while !demuxer.probe_done() && demuxer.probed.len() < PROBE_TAGS {
+			let Some((tag, body)) = demuxer.read_tag()? else {
+				break;
+			};
+			match tag.kind {
+				formater::TAG_VIDEO => Self::describe(&mut demuxer.video, &tag, &body, Self::video_track),
+				formater::TAG_AUDIO => Self::describe(&mut demuxer.audio, &tag, &body, Self::audio_track),
+				_ => {}
+			}
+			demuxer.probed.push_back((tag, body));
+		}
+
+		// video first, then audio, in stream id order
+		for (track, kind) in
+			[(&mut demuxer.video, StreamKind::Video), (&mut demuxer.audio, StreamKind::Audio)]
+		{
+			if let Some(track) = track {
+				let index = demuxer.streams.all().len();
+				track.stream_id = index as u32;
+				let stream =
+					Stream::new(index as u32, index, kind, track.codec.to_string(), formater::time());
+				demuxer.streams.add(stream.with_codec_private(track.codec_private.clone()));
+			}
+		}
+		if demuxer.streams.all().is_empty() {
+			return Err(error!("flv file has no supported audio or video stream"));
+		}
+		Ok(demuxer)
+	}
+
+	fn fill(&mut self, buf: &mut [u8]) -> Result<usize> {
+		let mut filled = 0;
+		while filled < buf.len() {
+			match self.reader.read(&mut buf[filled..])? {
+				0 => break,
+				read => filled += read,
+			}
+		}
+		Ok(filled)
+	}
+
+	/// The next tag and its body; a tag cut short by the end of the input ends
+	/// the stream.
+	fn read_tag(&mut self) -> Result<Option<(FlvTagHeader, Vec<u8>)>> {
+		if self.eof {
+			return Ok(None);
+		}
+		let mut bytes = [0u8; formater::TAG_HEADER_SIZE as usize];
+		if self.fill(&mut bytes)? < bytes.len() {
+			self.eof = true;
+			return Ok(None);
+		}
+		let tag = FlvTagHeader::parse(&bytes);
+		let mut body = vec![0u8; tag.size as usize];
+		if self.fill(&mut body)? < body.len() {
+			self.eof = true;
+			return Ok(None);
+		}
+		let mut previous_size = [0u8; 4];
+		if self.fill(&mut previous_size)? < previous_size.len() {
+			self.eof = true;
+		}
+		if tag.kind == formater::TAG_SCRIPT {
+			self.handle_script(&body);
+		}
+		Ok(Some((tag, body)))
+	}
+
+	fn probe_done(&self) -> bool {
+		let ready = |track: &Option<Track>, flagged: bool| match track {
+			Some(track) => track.ready,
+			None => !flagged,
+		};
+		let found = self.video.is_some() || self.audio.is_some();
+		found
+			&& ready(&self.video, self.header.has_video())
+			&& ready(&self.audio, self.header.has_audio())
+	}
+
+	fn describe(
+		track: &mut Option<Track>,
+		tag: &FlvTagHeader,
+		body: &[u8],
+		new: fn(&[u8]) -> Option<Track>,
+	) {
+		if tag.filtered || body.len() < 2 {
+			return;
+		}
+		match track {
+			None => *track = new(body),
+			Some(track) if !track.ready => {
+				if let Some(found) = new(body).filter(|found| found.codec == track.codec) {
+					*track = found;
+				}
+			}
+			Some(_) => {}
+		}
+	}
+
+	fn video_track(body: &[u8]) -> Option<Track> {
+		let codec_id = body[0] & 0x0F;
+		let codec = formater::video_codec(codec_id)?;
+		let (codec_private, ready) = match codec_id {
+			formater::CODEC_AVC if body[1] == formater::PACKET_SEQUENCE_HEADER => {
+				(body.get(5..).unwrap_or(&[]).to_vec(), true)
+			}
+			formater::CODEC_AVC => (Vec::new(), false),
+			// the width and height adjustment byte before each frame
+			_ => (vec![body[1]], true),
+		};
+		Some(Track { codec, codec_private, ready, stream_id: 0 })
+	}
+
+	fn audio_track(body: &[u8]) -> Option<Track> {
+		let sound_format = body[0] >> 4;
+		let codec = formater::audio_codec(sound_format)?;
+		let (codec_private, ready) = match sound_format {
+			formater::SOUND_AAC if body[1] == formater::PACKET_SEQUENCE_HEADER => {
+				(body[2..].to_vec(), true)
+			}
+			formater::SOUND_AAC => (Vec::new(), false),
+			_ => (Vec::new(), true),
+		};
+		Some(Track { codec, codec_private, ready, stream_id: 0 })
+	}
+
+	/// Keep the first `onMetaData`; its text values become the metadata.
+	fn handle_script(&mut self, body: &[u8]) {
+		if !self.on_metadata.is_empty() {
+			return;
+		}
+		let mut data = body;
+		let Ok(AmfValue::String(name)) = AmfValue::read(&mut data) else {
+			return;
+		};
+		if name != formater::ON_METADATA {
+			return;
+		}
+		let Ok(value) = AmfValue::read(&mut data) else {
+			return;
+		};
+		let Some(properties) = value.properties() else {
+			return;
+		};
+		for (key, value) in properties {
+			if let Some(text) = value.as_str() {
+				self.metadata.set(key, text.to_string());
+			}
+		}
+		self.on_metadata = properties.to_vec();
+	}
+
+	fn to_packet(&self, tag: &FlvTagHeader, body: &[u8]) -> Option<Packet> {
+		if tag.filtered || body.len() < 2 {
+			return None;
+		}
+		let dts = tag.timestamp as i64;
+		match tag.kind {
+			formater::TAG_VIDEO => {
+				let track = self.video.as_ref()?;
+				let frame_type = body[0] >> 4;
+				let codec_id = body[0] & 0x0F;
+				if formater::video_codec(codec_id) != Some(track.codec) {
+					return None;
+				}
+				let keyframe = frame_type == formater::FRAME_KEY;
+				let (data, pts) = match codec_id {
+					formater::CODEC_AVC if body[1] != formater::PACKET_DATA || body.len() < 5 => {
+						return None;
+					}
+					formater::CODEC_AVC => {
+						let offset = i32::from_be_bytes([body[2], body[3], body[4], 0]) >> 8;
+						(&body[5..], dts + offset as i64)
+					}
+					_ => (&body[2..], dts),
+				};
+				// video info and command frames carry no picture
+				if frame_type == formater::FRAME_INFO {
+					return None;
+				}
+				let packet = Packet::new(data.to_vec(), track.stream_id, formater::time());
+				Some(packet.with_pts(pts).with_dts(dts).with_keyframe(keyframe))
+			}
+			formater::TAG_AUDIO => {
+				let track = self.audio.as_ref()?;
+				let sound_format = body[0] >> 4;
+				if formater::audio_codec(sound_format) != Some(track.codec) {
+					return None;
+				}
+				let data = match sound_format {
+					formater::SOUND_AAC if body[1] != formater::PACKET_DATA => return None,
+					formater::SOUND_AAC => &body[2..],
+					_ => &body[1..],
+				};
+				let packet = Packet::new(data.to_vec(), track.stream_id, formater::time());
+				Some(packet.with_pts(dts).with_dts(dts).with_keyframe(true))
+			}
+			_ => None,
+		}
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		loop {
+			let (tag, body) = match self.probed.pop_front() {
+				Some(probed) => probed,
+				None => match self.read_tag()? {
+					Some(read) => read,
+					None => return Ok(None),
+				},
+			};
+			if let Some(packet) = self.to_packet(&tag, &body) {
+				return Ok(Some(packet));
+			}
+		}
+	}
+
+	pub fn header(&self) -> &FlvHeader {
+		&self.header
+	}
+
+	pub fn on_metadata(&self) -> &[(String, AmfValue)] {
+		&self.on_metadata
+	}
+
+	pub fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+
+	/// Seconds, as `onMetaData` announces it.
+	pub fn duration(&self) -> Option<f64> {
+		self
+			.on_metadata
+			.iter()
+			.find(|(key, _)| key == "duration")
+			.and_then(|(_, value)| value.as_number())
+	}
+}
+
+impl<R: MediaRead> Demuxer for FlvDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
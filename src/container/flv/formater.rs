@@ -0,0 +1,161 @@
+use crate::codecs::{audio, video};
+use crate::core::time::Time;
+use crate::io::{MediaRead, ReadPrimitives};
+use crate::{error, message::Result};
+
+pub const SIGNATURE: [u8; 3] = *b"FLV";
+pub const VERSION: u8 = 1;
+pub const HEADER_SIZE: u32 = 9;
+pub const TAG_HEADER_SIZE: u32 = 11;
+
+pub const FLAG_AUDIO: u8 = 0x04;
+pub const FLAG_VIDEO: u8 = 0x01;
+
+pub const TAG_AUDIO: u8 = 8;
+pub const TAG_VIDEO: u8 = 9;
+pub const TAG_SCRIPT: u8 = 18;
+
+pub const CODEC_VP6: u8 = 4;
+pub const CODEC_AVC: u8 = 7;
+
+pub const FRAME_KEY: u8 = 1;
+pub const FRAME_INTER: u8 = 2;
+pub const FRAME_INFO: u8 = 5;
+
+pub const SOUND_MP3: u8 = 2;
+pub const SOUND_AAC: u8 = 10;
+pub const SOUND_MP3_8K: u8 = 14;
+
+pub const PACKET_SEQUENCE_HEADER: u8 = 0;
+pub const PACKET_DATA: u8 = 1;
+pub const PACKET_END_OF_SEQUENCE: u8 = 2;
+
+pub const ON_METADATA: &str = "onMetaData";
+
+pub fn time() -> Time {
+	Time::new(1, 1000)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FlvHeader {
+	pub flags: u8,
+	pub data_offset: u32,
+}
+
+impl FlvHeader {
+	pub fn parse<R: MediaRead>(reader: &mut R) -> Result<Self> {
+		let mut signature = [0u8; 3];
+		reader.read_exact(&mut signature)?;
+		if signature != SIGNATURE {
+			return Err(error!("not an flv file"));
+		}
+		let version = reader.read_u8()?;
+		if version != VERSION {
+			return Err(error!("flv version {} is not supported", version));
+		}
+		let flags = reader.read_u8()?;
+		let data_offset = reader.read_u32_be()?;
+		if data_offset < HEADER_SIZE {
+			return Err(error!("flv data offset {} is inside the header", data_offset));
+		}
+		Ok(Self { flags, data_offset })
+	}
+
+	pub fn write(&self) -> Vec<u8> {
+		let mut out = Vec::with_capacity(HEADER_SIZE as usize);
+		out.extend_from_slice(&SIGNATURE);
+		out.push(VERSION);
+		out.push(self.flags);
+		out.extend_from_slice(&self.data_offset.to_be_bytes());
+		out
+	}
+
+	pub fn has_audio(&self) -> bool {
+		self.flags & FLAG_AUDIO != 0
+	}
+
+	pub fn has_video(&self) -> bool {
+		self.flags & FLAG_VIDEO != 0
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FlvTagHeader {
+	/// Tag type, with the filter bit cleared.
+	pub kind: u8,
+	pub filtered: bool,
+	pub size: u32,
+	/// Milliseconds, the 24-bit field extended by its upper byte.
+	pub timestamp: u32,
+}
+
+impl FlvTagHeader {
+	pub fn parse(bytes: &[u8; TAG_HEADER_SIZE as usize]) -> Self {
+		let u24 = |at: usize| u32::from_be_bytes([0, bytes[at], bytes[at + 1], bytes[at + 2]]);
+		Self {
+			kind: bytes[0] & 0x1F,
+			filtered: bytes[0] & 0x20 != 0,
+			size: u24(1),
+			timestamp: u24(4) | (bytes[7] as u32) << 24,
+		}
+	}
+
+	pub fn write(&self, out: &mut Vec<u8>) {
+		out.push(self.kind | if self.filtered { 0x20 } else { 0 });
+		out.extend_from_slice(&self.size.to_be_bytes()[1..]);
+		out.extend_from_slice(&self.timestamp.to_be_bytes()[1..]);
+		out.push((self.timestamp >> 24) as u8);
+		// stream id, always zero
+		out.extend_from_slice(&[0, 0, 0]);
+	}
+}
+
+pub fn video_codec(codec_id: u8) -> Option<&'static str> {
+	match codec_id {
+		CODEC_VP6 => Some(video::VP6),
+		CODEC_AVC => Some(video::H264),
+		_ => None,
+	}
+}
+
+pub fn video_codec_id(codec: &str) -> Result<u8> {
+	match codec {
+		video::VP6 => Ok(CODEC_VP6),
+		video::H264 => Ok(CODEC_AVC),
+		_ => Err(error!("'{}' has no flv video codec id", codec)),
+	}
+}
+
+pub fn audio_codec(sound_format: u8) -> Option<&'static str> {
+	match sound_format {
+		SOUND_MP3 | SOUND_MP3_8K => Some(audio::MP3),
+		SOUND_AAC => Some(audio::AAC),
+		_ => None,
+	}
+}
+
+pub fn mp3_flags(frame: &[u8]) -> Result<u8> {
+	if frame.len() < 4 || frame[0] != 0xFF || frame[1] & 0xE0 != 0xE0 {
+		return Err(error!("mp3 packet does not start with a frame header"));
+	}
+	let version = (frame[1] >> 3) & 0x03;
+	let rate_index = (frame[2] >> 2) & 0x03;
+	if version == 1 || rate_index == 3 {
+		return Err(error!("mp3 frame header is invalid"));
+	}
+	let base = [44100, 48000, 32000][rate_index as usize];
+	let sample_rate = match version {
+		3 => base,
+		2 => base / 2,
+		_ => base / 4,
+	};
+	let stereo = (frame[3] >> 6) != 3;
+
+	let (format, rate) = match sample_rate {
+		8000 => (SOUND_MP3_8K, 0),
+		11025 | 12000 => (SOUND_MP3, 1),
+		22050 | 24000 | 16000 => (SOUND_MP3, 2),
+		_ => (SOUND_MP3, 3),
+	};
+	Ok(format << 4 | rate << 2 | 0x02 | stereo as u8)
+}
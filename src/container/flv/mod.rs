@@ -0,0 +1,8 @@
+pub mod amf;
+pub mod demuxer;
+pub mod formater;
+pub mod muxer;
+pub use amf::AmfValue;
+pub use demuxer::FlvDemuxer;
+pub use formater::{FlvHeader, FlvTagHeader};
+pub use muxer::FlvMuxer;
@@ -0,0 +1,300 @@
+use super::amf::{self, AmfValue};
+use super::formater::{self, FlvHeader, FlvTagHeader};
+use crate::codecs::{audio, video};
+use crate::container::{self, Metadata};
+use crate::core::Muxer;
+use crate::core::compatible::Compatible;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream, StreamKind};
+use crate::core::time::Time;
+use crate::io::{MediaSeek, MediaWrite, SeekFrom, WritePrimitives};
+use crate::{error, message::Result};
+
+const WRITTEN_PROPERTIES: [&str; 4] = ["duration", "filesize", "videocodecid", "audiocodecid"];
+
+struct Output {
+	kind: StreamKind,
+	codec: String,
+	codec_private: Vec<u8>,
+	audio_flags: Option<u8>,
+	last_timestamp: Option<i64>,
+	last_duration: i64,
+}
+
+/// Writes one video and one audio stream at most. `onMetaData` goes out
+/// before the first tag, its duration and file size patched on `finalize`.
+pub struct FlvMuxer<W: MediaWrite + MediaSeek> {
+	writer: W,
+	streams: stream::Streams,
+	outputs: Vec<Output>,
+	metadata: Metadata,
+	header_written: bool,
+	duration_offset: u64,
+	filesize_offset: u64,
+	shift: Option<i64>,
+}
+
+impl<W: MediaWrite + MediaSeek> FlvMuxer<W> {
+	pub fn new(writer: W, streams: &[Stream]) -> Result<Self> {
+		if streams.is_empty() {
+			return Err(error!("flv muxer needs at least one stream"));
+		}
+		let compatible = Compatible::new();
+		let Some(entry) = compatible.container(container::FLV) else {
+			return Err(error!("flv is not a known container"));
+		};
+
+		let mut outputs: Vec<Output> = Vec::new();
+		let mut muxer_streams = stream::Streams::new_empty();
+		for (index, stream) in streams.iter().enumerate() {
+			match stream.kind {
+				StreamKind::Video => entry.assert_video_codec(&stream.codec)?,
+				StreamKind::Audio => entry.assert_audio_codec(&stream.codec)?,
+				StreamKind::Subtitle => return Err(error!("flv files cannot hold subtitles")),
+			}
+			if outputs.iter().any(|output| output.kind == stream.kind) {
+				return Err(error!("flv files hold one {:?} stream at most", stream.kind));
+			}
+			let private = &stream.codec_private;
+			match stream.codec.as_str() {
+				video::H264 if private.first() != Some(&1) => {
+					return Err(error!("flv h264 needs an avcC record, annex b is not supported"));
+				}
+				audio::AAC if private.len() < 2 => {
+					return Err(error!("flv aac needs an AudioSpecificConfig"));
+				}
+				_ => {}
+			}
+			outputs.push(Output {
+				kind: stream.kind,
+				codec: stream.codec.clone(),
+				codec_private: private.clone(),
+				audio_flags: None,
+				last_timestamp: None,
+				last_duration: 0,
+			});
+			let mut muxer_stream = stream.clone();
+			muxer_stream.index = index;
+			muxer_streams.add(muxer_stream);
+		}
+
+		Ok(Self {
+			writer,
+			streams: muxer_streams,
+			outputs,
+			metadata: Metadata::new(),
+			header_written: false,
+			duration_offset: 0,
+			filesize_offset: 0,
+			shift: None,
+		})
+	}
+
+	pub fn with_metadata(&mut self, metadata: Option<Metadata>) {
+		self.metadata = metadata.unwrap_or_default();
+	}
+
+	fn output(&self, kind: StreamKind) -> Option<&Output> {
+		self.outputs.iter().find(|output| output.kind == kind)
+	}
+
+	fn write_header(&mut self) -> Result<()> {
+		let mut flags = 0;
+		if self.output(StreamKind::Video).is_some() {
+			flags |= formater::FLAG_VIDEO;
+		}
+		if self.output(StreamKind::Audio).is_some() {
+			flags |= formater::FLAG_AUDIO;
+		}
+		let header = FlvHeader { flags, data_offset: formater::HEADER_SIZE };
+		self.writer.write_all(&header.write())?;
+		self.writer.write_u32_be(0)?;
+
+		let mut properties = vec![
+			("duration".to_string(), AmfValue::Number(0.0)),
+			("filesize".to_string(), AmfValue::Number(0.0)),
+		];
+		if let Some(output) = self.output(StreamKind::Video) {
+			let codec_id = formater::video_codec_id(&output.codec)?;
+			properties.push(("videocodecid".to_string(), AmfValue::Number(codec_id as f64)));
+		}
+		if let Some(output) = self.output(StreamKind::Audio) {
+			let sound_format = match output.codec.as_str() {
+				audio::AAC => formater::SOUND_AAC,
+				_ => formater::SOUND_MP3,
+			};
+			properties.push(("audiocodecid".to_string(), AmfValue::Number(sound_format as f64)));
+		}
+		for (key, value) in self.metadata.iter() {
+			if !WRITTEN_PROPERTIES.contains(&key) {
+				properties.push((key.to_string(), AmfValue::String(value.to_string())));
+			}
+		}
+
+		// the array is written by hand to learn where the two numbers land
+		let mut body = Vec::new();
+		AmfValue::String(formater::ON_METADATA.to_string()).write(&mut body);
+		body.push(amf::ECMA_ARRAY);
+		body.extend_from_slice(&(properties.len() as u32).to_be_bytes());
+		let tag_start = self.writer.stream_position()? + formater::TAG_HEADER_SIZE as u64;
+		for (key, value) in &properties {
+			amf::write_string(&mut body, key);
+			// past the number marker
+			let offset = tag_start + body.len() as u64 + 1;
+			match key.as_str() {
+				"duration" => self.duration_offset = offset,
+				"filesize" => self.filesize_offset = offset,
+				_ => {}
+			}
+			value.write(&mut body);
+		}
+		body.extend_from_slice(&[0, 0, amf::OBJECT_END]);
+		self.write_tag(formater::TAG_SCRIPT, 0, &body)?;
+
+		let sequence_headers: Vec<(u8, Vec<u8>)> = self
+			.outputs
+			.iter()
+			.filter_map(|output| {
+				let (kind, mut body) = match output.codec.as_str() {
+					video::H264 => (
+						formater::TAG_VIDEO,
+						vec![
+							formater::FRAME_KEY << 4 | formater::CODEC_AVC,
+							formater::PACKET_SEQUENCE_HEADER,
+							0,
+							0,
+							0,
+						],
+					),
+					audio::AAC => {
+						(formater::TAG_AUDIO, vec![Self::aac_flags(), formater::PACKET_SEQUENCE_HEADER])
+					}
+					_ => return None,
+				};
+				body.extend_from_slice(&output.codec_private);
+				Some((kind, body))
+			})
+			.collect();
+		for (kind, body) in sequence_headers {
+			self.write_tag(kind, 0, &body)?;
+		}
+		self.header_written = true;
+		Ok(())
+	}
+
+	/// AAC tags always claim 44.1 kHz stereo 16-bit; the AudioSpecificConfig
+	/// has the real values.
+	fn aac_flags() -> u8 {
+		formater::SOUND_AAC << 4 | 0x0F
+	}
+
+	fn write_tag(&mut self, kind: u8, timestamp: u32, body: &[u8]) -> Result<()> {
+		if body.len() >= 1 << 24 {
+			return Err(error!("flv tag of {} bytes does not fit a 24-bit size", body.len()));
+		}
+		let size = body.len() as u32;
+		let mut out = Vec::with_capacity(body.len() + 15);
+		FlvTagHeader { kind, filtered: false, size, timestamp }.write(&mut out);
+		out.extend_from_slice(body);
+		out.extend_from_slice(&(size + formater::TAG_HEADER_SIZE).to_be_bytes());
+		self.writer.write_all(&out)
+	}
+
+	fn to_milliseconds(value: i64, time: Time) -> i64 {
+		let numerator = value as i128 * time.num as i128 * 1000;
+		let denominator = time.den as i128;
+		((numerator + denominator / 2).div_euclid(denominator)) as i64
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		if !self.header_written {
+			self.write_header()?;
+		}
+		let index = self
+			.streams
+			.all()
+			.iter()
+			.position(|stream| stream.id == packet.stream_id)
+			.ok_or_else(|| error!("flv muxer has no stream {}", packet.stream_id))?;
+
+		let dts = Self::to_milliseconds(packet.dts, packet.time);
+		let pts = Self::to_milliseconds(packet.pts, packet.time);
+		let shift = *self.shift.get_or_insert(-dts.min(0));
+		let timestamp = (dts + shift).max(0);
+
+		let output = &mut self.outputs[index];
+		if let Some(last) = output.last_timestamp {
+			output.last_duration = (timestamp - last).max(0);
+		}
+		output.last_timestamp = Some(timestamp);
+
+		let frame_type = if packet.keyframe { formater::FRAME_KEY } else { formater::FRAME_INTER };
+		let mut body = Vec::with_capacity(packet.data.len() + 5);
+		let kind = match output.codec.as_str() {
+			video::H264 => {
+				let offset = (pts - dts).clamp(-(1 << 23), (1 << 23) - 1) as i32;
+				body.extend_from_slice(&[frame_type << 4 | formater::CODEC_AVC, formater::PACKET_DATA]);
+				body.extend_from_slice(&offset.to_be_bytes()[1..]);
+				formater::TAG_VIDEO
+			}
+			video::VP6 => {
+				let adjustment = output.codec_private.first().copied().unwrap_or(0);
+				body.extend_from_slice(&[frame_type << 4 | formater::CODEC_VP6, adjustment]);
+				formater::TAG_VIDEO
+			}
+			audio::AAC => {
+				body.extend_from_slice(&[Self::aac_flags(), formater::PACKET_DATA]);
+				formater::TAG_AUDIO
+			}
+			_ => {
+				let flags = match output.audio_flags {
+					Some(flags) => flags,
+					None => *output.audio_flags.insert(formater::mp3_flags(&packet.data)?),
+				};
+				body.push(flags);
+				formater::TAG_AUDIO
+			}
+		};
+		body.extend_from_slice(&packet.data);
+		self.write_tag(kind, timestamp as u32, &body)
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		if !self.header_written {
+			self.write_header()?;
+		}
+		let video = self.output(StreamKind::Video);
+		if let Some(output) = video.filter(|output| output.codec == video::H264) {
+			let timestamp = output.last_timestamp.unwrap_or(0) as u32;
+			let body =
+				[formater::FRAME_KEY << 4 | formater::CODEC_AVC, formater::PACKET_END_OF_SEQUENCE, 0, 0, 0];
+			self.write_tag(formater::TAG_VIDEO, timestamp, &body)?;
+		}
+
+		let end = self
+			.outputs
+			.iter()
+			.filter_map(|output| Some(output.last_timestamp? + output.last_duration))
+			.max()
+			.unwrap_or(0);
+		let filesize = self.writer.stream_position()?;
+		self.writer.seek(SeekFrom::Start(self.duration_offset))?;
+		self.writer.write_all(&(end as f64 / 1000.0).to_be_bytes())?;
+		self.writer.seek(SeekFrom::Start(self.filesize_offset))?;
+		self.writer.write_all(&(filesize as f64).to_be_bytes())?;
+		self.writer.seek(SeekFrom::End(0))?;
+		self.writer.flush()
+	}
+}
+
+impl<W: MediaWrite + MediaSeek> Muxer for FlvMuxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+}
@@ -0,0 +1,22 @@
+use crate::io::{MediaRead, MediaSeek, ReadPrimitives, SeekFrom};
+use crate::message::Result;
+
+const HEADER_SIZE: u64 = 10;
+const FLAG_FOOTER: u8 = 0x10;
+
+/// Size of an ID3v2 tag at the current position, including its header and
+/// footer, or 0 when there is none. The position is left unchanged.
+pub fn tag_size<R: MediaRead + MediaSeek>(reader: &mut R) -> Result<u64> {
+	let start = reader.stream_position()?;
+	let mut header = [0u8; HEADER_SIZE as usize];
+	let found = reader.read_exact(&mut header).is_ok() && &header[..3] == b"ID3";
+	reader.seek(SeekFrom::Start(start))?;
+	if !found {
+		return Ok(0);
+	}
+
+	// sizes are syncsafe: 7 bits per byte
+	let size = header[6..10].iter().fold(0u64, |size, byte| (size << 7) | (*byte & 0x7f) as u64);
+	let footer = if header[5] & FLAG_FOOTER != 0 { HEADER_SIZE } else { 0 };
+	Ok(HEADER_SIZE + size + footer)
+}
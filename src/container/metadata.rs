@@ -0,0 +1,46 @@
+#[derive(Debug, Clone, Default)]
+pub struct Metadata {
+	fields: Vec<(String, String)>,
+}
+
+impl Metadata {
+	pub fn new() -> Self {
+		Self { fields: Vec::new() }
+	}
+
+	pub fn set(&mut self, key: &str, value: String) {
+		match self.fields.iter_mut().find(|(k, _)| k == key) {
+			Some((_, current)) => *current = value,
+			None => self.fields.push((key.to_string(), value)),
+		}
+	}
+
+	pub fn get(&self, key: &str) -> Option<&str> {
+		self.fields.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+	}
+
+	pub fn remove(&mut self, key: &str) -> Option<String> {
+		let index = self.fields.iter().position(|(k, _)| k == key)?;
+		Some(self.fields.remove(index).1)
+	}
+
+	pub fn artist(&self) -> Option<&str> {
+		self.get("artist")
+	}
+
+	pub fn title(&self) -> Option<&str> {
+		self.get("title")
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+		self.fields.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+	}
+
+	pub fn len(&self) -> usize {
+		self.fields.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.fields.is_empty()
+	}
+}
@@ -0,0 +1,543 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+use super::ebml::{self, Children, EbmlReader, Element};
+use super::formater::{MkvChapter, MkvCuePoint, MkvInfo, MkvTag, MkvTrack};
+use crate::container::Metadata;
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream, time};
+use crate::io::{MediaRead, MediaSeek};
+use crate::{error, message::Result};
+
+const LACING_NONE: u8 = 0;
+const LACING_XIPH: u8 = 1;
+const LACING_FIXED: u8 = 2;
+const LACING_EBML: u8 = 3;
+/// Video frames held back to give them decode times; B-frame reordering
+/// never spans more.
+const REORDER_WINDOW: usize = 16;
+
+/// Matroska stores presentation times only. Decode times are the
+/// presentation times in sorted order, held back by the largest
+/// reordering seen in the first window so that they never pass them.
+#[derive(Default)]
+struct Reorder {
+	held: usize,
+	first: Vec<i64>,
+	pts: BinaryHeap<Reverse<i64>>,
+	delay: Option<i64>,
+}
+
+impl Reorder {
+	fn push(&mut self, pts: i64) {
+		if self.delay.is_none() {
+			self.first.push(pts);
+		}
+		self.pts.push(Reverse(pts));
+		self.held += 1;
+	}
+
+	fn pop(&mut self, packet: &mut Packet) {
+		let delay = *self.delay.get_or_insert_with(|| {
+			let mut sorted = self.first.clone();
+			sorted.sort_unstable();
+			let delay = sorted.iter().zip(&self.first).map(|(sorted, pts)| sorted - pts).max();
+			delay.unwrap_or(0).max(0)
+		});
+		if let Some(Reverse(pts)) = self.pts.pop() {
+			packet.dts = pts - delay;
+		}
+		self.held -= 1;
+	}
+}
+
+pub struct MkvDemuxer<R: MediaRead> {
+	reader: EbmlReader<R>,
+	info: MkvInfo,
+	tracks: Vec<MkvTrack>,
+	streams: stream::Streams,
+	metadata: Metadata,
+	chapters: Vec<MkvChapter>,
+	cues: Vec<MkvCuePoint>,
+	seek_head: Vec<(u32, u64)>,
+	/// Top-level elements already parsed, so the SeekHead does not load them twice.
+	loaded: Vec<u32>,
+	segment_offset: u64,
+	segment_end: Option<u64>,
+	cluster_timecode: Option<u64>,
+	next_element: Option<Element>,
+	/// Frames in file order, waiting to be returned; every track waits behind
+	/// a video frame until its decode time is known.
+	pending: VecDeque<Packet>,
+	reorder: Vec<Reorder>,
+}
+
+impl<R: MediaRead> MkvDemuxer<R> {
+	pub fn new(reader: R) -> Result<Self> {
+		let mut reader = EbmlReader::new(reader);
+		let doc_type = Self::read_header(&mut reader)?;
+
+		let segment = loop {
+			match reader.read_element()? {
+				Some(element) if element.id == ebml::SEGMENT => break element,
+				Some(element) => reader.skip(&element)?,
+				None => return Err(error!("mkv file has no segment")),
+			}
+		};
+
+		let mut demuxer = Self {
+			reader,
+			info: MkvInfo::new(doc_type),
+			tracks: Vec::new(),
+			streams: stream::Streams::new_empty(),
+			metadata: Metadata::new(),
+			chapters: Vec::new(),
+			cues: Vec::new(),
+			seek_head: Vec::new(),
+			loaded: Vec::new(),
+			segment_offset: segment.offset,
+			segment_end: segment.end(),
+			cluster_timecode: None,
+			next_element: None,
+			pending: VecDeque::new(),
+			reorder: Vec::new(),
+		};
+
+		// everything up to the first cluster describes the segment
+		while let Some(element) = demuxer.read_segment_child()? {
+			if element.id == ebml::CLUSTER {
+				demuxer.next_element = Some(element);
+				break;
+			}
+			match element.id {
+				ebml::INFO => {
+					let data = demuxer.reader.read_data(&element)?;
+					demuxer.info.parse(&data)?;
+				}
+				ebml::TRACKS => {
+					let data = demuxer.reader.read_data(&element)?;
+					demuxer.read_tracks(&data)?;
+				}
+				ebml::SEEK_HEAD => {
+					let data = demuxer.reader.read_data(&element)?;
+					demuxer.read_seek_head(&data)?;
+				}
+				ebml::CUES | ebml::TAGS | ebml::CHAPTERS => {
+					let data = demuxer.reader.read_data(&element)?;
+					demuxer.read_index(element.id, &data)?;
+				}
+				_ => demuxer.reader.skip(&element)?,
+			}
+		}
+
+		if demuxer.tracks.is_empty() {
+			return Err(error!("mkv file has no usable tracks"));
+		}
+
+		demuxer.reorder = demuxer.tracks.iter().map(|_| Reorder::default()).collect();
+		let time = demuxer.info.time();
+		for (index, track) in demuxer.tracks.iter().enumerate() {
+			let stream = stream::Stream::new(index as u32, index, track.kind, track.codec_name(), time);
+			demuxer.streams.add(stream.with_codec_private(track.codec_private.clone()));
+		}
+		let title = demuxer.info.title.clone().filter(|_| demuxer.metadata.title().is_none());
+		if let Some(title) = title {
+			demuxer.metadata.set("title", title);
+		}
+		Ok(demuxer)
+	}
+
+	fn read_header(reader: &mut EbmlReader<R>) -> Result<String> {
+		let header = match reader.read_element()? {
+			Some(element) if element.id == ebml::EBML => element,
+			_ => return Err(error!("not an ebml file")),
+		};
+
+		let mut doc_type = "matroska".to_string();
+		for child in Children::new(&reader.read_data(&header)?) {
+			let (id, payload) = child?;
+			match id {
+				ebml::DOC_TYPE => doc_type = ebml::read_string(payload),
+				ebml::EBML_READ_VERSION if ebml::read_uint(payload)? > 1 => {
+					return Err(error!("ebml read version is not supported"));
+				}
+				ebml::EBML_MAX_ID_LENGTH if ebml::read_uint(payload)? > 4 => {
+					return Err(error!("ebml ids longer than 4 bytes are not supported"));
+				}
+				ebml::EBML_MAX_SIZE_LENGTH if ebml::read_uint(payload)? > 8 => {
+					return Err(error!("ebml sizes longer than 8 bytes are not supported"));
+				}
+				_ => {}
+			}
+		}
+
+		if doc_type != "matroska" && doc_type != "webm" {
+			return Err(error!("ebml doc type '{}' is not matroska", doc_type));
+		}
+		Ok(doc_type)
+	}
+
+	fn read_segment_child(&mut self) -> Result<Option<Element>> {
+		if let Some(element) = self.next_element.take() {
+			return Ok(Some(element));
+		}
+		if self.segment_end.is_some_and(|end| self.reader.position() >= end) {
+			return Ok(None);
+		}
+		self.reader.read_element()
+	}
+
+	fn read_tracks(&mut self, data: &[u8]) -> Result<()> {
+		for entry in Children::new(data) {
+			let (id, entry) = entry?;
+			if id != ebml::TRACK_ENTRY {
+				continue;
+			}
+			if let Some(track) = MkvTrack::parse(entry)? {
+				if self.tracks.iter().any(|known| known.number == track.number) {
+					return Err(error!("mkv track number {} is used twice", track.number));
+				}
+				self.tracks.push(track);
+			}
+		}
+		Ok(())
+	}
+
+	fn read_seek_head(&mut self, data: &[u8]) -> Result<()> {
+		for seek in Children::new(data) {
+			let (id, seek) = seek?;
+			if id != ebml::SEEK {
+				continue;
+			}
+			let mut target = None;
+			let mut position = None;
+			for child in Children::new(seek) {
+				let (id, payload) = child?;
+				match id {
+					ebml::SEEK_ID => target = Some(ebml::read_uint(payload)? as u32),
+					ebml::SEEK_POSITION => position = Some(ebml::read_uint(payload)?),
+					_ => {}
+				}
+			}
+			if let (Some(target), Some(position)) = (target, position) {
+				self.seek_head.push((target, position));
+			}
+		}
+		Ok(())
+	}
+
+	fn read_index(&mut self, id: u32, data: &[u8]) -> Result<()> {
+		if self.loaded.contains(&id) {
+			return Ok(());
+		}
+		match id {
+			ebml::CUES => self.cues = MkvCuePoint::parse_all(data)?,
+			ebml::CHAPTERS => self.chapters = MkvChapter::parse_all(data)?,
+			ebml::TAGS => {
+				for tag in MkvTag::parse_all(data)? {
+					self.apply_tag(tag);
+				}
+			}
+			_ => return Ok(()),
+		}
+		self.loaded.push(id);
+		Ok(())
+	}
+
+	fn apply_tag(&mut self, tag: MkvTag) {
+		if tag.track_uids.is_empty() {
+			for (key, value) in tag.fields.iter() {
+				self.metadata.set(key, value.to_string());
+			}
+			return;
+		}
+		for track in self.tracks.iter_mut().filter(|track| tag.track_uids.contains(&track.uid)) {
+			for (key, value) in tag.fields.iter() {
+				track.tags.set(key, value.to_string());
+			}
+		}
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		loop {
+			if let Some(packet) = self.next_pending(false) {
+				return Ok(Some(packet));
+			}
+
+			let Some(element) = self.read_segment_child()? else {
+				return Ok(self.next_pending(true));
+			};
+
+			// clusters are entered rather than loaded; their children follow directly
+			match element.id {
+				ebml::CLUSTER => self.cluster_timecode = None,
+				ebml::TIMECODE => {
+					let data = self.reader.read_data(&element)?;
+					self.cluster_timecode = Some(ebml::read_uint(&data)?);
+				}
+				ebml::SIMPLE_BLOCK => {
+					let data = self.reader.read_data(&element)?;
+					self.read_block(&data, None, None)?;
+				}
+				ebml::BLOCK_GROUP => {
+					let data = self.reader.read_data(&element)?;
+					self.read_block_group(&data)?;
+				}
+				ebml::CUES | ebml::TAGS | ebml::CHAPTERS => {
+					let data = self.reader.read_data(&element)?;
+					self.read_index(element.id, &data)?;
+				}
+				_ => self.reader.skip(&element)?,
+			}
+		}
+	}
+
+	fn read_block_group(&mut self, data: &[u8]) -> Result<()> {
+		let mut block = None;
+		let mut duration = None;
+		let mut referenced = false;
+		for child in Children::new(data) {
+			let (id, payload) = child?;
+			match id {
+				ebml::BLOCK => block = Some(payload),
+				ebml::BLOCK_DURATION => duration = Some(ebml::read_uint(payload)?),
+				ebml::REFERENCE_BLOCK => referenced = true,
+				_ => {}
+			}
+		}
+		match block {
+			Some(block) => self.read_block(block, duration, Some(!referenced)),
+			None => Err(error!("mkv block group has no block")),
+		}
+	}
+
+	/// Split a (Simple)Block into packets. `keyframe` overrides the SimpleBlock flag
+	/// for blocks inside a group, which mark keyframes by the absence of references.
+	fn read_block(
+		&mut self,
+		data: &[u8],
+		duration: Option<u64>,
+		keyframe: Option<bool>,
+	) -> Result<()> {
+		let (track_number, length) = ebml::read_vint(data)?;
+		if data.len() < length + 3 {
+			return Err(error!("mkv block is truncated"));
+		}
+		let Some(index) = self.tracks.iter().position(|track| track.number == track_number) else {
+			return Ok(());
+		};
+
+		let relative = i16::from_be_bytes([data[length], data[length + 1]]) as i64;
+		let flags = data[length + 2];
+		let keyframe = keyframe.unwrap_or(flags & 0x80 != 0);
+		let frames = Self::split_lacing(&data[length + 3..], (flags >> 1) & 0x03)?;
+
+		let cluster =
+			self.cluster_timecode.ok_or_else(|| error!("mkv block before cluster timecode"))?;
+		let pts = cluster as i64 + relative;
+
+		// laced frames share one timestamp; spread them over the known duration
+		let track = &self.tracks[index];
+		let count = frames.len() as i64;
+		let span = match (duration, track.default_duration) {
+			(Some(duration), _) => Some(duration as i64),
+			(None, Some(frame)) => Some((frame * frames.len() as u64 / self.info.timecode_scale) as i64),
+			(None, None) => None,
+		};
+
+		let time = self.info.time();
+		for (frame_index, frame) in frames.into_iter().enumerate() {
+			let offset = span.map(|span| span * frame_index as i64 / count).unwrap_or(0);
+			let mut payload = Vec::with_capacity(track.stripped_header.len() + frame.len());
+			payload.extend_from_slice(&track.stripped_header);
+			payload.extend_from_slice(frame);
+
+			let packet = Packet::new(payload, index as u32, time)
+				.with_pts(pts + offset)
+				.with_dts(pts + offset)
+				.with_keyframe(keyframe);
+			if track.kind == stream::StreamKind::Video {
+				self.reorder[index].push(packet.pts);
+			}
+			self.pending.push_back(packet);
+		}
+		Ok(())
+	}
+
+	fn next_pending(&mut self, ended: bool) -> Option<Packet> {
+		let index = self.pending.front()?.stream_id as usize;
+		let video = self.tracks[index].kind == stream::StreamKind::Video;
+		if video && !ended && self.reorder[index].held <= REORDER_WINDOW {
+			return None;
+		}
+		let mut packet = self.pending.pop_front()?;
+		if video {
+			self.reorder[index].pop(&mut packet);
+		}
+		Some(packet)
+	}
+
+	fn split_lacing(data: &[u8], lacing: u8) -> Result<Vec<&[u8]>> {
+		if lacing == LACING_NONE {
+			return Ok(vec![data]);
+		}
+
+		let count = *data.first().ok_or_else(|| error!("mkv laced block is empty"))? as usize + 1;
+		let mut cursor = 1;
+		let mut sizes = Vec::with_capacity(count);
+
+		match lacing {
+			LACING_XIPH => {
+				for _ in 0..count - 1 {
+					let mut size = 0i64;
+					loop {
+						let byte = *data.get(cursor).ok_or_else(|| error!("mkv xiph lacing is truncated"))?;
+						cursor += 1;
+						size += byte as i64;
+						if byte != 255 {
+							break;
+						}
+					}
+					sizes.push(size);
+				}
+			}
+			LACING_EBML => {
+				if count > 1 {
+					let (first, length) = ebml::read_vint(&data[cursor..])?;
+					cursor += length;
+					sizes.push(first as i64);
+				}
+				for _ in 1..count.saturating_sub(1) {
+					let (delta, length) = ebml::read_svint(&data[cursor..])?;
+					cursor += length;
+					let size = sizes[sizes.len() - 1] + delta;
+					if size < 0 {
+						return Err(error!("mkv ebml lacing has negative frame size"));
+					}
+					sizes.push(size);
+				}
+			}
+			LACING_FIXED => {
+				let total = data.len() - cursor;
+				if !total.is_multiple_of(count) {
+					return Err(error!("mkv fixed lacing does not divide {} bytes by {}", total, count));
+				}
+				sizes.resize(count - 1, (total / count) as i64);
+			}
+			_ => unreachable!(),
+		}
+		let sizes: Vec<usize> = sizes.into_iter().map(|size| size as usize).collect();
+
+		let mut frames = Vec::with_capacity(count);
+		for size in sizes {
+			if cursor + size > data.len() {
+				return Err(error!("mkv laced frame overruns its block"));
+			}
+			frames.push(&data[cursor..cursor + size]);
+			cursor += size;
+		}
+		frames.push(&data[cursor..]);
+		Ok(frames)
+	}
+
+	pub fn info(&self) -> &MkvInfo {
+		&self.info
+	}
+
+	pub fn tracks(&self) -> &[MkvTrack] {
+		&self.tracks
+	}
+
+	pub fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+
+	pub fn chapters(&self) -> &[MkvChapter] {
+		&self.chapters
+	}
+
+	pub fn cues(&self) -> &[MkvCuePoint] {
+		&self.cues
+	}
+
+	pub fn time(&self) -> time::Time {
+		self.info.time()
+	}
+}
+
+impl<R: MediaRead + MediaSeek> MkvDemuxer<R> {
+	/// Load the Cues, Tags and Chapters the SeekHead points at, which muxers
+	/// usually write after the clusters.
+	pub fn load_seek_head(&mut self) -> Result<()> {
+		let resume = self.reader.position();
+		let targets: Vec<(u32, u64)> = self
+			.seek_head
+			.iter()
+			.filter(|(id, _)| matches!(*id, ebml::CUES | ebml::TAGS | ebml::CHAPTERS))
+			.filter(|(id, _)| !self.loaded.contains(id))
+			.copied()
+			.collect();
+
+		for (id, position) in targets {
+			self.reader.seek(self.segment_offset + position)?;
+			match self.reader.read_element()? {
+				Some(element) if element.id == id => {
+					let data = self.reader.read_data(&element)?;
+					self.read_index(id, &data)?;
+				}
+				_ => return Err(error!("mkv seek head entry {:#X} points at the wrong element", id)),
+			}
+		}
+		self.reader.seek(resume)
+	}
+
+	/// Move to the cluster holding the last cue at or before `timestamp` (in
+	/// segment ticks) and return that cue's time. Packets resume from there.
+	pub fn seek(&mut self, timestamp: i64) -> Result<i64> {
+		if self.cues.is_empty() {
+			self.load_seek_head()?;
+		}
+		if self.cues.is_empty() {
+			return Err(error!("mkv file has no cues to seek with"));
+		}
+
+		// prefer video cues, which land on keyframes every track can resume from
+		let video: Vec<u64> = self
+			.tracks
+			.iter()
+			.filter(|track| track.kind == stream::StreamKind::Video)
+			.map(|track| track.number)
+			.collect();
+		let candidates: Vec<&MkvCuePoint> = match video.is_empty() {
+			true => self.cues.iter().collect(),
+			false => self.cues.iter().filter(|cue| video.contains(&cue.track)).collect(),
+		};
+		let candidates = if candidates.is_empty() { self.cues.iter().collect() } else { candidates };
+
+		let target = timestamp.max(0) as u64;
+		let cue = candidates
+			.iter()
+			.rev()
+			.find(|cue| cue.time <= target)
+			.or(candidates.first())
+			.copied()
+			.copied()
+			.ok_or_else(|| error!("mkv file has no cues to seek with"))?;
+
+		self.reader.seek(self.segment_offset + cue.cluster_position)?;
+		self.next_element = None;
+		self.cluster_timecode = None;
+		self.pending.clear();
+		self.reorder.iter_mut().for_each(|reorder| *reorder = Reorder::default());
+		Ok(cue.time as i64)
+	}
+}
+
+impl<R: MediaRead> Demuxer for MkvDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
@@ -0,0 +1,376 @@
+use crate::io::{MediaRead, MediaSeek, ReadPrimitives, SeekFrom};
+use crate::{error, message::Result};
+
+// EBML header
+pub const EBML: u32 = 0x1A45DFA3;
+pub const EBML_VERSION: u32 = 0x4286;
+pub const EBML_READ_VERSION: u32 = 0x42F7;
+pub const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+pub const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+pub const DOC_TYPE: u32 = 0x4282;
+pub const DOC_TYPE_VERSION: u32 = 0x4287;
+pub const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+
+// global elements
+pub const VOID: u32 = 0xEC;
+pub const CRC32: u32 = 0xBF;
+
+// segment
+pub const SEGMENT: u32 = 0x18538067;
+pub const SEEK_HEAD: u32 = 0x114D9B74;
+pub const SEEK: u32 = 0x4DBB;
+pub const SEEK_ID: u32 = 0x53AB;
+pub const SEEK_POSITION: u32 = 0x53AC;
+
+// segment information
+pub const INFO: u32 = 0x1549A966;
+pub const TIMECODE_SCALE: u32 = 0x2AD7B1;
+pub const DURATION: u32 = 0x4489;
+pub const TITLE: u32 = 0x7BA9;
+pub const MUXING_APP: u32 = 0x4D80;
+pub const WRITING_APP: u32 = 0x5741;
+pub const DATE_UTC: u32 = 0x4461;
+
+// tracks
+pub const TRACKS: u32 = 0x1654AE6B;
+pub const TRACK_ENTRY: u32 = 0xAE;
+pub const TRACK_NUMBER: u32 = 0xD7;
+pub const TRACK_UID: u32 = 0x73C5;
+pub const TRACK_TYPE: u32 = 0x83;
+pub const FLAG_ENABLED: u32 = 0xB9;
+pub const FLAG_DEFAULT: u32 = 0x88;
+pub const FLAG_FORCED: u32 = 0x55AA;
+pub const FLAG_LACING: u32 = 0x9C;
+pub const DEFAULT_DURATION: u32 = 0x23E383;
+pub const NAME: u32 = 0x536E;
+pub const LANGUAGE: u32 = 0x22B59C;
+pub const LANGUAGE_BCP47: u32 = 0x22B59D;
+pub const CODEC_ID: u32 = 0x86;
+pub const CODEC_PRIVATE: u32 = 0x63A2;
+pub const CODEC_NAME: u32 = 0x258688;
+pub const CODEC_DELAY: u32 = 0x56AA;
+pub const SEEK_PRE_ROLL: u32 = 0x56BB;
+pub const VIDEO: u32 = 0xE0;
+pub const PIXEL_WIDTH: u32 = 0xB0;
+pub const PIXEL_HEIGHT: u32 = 0xBA;
+pub const DISPLAY_WIDTH: u32 = 0x54B0;
+pub const DISPLAY_HEIGHT: u32 = 0x54BA;
+pub const FLAG_INTERLACED: u32 = 0x9A;
+pub const AUDIO: u32 = 0xE1;
+pub const SAMPLING_FREQUENCY: u32 = 0xB5;
+pub const OUTPUT_SAMPLING_FREQUENCY: u32 = 0x78B5;
+pub const CHANNELS: u32 = 0x9F;
+pub const BIT_DEPTH: u32 = 0x6264;
+pub const CONTENT_ENCODINGS: u32 = 0x6D80;
+pub const CONTENT_ENCODING: u32 = 0x6240;
+pub const CONTENT_ENCODING_SCOPE: u32 = 0x5032;
+pub const CONTENT_ENCODING_TYPE: u32 = 0x5033;
+pub const CONTENT_COMPRESSION: u32 = 0x5034;
+pub const CONTENT_COMP_ALGO: u32 = 0x4254;
+pub const CONTENT_COMP_SETTINGS: u32 = 0x4255;
+pub const CONTENT_ENCRYPTION: u32 = 0x5035;
+
+// clusters
+pub const CLUSTER: u32 = 0x1F43B675;
+pub const TIMECODE: u32 = 0xE7;
+pub const POSITION: u32 = 0xA7;
+pub const PREV_SIZE: u32 = 0xAB;
+pub const SIMPLE_BLOCK: u32 = 0xA3;
+pub const BLOCK_GROUP: u32 = 0xA0;
+pub const BLOCK: u32 = 0xA1;
+pub const BLOCK_DURATION: u32 = 0x9B;
+pub const REFERENCE_BLOCK: u32 = 0xFB;
+pub const DISCARD_PADDING: u32 = 0x75A2;
+
+// cues
+pub const CUES: u32 = 0x1C53BB6B;
+pub const CUE_POINT: u32 = 0xBB;
+pub const CUE_TIME: u32 = 0xB3;
+pub const CUE_TRACK_POSITIONS: u32 = 0xB7;
+pub const CUE_TRACK: u32 = 0xF7;
+pub const CUE_CLUSTER_POSITION: u32 = 0xF1;
+pub const CUE_RELATIVE_POSITION: u32 = 0xF0;
+
+// chapters
+pub const CHAPTERS: u32 = 0x1043A770;
+pub const EDITION_ENTRY: u32 = 0x45B9;
+pub const CHAPTER_ATOM: u32 = 0xB6;
+pub const CHAPTER_UID: u32 = 0x73C4;
+pub const CHAPTER_TIME_START: u32 = 0x91;
+pub const CHAPTER_TIME_END: u32 = 0x92;
+pub const CHAPTER_FLAG_HIDDEN: u32 = 0x98;
+pub const CHAPTER_DISPLAY: u32 = 0x80;
+pub const CHAP_STRING: u32 = 0x85;
+pub const CHAP_LANGUAGE: u32 = 0x437C;
+
+// tags
+pub const TAGS: u32 = 0x1254C367;
+pub const TAG: u32 = 0x7373;
+pub const TARGETS: u32 = 0x63C0;
+pub const TARGET_TYPE_VALUE: u32 = 0x68CA;
+pub const TAG_TRACK_UID: u32 = 0x63C5;
+pub const SIMPLE_TAG: u32 = 0x67C8;
+pub const TAG_NAME: u32 = 0x45A3;
+pub const TAG_LANGUAGE: u32 = 0x447A;
+pub const TAG_STRING: u32 = 0x4487;
+pub const TAG_BINARY: u32 = 0x4485;
+
+pub const ATTACHMENTS: u32 = 0x1941A469;
+
+/// Largest element the parser will load into memory at once.
+pub const MAX_ELEMENT_SIZE: u64 = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Element {
+	pub id: u32,
+	/// `None` for elements written with an unknown size.
+	pub size: Option<u64>,
+	pub offset: u64,
+}
+
+impl Element {
+	pub fn end(&self) -> Option<u64> {
+		self.size.map(|size| self.offset + size)
+	}
+
+	pub fn known_size(&self) -> Result<u64> {
+		self.size.ok_or_else(|| error!("ebml element {:#X} has unknown size", self.id))
+	}
+}
+
+fn vint_length(first: u8) -> Result<usize> {
+	match first.leading_zeros() {
+		zeros @ 0..=7 => Ok(zeros as usize + 1),
+		_ => Err(error!("invalid ebml variable size integer")),
+	}
+}
+
+/// Decode a size vint, `None` when every value bit is set (unknown size).
+fn decode_size(bytes: &[u8]) -> Option<u64> {
+	let length = bytes.len();
+	let mut value = (bytes[0] & (0xFF_u16 >> length) as u8) as u64;
+	for byte in &bytes[1..] {
+		value = (value << 8) | *byte as u64;
+	}
+	if value == (1u64 << (7 * length)) - 1 { None } else { Some(value) }
+}
+
+fn decode_id(bytes: &[u8]) -> Result<u32> {
+	if bytes.len() > 4 {
+		return Err(error!("ebml element id longer than 4 bytes"));
+	}
+	Ok(bytes.iter().fold(0u32, |id, byte| (id << 8) | *byte as u32))
+}
+
+/// Read a vint with its length marker removed from the front of `data`.
+pub fn read_vint(data: &[u8]) -> Result<(u64, usize)> {
+	let first = *data.first().ok_or_else(|| error!("ebml data is truncated"))?;
+	let length = vint_length(first)?;
+	if data.len() < length {
+		return Err(error!("ebml data is truncated"));
+	}
+	let mut value = (first & (0xFF_u16 >> length) as u8) as u64;
+	for byte in &data[1..length] {
+		value = (value << 8) | *byte as u64;
+	}
+	Ok((value, length))
+}
+
+pub fn read_svint(data: &[u8]) -> Result<(i64, usize)> {
+	let (value, length) = read_vint(data)?;
+	let bias = (1i64 << (7 * length - 1)) - 1;
+	Ok((value as i64 - bias, length))
+}
+
+pub fn read_uint(data: &[u8]) -> Result<u64> {
+	if data.len() > 8 {
+		return Err(error!("ebml unsigned integer longer than 8 bytes"));
+	}
+	Ok(data.iter().fold(0u64, |value, byte| (value << 8) | *byte as u64))
+}
+
+pub fn read_int(data: &[u8]) -> Result<i64> {
+	if data.is_empty() {
+		return Ok(0);
+	}
+	let value = read_uint(data)?;
+	let shift = 64 - 8 * data.len() as u32;
+	Ok(((value << shift) as i64) >> shift)
+}
+
+pub fn read_float(data: &[u8]) -> Result<f64> {
+	match data.len() {
+		0 => Ok(0.0),
+		4 => Ok(f32::from_be_bytes([data[0], data[1], data[2], data[3]]) as f64),
+		8 => Ok(f64::from_be_bytes(data.try_into().unwrap())),
+		size => Err(error!("ebml float of {} bytes is invalid", size)),
+	}
+}
+
+pub fn read_string(data: &[u8]) -> String {
+	let end = data.iter().position(|byte| *byte == 0).unwrap_or(data.len());
+	String::from_utf8_lossy(&data[..end]).to_string()
+}
+
+/// Encode `value` as a vint of `length` bytes, or the shortest that fits.
+pub fn encode_vint(value: u64, length: Option<usize>) -> Vec<u8> {
+	let length =
+		length.unwrap_or_else(|| (1..8).find(|l| value < (1u64 << (7 * l)) - 1).unwrap_or(8));
+	let marked = value | (1u64 << (7 * length));
+	marked.to_be_bytes()[8 - length..].to_vec()
+}
+
+pub fn write_id(out: &mut Vec<u8>, id: u32) {
+	let bytes = id.to_be_bytes();
+	let skip = bytes.iter().take_while(|byte| **byte == 0).count().min(3);
+	out.extend_from_slice(&bytes[skip..]);
+}
+
+pub fn write_element(out: &mut Vec<u8>, id: u32, payload: &[u8]) {
+	write_id(out, id);
+	out.extend_from_slice(&encode_vint(payload.len() as u64, None));
+	out.extend_from_slice(payload);
+}
+
+pub fn write_uint(out: &mut Vec<u8>, id: u32, value: u64) {
+	let bytes = value.to_be_bytes();
+	let skip = bytes.iter().take_while(|byte| **byte == 0).count().min(7);
+	write_element(out, id, &bytes[skip..]);
+}
+
+pub fn write_float(out: &mut Vec<u8>, id: u32, value: f64) {
+	write_element(out, id, &value.to_be_bytes());
+}
+
+pub fn write_string(out: &mut Vec<u8>, id: u32, value: &str) {
+	write_element(out, id, value.as_bytes());
+}
+
+/// A `Void` element spanning exactly `size` bytes, header included (at least 2).
+pub fn write_void(out: &mut Vec<u8>, size: usize) {
+	// a one byte size field covers up to 126 bytes of payload, beyond that use eight
+	let length = if size - 2 <= 126 { 1 } else { 8 };
+	let payload = size - 1 - length;
+	write_id(out, VOID);
+	out.extend_from_slice(&encode_vint(payload as u64, Some(length)));
+	out.resize(out.len() + payload, 0);
+}
+
+pub struct Children<'a> {
+	data: &'a [u8],
+}
+
+impl<'a> Children<'a> {
+	pub fn new(data: &'a [u8]) -> Self {
+		Self { data }
+	}
+
+	fn next_child(&mut self) -> Result<(u32, &'a [u8])> {
+		let id_length = vint_length(self.data[0])?;
+		if self.data.len() < id_length + 1 {
+			return Err(error!("ebml element is truncated"));
+		}
+		let id = decode_id(&self.data[..id_length])?;
+		let size_length = vint_length(self.data[id_length])?;
+		let start = id_length + size_length;
+		if self.data.len() < start {
+			return Err(error!("ebml element is truncated"));
+		}
+
+		let end = match decode_size(&self.data[id_length..start]) {
+			Some(size) if size <= (self.data.len() - start) as u64 => start + size as usize,
+			Some(_) => return Err(error!("ebml element {:#X} overruns its parent", id)),
+			None => return Err(error!("ebml element {:#X} has unknown size", id)),
+		};
+
+		let payload = &self.data[start..end];
+		self.data = &self.data[end..];
+		Ok((id, payload))
+	}
+}
+
+impl<'a> Iterator for Children<'a> {
+	type Item = Result<(u32, &'a [u8])>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.data.is_empty() {
+			return None;
+		}
+		let child = self.next_child();
+		if child.is_err() {
+			self.data = &[];
+		}
+		Some(child)
+	}
+}
+
+pub struct EbmlReader<R: MediaRead> {
+	reader: R,
+	position: u64,
+}
+
+impl<R: MediaRead> EbmlReader<R> {
+	pub fn new(reader: R) -> Self {
+		Self { reader, position: 0 }
+	}
+
+	pub fn position(&self) -> u64 {
+		self.position
+	}
+
+	fn read_vint_bytes(&mut self, buf: &mut [u8; 8]) -> Result<usize> {
+		buf[0] = self.reader.read_u8()?;
+		let length = vint_length(buf[0])?;
+		self.reader.read_exact(&mut buf[1..length])?;
+		self.position += length as u64;
+		Ok(length)
+	}
+
+	pub fn read_element(&mut self) -> Result<Option<Element>> {
+		let mut first = [0u8; 1];
+		if self.reader.read(&mut first)? == 0 {
+			return Ok(None);
+		}
+
+		let id_length = vint_length(first[0])?;
+		let mut buf = [0u8; 8];
+		buf[0] = first[0];
+		self.reader.read_exact(&mut buf[1..id_length])?;
+		self.position += id_length as u64;
+		let id = decode_id(&buf[..id_length])?;
+
+		let size_length = self.read_vint_bytes(&mut buf)?;
+		let size = decode_size(&buf[..size_length]);
+		Ok(Some(Element { id, size, offset: self.position }))
+	}
+
+	pub fn read_data(&mut self, element: &Element) -> Result<Vec<u8>> {
+		let size = element.known_size()?;
+		if size > MAX_ELEMENT_SIZE {
+			return Err(error!("ebml element {:#X} is too large ({} bytes)", element.id, size));
+		}
+		let mut data = vec![0u8; size as usize];
+		self.reader.read_exact(&mut data)?;
+		self.position += size;
+		Ok(data)
+	}
+
+	pub fn skip(&mut self, element: &Element) -> Result<()> {
+		let mut remaining = element.known_size()?;
+		let mut buf = [0u8; 4096];
+		while remaining > 0 {
+			let chunk = remaining.min(buf.len() as u64) as usize;
+			self.reader.read_exact(&mut buf[..chunk])?;
+			remaining -= chunk as u64;
+			self.position += chunk as u64;
+		}
+		Ok(())
+	}
+}
+
+impl<R: MediaRead + MediaSeek> EbmlReader<R> {
+	pub fn seek(&mut self, position: u64) -> Result<()> {
+		self.position = self.reader.seek(SeekFrom::Start(position))?;
+		Ok(())
+	}
+}
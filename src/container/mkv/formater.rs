@@ -0,0 +1,599 @@
+use super::ebml::{self, Children};
+use crate::codecs::{audio, subtitle, video};
+use crate::container::{self, Metadata};
+use crate::core::compatible::Compatible;
+use crate::core::stream::StreamKind;
+use crate::core::time::Time;
+use crate::{error, message::Result};
+
+pub const TRACK_VIDEO: u64 = 1;
+pub const TRACK_AUDIO: u64 = 2;
+pub const TRACK_SUBTITLE: u64 = 0x11;
+
+/// Nanoseconds per tick when the segment does not say otherwise.
+pub const DEFAULT_TIMECODE_SCALE: u64 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MkvProfile {
+	Matroska,
+	/// Restricts tracks to the codecs `Compatible` lists for WebM.
+	WebM,
+}
+
+impl MkvProfile {
+	pub fn from_extension(extension: &str) -> Self {
+		match extension {
+			container::WEBM => Self::WebM,
+			_ => Self::Matroska,
+		}
+	}
+
+	pub fn doc_type(&self) -> &'static str {
+		match self {
+			Self::Matroska => "matroska",
+			Self::WebM => "webm",
+		}
+	}
+
+	pub fn validate(&self, track: &MkvTrack) -> Result<()> {
+		if *self == Self::Matroska {
+			return Ok(());
+		}
+		let compatible = Compatible::new();
+		let Some(webm) = compatible.container(container::WEBM) else {
+			return Err(error!("webm is not a known container"));
+		};
+		let codec = track.codec_name();
+		match track.kind {
+			StreamKind::Video => webm.assert_video_codec(&codec),
+			StreamKind::Audio => webm.assert_audio_codec(&codec),
+			StreamKind::Subtitle => webm.assert_subtitle_format(&codec),
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct MkvInfo {
+	pub doc_type: String,
+	pub timecode_scale: u64,
+	/// Segment duration in ticks.
+	pub duration: Option<f64>,
+	pub title: Option<String>,
+	pub muxing_app: Option<String>,
+	pub writing_app: Option<String>,
+}
+
+impl MkvInfo {
+	pub fn new(doc_type: String) -> Self {
+		Self {
+			doc_type,
+			timecode_scale: DEFAULT_TIMECODE_SCALE,
+			duration: None,
+			title: None,
+			muxing_app: None,
+			writing_app: None,
+		}
+	}
+
+	pub fn parse(&mut self, data: &[u8]) -> Result<()> {
+		for child in Children::new(data) {
+			let (id, payload) = child?;
+			match id {
+				ebml::TIMECODE_SCALE => self.timecode_scale = ebml::read_uint(payload)?,
+				ebml::DURATION => self.duration = Some(ebml::read_float(payload)?),
+				ebml::TITLE => self.title = Some(ebml::read_string(payload)),
+				ebml::MUXING_APP => self.muxing_app = Some(ebml::read_string(payload)),
+				ebml::WRITING_APP => self.writing_app = Some(ebml::read_string(payload)),
+				_ => {}
+			}
+		}
+		if self.timecode_scale == 0 || self.timecode_scale >= u32::MAX as u64 {
+			return Err(error!("mkv timecode scale {} is not supported", self.timecode_scale));
+		}
+		Ok(())
+	}
+
+	pub fn time(&self) -> Time {
+		Time::new(self.timecode_scale as u32, 1_000_000_000).simplify()
+	}
+
+	pub fn duration_seconds(&self) -> Option<f64> {
+		self.duration.map(|ticks| ticks * self.timecode_scale as f64 / 1e9)
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MkvVideo {
+	pub pixel_width: u32,
+	pub pixel_height: u32,
+	pub display_width: Option<u32>,
+	pub display_height: Option<u32>,
+	pub interlaced: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MkvAudio {
+	pub sample_rate: f64,
+	pub output_sample_rate: Option<f64>,
+	pub channels: u8,
+	pub bit_depth: Option<u16>,
+}
+
+impl Default for MkvAudio {
+	fn default() -> Self {
+		Self { sample_rate: 8000.0, output_sample_rate: None, channels: 1, bit_depth: None }
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct MkvTrack {
+	pub number: u64,
+	pub uid: u64,
+	pub kind: StreamKind,
+	pub codec_id: String,
+	pub codec_private: Vec<u8>,
+	pub name: Option<String>,
+	pub language: String,
+	pub default: bool,
+	pub forced: bool,
+	/// Nanoseconds per frame, when the muxer recorded it.
+	pub default_duration: Option<u64>,
+	pub codec_delay: u64,
+	pub seek_pre_roll: u64,
+	/// Bytes removed from the front of every frame by header stripping.
+	pub stripped_header: Vec<u8>,
+	pub video: Option<MkvVideo>,
+	pub audio: Option<MkvAudio>,
+	pub tags: Metadata,
+}
+
+impl MkvTrack {
+	pub fn new(number: u64, kind: StreamKind, codec_id: String) -> Self {
+		Self {
+			number,
+			uid: number,
+			kind,
+			codec_id,
+			codec_private: Vec::new(),
+			name: None,
+			language: "eng".to_string(),
+			default: true,
+			forced: false,
+			default_duration: None,
+			codec_delay: 0,
+			seek_pre_roll: 0,
+			stripped_header: Vec::new(),
+			video: None,
+			audio: None,
+			tags: Metadata::new(),
+		}
+	}
+
+	pub fn for_codec(
+		number: u64,
+		kind: StreamKind,
+		codec: &str,
+		profile: MkvProfile,
+	) -> Result<Self> {
+		let codec_id = match (codec, profile) {
+			(video::H264, _) => "V_MPEG4/ISO/AVC",
+			(video::H265 | video::HEVC, _) => "V_MPEGH/ISO/HEVC",
+			(video::VP8, _) => "V_VP8",
+			(video::VP9, _) => "V_VP9",
+			(video::AV1, _) => "V_AV1",
+			(video::MPEG2, _) => "V_MPEG2",
+			(video::MPEG4, _) => "V_MPEG4/ISO/ASP",
+			(video::THEORA, _) => "V_THEORA",
+			(video::MJPEG, _) => "V_MJPEG",
+			(video::PRORES, _) => "V_PRORES",
+			(audio::AAC, _) => "A_AAC",
+			(audio::VORBIS, _) => "A_VORBIS",
+			(audio::OPUS, _) => "A_OPUS",
+			(audio::MP3, _) => "A_MPEG/L3",
+			(audio::MP2, _) => "A_MPEG/L2",
+			(audio::FLAC, _) => "A_FLAC",
+			(audio::ALAC, _) => "A_ALAC",
+			(audio::AC3, _) => "A_AC3",
+			(audio::EAC3, _) => "A_EAC3",
+			(audio::TTA, _) => "A_TTA1",
+			(audio::WAVPACK, _) => "A_WAVPACK4",
+			(audio::PCM_S16LE | audio::PCM_S24LE, _) => "A_PCM/INT/LIT",
+			(audio::PCM_F32LE, _) => "A_PCM/FLOAT/IEEE",
+			(subtitle::SRT, _) => "S_TEXT/UTF8",
+			(subtitle::ASS, _) => "S_TEXT/ASS",
+			(subtitle::VTT, MkvProfile::WebM) => "D_WEBVTT/SUBTITLES",
+			(subtitle::VTT, MkvProfile::Matroska) => "S_TEXT/WEBVTT",
+			_ => return Err(error!("codec '{}' cannot be stored in {}", codec, profile.doc_type())),
+		};
+		let mut track = Self::new(number, kind, codec_id.to_string());
+		if kind == StreamKind::Audio {
+			let bit_depth = match codec {
+				audio::PCM_S16LE => Some(16),
+				audio::PCM_S24LE => Some(24),
+				audio::PCM_F32LE => Some(32),
+				_ => None,
+			};
+			track.audio = Some(MkvAudio { bit_depth, ..Default::default() });
+		}
+		Ok(track)
+	}
+
+	pub fn with_codec_private(mut self, codec_private: Vec<u8>) -> Self {
+		self.codec_private = codec_private;
+		self
+	}
+
+	pub fn with_video(mut self, video: MkvVideo) -> Self {
+		self.video = Some(video);
+		self
+	}
+
+	/// Sets the audio settings, keeping a bit depth already implied by the codec.
+	pub fn with_audio(mut self, audio: MkvAudio) -> Self {
+		let bit_depth = audio.bit_depth.or(self.audio.and_then(|current| current.bit_depth));
+		self.audio = Some(MkvAudio { bit_depth, ..audio });
+		self
+	}
+
+	pub fn with_default_duration(mut self, nanoseconds: u64) -> Self {
+		self.default_duration = Some(nanoseconds);
+		self
+	}
+
+	/// Nanoseconds a block of `size` bytes lasts, when the track makes that knowable.
+	pub fn block_duration(&self, size: usize) -> Option<u64> {
+		if let Some(duration) = self.default_duration {
+			return Some(duration);
+		}
+		let audio = self.audio?;
+		let bytes_per_frame = audio.channels as u64 * audio.bit_depth? as u64 / 8;
+		let pcm = self.codec_id.starts_with("A_PCM/");
+		if !pcm || bytes_per_frame == 0 || audio.sample_rate <= 0.0 {
+			return None;
+		}
+		let frames = size as u64 / bytes_per_frame;
+		Some((frames as f64 * 1e9 / audio.sample_rate).round() as u64)
+	}
+
+	pub fn track_type(&self) -> u64 {
+		match self.kind {
+			StreamKind::Video => TRACK_VIDEO,
+			StreamKind::Audio => TRACK_AUDIO,
+			StreamKind::Subtitle => TRACK_SUBTITLE,
+		}
+	}
+
+	pub fn parse(data: &[u8]) -> Result<Option<Self>> {
+		let mut track_type = 0;
+		let mut track = Self::new(0, StreamKind::Video, String::new());
+
+		for child in Children::new(data) {
+			let (id, payload) = child?;
+			match id {
+				ebml::TRACK_NUMBER => track.number = ebml::read_uint(payload)?,
+				ebml::TRACK_UID => track.uid = ebml::read_uint(payload)?,
+				ebml::TRACK_TYPE => track_type = ebml::read_uint(payload)?,
+				ebml::FLAG_DEFAULT => track.default = ebml::read_uint(payload)? != 0,
+				ebml::FLAG_FORCED => track.forced = ebml::read_uint(payload)? != 0,
+				ebml::DEFAULT_DURATION => track.default_duration = Some(ebml::read_uint(payload)?),
+				ebml::NAME => track.name = Some(ebml::read_string(payload)),
+				ebml::LANGUAGE => track.language = ebml::read_string(payload),
+				ebml::CODEC_ID => track.codec_id = ebml::read_string(payload),
+				ebml::CODEC_PRIVATE => track.codec_private = payload.to_vec(),
+				ebml::CODEC_DELAY => track.codec_delay = ebml::read_uint(payload)?,
+				ebml::SEEK_PRE_ROLL => track.seek_pre_roll = ebml::read_uint(payload)?,
+				ebml::VIDEO => track.video = Some(Self::parse_video(payload)?),
+				ebml::AUDIO => track.audio = Some(Self::parse_audio(payload)?),
+				ebml::CONTENT_ENCODINGS => track.parse_encodings(payload)?,
+				_ => {}
+			}
+		}
+
+		if track.number == 0 {
+			return Err(error!("mkv track entry has no track number"));
+		}
+		track.kind = match track_type {
+			TRACK_VIDEO => StreamKind::Video,
+			TRACK_AUDIO => StreamKind::Audio,
+			TRACK_SUBTITLE => StreamKind::Subtitle,
+			_ => return Ok(None),
+		};
+		Ok(Some(track))
+	}
+
+	fn parse_video(data: &[u8]) -> Result<MkvVideo> {
+		let mut video = MkvVideo::default();
+		for child in Children::new(data) {
+			let (id, payload) = child?;
+			let value = || ebml::read_uint(payload).map(|value| value as u32);
+			match id {
+				ebml::PIXEL_WIDTH => video.pixel_width = value()?,
+				ebml::PIXEL_HEIGHT => video.pixel_height = value()?,
+				ebml::DISPLAY_WIDTH => video.display_width = Some(value()?),
+				ebml::DISPLAY_HEIGHT => video.display_height = Some(value()?),
+				ebml::FLAG_INTERLACED => video.interlaced = value()? == 1,
+				_ => {}
+			}
+		}
+		Ok(video)
+	}
+
+	fn parse_audio(data: &[u8]) -> Result<MkvAudio> {
+		let mut audio = MkvAudio::default();
+		for child in Children::new(data) {
+			let (id, payload) = child?;
+			match id {
+				ebml::SAMPLING_FREQUENCY => audio.sample_rate = ebml::read_float(payload)?,
+				ebml::OUTPUT_SAMPLING_FREQUENCY => {
+					audio.output_sample_rate = Some(ebml::read_float(payload)?)
+				}
+				ebml::CHANNELS => audio.channels = ebml::read_uint(payload)?.min(255) as u8,
+				ebml::BIT_DEPTH => audio.bit_depth = Some(ebml::read_uint(payload)? as u16),
+				_ => {}
+			}
+		}
+		Ok(audio)
+	}
+
+	/// Only header stripping is understood; zlib and friends need a decompressor.
+	fn parse_encodings(&mut self, data: &[u8]) -> Result<()> {
+		for encoding in Children::new(data) {
+			let (id, encoding) = encoding?;
+			if id != ebml::CONTENT_ENCODING {
+				continue;
+			}
+			for child in Children::new(encoding) {
+				let (id, payload) = child?;
+				match id {
+					ebml::CONTENT_ENCRYPTION => {
+						return Err(error!("mkv track {} is encrypted", self.number));
+					}
+					ebml::CONTENT_COMPRESSION => self.parse_compression(payload)?,
+					_ => {}
+				}
+			}
+		}
+		Ok(())
+	}
+
+	fn parse_compression(&mut self, data: &[u8]) -> Result<()> {
+		// zlib is the default algorithm when none is written
+		let mut algo = 0;
+		let mut settings = Vec::new();
+		for child in Children::new(data) {
+			let (id, payload) = child?;
+			match id {
+				ebml::CONTENT_COMP_ALGO => algo = ebml::read_uint(payload)?,
+				ebml::CONTENT_COMP_SETTINGS => settings = payload.to_vec(),
+				_ => {}
+			}
+		}
+		match algo {
+			3 => {
+				self.stripped_header = settings;
+				Ok(())
+			}
+			_ => Err(error!("mkv track {} uses unsupported compression {}", self.number, algo)),
+		}
+	}
+
+	/// Codec name in this crate's vocabulary, or the Matroska codec ID when unknown.
+	pub fn codec_name(&self) -> String {
+		let bit_depth = self.audio.and_then(|audio| audio.bit_depth);
+		let name = match (self.codec_id.as_str(), bit_depth) {
+			("V_MPEG4/ISO/AVC", _) => video::H264,
+			("V_MPEGH/ISO/HEVC", _) => video::H265,
+			("V_VP8", _) => video::VP8,
+			("V_VP9", _) => video::VP9,
+			("V_AV1", _) => video::AV1,
+			("V_MPEG2" | "V_MPEG1", _) => video::MPEG2,
+			("V_MPEG4/ISO/ASP" | "V_MPEG4/ISO/SP" | "V_MPEG4/ISO/AP", _) => video::MPEG4,
+			("V_THEORA", _) => video::THEORA,
+			("V_MJPEG", _) => video::MJPEG,
+			("V_PRORES", _) => video::PRORES,
+			("V_UNCOMPRESSED", _) => video::RAWVIDEO,
+			("A_VORBIS", _) => audio::VORBIS,
+			("A_OPUS", _) => audio::OPUS,
+			("A_MPEG/L3", _) => audio::MP3,
+			("A_MPEG/L2", _) => audio::MP2,
+			("A_FLAC", _) => audio::FLAC,
+			("A_ALAC", _) => audio::ALAC,
+			("A_AC3", _) => audio::AC3,
+			("A_EAC3", _) => audio::EAC3,
+			("A_TTA1", _) => audio::TTA,
+			("A_WAVPACK4", _) => audio::WAVPACK,
+			("A_PCM/INT/LIT", Some(16)) => audio::PCM_S16LE,
+			("A_PCM/INT/LIT", Some(24)) => audio::PCM_S24LE,
+			("A_PCM/FLOAT/IEEE", Some(32)) => audio::PCM_F32LE,
+			("S_TEXT/UTF8", _) => subtitle::SRT,
+			("S_TEXT/ASS" | "S_TEXT/SSA" | "S_ASS" | "S_SSA", _) => subtitle::ASS,
+			("S_TEXT/WEBVTT" | "D_WEBVTT/SUBTITLES", _) => subtitle::VTT,
+			(id, _) if id.starts_with("A_AAC") => audio::AAC,
+			(id, _) => id,
+		};
+		name.to_string()
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct MkvChapter {
+	pub uid: u64,
+	/// Start and end in nanoseconds.
+	pub start: u64,
+	pub end: Option<u64>,
+	pub title: Option<String>,
+	pub language: Option<String>,
+	pub hidden: bool,
+}
+
+impl MkvChapter {
+	pub fn parse_all(data: &[u8]) -> Result<Vec<Self>> {
+		let mut chapters = Vec::new();
+		for edition in Children::new(data) {
+			let (id, edition) = edition?;
+			if id == ebml::EDITION_ENTRY {
+				Self::parse_atoms(edition, &mut chapters)?;
+			}
+		}
+		Ok(chapters)
+	}
+
+	fn parse_atoms(data: &[u8], chapters: &mut Vec<Self>) -> Result<()> {
+		for atom in Children::new(data) {
+			let (id, atom) = atom?;
+			if id == ebml::CHAPTER_ATOM {
+				Self::parse_atom(atom, chapters)?;
+			}
+		}
+		Ok(())
+	}
+
+	fn parse_atom(data: &[u8], chapters: &mut Vec<Self>) -> Result<()> {
+		let mut chapter =
+			Self { uid: 0, start: 0, end: None, title: None, language: None, hidden: false };
+		let mut nested = Vec::new();
+
+		for child in Children::new(data) {
+			let (id, payload) = child?;
+			match id {
+				ebml::CHAPTER_UID => chapter.uid = ebml::read_uint(payload)?,
+				ebml::CHAPTER_TIME_START => chapter.start = ebml::read_uint(payload)?,
+				ebml::CHAPTER_TIME_END => chapter.end = Some(ebml::read_uint(payload)?),
+				ebml::CHAPTER_FLAG_HIDDEN => chapter.hidden = ebml::read_uint(payload)? != 0,
+				ebml::CHAPTER_DISPLAY if chapter.title.is_none() => {
+					for display in Children::new(payload) {
+						let (id, value) = display?;
+						match id {
+							ebml::CHAP_STRING => chapter.title = Some(ebml::read_string(value)),
+							ebml::CHAP_LANGUAGE => chapter.language = Some(ebml::read_string(value)),
+							_ => {}
+						}
+					}
+				}
+				ebml::CHAPTER_ATOM => Self::parse_atom(payload, &mut nested)?,
+				_ => {}
+			}
+		}
+
+		chapters.push(chapter);
+		chapters.append(&mut nested);
+		Ok(())
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MkvCuePoint {
+	/// Timestamp in segment ticks.
+	pub time: u64,
+	pub track: u64,
+	/// Cluster offset from the start of the segment payload.
+	pub cluster_position: u64,
+	/// Block offset from the start of the cluster payload.
+	pub relative_position: Option<u64>,
+}
+
+impl MkvCuePoint {
+	pub fn parse_all(data: &[u8]) -> Result<Vec<Self>> {
+		let mut cues = Vec::new();
+		for point in Children::new(data) {
+			let (id, point) = point?;
+			if id != ebml::CUE_POINT {
+				continue;
+			}
+
+			let mut time = None;
+			let mut positions = Vec::new();
+			for child in Children::new(point) {
+				let (id, payload) = child?;
+				match id {
+					ebml::CUE_TIME => time = Some(ebml::read_uint(payload)?),
+					ebml::CUE_TRACK_POSITIONS => positions.push(payload),
+					_ => {}
+				}
+			}
+			let Some(time) = time else {
+				continue;
+			};
+
+			for position in positions {
+				let mut cue = Self { time, track: 0, cluster_position: 0, relative_position: None };
+				for child in Children::new(position) {
+					let (id, payload) = child?;
+					match id {
+						ebml::CUE_TRACK => cue.track = ebml::read_uint(payload)?,
+						ebml::CUE_CLUSTER_POSITION => cue.cluster_position = ebml::read_uint(payload)?,
+						ebml::CUE_RELATIVE_POSITION => cue.relative_position = Some(ebml::read_uint(payload)?),
+						_ => {}
+					}
+				}
+				cues.push(cue);
+			}
+		}
+		cues.sort_by_key(|cue| cue.time);
+		Ok(cues)
+	}
+}
+
+pub struct MkvTag {
+	pub track_uids: Vec<u64>,
+	pub fields: Metadata,
+}
+
+impl MkvTag {
+	pub fn parse_all(data: &[u8]) -> Result<Vec<Self>> {
+		let mut tags = Vec::new();
+		for tag in Children::new(data) {
+			let (id, tag) = tag?;
+			if id != ebml::TAG {
+				continue;
+			}
+
+			let mut parsed = Self { track_uids: Vec::new(), fields: Metadata::new() };
+			for child in Children::new(tag) {
+				let (id, payload) = child?;
+				match id {
+					ebml::TARGETS => {
+						for target in Children::new(payload) {
+							let (id, value) = target?;
+							if id == ebml::TAG_TRACK_UID {
+								parsed.track_uids.push(ebml::read_uint(value)?);
+							}
+						}
+					}
+					ebml::SIMPLE_TAG => Self::parse_simple_tag(payload, "", &mut parsed.fields)?,
+					_ => {}
+				}
+			}
+			tags.push(parsed);
+		}
+		Ok(tags)
+	}
+
+	/// Nested simple tags are keyed by their parent path, e.g. `artist/url`.
+	fn parse_simple_tag(data: &[u8], prefix: &str, fields: &mut Metadata) -> Result<()> {
+		let mut name = String::new();
+		let mut value = None;
+		let mut nested = Vec::new();
+		for child in Children::new(data) {
+			let (id, payload) = child?;
+			match id {
+				ebml::TAG_NAME => name = ebml::read_string(payload).to_lowercase(),
+				ebml::TAG_STRING => value = Some(ebml::read_string(payload)),
+				ebml::SIMPLE_TAG => nested.push(payload),
+				_ => {}
+			}
+		}
+		if name.is_empty() {
+			return Ok(());
+		}
+
+		let key = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+		if let Some(value) = value {
+			fields.set(&key, value);
+		}
+		for payload in nested {
+			Self::parse_simple_tag(payload, &key, fields)?;
+		}
+		Ok(())
+	}
+}
@@ -1 +1,9 @@
-
+pub mod demuxer;
+pub mod ebml;
+pub mod formater;
+pub mod muxer;
+pub use demuxer::MkvDemuxer;
+pub use formater::{
+	MkvAudio, MkvChapter, MkvCuePoint, MkvInfo, MkvProfile, MkvTag, MkvTrack, MkvVideo,
+};
+pub use muxer::MkvMuxer;
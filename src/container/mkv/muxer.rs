@@ -0,0 +1,452 @@
+use std::collections::VecDeque;
+
+use super::ebml;
+use super::formater::{DEFAULT_TIMECODE_SCALE, MkvCuePoint, MkvProfile, MkvTrack};
+use crate::container::Metadata;
+use crate::core::Muxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream, StreamKind};
+use crate::core::time::Time;
+use crate::io::{MediaSeek, MediaWrite, SeekFrom, WritePrimitives};
+use crate::{error, message::Result};
+
+pub struct MkvMuxer<W: MediaWrite + MediaSeek> {
+	writer: W,
+	tracks: Vec<MkvTrack>,
+	streams: stream::Streams,
+	metadata: Metadata,
+	/// Absolute offset of the segment payload; element positions are relative to it.
+	segment_offset: u64,
+	info_position: u64,
+	tracks_position: u64,
+	/// Absolute offset of the 8-byte Duration float patched in `finalize`.
+	duration_offset: u64,
+	/// Payload of the open cluster, starting with its Timecode.
+	cluster: Vec<u8>,
+	cluster_timecode: Option<i64>,
+	/// Timecode of the last cluster written; later ones never start before it.
+	last_cluster_timecode: i64,
+	cluster_tracks: Vec<bool>,
+	/// Cue points of the open cluster, positioned once it is written.
+	cluster_cues: Vec<MkvCuePoint>,
+	cues: Vec<MkvCuePoint>,
+	has_video: bool,
+	end_timecode: i64,
+	queued: Vec<VecDeque<Packet>>,
+	latest_queued: i64,
+}
+
+impl<W: MediaWrite + MediaSeek> MkvMuxer<W> {
+	/// Space kept after the segment header for the SeekHead written in `finalize`.
+	const SEEK_HEAD_SPACE: usize = 128;
+	/// Clusters close before their relative block timecodes could overflow an i16,
+	/// or after this many ticks when there is no video keyframe to split on.
+	const CLUSTER_SPAN: i64 = 5000;
+	const CLUSTER_SIZE_LIMIT: usize = 5 * 1024 * 1024;
+	/// Ticks a track with nothing queued is waited for, so sparse tracks such
+	/// as subtitles do not hold the others back.
+	const INTERLEAVE_SPAN: i64 = 1000;
+
+	pub fn new(mut writer: W, tracks: Vec<MkvTrack>, profile: MkvProfile) -> Result<Self> {
+		if tracks.is_empty() {
+			return Err(error!("{} muxer needs at least one track", profile.doc_type()));
+		}
+
+		let mut tracks = tracks;
+		for (index, track) in tracks.iter_mut().enumerate() {
+			profile.validate(track)?;
+			if profile == MkvProfile::WebM && track.kind == StreamKind::Subtitle {
+				// webm spells its only subtitle codec differently from matroska
+				track.codec_id = "D_WEBVTT/SUBTITLES".to_string();
+			}
+			track.number = index as u64 + 1;
+			if track.uid == 0 {
+				track.uid = track.number;
+			}
+			// frames are written whole, so stripped headers must already be restored
+			track.stripped_header.clear();
+		}
+
+		let mut header = Vec::new();
+		Self::write_ebml_header(&mut header, profile);
+		ebml::write_id(&mut header, ebml::SEGMENT);
+		// unknown size until finalize patches it
+		header.extend_from_slice(&ebml::encode_vint((1u64 << 56) - 1, Some(8)));
+		writer.write_all(&header)?;
+		let segment_offset = writer.stream_position()?;
+
+		let mut head = Vec::new();
+		ebml::write_void(&mut head, Self::SEEK_HEAD_SPACE);
+		let info_position = head.len() as u64;
+		Self::write_info(&mut head);
+		let duration_offset = segment_offset + head.len() as u64 - 8;
+		let tracks_position = head.len() as u64;
+		Self::write_tracks(&mut head, &tracks);
+		writer.write_all(&head)?;
+
+		let time = Time::new(1, 1_000_000_000 / DEFAULT_TIMECODE_SCALE as u32);
+		let mut streams = stream::Streams::new_empty();
+		for (index, track) in tracks.iter().enumerate() {
+			let stream = Stream::new(index as u32, index, track.kind, track.codec_name(), time);
+			streams.add(stream.with_codec_private(track.codec_private.clone()));
+		}
+
+		Ok(Self {
+			writer,
+			has_video: tracks.iter().any(|track| track.kind == StreamKind::Video),
+			cluster_tracks: vec![false; tracks.len()],
+			queued: vec![VecDeque::new(); tracks.len()],
+			tracks,
+			streams,
+			metadata: Metadata::new(),
+			segment_offset,
+			info_position,
+			tracks_position,
+			duration_offset,
+			cluster: Vec::new(),
+			cluster_timecode: None,
+			last_cluster_timecode: 0,
+			cluster_cues: Vec::new(),
+			cues: Vec::new(),
+			end_timecode: 0,
+			latest_queued: i64::MIN,
+		})
+	}
+
+	pub fn with_metadata(&mut self, metadata: Option<Metadata>) {
+		self.metadata = metadata.unwrap_or_default();
+	}
+
+	fn write_ebml_header(out: &mut Vec<u8>, profile: MkvProfile) {
+		let mut header = Vec::new();
+		ebml::write_uint(&mut header, ebml::EBML_VERSION, 1);
+		ebml::write_uint(&mut header, ebml::EBML_READ_VERSION, 1);
+		ebml::write_uint(&mut header, ebml::EBML_MAX_ID_LENGTH, 4);
+		ebml::write_uint(&mut header, ebml::EBML_MAX_SIZE_LENGTH, 8);
+		ebml::write_string(&mut header, ebml::DOC_TYPE, profile.doc_type());
+		ebml::write_uint(&mut header, ebml::DOC_TYPE_VERSION, 4);
+		ebml::write_uint(&mut header, ebml::DOC_TYPE_READ_VERSION, 2);
+		ebml::write_element(out, ebml::EBML, &header);
+	}
+
+	/// Duration goes last so its float sits at the very end of the element.
+	fn write_info(out: &mut Vec<u8>) {
+		let mut info = Vec::new();
+		ebml::write_uint(&mut info, ebml::TIMECODE_SCALE, DEFAULT_TIMECODE_SCALE);
+		ebml::write_string(&mut info, ebml::MUXING_APP, "ffmpreg");
+		ebml::write_string(&mut info, ebml::WRITING_APP, "ffmpreg");
+		ebml::write_float(&mut info, ebml::DURATION, 0.0);
+		ebml::write_element(out, ebml::INFO, &info);
+	}
+
+	fn write_tracks(out: &mut Vec<u8>, tracks: &[MkvTrack]) {
+		let mut entries = Vec::new();
+		for track in tracks {
+			let mut entry = Vec::new();
+			ebml::write_uint(&mut entry, ebml::TRACK_NUMBER, track.number);
+			ebml::write_uint(&mut entry, ebml::TRACK_UID, track.uid);
+			ebml::write_uint(&mut entry, ebml::TRACK_TYPE, track.track_type());
+			ebml::write_uint(&mut entry, ebml::FLAG_DEFAULT, track.default as u64);
+			ebml::write_uint(&mut entry, ebml::FLAG_LACING, 0);
+			if track.forced {
+				ebml::write_uint(&mut entry, ebml::FLAG_FORCED, 1);
+			}
+			if let Some(duration) = track.default_duration {
+				ebml::write_uint(&mut entry, ebml::DEFAULT_DURATION, duration);
+			}
+			if let Some(name) = &track.name {
+				ebml::write_string(&mut entry, ebml::NAME, name);
+			}
+			ebml::write_string(&mut entry, ebml::LANGUAGE, &track.language);
+			ebml::write_string(&mut entry, ebml::CODEC_ID, &track.codec_id);
+			if !track.codec_private.is_empty() {
+				ebml::write_element(&mut entry, ebml::CODEC_PRIVATE, &track.codec_private);
+			}
+			if track.codec_delay > 0 {
+				ebml::write_uint(&mut entry, ebml::CODEC_DELAY, track.codec_delay);
+			}
+			if track.seek_pre_roll > 0 {
+				ebml::write_uint(&mut entry, ebml::SEEK_PRE_ROLL, track.seek_pre_roll);
+			}
+
+			if let Some(video) = &track.video {
+				let mut settings = Vec::new();
+				ebml::write_uint(&mut settings, ebml::PIXEL_WIDTH, video.pixel_width as u64);
+				ebml::write_uint(&mut settings, ebml::PIXEL_HEIGHT, video.pixel_height as u64);
+				if let (Some(width), Some(height)) = (video.display_width, video.display_height) {
+					ebml::write_uint(&mut settings, ebml::DISPLAY_WIDTH, width as u64);
+					ebml::write_uint(&mut settings, ebml::DISPLAY_HEIGHT, height as u64);
+				}
+				if video.interlaced {
+					ebml::write_uint(&mut settings, ebml::FLAG_INTERLACED, 1);
+				}
+				ebml::write_element(&mut entry, ebml::VIDEO, &settings);
+			}
+
+			if let Some(audio) = &track.audio {
+				let mut settings = Vec::new();
+				ebml::write_float(&mut settings, ebml::SAMPLING_FREQUENCY, audio.sample_rate);
+				if let Some(rate) = audio.output_sample_rate {
+					ebml::write_float(&mut settings, ebml::OUTPUT_SAMPLING_FREQUENCY, rate);
+				}
+				ebml::write_uint(&mut settings, ebml::CHANNELS, audio.channels as u64);
+				if let Some(bit_depth) = audio.bit_depth {
+					ebml::write_uint(&mut settings, ebml::BIT_DEPTH, bit_depth as u64);
+				}
+				ebml::write_element(&mut entry, ebml::AUDIO, &settings);
+			}
+
+			ebml::write_element(&mut entries, ebml::TRACK_ENTRY, &entry);
+		}
+		ebml::write_element(out, ebml::TRACKS, &entries);
+	}
+
+	fn to_timecode(pts: i64, time: Time) -> i64 {
+		let nanoseconds = pts as i128 * time.num as i128 * 1_000_000_000 / time.den as i128;
+		let scale = DEFAULT_TIMECODE_SCALE as i128;
+		((nanoseconds + scale / 2).div_euclid(scale)) as i64
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		let Some(queue) = self.queued.get_mut(packet.stream_id as usize) else {
+			return Err(error!("mkv muxer has no stream {}", packet.stream_id));
+		};
+		self.latest_queued = self.latest_queued.max(Self::to_timecode(packet.dts, packet.time));
+		queue.push_back(packet);
+		while let Some(packet) = self.next_queued(false) {
+			self.write_block(packet)?;
+		}
+		Ok(())
+	}
+
+	/// The queued packet with the earliest decode time, once every track has
+	/// one queued or the others have waited long enough.
+	fn next_queued(&mut self, flush: bool) -> Option<Packet> {
+		let (index, earliest) = self
+			.queued
+			.iter()
+			.enumerate()
+			.filter_map(|(index, queue)| queue.front().map(|packet| (index, packet)))
+			.map(|(index, packet)| (index, Self::to_timecode(packet.dts, packet.time)))
+			.min_by_key(|(_, time)| *time)?;
+		let waiting = self.queued.iter().any(VecDeque::is_empty);
+		if waiting && !flush && self.latest_queued - earliest < Self::INTERLEAVE_SPAN {
+			return None;
+		}
+		self.queued[index].pop_front()
+	}
+
+	fn write_block(&mut self, packet: Packet) -> Result<()> {
+		let index = packet.stream_id as usize;
+		let track = &self.tracks[index];
+		let (number, video) = (track.number, track.kind == StreamKind::Video);
+		let duration = track.block_duration(packet.data.len()).unwrap_or(0);
+
+		let timecode = Self::to_timecode(packet.pts, packet.time);
+		// only video has frames that depend on others
+		let keyframe = packet.keyframe || !video;
+
+		let split = match self.cluster_timecode {
+			None => true,
+			Some(start) => {
+				let relative = timecode - start;
+				relative > i16::MAX as i64
+					|| relative < i16::MIN as i64
+					|| self.cluster.len() > Self::CLUSTER_SIZE_LIMIT
+					|| (video && keyframe)
+					|| (!self.has_video && relative >= Self::CLUSTER_SPAN)
+			}
+		};
+		if split {
+			self.flush_cluster()?;
+			let start = timecode.max(self.last_cluster_timecode);
+			ebml::write_uint(&mut self.cluster, ebml::TIMECODE, start as u64);
+			self.cluster_timecode = Some(start);
+			self.last_cluster_timecode = start;
+		}
+
+		let start = self.cluster_timecode.unwrap_or(0);
+		let relative = timecode - start;
+		if !(i16::MIN as i64..=i16::MAX as i64).contains(&relative) {
+			return Err(error!("mkv block timestamp {} is out of range", timecode));
+		}
+
+		// cue video keyframes, or each track's first block per cluster without video
+		let cue = match self.has_video {
+			true => video && keyframe,
+			false => !self.cluster_tracks[index],
+		};
+		if cue {
+			self.cluster_cues.push(MkvCuePoint {
+				time: timecode.max(0) as u64,
+				track: number,
+				cluster_position: 0,
+				relative_position: Some(self.cluster.len() as u64),
+			});
+		}
+		self.cluster_tracks[index] = true;
+
+		let mut block = ebml::encode_vint(number, None);
+		block.extend_from_slice(&(relative as i16).to_be_bytes());
+		block.push(if keyframe { 0x80 } else { 0x00 });
+		block.extend_from_slice(&packet.data);
+		ebml::write_element(&mut self.cluster, ebml::SIMPLE_BLOCK, &block);
+
+		self.end_timecode =
+			self.end_timecode.max(timecode + (duration / DEFAULT_TIMECODE_SCALE) as i64);
+		Ok(())
+	}
+
+	fn flush_cluster(&mut self) -> Result<()> {
+		if self.cluster.is_empty() {
+			return Ok(());
+		}
+
+		let position = self.writer.stream_position()? - self.segment_offset;
+		let mut header = Vec::new();
+		ebml::write_id(&mut header, ebml::CLUSTER);
+		header.extend_from_slice(&ebml::encode_vint(self.cluster.len() as u64, None));
+		self.writer.write_all(&header)?;
+		self.writer.write_all(&self.cluster)?;
+
+		for mut cue in self.cluster_cues.drain(..) {
+			cue.cluster_position = position;
+			self.cues.push(cue);
+		}
+		self.cluster.clear();
+		self.cluster_tracks.fill(false);
+		self.cluster_timecode = None;
+		Ok(())
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		while let Some(packet) = self.next_queued(true) {
+			self.write_block(packet)?;
+		}
+		self.flush_cluster()?;
+
+		let mut seek_head =
+			vec![(ebml::INFO, self.info_position), (ebml::TRACKS, self.tracks_position)];
+		let mut tail = Vec::new();
+		let tail_position = self.writer.stream_position()? - self.segment_offset;
+
+		if !self.cues.is_empty() {
+			seek_head.push((ebml::CUES, tail_position));
+			Self::write_cues(&mut tail, &self.cues);
+		}
+		let tags = self.write_tags();
+		if !tags.is_empty() {
+			seek_head.push((ebml::TAGS, tail_position + tail.len() as u64));
+			tail.extend_from_slice(&tags);
+		}
+		self.writer.write_all(&tail)?;
+		let end = self.writer.stream_position()?;
+
+		let mut head = Vec::new();
+		Self::write_seek_head(&mut head, &seek_head);
+		if head.len() + 2 > Self::SEEK_HEAD_SPACE {
+			return Err(error!("mkv seek head does not fit its reserved space"));
+		}
+		let padding = Self::SEEK_HEAD_SPACE - head.len();
+		ebml::write_void(&mut head, padding);
+		self.writer.seek(SeekFrom::Start(self.segment_offset))?;
+		self.writer.write_all(&head)?;
+
+		self.writer.seek(SeekFrom::Start(self.duration_offset))?;
+		self.writer.write_f64_be(self.end_timecode as f64)?;
+
+		let segment_size = ebml::encode_vint(end - self.segment_offset, Some(8));
+		self.writer.seek(SeekFrom::Start(self.segment_offset - 8))?;
+		self.writer.write_all(&segment_size)?;
+
+		self.writer.seek(SeekFrom::End(0))?;
+		self.writer.flush()
+	}
+
+	fn write_seek_head(out: &mut Vec<u8>, entries: &[(u32, u64)]) {
+		let mut seeks = Vec::new();
+		for (id, position) in entries {
+			let mut seek = Vec::new();
+			ebml::write_element(&mut seek, ebml::SEEK_ID, &id.to_be_bytes());
+			ebml::write_uint(&mut seek, ebml::SEEK_POSITION, *position);
+			ebml::write_element(&mut seeks, ebml::SEEK, &seek);
+		}
+		ebml::write_element(out, ebml::SEEK_HEAD, &seeks);
+	}
+
+	fn write_cues(out: &mut Vec<u8>, cues: &[MkvCuePoint]) {
+		let mut points = Vec::new();
+		for cue in cues {
+			let mut position = Vec::new();
+			ebml::write_uint(&mut position, ebml::CUE_TRACK, cue.track);
+			ebml::write_uint(&mut position, ebml::CUE_CLUSTER_POSITION, cue.cluster_position);
+			if let Some(relative) = cue.relative_position {
+				ebml::write_uint(&mut position, ebml::CUE_RELATIVE_POSITION, relative);
+			}
+
+			let mut point = Vec::new();
+			ebml::write_uint(&mut point, ebml::CUE_TIME, cue.time);
+			ebml::write_element(&mut point, ebml::CUE_TRACK_POSITIONS, &position);
+			ebml::write_element(&mut points, ebml::CUE_POINT, &point);
+		}
+		ebml::write_element(out, ebml::CUES, &points);
+	}
+
+	/// Global tags target the whole segment, track tags their track UID.
+	fn write_tags(&self) -> Vec<u8> {
+		let mut tags = Vec::new();
+		if !self.metadata.is_empty() {
+			let mut targets = Vec::new();
+			ebml::write_uint(&mut targets, ebml::TARGET_TYPE_VALUE, 50);
+			Self::write_tag(&mut tags, &targets, &self.metadata);
+		}
+		for track in self.tracks.iter().filter(|track| !track.tags.is_empty()) {
+			let mut targets = Vec::new();
+			ebml::write_uint(&mut targets, ebml::TAG_TRACK_UID, track.uid);
+			Self::write_tag(&mut tags, &targets, &track.tags);
+		}
+
+		let mut out = Vec::new();
+		if !tags.is_empty() {
+			ebml::write_element(&mut out, ebml::TAGS, &tags);
+		}
+		out
+	}
+
+	fn write_tag(out: &mut Vec<u8>, targets: &[u8], fields: &Metadata) {
+		let fields: Vec<(&str, &str)> = fields.iter().collect();
+		let mut tag = Vec::new();
+		ebml::write_element(&mut tag, ebml::TARGETS, targets);
+		Self::write_simple_tags(&mut tag, &fields, "");
+		ebml::write_element(out, ebml::TAG, &tag);
+	}
+
+	fn write_simple_tags(out: &mut Vec<u8>, fields: &[(&str, &str)], prefix: &str) {
+		for (key, value) in fields {
+			let Some(name) = key.strip_prefix(prefix) else {
+				continue;
+			};
+			if name.is_empty() || name.contains('/') {
+				continue;
+			}
+
+			let mut simple = Vec::new();
+			ebml::write_string(&mut simple, ebml::TAG_NAME, &name.to_uppercase());
+			ebml::write_string(&mut simple, ebml::TAG_STRING, value);
+			Self::write_simple_tags(&mut simple, fields, &format!("{}/", key));
+			ebml::write_element(out, ebml::SIMPLE_TAG, &simple);
+		}
+	}
+}
+
+impl<W: MediaWrite + MediaSeek> Muxer for MkvMuxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+}
@@ -1,6 +1,27 @@
+pub mod ape;
+pub mod apev2;
+pub mod avi;
+pub mod caf;
+pub mod dsd;
+pub mod flv;
+pub mod id3v2;
 pub mod mkv;
+pub mod mp4;
+pub mod mxf;
+pub mod nut;
 pub mod raw;
+pub mod rawvideo;
+pub mod riff;
+pub mod ts;
+pub mod tta;
+pub mod w64;
 pub mod wav;
+pub mod wavpack;
+pub mod webm;
+pub mod y4m;
 
 mod constants;
+mod metadata;
 pub use constants::*;
+pub use metadata::Metadata;
+pub use y4m::{Y4mReader, Y4mWriter};
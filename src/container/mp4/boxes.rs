@@ -0,0 +1,253 @@
+use crate::io::{MediaRead, MediaSeek, ReadPrimitives, SeekFrom};
+use crate::{error, message::Result};
+
+pub type FourCC = [u8; 4];
+
+pub const FTYP: FourCC = *b"ftyp";
+pub const MOOV: FourCC = *b"moov";
+pub const MVHD: FourCC = *b"mvhd";
+pub const TRAK: FourCC = *b"trak";
+pub const TKHD: FourCC = *b"tkhd";
+pub const EDTS: FourCC = *b"edts";
+pub const ELST: FourCC = *b"elst";
+pub const MDIA: FourCC = *b"mdia";
+pub const MDHD: FourCC = *b"mdhd";
+pub const HDLR: FourCC = *b"hdlr";
+pub const MINF: FourCC = *b"minf";
+pub const STBL: FourCC = *b"stbl";
+pub const STSD: FourCC = *b"stsd";
+pub const STTS: FourCC = *b"stts";
+pub const CTTS: FourCC = *b"ctts";
+pub const STSC: FourCC = *b"stsc";
+pub const STSZ: FourCC = *b"stsz";
+pub const STZ2: FourCC = *b"stz2";
+pub const STCO: FourCC = *b"stco";
+pub const CO64: FourCC = *b"co64";
+pub const STSS: FourCC = *b"stss";
+pub const MVEX: FourCC = *b"mvex";
+pub const TREX: FourCC = *b"trex";
+pub const MOOF: FourCC = *b"moof";
+pub const MFHD: FourCC = *b"mfhd";
+pub const TRAF: FourCC = *b"traf";
+pub const TFHD: FourCC = *b"tfhd";
+pub const TFDT: FourCC = *b"tfdt";
+pub const TRUN: FourCC = *b"trun";
+pub const MDAT: FourCC = *b"mdat";
+pub const SIDX: FourCC = *b"sidx";
+pub const STYP: FourCC = *b"styp";
+pub const UDTA: FourCC = *b"udta";
+pub const META: FourCC = *b"meta";
+pub const ILST: FourCC = *b"ilst";
+pub const DATA: FourCC = *b"data";
+pub const CMOV: FourCC = *b"cmov";
+
+// sample entry configuration boxes
+pub const AVCC: FourCC = *b"avcC";
+pub const HVCC: FourCC = *b"hvcC";
+pub const VPCC: FourCC = *b"vpcC";
+pub const AV1C: FourCC = *b"av1C";
+pub const ESDS: FourCC = *b"esds";
+pub const DOPS: FourCC = *b"dOps";
+pub const DFLA: FourCC = *b"dfLa";
+pub const ALAC: FourCC = *b"alac";
+pub const DAC3: FourCC = *b"dac3";
+pub const DEC3: FourCC = *b"dec3";
+pub const WAVE: FourCC = *b"wave";
+
+/// Largest box loaded into memory whole; media data is always read per sample.
+pub const MAX_LOADED_SIZE: u64 = 256 * 1024 * 1024;
+
+pub fn fourcc_string(fourcc: &FourCC) -> String {
+	String::from_utf8_lossy(fourcc).to_string()
+}
+
+pub struct Bytes<'a> {
+	data: &'a [u8],
+	position: usize,
+}
+
+impl<'a> Bytes<'a> {
+	pub fn new(data: &'a [u8]) -> Self {
+		Self { data, position: 0 }
+	}
+
+	pub fn remaining(&self) -> usize {
+		self.data.len() - self.position
+	}
+
+	pub fn rest(&self) -> &'a [u8] {
+		&self.data[self.position..]
+	}
+
+	pub fn take(&mut self, size: usize) -> Result<&'a [u8]> {
+		if self.remaining() < size {
+			return Err(error!("mp4 box is truncated"));
+		}
+		let bytes = &self.data[self.position..self.position + size];
+		self.position += size;
+		Ok(bytes)
+	}
+
+	pub fn skip(&mut self, size: usize) -> Result<()> {
+		self.take(size).map(|_| ())
+	}
+
+	pub fn u8(&mut self) -> Result<u8> {
+		Ok(self.take(1)?[0])
+	}
+
+	pub fn u16(&mut self) -> Result<u16> {
+		Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+	}
+
+	pub fn u24(&mut self) -> Result<u32> {
+		let bytes = self.take(3)?;
+		Ok(u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]))
+	}
+
+	pub fn u32(&mut self) -> Result<u32> {
+		Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+	}
+
+	pub fn i32(&mut self) -> Result<i32> {
+		Ok(self.u32()? as i32)
+	}
+
+	pub fn u64(&mut self) -> Result<u64> {
+		Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+	}
+
+	pub fn fourcc(&mut self) -> Result<FourCC> {
+		Ok(self.take(4)?.try_into().unwrap())
+	}
+
+	pub fn full_header(&mut self) -> Result<(u8, u32)> {
+		let version = self.u8()?;
+		Ok((version, self.u24()?))
+	}
+
+	/// A u32 in version 0 boxes, a u64 in version 1.
+	pub fn versioned(&mut self, version: u8) -> Result<u64> {
+		match version {
+			0 => self.u32().map(|value| value as u64),
+			_ => self.u64(),
+		}
+	}
+}
+
+pub struct Children<'a> {
+	data: &'a [u8],
+}
+
+impl<'a> Children<'a> {
+	pub fn new(data: &'a [u8]) -> Self {
+		Self { data }
+	}
+
+	fn next_child(&mut self) -> Result<(FourCC, &'a [u8])> {
+		let mut bytes = Bytes::new(self.data);
+		let size = bytes.u32()? as u64;
+		let kind = bytes.fourcc()?;
+		let (header, size) = match size {
+			0 => (8, self.data.len() as u64),
+			1 => (16, bytes.u64()?),
+			size => (8, size),
+		};
+		if size < header || size > self.data.len() as u64 {
+			return Err(error!("mp4 box '{}' has invalid size {}", fourcc_string(&kind), size));
+		}
+		let payload = &self.data[header as usize..size as usize];
+		self.data = &self.data[size as usize..];
+		Ok((kind, payload))
+	}
+}
+
+impl<'a> Iterator for Children<'a> {
+	type Item = Result<(FourCC, &'a [u8])>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		// fewer than 8 bytes can only be padding
+		if self.data.len() < 8 {
+			return None;
+		}
+		let child = self.next_child();
+		if child.is_err() {
+			self.data = &[];
+		}
+		Some(child)
+	}
+}
+
+pub fn find(data: &[u8], kind: FourCC) -> Result<Option<&[u8]>> {
+	for child in Children::new(data) {
+		let (child_kind, payload) = child?;
+		if child_kind == kind {
+			return Ok(Some(payload));
+		}
+	}
+	Ok(None)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BoxHeader {
+	pub kind: FourCC,
+	/// Absolute offset of the box header.
+	pub start: u64,
+	/// Absolute offset of the payload.
+	pub offset: u64,
+	pub size: u64,
+}
+
+impl BoxHeader {
+	pub fn end(&self) -> u64 {
+		self.offset + self.size
+	}
+}
+
+pub fn read_header<R: MediaRead + MediaSeek>(
+	reader: &mut R,
+	file_size: u64,
+) -> Result<Option<BoxHeader>> {
+	let start = reader.stream_position()?;
+	if start + 8 > file_size {
+		return Ok(None);
+	}
+	let size = reader.read_u32_be()? as u64;
+	let mut kind = [0u8; 4];
+	reader.read_exact(&mut kind)?;
+	let (header, size) = match size {
+		0 => (8, file_size - start),
+		1 => (16, reader.read_u64_be()?),
+		size => (8, size),
+	};
+	if size < header || start + size > file_size {
+		return Err(error!("mp4 box '{}' has invalid size {}", fourcc_string(&kind), size));
+	}
+	Ok(Some(BoxHeader { kind, start, offset: start + header, size: size - header }))
+}
+
+pub fn read_payload<R: MediaRead + MediaSeek>(
+	reader: &mut R,
+	header: &BoxHeader,
+) -> Result<Vec<u8>> {
+	if header.size > MAX_LOADED_SIZE {
+		return Err(error!("mp4 box '{}' is too large to load", fourcc_string(&header.kind)));
+	}
+	reader.seek(SeekFrom::Start(header.offset))?;
+	let mut data = vec![0u8; header.size as usize];
+	reader.read_exact(&mut data)?;
+	Ok(data)
+}
+
+pub fn write_box(out: &mut Vec<u8>, kind: FourCC, payload: &[u8]) {
+	out.extend_from_slice(&(payload.len() as u32 + 8).to_be_bytes());
+	out.extend_from_slice(&kind);
+	out.extend_from_slice(payload);
+}
+
+pub fn write_full_box(out: &mut Vec<u8>, kind: FourCC, version: u8, flags: u32, payload: &[u8]) {
+	let mut full = Vec::with_capacity(payload.len() + 4);
+	full.extend_from_slice(&((version as u32) << 24 | flags).to_be_bytes());
+	full.extend_from_slice(payload);
+	write_box(out, kind, &full);
+}
@@ -0,0 +1,325 @@
+use super::boxes;
+use super::formater::{Mp4FileType, Mp4Profile, Mp4Sample, Mp4Track};
+use super::fragment::{self, FragmentRun};
+use super::moov::{self, MovieBuilder, TrackTiming};
+use crate::container::Metadata;
+use crate::core::Muxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream, StreamKind};
+use crate::io::{File, MediaWrite, WritePrimitives};
+use crate::{error, message::Result};
+
+pub const DEFAULT_FRAGMENT_DURATION: f64 = 2.0;
+
+/// Fragmented MP4 writer: an init segment (`ftyp` and a `moov` with `mvex`)
+/// followed by `sidx`/`moof`/`mdat` fragments cut at keyframes.
+pub struct CmafMuxer<W: MediaWrite> {
+	writer: W,
+	profile: Mp4Profile,
+	tracks: Vec<Mp4Track>,
+	streams: stream::Streams,
+	metadata: Metadata,
+	data: Vec<Vec<u8>>,
+	/// Decode time of each track's first sample; `tfdt` counts from it.
+	first_dts: Vec<Option<i64>>,
+	last_dts: Vec<Option<i64>>,
+	/// Duration given to a fragment's last sample while its successor is unknown.
+	last_durations: Vec<u32>,
+	open: Vec<bool>,
+	origins: Vec<Option<i64>>,
+	primed: Vec<bool>,
+	/// Track whose keyframes start fragments: the first video track, if any.
+	reference: usize,
+	fragment_duration: f64,
+	fragment_start: Option<i64>,
+	sequence: u32,
+	init_written: bool,
+	segment_pattern: Option<String>,
+}
+
+impl<W: MediaWrite> CmafMuxer<W> {
+	pub fn new(
+		writer: W,
+		tracks: Vec<Mp4Track>,
+		profile: Mp4Profile,
+		fragment_duration: f64,
+	) -> Result<Self> {
+		if tracks.is_empty() {
+			return Err(error!("fragmented {} muxer needs at least one track", profile.extension()));
+		}
+		if profile == Mp4Profile::Mov {
+			return Err(error!("fragmented output is only supported for mp4 and m4a"));
+		}
+		if fragment_duration.is_nan() || fragment_duration <= 0.0 {
+			return Err(error!("fragment duration must be positive, got {}", fragment_duration));
+		}
+
+		let mut tracks = tracks;
+		for (index, track) in tracks.iter_mut().enumerate() {
+			profile.validate(track)?;
+			track.id = index as u32 + 1;
+			track.duration = 0;
+			track.edits.clear();
+			track.samples.clear();
+		}
+
+		let mut streams = stream::Streams::new_empty();
+		for (index, track) in tracks.iter().enumerate() {
+			let stream = Stream::new(index as u32, index, track.kind, track.codec_name(), track.time());
+			streams.add(stream.with_codec_private(track.entry.codec_private.clone()));
+		}
+		let reference =
+			tracks.iter().position(|track| track.kind == StreamKind::Video).unwrap_or_default();
+
+		let count = tracks.len();
+		Ok(Self {
+			writer,
+			profile,
+			tracks,
+			streams,
+			metadata: Metadata::new(),
+			data: vec![Vec::new(); count],
+			first_dts: vec![None; count],
+			last_dts: vec![None; count],
+			last_durations: vec![0; count],
+			open: vec![false; count],
+			origins: vec![None; count],
+			primed: vec![false; count],
+			reference,
+			fragment_duration,
+			fragment_start: None,
+			sequence: 1,
+			init_written: false,
+			segment_pattern: None,
+		})
+	}
+
+	pub fn with_metadata(&mut self, metadata: Option<Metadata>) {
+		self.metadata = metadata.unwrap_or_default();
+	}
+
+	/// Write each fragment to its own file, `%d` standing for its sequence
+	/// number; the main writer then only gets the init segment.
+	pub fn with_segment_files(&mut self, pattern: &str) -> Result<()> {
+		if !pattern.contains("%d") {
+			return Err(error!("segment file pattern '{}' has no %d", pattern));
+		}
+		self.segment_pattern = Some(pattern.to_string());
+		Ok(())
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		let index = packet.stream_id as usize;
+		let Some(track) = self.tracks.get_mut(index) else {
+			return Err(error!("mp4 muxer has no stream {}", packet.stream_id));
+		};
+		let dts = track.rescale(packet.dts, packet.time);
+		let pts = track.rescale(packet.pts, packet.time);
+
+		if let Some(last_dts) = self.last_dts[index] {
+			if dts < last_dts {
+				return Err(error!("mp4 track {} dts {} goes backwards", track.id, dts));
+			}
+			let duration = u32::try_from(dts - last_dts)
+				.map_err(|_| error!("mp4 track {} has a gap too long for one sample", track.id))?;
+			// the previous sample may already sit in a written fragment
+			if let Some(last) = track.samples.last_mut() {
+				last.duration = duration;
+			}
+			self.last_durations[index] = duration;
+			self.open[index] = false;
+		}
+
+		let starts_fragment = packet.keyframe || track.kind != StreamKind::Video;
+		if index == self.reference && starts_fragment {
+			let timescale = track.timescale as f64;
+			let elapsed = self.fragment_start.map(|start| (dts - start) as f64 / timescale);
+			if elapsed.is_some_and(|elapsed| elapsed >= self.fragment_duration) {
+				self.flush_fragment()?;
+			}
+		}
+		if index == self.reference && self.fragment_start.is_none() {
+			self.fragment_start = Some(dts);
+		}
+
+		// leading discarded packets are encoder delay, hidden by the edit list
+		if self.origins[index].is_none() {
+			match packet.discard {
+				true => self.primed[index] = true,
+				false => self.origins[index] = Some(if self.primed[index] { pts.max(0) } else { 0 }),
+			}
+		}
+
+		let track = &mut self.tracks[index];
+		let size = u32::try_from(packet.data.len())
+			.map_err(|_| error!("mp4 sample of {} bytes is too large", packet.data.len()))?;
+		track.samples.push(Mp4Sample {
+			offset: 0,
+			size,
+			dts,
+			pts,
+			duration: 0,
+			keyframe: packet.keyframe || track.kind != StreamKind::Video,
+		});
+		self.data[index].extend_from_slice(&packet.data);
+		self.first_dts[index].get_or_insert(dts);
+		self.last_dts[index] = Some(dts);
+		self.open[index] = true;
+		Ok(())
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		self.flush_fragment()?;
+		if !self.init_written {
+			self.write_init()?;
+		}
+		self.writer.flush()
+	}
+
+	/// `ftyp` and a `moov` with empty sample tables, its edit lists taken from
+	/// the first fragment.
+	fn write_init(&mut self) -> Result<()> {
+		let timings: Vec<TrackTiming> = self
+			.tracks
+			.iter()
+			.zip(&self.origins)
+			.map(|(track, origin)| {
+				let timing = TrackTiming::new(track, origin.unwrap_or(0));
+				// the length of a fragmented movie is not known up front
+				TrackTiming { presentation: 0, movie_duration: 0, ..timing }
+			})
+			.collect();
+		let builder = MovieBuilder {
+			profile: self.profile,
+			tracks: &self.tracks,
+			timings: &timings,
+			metadata: &self.metadata,
+			shift: 0,
+			fragmented: true,
+		};
+
+		let mut init = Vec::new();
+		moov::write_ftyp(&mut init, boxes::FTYP, &self.profile.fragmented_file_type());
+		init.extend_from_slice(&builder.build());
+		self.writer.write_all(&init)?;
+		self.init_written = true;
+		Ok(())
+	}
+
+	fn flush_fragment(&mut self) -> Result<()> {
+		if self.tracks.iter().all(|track| track.samples.is_empty()) {
+			return Ok(());
+		}
+		if !self.init_written {
+			self.write_init()?;
+		}
+		// a last sample without a successor yet lasts as long as the one before
+		for (index, track) in self.tracks.iter_mut().enumerate() {
+			if let Some(last) = track.samples.last_mut().filter(|_| self.open[index]) {
+				last.duration = self.last_durations[index];
+			}
+		}
+
+		let runs: Vec<FragmentRun> = self
+			.tracks
+			.iter()
+			.enumerate()
+			.filter(|(_, track)| !track.samples.is_empty())
+			.map(|(index, track)| FragmentRun {
+				track_id: track.id,
+				base_decode_time: (track.samples[0].dts - self.first_dts[index].unwrap_or(0)) as u64,
+				samples: &track.samples,
+			})
+			.collect();
+		let moof = fragment::write_moof(self.sequence, &runs);
+
+		let data_size: usize = self.data.iter().map(|data| data.len()).sum();
+		let mut mdat = Vec::with_capacity(data_size + 16);
+		match u32::try_from(data_size + 8) {
+			Ok(size) => {
+				mdat.extend_from_slice(&size.to_be_bytes());
+				mdat.extend_from_slice(&boxes::MDAT);
+			}
+			Err(_) => {
+				mdat.extend_from_slice(&1u32.to_be_bytes());
+				mdat.extend_from_slice(&boxes::MDAT);
+				mdat.extend_from_slice(&(data_size as u64 + 16).to_be_bytes());
+			}
+		}
+		for (track, data) in self.tracks.iter().zip(&self.data) {
+			if !track.samples.is_empty() {
+				mdat.extend_from_slice(data);
+			}
+		}
+
+		let sidx = self.write_sidx((moof.len() + mdat.len()) as u32);
+		match &self.segment_pattern {
+			None => {
+				self.writer.write_all(&sidx)?;
+				self.writer.write_all(&moof)?;
+				self.writer.write_all(&mdat)?;
+			}
+			Some(pattern) => {
+				let path = pattern.replace("%d", &self.sequence.to_string());
+				let mut file = File::create(&path)?;
+				let mut styp = Vec::new();
+				moov::write_ftyp(&mut styp, boxes::STYP, &Self::segment_type());
+				file.write_all(&styp)?;
+				file.write_all(&sidx)?;
+				file.write_all(&moof)?;
+				file.write_all(&mdat)?;
+				file.flush()?;
+			}
+		}
+
+		for track in self.tracks.iter_mut() {
+			track.samples.clear();
+		}
+		for data in self.data.iter_mut() {
+			data.clear();
+		}
+		self.fragment_start = None;
+		self.sequence += 1;
+		Ok(())
+	}
+
+	/// Index of the open fragment on the reference track, or the first track
+	/// with samples when the reference has none.
+	fn write_sidx(&self, size: u32) -> Vec<u8> {
+		let index = match self.tracks[self.reference].samples.is_empty() {
+			true => self.tracks.iter().position(|track| !track.samples.is_empty()).unwrap_or(0),
+			false => self.reference,
+		};
+		let track = &self.tracks[index];
+		let first_dts = self.first_dts[index].unwrap_or(0);
+		let earliest = track.samples.iter().map(|sample| sample.pts).min().unwrap_or(first_dts);
+		let duration: u64 = track.samples.iter().map(|sample| sample.duration as u64).sum();
+		fragment::write_sidx(
+			track.id,
+			track.timescale,
+			(earliest - first_dts).max(0) as u64,
+			size,
+			duration.min(u32::MAX as u64) as u32,
+		)
+	}
+
+	fn segment_type() -> Mp4FileType {
+		Mp4FileType {
+			major_brand: "msdh".to_string(),
+			minor_version: 0,
+			compatible_brands: ["msdh", "msix", "cmfs"].iter().map(|brand| brand.to_string()).collect(),
+		}
+	}
+}
+
+impl<W: MediaWrite> Muxer for CmafMuxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+}
@@ -0,0 +1,247 @@
+use super::boxes::{self, Bytes, Children};
+use super::formater::{
+	self, Mp4EditEntry, Mp4FileType, Mp4SampleEntry, Mp4Track, Mp4TrackDefaults,
+};
+use super::sample_table::SampleTable;
+use super::{fragment, metadata};
+use crate::container::Metadata;
+use crate::core::Demuxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, StreamKind};
+use crate::io::{MediaRead, MediaSeek, ReadPrimitives, SeekFrom};
+use crate::{error, message::Result};
+
+pub struct Mp4Demuxer<R: MediaRead + MediaSeek> {
+	reader: R,
+	file_size: u64,
+	file_type: Mp4FileType,
+	movie_timescale: u32,
+	tracks: Vec<Mp4Track>,
+	streams: stream::Streams,
+	metadata: Metadata,
+	/// (track, sample) pairs in file order, so reading never seeks backwards
+	/// across interleaved chunks.
+	order: Vec<(usize, usize)>,
+	position: usize,
+}
+
+impl<R: MediaRead + MediaSeek> Mp4Demuxer<R> {
+	pub fn new(mut reader: R) -> Result<Self> {
+		let file_size = reader.stream_len()?;
+		reader.seek(SeekFrom::Start(0))?;
+
+		let mut demuxer = Self {
+			reader,
+			file_size,
+			file_type: Mp4FileType::default(),
+			movie_timescale: 0,
+			tracks: Vec::new(),
+			streams: stream::Streams::new_empty(),
+			metadata: Metadata::new(),
+			order: Vec::new(),
+			position: 0,
+		};
+
+		let mut found_moov = false;
+		let mut defaults = Vec::new();
+		while let Some(header) = boxes::read_header(&mut demuxer.reader, file_size)? {
+			match header.kind {
+				boxes::FTYP => {
+					let data = boxes::read_payload(&mut demuxer.reader, &header)?;
+					demuxer.file_type = Mp4FileType::parse(&data)?;
+				}
+				boxes::MOOV => {
+					let data = boxes::read_payload(&mut demuxer.reader, &header)?;
+					defaults = demuxer.read_moov(&data)?;
+					found_moov = true;
+				}
+				boxes::MOOF if found_moov => {
+					let data = boxes::read_payload(&mut demuxer.reader, &header)?;
+					fragment::parse_moof(&data, header.start, &defaults, &mut demuxer.tracks)?;
+				}
+				boxes::MOOF => return Err(error!("mp4 fragment comes before the moov box")),
+				_ => {}
+			}
+			demuxer.reader.seek(SeekFrom::Start(header.end()))?;
+		}
+		if !found_moov {
+			return Err(error!("mp4 file has no moov box"));
+		}
+
+		for track in demuxer.tracks.iter_mut() {
+			let offset = track.edit_offset(demuxer.movie_timescale);
+			for sample in track.samples.iter_mut() {
+				sample.pts += offset;
+				sample.dts += offset;
+			}
+		}
+
+		for (index, track) in demuxer.tracks.iter().enumerate() {
+			let stream =
+				stream::Stream::new(index as u32, index, track.kind, track.codec_name(), track.time());
+			demuxer.streams.add(stream.with_codec_private(track.entry.codec_private.clone()));
+			demuxer.order.extend((0..track.samples.len()).map(|sample| (index, sample)));
+		}
+		let tracks = &demuxer.tracks;
+		demuxer.order.sort_by_key(|(track, sample)| tracks[*track].samples[*sample].offset);
+		Ok(demuxer)
+	}
+
+	fn read_moov(&mut self, data: &[u8]) -> Result<Vec<Mp4TrackDefaults>> {
+		let mut defaults = Vec::new();
+		for child in Children::new(data) {
+			let (kind, payload) = child?;
+			match kind {
+				boxes::MVHD => {
+					let mut bytes = Bytes::new(payload);
+					let (version, _) = bytes.full_header()?;
+					bytes.versioned(version)?;
+					bytes.versioned(version)?;
+					self.movie_timescale = bytes.u32()?;
+				}
+				boxes::TRAK => {
+					if let Some(track) = Self::read_trak(payload)? {
+						if self.tracks.iter().any(|known| known.id == track.id) {
+							return Err(error!("mp4 track id {} is used twice", track.id));
+						}
+						self.tracks.push(track);
+					}
+				}
+				boxes::MVEX => {
+					for child in Children::new(payload) {
+						let (kind, payload) = child?;
+						if kind == boxes::TREX {
+							defaults.push(Mp4TrackDefaults::parse(payload)?);
+						}
+					}
+				}
+				boxes::UDTA => metadata::parse_udta(payload, &mut self.metadata)?,
+				boxes::CMOV => return Err(error!("compressed mp4 movie headers are not supported")),
+				_ => {}
+			}
+		}
+		Ok(defaults)
+	}
+
+	fn read_trak(data: &[u8]) -> Result<Option<Mp4Track>> {
+		let tkhd = boxes::find(data, boxes::TKHD)?.ok_or_else(|| error!("mp4 trak has no tkhd"))?;
+		let mut bytes = Bytes::new(tkhd);
+		let (version, _) = bytes.full_header()?;
+		bytes.versioned(version)?;
+		bytes.versioned(version)?;
+		let id = bytes.u32()?;
+
+		let mdia =
+			boxes::find(data, boxes::MDIA)?.ok_or_else(|| error!("mp4 track {} has no mdia", id))?;
+		let handler = match boxes::find(mdia, boxes::HDLR)? {
+			Some(hdlr) => {
+				let mut bytes = Bytes::new(hdlr);
+				bytes.full_header()?;
+				bytes.skip(4)?;
+				bytes.fourcc()?
+			}
+			None => return Err(error!("mp4 track {} has no hdlr", id)),
+		};
+		let kind = match &handler {
+			b"vide" => StreamKind::Video,
+			b"soun" => StreamKind::Audio,
+			b"sbtl" | b"text" | b"subt" => StreamKind::Subtitle,
+			_ => return Ok(None),
+		};
+
+		let mdhd =
+			boxes::find(mdia, boxes::MDHD)?.ok_or_else(|| error!("mp4 track {} has no mdhd", id))?;
+		let mut bytes = Bytes::new(mdhd);
+		let (version, _) = bytes.full_header()?;
+		bytes.versioned(version)?;
+		bytes.versioned(version)?;
+		let timescale = bytes.u32()?;
+		let duration = bytes.versioned(version)?;
+		let language = formater::language_code(bytes.u16()?);
+		if timescale == 0 {
+			return Err(error!("mp4 track {} has a zero timescale", id));
+		}
+
+		let stbl = boxes::find(mdia, boxes::MINF)?
+			.map(|minf| boxes::find(minf, boxes::STBL))
+			.transpose()?
+			.flatten()
+			.ok_or_else(|| error!("mp4 track {} has no stbl", id))?;
+		let stsd =
+			boxes::find(stbl, boxes::STSD)?.ok_or_else(|| error!("mp4 track {} has no stsd", id))?;
+		let entry = Mp4SampleEntry::parse_stsd(stsd, kind)?;
+		let samples = SampleTable::parse(stbl)?.samples()?;
+
+		let edits = match boxes::find(data, boxes::EDTS)?.map(|edts| boxes::find(edts, boxes::ELST)) {
+			Some(elst) => Self::read_elst(elst?.unwrap_or(&[]))?,
+			None => Vec::new(),
+		};
+
+		Ok(Some(Mp4Track { id, kind, timescale, duration, language, entry, edits, samples }))
+	}
+
+	fn read_elst(data: &[u8]) -> Result<Vec<Mp4EditEntry>> {
+		if data.is_empty() {
+			return Ok(Vec::new());
+		}
+		let mut bytes = Bytes::new(data);
+		let (version, _) = bytes.full_header()?;
+		let count = bytes.u32()?;
+		let mut edits = Vec::new();
+		for _ in 0..count {
+			let segment_duration = bytes.versioned(version)?;
+			let media_time = match version {
+				0 => bytes.i32()? as i64,
+				_ => bytes.u64()? as i64,
+			};
+			let _media_rate = bytes.u32()?;
+			edits.push(Mp4EditEntry { segment_duration, media_time });
+		}
+		Ok(edits)
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		let Some(&(index, sample)) = self.order.get(self.position) else {
+			return Ok(None);
+		};
+		self.position += 1;
+
+		let track = &self.tracks[index];
+		let sample = track.samples[sample];
+		if sample.offset + sample.size as u64 > self.file_size {
+			return Err(error!("mp4 sample at {} lies past the end of the file", sample.offset));
+		}
+		self.reader.seek(SeekFrom::Start(sample.offset))?;
+		let mut data = vec![0u8; sample.size as usize];
+		self.reader.read_exact(&mut data)?;
+
+		let mut packet = Packet::new(data, index as u32, track.time())
+			.with_pts(sample.pts)
+			.with_dts(sample.dts)
+			.with_keyframe(sample.keyframe);
+		// audio the edit list cuts from the start is priming, decoded but not played
+		packet.discard = track.kind == StreamKind::Audio && sample.pts + sample.duration as i64 <= 0;
+		Ok(Some(packet))
+	}
+
+	pub fn file_type(&self) -> &Mp4FileType {
+		&self.file_type
+	}
+
+	pub fn tracks(&self) -> &[Mp4Track] {
+		&self.tracks
+	}
+
+	pub fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+}
+
+impl<R: MediaRead + MediaSeek> Demuxer for Mp4Demuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
@@ -0,0 +1,427 @@
+use super::boxes::{self, Bytes, Children, FourCC};
+use crate::codecs::{audio, subtitle, video};
+use crate::container;
+use crate::core::compatible::Compatible;
+use crate::core::stream::StreamKind;
+use crate::core::time::Time;
+use crate::{error, message::Result};
+
+/// Which flavour of ISO BMFF a muxer writes; decides brands and allowed codecs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mp4Profile {
+	Mp4,
+	Mov,
+	M4a,
+}
+
+impl Mp4Profile {
+	pub fn from_extension(extension: &str) -> Self {
+		match extension {
+			container::MOV => Self::Mov,
+			container::M4A => Self::M4a,
+			_ => Self::Mp4,
+		}
+	}
+
+	pub fn extension(&self) -> &'static str {
+		match self {
+			Self::Mp4 => container::MP4,
+			Self::Mov => container::MOV,
+			Self::M4a => container::M4A,
+		}
+	}
+
+	pub fn file_type(&self) -> Mp4FileType {
+		let (major_brand, minor_version, compatible_brands) = match self {
+			Self::Mp4 => ("isom", 0x200, &["isom", "iso2", "mp41"][..]),
+			Self::Mov => ("qt  ", 0x200, &["qt  "][..]),
+			Self::M4a => ("M4A ", 0x200, &["M4A ", "isom", "iso2"][..]),
+		};
+		Mp4FileType {
+			major_brand: major_brand.to_string(),
+			minor_version,
+			compatible_brands: compatible_brands.iter().map(|brand| brand.to_string()).collect(),
+		}
+	}
+
+	pub fn fragmented_file_type(&self) -> Mp4FileType {
+		let brands: &[&str] = match self {
+			Self::M4a => &["iso6", "cmfc", "M4A "],
+			_ => &["iso6", "cmfc", "mp41"],
+		};
+		Mp4FileType {
+			major_brand: "iso6".to_string(),
+			minor_version: 0,
+			compatible_brands: brands.iter().map(|brand| brand.to_string()).collect(),
+		}
+	}
+
+	pub fn validate(&self, track: &Mp4Track) -> Result<()> {
+		let compatible = Compatible::new();
+		let Some(entry) = compatible.container(self.extension()) else {
+			return Err(error!("{} is not a known container", self.extension()));
+		};
+		let codec = track.codec_name();
+		match track.kind {
+			StreamKind::Video => entry.assert_video_codec(&codec),
+			StreamKind::Audio => entry.assert_audio_codec(&codec),
+			StreamKind::Subtitle => entry.assert_subtitle_format(&codec),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Mp4FileType {
+	pub major_brand: String,
+	pub minor_version: u32,
+	pub compatible_brands: Vec<String>,
+}
+
+impl Mp4FileType {
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		let mut bytes = Bytes::new(data);
+		let major_brand = boxes::fourcc_string(&bytes.fourcc()?);
+		let minor_version = bytes.u32()?;
+		let mut compatible_brands = Vec::new();
+		while bytes.remaining() >= 4 {
+			compatible_brands.push(boxes::fourcc_string(&bytes.fourcc()?));
+		}
+		Ok(Self { major_brand, minor_version, compatible_brands })
+	}
+
+	/// QuickTime files lay out some sample entries and metadata differently.
+	pub fn is_quicktime(&self) -> bool {
+		self.major_brand == "qt  "
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Mp4Sample {
+	/// Absolute file offset of the sample data.
+	pub offset: u64,
+	pub size: u32,
+	/// Decode and presentation timestamps in the track timescale.
+	pub dts: i64,
+	pub pts: i64,
+	pub duration: u32,
+	pub keyframe: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Mp4SampleEntry {
+	pub fourcc: String,
+	/// Decoder configuration: avcC, hvcC, esds decoder info, dOps and so on.
+	pub codec_private: Vec<u8>,
+	/// MPEG-4 object type from `esds`, which tells AAC from MP3 in `mp4a`.
+	pub object_type: Option<u8>,
+	pub width: u16,
+	pub height: u16,
+	pub channels: u16,
+	pub sample_size: u16,
+	pub sample_rate: f64,
+}
+
+impl Mp4SampleEntry {
+	const VISUAL_HEADER: usize = 78;
+	const AUDIO_HEADER: usize = 28;
+
+	pub fn parse_stsd(data: &[u8], kind: StreamKind) -> Result<Self> {
+		let mut bytes = Bytes::new(data);
+		bytes.full_header()?;
+		let count = bytes.u32()?;
+		if count == 0 {
+			return Err(error!("mp4 stsd has no sample entries"));
+		}
+		let (fourcc, payload) = Children::new(bytes.rest())
+			.next()
+			.ok_or_else(|| error!("mp4 stsd has no sample entries"))??;
+		Self::parse(fourcc, payload, kind)
+	}
+
+	fn parse(fourcc: FourCC, data: &[u8], kind: StreamKind) -> Result<Self> {
+		let mut entry = Self { fourcc: boxes::fourcc_string(&fourcc), ..Default::default() };
+		let mut bytes = Bytes::new(data);
+
+		let children = match kind {
+			StreamKind::Video => {
+				bytes.skip(24)?;
+				entry.width = bytes.u16()?;
+				entry.height = bytes.u16()?;
+				data.get(Self::VISUAL_HEADER..).unwrap_or(&[])
+			}
+			StreamKind::Audio => {
+				bytes.skip(8)?;
+				let version = bytes.u16()?;
+				bytes.skip(6)?;
+				entry.channels = bytes.u16()?;
+				entry.sample_size = bytes.u16()?;
+				bytes.skip(4)?;
+				entry.sample_rate = (bytes.u32()? >> 16) as f64;
+				// quicktime sound descriptions grow with their version
+				let extra = match version {
+					1 => 16,
+					2 => {
+						bytes.skip(4)?;
+						entry.sample_rate = f64::from_bits(bytes.u64()?);
+						entry.channels = bytes.u32()? as u16;
+						bytes.skip(4)?;
+						entry.sample_size = bytes.u32()? as u16;
+						36
+					}
+					_ => 0,
+				};
+				data.get(Self::AUDIO_HEADER + extra..).unwrap_or(&[])
+			}
+			StreamKind::Subtitle => &[],
+		};
+
+		for child in Children::new(children) {
+			let (kind, payload) = child?;
+			entry.read_config(kind, payload)?;
+		}
+		Ok(entry)
+	}
+
+	fn read_config(&mut self, kind: FourCC, payload: &[u8]) -> Result<()> {
+		match kind {
+			boxes::AVCC | boxes::HVCC | boxes::AV1C | boxes::DAC3 | boxes::DEC3 => {
+				self.codec_private = payload.to_vec()
+			}
+			// full boxes whose version and flags are not part of the codec setup
+			boxes::VPCC | boxes::DFLA | boxes::ALAC => {
+				self.codec_private = payload.get(4..).unwrap_or(&[]).to_vec()
+			}
+			boxes::DOPS => self.codec_private = payload.to_vec(),
+			boxes::ESDS => self.read_esds(payload)?,
+			// quicktime wraps the real configuration in a `wave` atom
+			boxes::WAVE => {
+				for child in Children::new(payload) {
+					let (kind, payload) = child?;
+					if kind == boxes::ESDS {
+						self.read_esds(payload)?;
+					}
+				}
+			}
+			_ => {}
+		}
+		Ok(())
+	}
+
+	fn read_esds(&mut self, data: &[u8]) -> Result<()> {
+		let mut bytes = Bytes::new(data);
+		bytes.full_header()?;
+
+		while bytes.remaining() > 0 {
+			let tag = bytes.u8()?;
+			let mut size = 0usize;
+			for _ in 0..4 {
+				let byte = bytes.u8()?;
+				size = (size << 7) | (byte & 0x7F) as usize;
+				if byte & 0x80 == 0 {
+					break;
+				}
+			}
+			match tag {
+				// ES_Descriptor: descend into its nested descriptors
+				0x03 => {
+					bytes.skip(2)?;
+					let flags = bytes.u8()?;
+					if flags & 0x80 != 0 {
+						bytes.skip(2)?;
+					}
+					if flags & 0x40 != 0 {
+						let length = bytes.u8()? as usize;
+						bytes.skip(length)?;
+					}
+					if flags & 0x20 != 0 {
+						bytes.skip(2)?;
+					}
+				}
+				// DecoderConfigDescriptor
+				0x04 => {
+					self.object_type = Some(bytes.u8()?);
+					bytes.skip(12)?;
+				}
+				0x05 => {
+					self.codec_private = bytes.take(size.min(bytes.remaining()))?.to_vec();
+					return Ok(());
+				}
+				_ => bytes.skip(size.min(bytes.remaining()))?,
+			}
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Mp4EditEntry {
+	/// Duration in the movie timescale.
+	pub segment_duration: u64,
+	/// Start in the media timescale, -1 for an empty edit.
+	pub media_time: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Mp4Track {
+	pub id: u32,
+	pub kind: StreamKind,
+	pub timescale: u32,
+	/// Media duration in the track timescale.
+	pub duration: u64,
+	pub language: String,
+	pub entry: Mp4SampleEntry,
+	pub edits: Vec<Mp4EditEntry>,
+	pub samples: Vec<Mp4Sample>,
+}
+
+impl Mp4Track {
+	pub fn new(id: u32, kind: StreamKind, timescale: u32, entry: Mp4SampleEntry) -> Self {
+		Self {
+			id,
+			kind,
+			timescale,
+			duration: 0,
+			language: "und".to_string(),
+			entry,
+			edits: Vec::new(),
+			samples: Vec::new(),
+		}
+	}
+
+	pub fn for_codec(id: u32, kind: StreamKind, codec: &str, timescale: u32) -> Result<Self> {
+		let (fourcc, object_type) = match codec {
+			video::H264 => ("avc1", None),
+			video::H265 | video::HEVC => ("hvc1", None),
+			audio::AAC => ("mp4a", Some(0x40)),
+			audio::MP3 => ("mp4a", Some(0x6B)),
+			audio::ALAC => ("alac", None),
+			audio::OPUS => ("Opus", None),
+			subtitle::MOV_TEXT => ("tx3g", None),
+			_ => return Err(error!("codec '{}' cannot be stored in mp4", codec)),
+		};
+		let entry = Mp4SampleEntry { fourcc: fourcc.to_string(), object_type, ..Default::default() };
+		Ok(Self::new(id, kind, timescale, entry))
+	}
+
+	pub fn with_codec_private(mut self, codec_private: Vec<u8>) -> Self {
+		self.entry.codec_private = codec_private;
+		self
+	}
+
+	pub fn with_video(mut self, width: u16, height: u16) -> Self {
+		self.entry.width = width;
+		self.entry.height = height;
+		self
+	}
+
+	pub fn with_audio(mut self, sample_rate: f64, channels: u16, sample_size: u16) -> Self {
+		self.entry.sample_rate = sample_rate;
+		self.entry.channels = channels;
+		self.entry.sample_size = sample_size;
+		self
+	}
+
+	pub fn with_language(mut self, language: &str) -> Self {
+		self.language = language.to_string();
+		self
+	}
+
+	pub fn time(&self) -> Time {
+		Time::new(1, self.timescale)
+	}
+
+	/// A timestamp in `time` units converted to this track's timescale.
+	pub fn rescale(&self, value: i64, time: Time) -> i64 {
+		if time.num == 1 && time.den == self.timescale {
+			return value;
+		}
+		let scaled = value as i128 * time.num as i128 * self.timescale as i128;
+		let den = time.den as i128;
+		(scaled + den / 2).div_euclid(den) as i64
+	}
+
+	pub fn codec_name(&self) -> String {
+		let entry = &self.entry;
+		let name = match (entry.fourcc.as_str(), entry.object_type) {
+			("avc1" | "avc3", _) => video::H264,
+			("hvc1" | "hev1", _) => video::H265,
+			("vp08", _) => video::VP8,
+			("vp09", _) => video::VP9,
+			("av01", _) => video::AV1,
+			("mp4v", Some(0x60..=0x65 | 0x6A)) => video::MPEG2,
+			("mp4v", _) => video::MPEG4,
+			("jpeg" | "mjpa" | "mjpb", _) => video::MJPEG,
+			("apch" | "apcn" | "apcs" | "apco" | "ap4h" | "ap4x", _) => video::PRORES,
+			("mp4a", Some(0x69 | 0x6B)) => audio::MP3,
+			("mp4a", _) => audio::AAC,
+			(".mp3", _) => audio::MP3,
+			("Opus", _) => audio::OPUS,
+			("fLaC", _) => audio::FLAC,
+			("alac", _) => audio::ALAC,
+			("ac-3", _) => audio::AC3,
+			("ec-3", _) => audio::EAC3,
+			("sowt", _) if entry.sample_size == 16 => audio::PCM_S16LE,
+			("tx3g" | "text", _) => subtitle::MOV_TEXT,
+			("wvtt", _) => subtitle::VTT,
+			(fourcc, _) => fourcc.trim(),
+		};
+		name.to_string()
+	}
+
+	/// Ticks the edit list shifts presentation by: empty edits delay the
+	/// track, the first media edit skips into it (encoder delay, B-frame reorder).
+	pub fn edit_offset(&self, movie_timescale: u32) -> i64 {
+		let mut offset = 0i64;
+		for edit in &self.edits {
+			if edit.media_time == -1 {
+				let ticks = edit.segment_duration as i128 * self.timescale as i128;
+				offset += (ticks / movie_timescale.max(1) as i128) as i64;
+				continue;
+			}
+			return offset - edit.media_time;
+		}
+		offset
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mp4TrackDefaults {
+	pub track_id: u32,
+	pub sample_duration: u32,
+	pub sample_size: u32,
+	pub sample_flags: u32,
+}
+
+impl Mp4TrackDefaults {
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		let mut bytes = Bytes::new(data);
+		bytes.full_header()?;
+		let track_id = bytes.u32()?;
+		let _description_index = bytes.u32()?;
+		Ok(Self {
+			track_id,
+			sample_duration: bytes.u32()?,
+			sample_size: bytes.u32()?,
+			sample_flags: bytes.u32()?,
+		})
+	}
+}
+
+/// The non-sync bit of ISO BMFF sample flags.
+pub const SAMPLE_IS_NON_SYNC: u32 = 0x0001_0000;
+
+pub fn language_code(packed: u16) -> String {
+	if packed == 0 || packed == 0x7FFF {
+		return "und".to_string();
+	}
+	(0..3).rev().map(|shift| (((packed >> (shift * 5)) & 0x1F) as u8 + 0x60) as char).collect()
+}
+
+/// Pack a three letter ISO 639-2 code the way `mdhd` stores it.
+pub fn pack_language(code: &str) -> u16 {
+	let bytes = code.as_bytes();
+	if bytes.len() != 3 || !bytes.iter().all(|byte| byte.is_ascii_lowercase()) {
+		return pack_language("und");
+	}
+	bytes.iter().fold(0u16, |packed, byte| (packed << 5) | (byte - 0x60) as u16)
+}
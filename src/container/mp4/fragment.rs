@@ -0,0 +1,251 @@
+use super::boxes::{self, Bytes, Children};
+use super::formater::{Mp4Sample, Mp4Track, Mp4TrackDefaults, SAMPLE_IS_NON_SYNC};
+use crate::{error, message::Result};
+
+const TFHD_BASE_DATA_OFFSET: u32 = 0x01;
+const TFHD_DESCRIPTION_INDEX: u32 = 0x02;
+const TFHD_DEFAULT_DURATION: u32 = 0x08;
+const TFHD_DEFAULT_SIZE: u32 = 0x10;
+const TFHD_DEFAULT_FLAGS: u32 = 0x20;
+const TFHD_BASE_IS_MOOF: u32 = 0x02_0000;
+
+const TRUN_DATA_OFFSET: u32 = 0x01;
+const TRUN_FIRST_SAMPLE_FLAGS: u32 = 0x04;
+const TRUN_DURATION: u32 = 0x100;
+const TRUN_SIZE: u32 = 0x200;
+const TRUN_FLAGS: u32 = 0x400;
+const TRUN_COMPOSITION_OFFSET: u32 = 0x800;
+
+struct FragmentHeader {
+	track_id: u32,
+	base_offset: Option<u64>,
+	base_is_moof: bool,
+	defaults: Mp4TrackDefaults,
+}
+
+impl FragmentHeader {
+	fn parse(data: &[u8], defaults: &[Mp4TrackDefaults]) -> Result<Self> {
+		let mut bytes = Bytes::new(data);
+		let (_, flags) = bytes.full_header()?;
+		let track_id = bytes.u32()?;
+		let mut defaults =
+			defaults.iter().find(|entry| entry.track_id == track_id).copied().unwrap_or_default();
+
+		let base_offset = match flags & TFHD_BASE_DATA_OFFSET {
+			0 => None,
+			_ => Some(bytes.u64()?),
+		};
+		if flags & TFHD_DESCRIPTION_INDEX != 0 {
+			bytes.skip(4)?;
+		}
+		if flags & TFHD_DEFAULT_DURATION != 0 {
+			defaults.sample_duration = bytes.u32()?;
+		}
+		if flags & TFHD_DEFAULT_SIZE != 0 {
+			defaults.sample_size = bytes.u32()?;
+		}
+		if flags & TFHD_DEFAULT_FLAGS != 0 {
+			defaults.sample_flags = bytes.u32()?;
+		}
+		Ok(Self { track_id, base_offset, base_is_moof: flags & TFHD_BASE_IS_MOOF != 0, defaults })
+	}
+}
+
+/// Append the samples a `moof` describes to their tracks. `moof_start` is the
+/// absolute offset of the moof header, which data offsets are relative to.
+pub fn parse_moof(
+	data: &[u8],
+	moof_start: u64,
+	defaults: &[Mp4TrackDefaults],
+	tracks: &mut [Mp4Track],
+) -> Result<()> {
+	// without an explicit base, each traf continues where the previous one's data ended
+	let mut data_end = moof_start;
+	for child in Children::new(data) {
+		let (kind, traf) = child?;
+		if kind == boxes::TRAF {
+			data_end = parse_traf(traf, moof_start, data_end, defaults, tracks)?;
+		}
+	}
+	Ok(())
+}
+
+fn parse_traf(
+	data: &[u8],
+	moof_start: u64,
+	data_end: u64,
+	defaults: &[Mp4TrackDefaults],
+	tracks: &mut [Mp4Track],
+) -> Result<u64> {
+	let header = match boxes::find(data, boxes::TFHD)? {
+		Some(tfhd) => FragmentHeader::parse(tfhd, defaults)?,
+		None => return Err(error!("mp4 traf has no tfhd")),
+	};
+	let Some(track) = tracks.iter_mut().find(|track| track.id == header.track_id) else {
+		return Ok(data_end);
+	};
+
+	let base = match (header.base_offset, header.base_is_moof) {
+		(Some(offset), _) => offset,
+		(None, true) => moof_start,
+		(None, false) => data_end,
+	};
+	let mut dts = track.samples.last().map_or(0, |sample| sample.dts + sample.duration as i64);
+	let mut cursor = base;
+
+	for child in Children::new(data) {
+		let (kind, payload) = child?;
+		match kind {
+			boxes::TFDT => {
+				let mut bytes = Bytes::new(payload);
+				let (version, _) = bytes.full_header()?;
+				dts = bytes.versioned(version)? as i64;
+			}
+			boxes::TRUN => {
+				let mut bytes = Bytes::new(payload);
+				let (version, flags) = bytes.full_header()?;
+				let count = bytes.u32()?;
+				if flags & TRUN_DATA_OFFSET != 0 {
+					cursor = base.wrapping_add_signed(bytes.i32()? as i64);
+				}
+				let first_flags = match flags & TRUN_FIRST_SAMPLE_FLAGS {
+					0 => None,
+					_ => Some(bytes.u32()?),
+				};
+
+				let fields = [TRUN_DURATION, TRUN_SIZE, TRUN_FLAGS, TRUN_COMPOSITION_OFFSET];
+				let entry_size = fields.iter().filter(|field| flags & **field != 0).count() * 4;
+				if bytes.remaining() < count as usize * entry_size {
+					return Err(error!("mp4 trun is truncated"));
+				}
+
+				for index in 0..count {
+					let duration = match flags & TRUN_DURATION {
+						0 => header.defaults.sample_duration,
+						_ => bytes.u32()?,
+					};
+					let size = match flags & TRUN_SIZE {
+						0 => header.defaults.sample_size,
+						_ => bytes.u32()?,
+					};
+					let mut sample_flags = match flags & TRUN_FLAGS {
+						0 => header.defaults.sample_flags,
+						_ => bytes.u32()?,
+					};
+					if let (0, Some(first)) = (index, first_flags) {
+						sample_flags = first;
+					}
+					let composition_offset = match (flags & TRUN_COMPOSITION_OFFSET, version) {
+						(0, _) => 0,
+						(_, 0) => bytes.u32()? as i64,
+						_ => bytes.i32()? as i64,
+					};
+
+					track.samples.push(Mp4Sample {
+						offset: cursor,
+						size,
+						dts,
+						pts: dts + composition_offset,
+						duration,
+						keyframe: sample_flags & SAMPLE_IS_NON_SYNC == 0,
+					});
+					cursor += size as u64;
+					dts += duration as i64;
+				}
+			}
+			_ => {}
+		}
+	}
+	Ok(cursor)
+}
+
+pub struct FragmentRun<'a> {
+	pub track_id: u32,
+	/// Decode time of the first sample, in the track timescale.
+	pub base_decode_time: u64,
+	pub samples: &'a [Mp4Sample],
+}
+
+/// Sync samples depend on nothing; the others depend on earlier samples.
+fn sample_flags(keyframe: bool) -> u32 {
+	match keyframe {
+		true => 0x0200_0000,
+		false => 0x0100_0000 | SAMPLE_IS_NON_SYNC,
+	}
+}
+
+pub fn write_moof(sequence: u32, runs: &[FragmentRun]) -> Vec<u8> {
+	// data offsets count from the start of the moof, so its size comes first
+	let size = build_moof(sequence, runs, 0).len() as u64;
+	build_moof(sequence, runs, size + 8)
+}
+
+fn build_moof(sequence: u32, runs: &[FragmentRun], data_offset: u64) -> Vec<u8> {
+	let mut moof = Vec::new();
+	boxes::write_full_box(&mut moof, boxes::MFHD, 0, 0, &sequence.to_be_bytes());
+
+	let mut data_offset = data_offset;
+	for run in runs {
+		let mut traf = Vec::new();
+		boxes::write_full_box(
+			&mut traf,
+			boxes::TFHD,
+			0,
+			TFHD_BASE_IS_MOOF,
+			&run.track_id.to_be_bytes(),
+		);
+		boxes::write_full_box(&mut traf, boxes::TFDT, 1, 0, &run.base_decode_time.to_be_bytes());
+
+		let mut flags = TRUN_DATA_OFFSET | TRUN_DURATION | TRUN_SIZE | TRUN_FLAGS;
+		if run.samples.iter().any(|sample| sample.pts != sample.dts) {
+			flags |= TRUN_COMPOSITION_OFFSET;
+		}
+		// version 1 makes composition offsets signed
+		let version = run.samples.iter().any(|sample| sample.pts < sample.dts) as u8;
+
+		let mut trun = (run.samples.len() as u32).to_be_bytes().to_vec();
+		trun.extend_from_slice(&(data_offset as i32).to_be_bytes());
+		for sample in run.samples {
+			trun.extend_from_slice(&sample.duration.to_be_bytes());
+			trun.extend_from_slice(&sample.size.to_be_bytes());
+			trun.extend_from_slice(&sample_flags(sample.keyframe).to_be_bytes());
+			if flags & TRUN_COMPOSITION_OFFSET != 0 {
+				trun.extend_from_slice(&((sample.pts - sample.dts) as i32).to_be_bytes());
+			}
+		}
+		boxes::write_full_box(&mut traf, boxes::TRUN, version, flags, &trun);
+		boxes::write_box(&mut moof, boxes::TRAF, &traf);
+		data_offset += run.samples.iter().map(|sample| sample.size as u64).sum::<u64>();
+	}
+
+	let mut out = Vec::new();
+	boxes::write_box(&mut out, boxes::MOOF, &moof);
+	out
+}
+
+/// A `sidx` with one reference: the `size` bytes of moof and mdat right after
+/// it, starting with a sync sample.
+pub fn write_sidx(
+	track_id: u32,
+	timescale: u32,
+	earliest_time: u64,
+	size: u32,
+	duration: u32,
+) -> Vec<u8> {
+	let mut sidx = Vec::new();
+	sidx.extend_from_slice(&track_id.to_be_bytes());
+	sidx.extend_from_slice(&timescale.to_be_bytes());
+	sidx.extend_from_slice(&earliest_time.to_be_bytes());
+	// first offset: the reference starts right after this box
+	sidx.extend_from_slice(&0u64.to_be_bytes());
+	sidx.extend_from_slice(&0u16.to_be_bytes());
+	sidx.extend_from_slice(&1u16.to_be_bytes());
+	sidx.extend_from_slice(&(size & 0x7FFF_FFFF).to_be_bytes());
+	sidx.extend_from_slice(&duration.to_be_bytes());
+	// starts with SAP, SAP type 1
+	sidx.extend_from_slice(&0x9000_0000u32.to_be_bytes());
+
+	let mut out = Vec::new();
+	boxes::write_full_box(&mut out, boxes::SIDX, 1, 0, &sidx);
+	out
+}
@@ -0,0 +1,177 @@
+use super::boxes::{self, Bytes, Children, FourCC};
+use crate::container::Metadata;
+use crate::message::Result;
+
+const DATA_UTF8: u32 = 1;
+const DATA_INTEGER: u32 = 21;
+
+const KEYS: &[(FourCC, &str)] = &[
+	(*b"\xA9nam", "title"),
+	(*b"\xA9ART", "artist"),
+	(*b"aART", "album_artist"),
+	(*b"\xA9alb", "album"),
+	(*b"\xA9day", "date"),
+	(*b"\xA9gen", "genre"),
+	(*b"\xA9cmt", "comment"),
+	(*b"\xA9wrt", "composer"),
+	(*b"\xA9too", "encoder"),
+	(*b"\xA9lyr", "lyrics"),
+	(*b"\xA9grp", "grouping"),
+	(*b"cprt", "copyright"),
+	(*b"\xA9cpy", "copyright"),
+	(*b"desc", "description"),
+	(*b"trkn", "track"),
+	(*b"disk", "disc"),
+	(*b"tmpo", "bpm"),
+	(*b"cpil", "compilation"),
+];
+
+fn key_name(key: &FourCC) -> Option<&'static str> {
+	KEYS.iter().find(|(fourcc, _)| fourcc == key).map(|(_, name)| *name)
+}
+
+fn name_key(name: &str) -> Option<FourCC> {
+	KEYS.iter().find(|(_, known)| *known == name).map(|(fourcc, _)| *fourcc)
+}
+
+/// Read the `udta` of a movie: iTunes `meta/ilst` items and QuickTime `©xxx` strings.
+pub fn parse_udta(data: &[u8], metadata: &mut Metadata) -> Result<()> {
+	for child in Children::new(data) {
+		let (kind, payload) = child?;
+		if kind == boxes::META {
+			parse_meta(payload, metadata)?;
+			continue;
+		}
+		let name = key_name(&kind).filter(|_| kind[0] == 0xA9);
+		if let Some((name, value)) = name.zip(quicktime_string(payload)) {
+			metadata.set(name, value);
+		}
+	}
+	Ok(())
+}
+
+/// `meta` is a full box in ISO files but a plain atom in QuickTime ones.
+fn parse_meta(data: &[u8], metadata: &mut Metadata) -> Result<()> {
+	let children = match data.get(..4) {
+		Some([0, 0, 0, 0]) => &data[4..],
+		_ => data,
+	};
+	if let Some(list) = boxes::find(children, boxes::ILST)? {
+		parse_ilst(list, metadata)?;
+	}
+	Ok(())
+}
+
+fn parse_ilst(data: &[u8], metadata: &mut Metadata) -> Result<()> {
+	for item in Children::new(data) {
+		let (key, payload) = item?;
+		let mut freeform = None;
+		let mut value = None;
+
+		for child in Children::new(payload) {
+			let (kind, child) = child?;
+			match &kind {
+				b"name" => {
+					freeform = child.get(4..).map(|name| String::from_utf8_lossy(name).to_lowercase())
+				}
+				b"data" => value = data_value(&key, child)?,
+				_ => {}
+			}
+		}
+
+		let name = match (&key, freeform) {
+			(b"----", Some(name)) => name,
+			_ => match key_name(&key) {
+				Some(name) => name.to_string(),
+				None => continue,
+			},
+		};
+		if let Some(value) = value {
+			metadata.set(&name, value);
+		}
+	}
+	Ok(())
+}
+
+fn data_value(key: &FourCC, data: &[u8]) -> Result<Option<String>> {
+	let mut bytes = Bytes::new(data);
+	let kind = bytes.u32()? & 0x00FF_FFFF;
+	let _locale = bytes.u32()?;
+	let value = bytes.rest();
+
+	let text = match (key, kind) {
+		// track and disc numbers are binary `number/total` pairs
+		(b"trkn" | b"disk", _) if value.len() >= 6 => {
+			let number = u16::from_be_bytes([value[2], value[3]]);
+			let total = u16::from_be_bytes([value[4], value[5]]);
+			match total {
+				0 => number.to_string(),
+				_ => format!("{}/{}", number, total),
+			}
+		}
+		(_, DATA_UTF8) => String::from_utf8_lossy(value).to_string(),
+		(_, DATA_INTEGER) | (b"tmpo" | b"cpil", _) if !value.is_empty() && value.len() <= 8 => {
+			let number = value.iter().fold(0i64, |acc, byte| (acc << 8) | *byte as i64);
+			number.to_string()
+		}
+		_ => return Ok(None),
+	};
+	Ok(Some(text))
+}
+
+/// QuickTime user data strings: a length, a language code and the text.
+fn quicktime_string(data: &[u8]) -> Option<String> {
+	let mut bytes = Bytes::new(data);
+	let length = bytes.u16().ok()? as usize;
+	let _language = bytes.u16().ok()?;
+	let text = bytes.take(length.min(bytes.remaining())).ok()?;
+	Some(String::from_utf8_lossy(text).to_string())
+}
+
+/// A `udta` holding an iTunes `meta/ilst`; names without an item key become
+/// freeform `----` items.
+pub fn write_udta(metadata: &Metadata) -> Vec<u8> {
+	let mut list = Vec::new();
+	for (name, value) in metadata.iter() {
+		let key = name_key(name);
+		let mut item = Vec::new();
+		if key.is_none() {
+			boxes::write_full_box(&mut item, *b"mean", 0, 0, b"com.apple.iTunes");
+			boxes::write_full_box(&mut item, *b"name", 0, 0, name.to_uppercase().as_bytes());
+		}
+		let (kind, data) = match key.as_ref() {
+			Some(b"trkn" | b"disk") => (0, number_pair(value)),
+			Some(b"tmpo") => (DATA_INTEGER, value.parse::<u16>().unwrap_or(0).to_be_bytes().to_vec()),
+			Some(b"cpil") => (DATA_INTEGER, vec![(value == "1" || value == "true") as u8]),
+			_ => (DATA_UTF8, value.as_bytes().to_vec()),
+		};
+		let mut payload = kind.to_be_bytes().to_vec();
+		payload.extend_from_slice(&0u32.to_be_bytes());
+		payload.extend_from_slice(&data);
+		boxes::write_box(&mut item, boxes::DATA, &payload);
+		boxes::write_box(&mut list, key.unwrap_or(*b"----"), &item);
+	}
+
+	let mut handler = vec![0u8; 4];
+	handler.extend_from_slice(b"mdirappl");
+	handler.extend_from_slice(&[0u8; 9]);
+	let mut meta = Vec::new();
+	boxes::write_full_box(&mut meta, boxes::HDLR, 0, 0, &handler);
+	boxes::write_box(&mut meta, boxes::ILST, &list);
+
+	let mut udta = Vec::new();
+	boxes::write_full_box(&mut udta, boxes::META, 0, 0, &meta);
+	let mut out = Vec::new();
+	boxes::write_box(&mut out, boxes::UDTA, &udta);
+	out
+}
+
+/// `3/12` as the binary track and disc number layout.
+fn number_pair(value: &str) -> Vec<u8> {
+	let (number, total) = value.split_once('/').unwrap_or((value, "0"));
+	let mut data = vec![0u8; 2];
+	data.extend_from_slice(&number.trim().parse::<u16>().unwrap_or(0).to_be_bytes());
+	data.extend_from_slice(&total.trim().parse::<u16>().unwrap_or(0).to_be_bytes());
+	data.extend_from_slice(&[0u8; 2]);
+	data
+}
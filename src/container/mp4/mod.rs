@@ -0,0 +1,15 @@
+pub mod boxes;
+pub mod cmaf;
+pub mod demuxer;
+pub mod formater;
+pub mod fragment;
+pub mod metadata;
+pub mod moov;
+pub mod muxer;
+pub mod sample_table;
+pub use cmaf::CmafMuxer;
+pub use demuxer::Mp4Demuxer;
+pub use formater::{
+	Mp4EditEntry, Mp4FileType, Mp4Profile, Mp4Sample, Mp4SampleEntry, Mp4Track, Mp4TrackDefaults,
+};
+pub use muxer::Mp4Muxer;
@@ -0,0 +1,461 @@
+use super::boxes::{self, FourCC};
+use super::formater::{self, Mp4FileType, Mp4Profile, Mp4Sample, Mp4Track};
+use super::metadata;
+use crate::container::Metadata;
+use crate::core::stream::StreamKind;
+
+pub const MOVIE_TIMESCALE: u32 = 1000;
+const MATRIX: [u32; 9] = [0x10000, 0, 0, 0, 0x10000, 0, 0, 0, 0x4000_0000];
+
+#[derive(Debug, Default)]
+pub struct TrackTiming {
+	/// Delay before the first presented sample, written as an empty edit.
+	pub empty: i64,
+	/// Media time shown at the start of the presentation.
+	pub media_time: i64,
+	pub presentation: i64,
+	pub movie_duration: u64,
+}
+
+impl TrackTiming {
+	pub fn new(track: &Mp4Track, origin: i64) -> Self {
+		let Some(first) = track.samples.first() else {
+			return Self::default();
+		};
+		let start = track.samples.iter().map(|sample| sample.pts).min().unwrap_or(0) - origin;
+		let end = track.samples.iter().map(|sample| sample.pts + sample.duration as i64).max();
+		let empty = start.max(0);
+		let presentation = (end.unwrap_or(0) - origin - empty).max(0);
+		Self {
+			empty,
+			media_time: empty - (first.dts - origin),
+			presentation,
+			movie_duration: to_movie((empty + presentation) as u64, track.timescale),
+		}
+	}
+
+	fn needs_edits(&self) -> bool {
+		self.empty > 0 || self.media_time != 0
+	}
+}
+
+pub fn to_movie(ticks: u64, timescale: u32) -> u64 {
+	let scaled = ticks as u128 * MOVIE_TIMESCALE as u128;
+	((scaled + timescale as u128 / 2) / timescale.max(1) as u128) as u64
+}
+
+pub fn push_u16(out: &mut Vec<u8>, value: u16) {
+	out.extend_from_slice(&value.to_be_bytes());
+}
+
+pub fn push_u32(out: &mut Vec<u8>, value: u32) {
+	out.extend_from_slice(&value.to_be_bytes());
+}
+
+pub fn push_versioned(out: &mut Vec<u8>, version: u8, value: u64) {
+	match version {
+		0 => push_u32(out, value as u32),
+		_ => out.extend_from_slice(&value.to_be_bytes()),
+	}
+}
+
+fn push_matrix(out: &mut Vec<u8>) {
+	MATRIX.iter().for_each(|value| push_u32(out, *value));
+}
+
+fn runs<T: PartialEq + Copy>(values: impl Iterator<Item = T>) -> Vec<(u32, T)> {
+	let mut runs: Vec<(u32, T)> = Vec::new();
+	for value in values {
+		match runs.last_mut() {
+			Some((count, last)) if *last == value => *count += 1,
+			_ => runs.push((1, value)),
+		}
+	}
+	runs
+}
+
+/// `ftyp`, or `styp` at the start of a media segment.
+pub fn write_ftyp(out: &mut Vec<u8>, kind: FourCC, file_type: &Mp4FileType) {
+	let mut payload = file_type.major_brand.as_bytes().to_vec();
+	push_u32(&mut payload, file_type.minor_version);
+	for brand in &file_type.compatible_brands {
+		payload.extend_from_slice(brand.as_bytes());
+	}
+	boxes::write_box(out, kind, &payload);
+}
+
+/// Builds `moov` from finished sample tables, or from empty ones for a
+/// fragmented file whose samples follow in `moof` boxes.
+pub struct MovieBuilder<'a> {
+	pub profile: Mp4Profile,
+	pub tracks: &'a [Mp4Track],
+	pub timings: &'a [TrackTiming],
+	pub metadata: &'a Metadata,
+	/// Added to every chunk offset, for a `moov` moved ahead of its media data.
+	pub shift: u64,
+	pub fragmented: bool,
+}
+
+impl MovieBuilder<'_> {
+	pub fn build(&self) -> Vec<u8> {
+		let duration = self.timings.iter().map(|timing| timing.movie_duration).max().unwrap_or(0);
+		let mut moov = Vec::new();
+		Self::write_mvhd(&mut moov, duration, self.tracks.len() as u32 + 1);
+		for (track, timing) in self.tracks.iter().zip(self.timings) {
+			self.write_trak(&mut moov, track, timing);
+		}
+		if self.fragmented {
+			Self::write_mvex(&mut moov, self.tracks);
+		}
+		if !self.metadata.is_empty() {
+			moov.extend_from_slice(&metadata::write_udta(self.metadata));
+		}
+
+		let mut out = Vec::new();
+		boxes::write_box(&mut out, boxes::MOOV, &moov);
+		out
+	}
+
+	fn write_mvhd(out: &mut Vec<u8>, duration: u64, next_track_id: u32) {
+		let version = (duration > u32::MAX as u64) as u8;
+		let mut payload = Vec::new();
+		push_versioned(&mut payload, version, 0);
+		push_versioned(&mut payload, version, 0);
+		push_u32(&mut payload, MOVIE_TIMESCALE);
+		push_versioned(&mut payload, version, duration);
+		push_u32(&mut payload, 0x0001_0000);
+		push_u16(&mut payload, 0x0100);
+		payload.extend_from_slice(&[0u8; 10]);
+		push_matrix(&mut payload);
+		payload.extend_from_slice(&[0u8; 24]);
+		push_u32(&mut payload, next_track_id);
+		boxes::write_full_box(out, boxes::MVHD, version, 0, &payload);
+	}
+
+	fn write_trak(&self, out: &mut Vec<u8>, track: &Mp4Track, timing: &TrackTiming) {
+		let mut trak = Vec::new();
+		Self::write_tkhd(&mut trak, track, timing.movie_duration);
+		if timing.needs_edits() {
+			Self::write_edts(&mut trak, track, timing);
+		}
+
+		let mut mdia = Vec::new();
+		let version = (track.duration > u32::MAX as u64) as u8;
+		let mut mdhd = Vec::new();
+		push_versioned(&mut mdhd, version, 0);
+		push_versioned(&mut mdhd, version, 0);
+		push_u32(&mut mdhd, track.timescale);
+		push_versioned(&mut mdhd, version, track.duration);
+		push_u16(&mut mdhd, formater::pack_language(&track.language));
+		push_u16(&mut mdhd, 0);
+		boxes::write_full_box(&mut mdia, boxes::MDHD, version, 0, &mdhd);
+		self.write_hdlr(&mut mdia, track.kind);
+
+		let mut minf = Vec::new();
+		match track.kind {
+			StreamKind::Video => boxes::write_full_box(&mut minf, *b"vmhd", 0, 1, &[0u8; 8]),
+			StreamKind::Audio => boxes::write_full_box(&mut minf, *b"smhd", 0, 0, &[0u8; 4]),
+			StreamKind::Subtitle => boxes::write_full_box(&mut minf, *b"nmhd", 0, 0, &[]),
+		}
+		let mut dref = Vec::new();
+		push_u32(&mut dref, 1);
+		boxes::write_full_box(&mut dref, *b"url ", 0, 1, &[]);
+		let mut dinf = Vec::new();
+		boxes::write_full_box(&mut dinf, *b"dref", 0, 0, &dref);
+		boxes::write_box(&mut minf, *b"dinf", &dinf);
+		// fragmented files list their samples in moof boxes instead
+		let samples: &[Mp4Sample] = if self.fragmented { &[] } else { &track.samples };
+		Self::write_stbl(&mut minf, track, samples, self.shift);
+
+		boxes::write_box(&mut mdia, boxes::MINF, &minf);
+		boxes::write_box(&mut trak, boxes::MDIA, &mdia);
+		boxes::write_box(out, boxes::TRAK, &trak);
+	}
+
+	/// Every track's fragments use its first sample description; sample
+	/// durations, sizes and flags are given per fragment.
+	fn write_mvex(out: &mut Vec<u8>, tracks: &[Mp4Track]) {
+		let mut mvex = Vec::new();
+		for track in tracks {
+			let mut trex = Vec::new();
+			push_u32(&mut trex, track.id);
+			push_u32(&mut trex, 1);
+			trex.extend_from_slice(&[0u8; 12]);
+			boxes::write_full_box(&mut mvex, boxes::TREX, 0, 0, &trex);
+		}
+		boxes::write_box(out, boxes::MVEX, &mvex);
+	}
+
+	fn write_tkhd(out: &mut Vec<u8>, track: &Mp4Track, duration: u64) {
+		let version = (duration > u32::MAX as u64) as u8;
+		let mut payload = Vec::new();
+		push_versioned(&mut payload, version, 0);
+		push_versioned(&mut payload, version, 0);
+		push_u32(&mut payload, track.id);
+		push_u32(&mut payload, 0);
+		push_versioned(&mut payload, version, duration);
+		payload.extend_from_slice(&[0u8; 8]);
+		push_u16(&mut payload, 0);
+		push_u16(&mut payload, 0);
+		push_u16(&mut payload, if track.kind == StreamKind::Audio { 0x0100 } else { 0 });
+		push_u16(&mut payload, 0);
+		push_matrix(&mut payload);
+		push_u32(&mut payload, (track.entry.width as u32) << 16);
+		push_u32(&mut payload, (track.entry.height as u32) << 16);
+		// enabled and in movie
+		boxes::write_full_box(out, boxes::TKHD, version, 0x03, &payload);
+	}
+
+	/// An empty edit delays a late start; the media edit skips encoder delay
+	/// and reorder offsets before the first presented sample.
+	fn write_edts(out: &mut Vec<u8>, track: &Mp4Track, timing: &TrackTiming) {
+		let mut entries = Vec::new();
+		if timing.empty > 0 {
+			entries.push((to_movie(timing.empty as u64, track.timescale), -1));
+		}
+		entries.push((to_movie(timing.presentation as u64, track.timescale), timing.media_time));
+
+		let version =
+			entries.iter().any(|(duration, time)| *duration > u32::MAX as u64 || *time > i32::MAX as i64)
+				as u8;
+		let mut elst = Vec::new();
+		push_u32(&mut elst, entries.len() as u32);
+		for (duration, media_time) in entries {
+			push_versioned(&mut elst, version, duration);
+			push_versioned(&mut elst, version, media_time as u64);
+			push_u32(&mut elst, 0x0001_0000);
+		}
+		let mut edts = Vec::new();
+		boxes::write_full_box(&mut edts, boxes::ELST, version, 0, &elst);
+		boxes::write_box(out, boxes::EDTS, &edts);
+	}
+
+	fn write_hdlr(&self, out: &mut Vec<u8>, kind: StreamKind) {
+		let (handler, name) = match (kind, self.profile) {
+			(StreamKind::Video, _) => (b"vide", "VideoHandler"),
+			(StreamKind::Audio, _) => (b"soun", "SoundHandler"),
+			(StreamKind::Subtitle, Mp4Profile::Mov) => (b"text", "TextHandler"),
+			(StreamKind::Subtitle, _) => (b"sbtl", "SubtitleHandler"),
+		};
+		let mut payload = Vec::new();
+		// quicktime names the component type where iso files keep zero
+		match self.profile {
+			Mp4Profile::Mov => payload.extend_from_slice(b"mhlr"),
+			_ => push_u32(&mut payload, 0),
+		}
+		payload.extend_from_slice(handler);
+		payload.extend_from_slice(&[0u8; 12]);
+		payload.extend_from_slice(name.as_bytes());
+		payload.push(0);
+		boxes::write_full_box(out, boxes::HDLR, 0, 0, &payload);
+	}
+
+	fn write_stbl(out: &mut Vec<u8>, track: &Mp4Track, samples: &[Mp4Sample], shift: u64) {
+		let mut stbl = Vec::new();
+
+		let mut stsd = Vec::new();
+		push_u32(&mut stsd, 1);
+		Self::write_sample_entry(&mut stsd, track);
+		boxes::write_full_box(&mut stbl, boxes::STSD, 0, 0, &stsd);
+
+		let deltas = runs(samples.iter().map(|sample| sample.duration));
+		let mut stts = Vec::new();
+		push_u32(&mut stts, deltas.len() as u32);
+		for (count, delta) in deltas {
+			push_u32(&mut stts, count);
+			push_u32(&mut stts, delta);
+		}
+		boxes::write_full_box(&mut stbl, boxes::STTS, 0, 0, &stts);
+
+		if samples.iter().any(|sample| sample.pts != sample.dts) {
+			let offsets = runs(samples.iter().map(|sample| (sample.pts - sample.dts) as i32));
+			let version = offsets.iter().any(|(_, offset)| *offset < 0) as u8;
+			let mut ctts = Vec::new();
+			push_u32(&mut ctts, offsets.len() as u32);
+			for (count, offset) in offsets {
+				push_u32(&mut ctts, count);
+				push_u32(&mut ctts, offset as u32);
+			}
+			boxes::write_full_box(&mut stbl, boxes::CTTS, version, 0, &ctts);
+		}
+
+		if samples.iter().any(|sample| !sample.keyframe) {
+			let sync: Vec<u32> = (1..=samples.len() as u32)
+				.filter(|number| samples[*number as usize - 1].keyframe)
+				.collect();
+			let mut stss = Vec::new();
+			push_u32(&mut stss, sync.len() as u32);
+			sync.iter().for_each(|number| push_u32(&mut stss, *number));
+			boxes::write_full_box(&mut stbl, boxes::STSS, 0, 0, &stss);
+		}
+
+		// a chunk is a run of samples stored back to back
+		let mut chunks: Vec<(u64, u32)> = Vec::new();
+		let mut chunk_end = None;
+		for sample in samples {
+			match chunks.last_mut() {
+				Some((_, count)) if chunk_end == Some(sample.offset) => *count += 1,
+				_ => chunks.push((sample.offset, 1)),
+			}
+			chunk_end = Some(sample.offset + sample.size as u64);
+		}
+
+		let mut stsc = Vec::new();
+		let mut entries = 0u32;
+		let mut previous = None;
+		for (index, (_, count)) in chunks.iter().enumerate() {
+			if previous != Some(*count) {
+				push_u32(&mut stsc, index as u32 + 1);
+				push_u32(&mut stsc, *count);
+				push_u32(&mut stsc, 1);
+				entries += 1;
+				previous = Some(*count);
+			}
+		}
+		let mut payload = entries.to_be_bytes().to_vec();
+		payload.extend_from_slice(&stsc);
+		boxes::write_full_box(&mut stbl, boxes::STSC, 0, 0, &payload);
+
+		let mut stsz = Vec::new();
+		let constant = samples
+			.first()
+			.map(|first| first.size)
+			.filter(|size| samples.iter().all(|sample| sample.size == *size));
+		push_u32(&mut stsz, constant.unwrap_or(0));
+		push_u32(&mut stsz, samples.len() as u32);
+		if constant.is_none() {
+			samples.iter().for_each(|sample| push_u32(&mut stsz, sample.size));
+		}
+		boxes::write_full_box(&mut stbl, boxes::STSZ, 0, 0, &stsz);
+
+		let large = chunks.last().is_some_and(|(offset, _)| offset + shift > u32::MAX as u64);
+		let mut offsets = Vec::new();
+		push_u32(&mut offsets, chunks.len() as u32);
+		for (offset, _) in &chunks {
+			match large {
+				true => offsets.extend_from_slice(&(offset + shift).to_be_bytes()),
+				false => push_u32(&mut offsets, (offset + shift) as u32),
+			}
+		}
+		let kind = if large { boxes::CO64 } else { boxes::STCO };
+		boxes::write_full_box(&mut stbl, kind, 0, 0, &offsets);
+
+		boxes::write_box(out, boxes::STBL, &stbl);
+	}
+
+	fn write_sample_entry(out: &mut Vec<u8>, track: &Mp4Track) {
+		let entry = &track.entry;
+		let mut fourcc: FourCC = *b"    ";
+		let name = entry.fourcc.as_bytes();
+		fourcc[..name.len().min(4)].copy_from_slice(&name[..name.len().min(4)]);
+
+		let mut payload = vec![0u8; 6];
+		// data reference index
+		push_u16(&mut payload, 1);
+		match track.kind {
+			StreamKind::Video => {
+				payload.extend_from_slice(&[0u8; 16]);
+				push_u16(&mut payload, entry.width);
+				push_u16(&mut payload, entry.height);
+				push_u32(&mut payload, 0x0048_0000);
+				push_u32(&mut payload, 0x0048_0000);
+				push_u32(&mut payload, 0);
+				push_u16(&mut payload, 1);
+				payload.extend_from_slice(&[0u8; 32]);
+				push_u16(&mut payload, 0x0018);
+				push_u16(&mut payload, 0xFFFF);
+				match &fourcc {
+					b"avc1" | b"avc3" => boxes::write_box(&mut payload, boxes::AVCC, &entry.codec_private),
+					b"hvc1" | b"hev1" => boxes::write_box(&mut payload, boxes::HVCC, &entry.codec_private),
+					_ => {}
+				}
+			}
+			StreamKind::Audio => {
+				let opus = &fourcc == b"Opus";
+				// opus always decodes at 48 kHz whatever the input rate was
+				let sample_rate = if opus { 48_000 } else { entry.sample_rate as u32 };
+				payload.extend_from_slice(&[0u8; 8]);
+				push_u16(&mut payload, entry.channels.max(1));
+				push_u16(&mut payload, if entry.sample_size == 0 { 16 } else { entry.sample_size });
+				push_u32(&mut payload, 0);
+				push_u32(&mut payload, sample_rate.min(u16::MAX as u32) << 16);
+				match &fourcc {
+					b"mp4a" => {
+						let object_type = entry.object_type.unwrap_or(0x40);
+						Self::write_esds(&mut payload, track.id, object_type, &entry.codec_private)
+					}
+					b"alac" => boxes::write_full_box(&mut payload, boxes::ALAC, 0, 0, &entry.codec_private),
+					b"Opus" => {
+						boxes::write_box(&mut payload, boxes::DOPS, &Self::opus_config(&entry.codec_private))
+					}
+					_ => {}
+				}
+			}
+			StreamKind::Subtitle => Self::write_text_entry(&mut payload),
+		}
+		boxes::write_box(out, fourcc, &payload);
+	}
+
+	/// ES descriptor with the decoder config and its specific info (an
+	/// AudioSpecificConfig for AAC); sizes use the 4-byte expanded form.
+	fn write_esds(out: &mut Vec<u8>, track_id: u32, object_type: u8, private: &[u8]) {
+		fn descriptor(out: &mut Vec<u8>, tag: u8, payload: &[u8]) {
+			let size = payload.len() as u32;
+			out.push(tag);
+			for shift in [21, 14, 7] {
+				out.push(0x80 | ((size >> shift) & 0x7F) as u8);
+			}
+			out.push((size & 0x7F) as u8);
+			out.extend_from_slice(payload);
+		}
+
+		// audio stream type, upstream flag clear, reserved bit set
+		let mut config = vec![object_type, 0x05 << 2 | 1];
+		config.extend_from_slice(&[0u8; 11]);
+		if !private.is_empty() {
+			descriptor(&mut config, 0x05, private);
+		}
+		let mut es = (track_id as u16).to_be_bytes().to_vec();
+		es.push(0);
+		descriptor(&mut es, 0x04, &config);
+		descriptor(&mut es, 0x06, &[0x02]);
+
+		let mut payload = Vec::new();
+		descriptor(&mut payload, 0x03, &es);
+		boxes::write_full_box(out, boxes::ESDS, 0, 0, &payload);
+	}
+
+	/// `dOps` is the Ogg `OpusHead` made big-endian, without its magic.
+	fn opus_config(private: &[u8]) -> Vec<u8> {
+		let Some(head) = private.strip_prefix(b"OpusHead").filter(|head| head.len() >= 11) else {
+			return private.to_vec();
+		};
+		let mut config = vec![0, head[1]];
+		config.extend_from_slice(&u16::from_le_bytes([head[2], head[3]]).to_be_bytes());
+		config
+			.extend_from_slice(&u32::from_le_bytes([head[4], head[5], head[6], head[7]]).to_be_bytes());
+		config.extend_from_slice(&i16::from_le_bytes([head[8], head[9]]).to_be_bytes());
+		config.extend_from_slice(&head[10..]);
+		config
+	}
+
+	/// 3GPP timed text defaults: centred at the bottom, white 18pt serif.
+	fn write_text_entry(out: &mut Vec<u8>) {
+		push_u32(out, 0);
+		out.extend_from_slice(&[0x01, 0xFF]);
+		out.extend_from_slice(&[0u8; 4]);
+		out.extend_from_slice(&[0u8; 8]);
+		push_u16(out, 0);
+		push_u16(out, 0);
+		push_u16(out, 1);
+		out.extend_from_slice(&[0x00, 0x12]);
+		out.extend_from_slice(&[0xFF; 4]);
+
+		let mut fonts = Vec::new();
+		push_u16(&mut fonts, 1);
+		push_u16(&mut fonts, 1);
+		fonts.push(5);
+		fonts.extend_from_slice(b"Serif");
+		boxes::write_box(out, *b"ftab", &fonts);
+	}
+}
@@ -0,0 +1,210 @@
+use super::boxes;
+use super::formater::{Mp4Profile, Mp4Sample, Mp4Track};
+use super::moov::{self, MovieBuilder, TrackTiming};
+use crate::container::Metadata;
+use crate::core::Muxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream, StreamKind};
+use crate::io::{MediaRead, MediaSeek, MediaWrite, ReadPrimitives, SeekFrom, WritePrimitives};
+use crate::{error, message::Result};
+
+/// Moves `length` bytes at `start` forward by `shift`, for writers that can be read back.
+type Relocate<W> = fn(&mut W, u64, u64, u64) -> Result<()>;
+
+pub struct Mp4Muxer<W: MediaWrite + MediaSeek> {
+	writer: W,
+	profile: Mp4Profile,
+	tracks: Vec<Mp4Track>,
+	streams: stream::Streams,
+	metadata: Metadata,
+	/// Where each track's presentation starts: past its leading discarded packets.
+	origins: Vec<Option<i64>>,
+	primed: Vec<bool>,
+	mdat_start: u64,
+	position: u64,
+	relocate: Option<Relocate<W>>,
+}
+
+impl<W: MediaWrite + MediaSeek> Mp4Muxer<W> {
+	pub fn new(mut writer: W, tracks: Vec<Mp4Track>, profile: Mp4Profile) -> Result<Self> {
+		if tracks.is_empty() {
+			return Err(error!("{} muxer needs at least one track", profile.extension()));
+		}
+
+		let mut tracks = tracks;
+		for (index, track) in tracks.iter_mut().enumerate() {
+			profile.validate(track)?;
+			track.id = index as u32 + 1;
+			track.duration = 0;
+			track.edits.clear();
+			track.samples.clear();
+		}
+
+		let mut header = Vec::new();
+		moov::write_ftyp(&mut header, boxes::FTYP, &profile.file_type());
+		// 64-bit size so large files need no rewrite, patched in finalize
+		moov::push_u32(&mut header, 1);
+		header.extend_from_slice(&boxes::MDAT);
+		header.extend_from_slice(&0u64.to_be_bytes());
+		writer.write_all(&header)?;
+		let position = writer.stream_position()?;
+
+		let mut streams = stream::Streams::new_empty();
+		for (index, track) in tracks.iter().enumerate() {
+			let stream = Stream::new(index as u32, index, track.kind, track.codec_name(), track.time());
+			streams.add(stream.with_codec_private(track.entry.codec_private.clone()));
+		}
+
+		Ok(Self {
+			writer,
+			profile,
+			origins: vec![None; tracks.len()],
+			primed: vec![false; tracks.len()],
+			tracks,
+			streams,
+			metadata: Metadata::new(),
+			mdat_start: position - 16,
+			position,
+			relocate: None,
+		})
+	}
+
+	pub fn with_metadata(&mut self, metadata: Option<Metadata>) {
+		self.metadata = metadata.unwrap_or_default();
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		let index = packet.stream_id as usize;
+		let Some(track) = self.tracks.get_mut(index) else {
+			return Err(error!("mp4 muxer has no stream {}", packet.stream_id));
+		};
+		let dts = track.rescale(packet.dts, packet.time);
+		let pts = track.rescale(packet.pts, packet.time);
+
+		if let Some(last) = track.samples.last_mut() {
+			if dts < last.dts {
+				return Err(error!("mp4 track {} dts {} goes backwards", track.id, dts));
+			}
+			last.duration = u32::try_from(dts - last.dts)
+				.map_err(|_| error!("mp4 track {} has a gap too long for one sample", track.id))?;
+		}
+
+		// leading discarded packets are encoder delay, hidden by the edit list
+		if self.origins[index].is_none() {
+			match packet.discard {
+				true => self.primed[index] = true,
+				false => self.origins[index] = Some(if self.primed[index] { pts.max(0) } else { 0 }),
+			}
+		}
+
+		let size = u32::try_from(packet.data.len())
+			.map_err(|_| error!("mp4 sample of {} bytes is too large", packet.data.len()))?;
+		track.samples.push(Mp4Sample {
+			offset: self.position,
+			size,
+			dts,
+			pts,
+			duration: 0,
+			// only video has frames that depend on others
+			keyframe: packet.keyframe || track.kind != StreamKind::Video,
+		});
+		self.writer.write_all(&packet.data)?;
+		self.position += size as u64;
+		Ok(())
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		// the last sample has nothing after it, so it repeats the previous duration
+		for track in self.tracks.iter_mut() {
+			let count = track.samples.len();
+			if count >= 2 {
+				track.samples[count - 1].duration = track.samples[count - 2].duration;
+			}
+			track.duration = track.samples.iter().map(|sample| sample.duration as u64).sum();
+		}
+		let timings: Vec<TrackTiming> = self
+			.tracks
+			.iter()
+			.zip(&self.origins)
+			.map(|(track, origin)| TrackTiming::new(track, origin.unwrap_or(0)))
+			.collect();
+
+		let mdat_size = self.position - self.mdat_start;
+		self.writer.seek(SeekFrom::Start(self.mdat_start + 8))?;
+		self.writer.write_all(&mdat_size.to_be_bytes())?;
+
+		match self.relocate {
+			None => {
+				let moov = self.build_moov(&timings, 0);
+				self.writer.seek(SeekFrom::Start(self.position))?;
+				self.writer.write_all(&moov)?;
+			}
+			Some(relocate) => {
+				// chunk offsets move with the media data, which can grow the moov itself
+				let mut moov = self.build_moov(&timings, 0);
+				loop {
+					let shift = moov.len() as u64;
+					moov = self.build_moov(&timings, shift);
+					if moov.len() as u64 == shift {
+						break;
+					}
+				}
+				relocate(&mut self.writer, self.mdat_start, mdat_size, moov.len() as u64)?;
+				self.writer.seek(SeekFrom::Start(self.mdat_start))?;
+				self.writer.write_all(&moov)?;
+			}
+		}
+
+		self.writer.seek(SeekFrom::End(0))?;
+		self.writer.flush()
+	}
+
+	fn build_moov(&self, timings: &[TrackTiming], shift: u64) -> Vec<u8> {
+		let builder = MovieBuilder {
+			profile: self.profile,
+			tracks: &self.tracks,
+			timings,
+			metadata: &self.metadata,
+			shift,
+			fragmented: false,
+		};
+		builder.build()
+	}
+}
+
+impl<W: MediaRead + MediaWrite + MediaSeek> Mp4Muxer<W> {
+	/// Put `moov` ahead of the media data in `finalize`, so playback can start
+	/// before the whole file is downloaded.
+	pub fn with_faststart(&mut self, faststart: bool) {
+		self.relocate = faststart.then_some(Self::relocate as Relocate<W>);
+	}
+
+	/// Copies back to front, so the overlapping ranges never overwrite unread data.
+	fn relocate(writer: &mut W, start: u64, length: u64, shift: u64) -> Result<()> {
+		const BLOCK_SIZE: u64 = 1024 * 1024;
+		let mut buffer = vec![0u8; BLOCK_SIZE.min(length) as usize];
+		let mut end = start + length;
+		while end > start {
+			let size = (end - start).min(BLOCK_SIZE);
+			let from = end - size;
+			writer.seek(SeekFrom::Start(from))?;
+			writer.read_exact(&mut buffer[..size as usize])?;
+			writer.seek(SeekFrom::Start(from + shift))?;
+			writer.write_all(&buffer[..size as usize])?;
+			end = from;
+		}
+		Ok(())
+	}
+}
+
+impl<W: MediaWrite + MediaSeek> Muxer for Mp4Muxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+}
@@ -0,0 +1,233 @@
+use super::boxes::{self, Bytes, Children};
+use super::formater::Mp4Sample;
+use crate::{error, message::Result};
+
+#[derive(Debug, Default)]
+pub struct SampleTable {
+	/// (sample count, delta)
+	time_to_sample: Vec<(u32, u32)>,
+	/// (sample count, composition offset)
+	composition_offsets: Vec<(u32, i32)>,
+	/// (first chunk, samples per chunk), first chunk 1-based
+	sample_to_chunk: Vec<(u32, u32)>,
+	/// Per-sample sizes, empty when every sample has `constant_size`.
+	sizes: Vec<u32>,
+	constant_size: u32,
+	sample_count: usize,
+	chunk_offsets: Vec<u64>,
+	/// 1-based sync sample numbers, `None` when every sample is a sync sample.
+	sync_samples: Option<Vec<u32>>,
+}
+
+impl SampleTable {
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		let mut table = Self::default();
+		for child in Children::new(data) {
+			let (kind, payload) = child?;
+			let mut bytes = Bytes::new(payload);
+			match kind {
+				boxes::STTS => {
+					bytes.full_header()?;
+					for _ in 0..Self::entry_count(&mut bytes, 8)? {
+						table.time_to_sample.push((bytes.u32()?, bytes.u32()?));
+					}
+				}
+				boxes::CTTS => {
+					// version 0 offsets are unsigned, but writers store negative ones there too
+					bytes.full_header()?;
+					for _ in 0..Self::entry_count(&mut bytes, 8)? {
+						table.composition_offsets.push((bytes.u32()?, bytes.i32()?));
+					}
+				}
+				boxes::STSC => {
+					bytes.full_header()?;
+					for _ in 0..Self::entry_count(&mut bytes, 12)? {
+						let first_chunk = bytes.u32()?;
+						let samples_per_chunk = bytes.u32()?;
+						let _description_index = bytes.u32()?;
+						table.sample_to_chunk.push((first_chunk, samples_per_chunk));
+					}
+				}
+				boxes::STSZ => {
+					bytes.full_header()?;
+					table.constant_size = bytes.u32()?;
+					if table.constant_size == 0 {
+						let count = Self::entry_count(&mut bytes, 4)?;
+						table.sizes = (0..count).map(|_| bytes.u32()).collect::<Result<_>>()?;
+						table.sample_count = table.sizes.len();
+					} else {
+						table.sample_count = bytes.u32()? as usize;
+					}
+				}
+				boxes::STZ2 => {
+					table.sizes = Self::parse_compact_sizes(&mut bytes)?;
+					table.sample_count = table.sizes.len();
+				}
+				boxes::STCO => {
+					bytes.full_header()?;
+					for _ in 0..Self::entry_count(&mut bytes, 4)? {
+						table.chunk_offsets.push(bytes.u32()? as u64);
+					}
+				}
+				boxes::CO64 => {
+					bytes.full_header()?;
+					for _ in 0..Self::entry_count(&mut bytes, 8)? {
+						table.chunk_offsets.push(bytes.u64()?);
+					}
+				}
+				boxes::STSS => {
+					bytes.full_header()?;
+					let mut sync = Vec::new();
+					for _ in 0..Self::entry_count(&mut bytes, 4)? {
+						sync.push(bytes.u32()?);
+					}
+					table.sync_samples = Some(sync);
+				}
+				_ => {}
+			}
+		}
+		Ok(table)
+	}
+
+	fn entry_count(bytes: &mut Bytes, entry_size: usize) -> Result<u32> {
+		let count = bytes.u32()?;
+		if bytes.remaining() < count as usize * entry_size {
+			return Err(error!("mp4 sample table is truncated"));
+		}
+		Ok(count)
+	}
+
+	fn parse_compact_sizes(bytes: &mut Bytes) -> Result<Vec<u32>> {
+		bytes.full_header()?;
+		bytes.skip(3)?;
+		let field_size = bytes.u8()?;
+		let count = bytes.u32()? as usize;
+		if bytes.remaining() < (count * field_size as usize).div_ceil(8) {
+			return Err(error!("mp4 sample table is truncated"));
+		}
+		let mut sizes = Vec::with_capacity(count);
+		match field_size {
+			4 => {
+				for index in 0..count {
+					if index % 2 == 0 {
+						let byte = bytes.u8()?;
+						sizes.push((byte >> 4) as u32);
+						if index + 1 < count {
+							sizes.push((byte & 0x0F) as u32);
+						}
+					}
+				}
+			}
+			8 => {
+				for _ in 0..count {
+					sizes.push(bytes.u8()? as u32);
+				}
+			}
+			16 => {
+				for _ in 0..count {
+					sizes.push(bytes.u16()? as u32);
+				}
+			}
+			size => return Err(error!("mp4 stz2 field size {} is invalid", size)),
+		}
+		Ok(sizes)
+	}
+
+	/// Raw audio stores one sample per audio frame; those are grouped per chunk
+	/// instead of becoming millions of tiny packets.
+	fn groups_chunks(&self) -> bool {
+		self.constant_size > 0
+			&& self.composition_offsets.is_empty()
+			&& self.time_to_sample.iter().all(|(_, delta)| *delta == 1)
+	}
+
+	fn size(&self, sample: usize) -> u32 {
+		match self.constant_size {
+			0 => self.sizes[sample],
+			size => size,
+		}
+	}
+
+	fn chunks(&self) -> impl Iterator<Item = (u64, u32)> + '_ {
+		self.sample_to_chunk.iter().enumerate().flat_map(move |(index, entry)| {
+			let first = entry.0.max(1) as usize - 1;
+			let last = match self.sample_to_chunk.get(index + 1) {
+				Some(next) => (next.0.max(1) as usize - 1).min(self.chunk_offsets.len()),
+				None => self.chunk_offsets.len(),
+			};
+			self.chunk_offsets[first.min(last)..last].iter().map(move |offset| (*offset, entry.1))
+		})
+	}
+
+	pub fn samples(&self) -> Result<Vec<Mp4Sample>> {
+		if self.groups_chunks() {
+			return self.chunk_samples();
+		}
+
+		let count = self.sample_count;
+		let mut samples = Vec::with_capacity(count.min(self.chunk_offsets.len() * 64));
+
+		// chunk offsets and sizes give each sample's position
+		let mut sample = 0usize;
+		for (mut offset, samples_per_chunk) in self.chunks() {
+			for _ in 0..samples_per_chunk {
+				if sample >= count {
+					break;
+				}
+				let size = self.size(sample);
+				samples.push(Mp4Sample { offset, size, dts: 0, pts: 0, duration: 0, keyframe: true });
+				offset += size as u64;
+				sample += 1;
+			}
+		}
+		if samples.len() < count {
+			return Err(error!("mp4 chunk table covers {} of {} samples", samples.len(), count));
+		}
+
+		let mut dts = 0i64;
+		let mut deltas =
+			self.time_to_sample.iter().flat_map(|(count, delta)| (0..*count).map(move |_| *delta));
+		let mut offsets =
+			self.composition_offsets.iter().flat_map(|(count, offset)| (0..*count).map(move |_| *offset));
+		for sample in samples.iter_mut() {
+			let duration = deltas.next().unwrap_or(0);
+			sample.dts = dts;
+			sample.pts = dts + offsets.next().unwrap_or(0) as i64;
+			sample.duration = duration;
+			dts += duration as i64;
+		}
+
+		if let Some(sync) = &self.sync_samples {
+			samples.iter_mut().for_each(|sample| sample.keyframe = false);
+			for number in sync {
+				if let Some(sample) = samples.get_mut((*number as usize).wrapping_sub(1)) {
+					sample.keyframe = true;
+				}
+			}
+		}
+		Ok(samples)
+	}
+
+	fn chunk_samples(&self) -> Result<Vec<Mp4Sample>> {
+		let mut samples = Vec::with_capacity(self.chunk_offsets.len());
+		let mut remaining = self.sample_count as u64;
+		let mut dts = 0i64;
+		for (offset, samples_per_chunk) in self.chunks() {
+			let frames = (samples_per_chunk as u64).min(remaining);
+			if frames == 0 {
+				break;
+			}
+			samples.push(Mp4Sample {
+				offset,
+				size: (frames * self.constant_size as u64) as u32,
+				dts,
+				pts: dts,
+				duration: frames as u32,
+				keyframe: true,
+			});
+			remaining -= frames;
+			dts += frames as i64;
+		}
+		Ok(samples)
+	}
+}
@@ -0,0 +1,614 @@
+use super::formater::{
+	self, MxfDescriptor, MxfIndexSegment, MxfPartition, MxfPrimer, MxfSet, MxfTimecode, MxfTrack,
+	Wrapping,
+};
+use super::klv::{self, Klv, Ul};
+use crate::container::Metadata;
+use crate::core::Demuxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream, StreamKind};
+use crate::core::time::Time;
+use crate::io::{MediaRead, MediaSeek, ReadPrimitives, SeekFrom};
+use crate::{error, message::Result};
+use std::collections::HashMap;
+
+const MAX_RUN_IN: u64 = 65536;
+
+#[derive(Debug, Clone, Copy)]
+struct Element {
+	track_number: u32,
+	offset: u64,
+	length: u64,
+	body_sid: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MxfSample {
+	stream: usize,
+	offset: u64,
+	size: u32,
+	pts: i64,
+	dts: i64,
+	/// Unknown for pictures the index does not cover, settled from the data.
+	keyframe: Option<bool>,
+}
+
+struct Output {
+	track: MxfTrack,
+	track_index: usize,
+	codec: &'static str,
+	body_sid: Option<u32>,
+	index_sid: Option<u32>,
+}
+
+type SetsByUid = HashMap<Ul, MxfSet>;
+
+pub struct MxfDemuxer<R: MediaRead + MediaSeek> {
+	reader: R,
+	partitions: Vec<MxfPartition>,
+	primer: MxfPrimer,
+	operational_pattern: Option<Ul>,
+	tracks: Vec<MxfTrack>,
+	stream_tracks: Vec<usize>,
+	timecode: Option<MxfTimecode>,
+	index_segments: Vec<MxfIndexSegment>,
+	metadata: Metadata,
+	streams: stream::Streams,
+	samples: Vec<MxfSample>,
+	next: usize,
+}
+
+impl<R: MediaRead + MediaSeek> MxfDemuxer<R> {
+	pub fn new(mut reader: R) -> Result<Self> {
+		let file_size = reader.stream_len()?;
+		let start = Self::find_header_partition(&mut reader, file_size)?;
+
+		let mut partitions: Vec<MxfPartition> = Vec::new();
+		let mut primer = MxfPrimer::default();
+		let mut header_metadata: Vec<(usize, SetsByUid)> = Vec::new();
+		let mut index_segments = Vec::new();
+		let mut elements = Vec::new();
+		let mut position = start;
+		// a value running past the end stops the walk
+		while file_size.saturating_sub(position) >= 17 {
+			reader.seek(SeekFrom::Start(position))?;
+			let klv = Klv::read(&mut reader, position)?;
+			if klv.key[..4] != klv::UL_PREFIX {
+				return Err(error!("mxf klv at offset {} has no smpte label", position));
+			}
+			let end = klv.end()?;
+			let key = &klv.key;
+			if formater::is_partition_pack(key) {
+				partitions.push(MxfPartition::parse(key, &klv.read_value(&mut reader)?)?);
+			} else if *key == formater::PRIMER_PACK {
+				primer = MxfPrimer::parse(&klv.read_value(&mut reader)?);
+				header_metadata.push((partitions.len().saturating_sub(1), HashMap::new()));
+			} else if formater::is_metadata_set(key) {
+				let value = klv.read_value(&mut reader)?;
+				let (Some(set), Some((_, sets))) = (MxfSet::parse(key, &value), header_metadata.last_mut())
+				else {
+					position = end;
+					continue;
+				};
+				sets.insert(set.instance_uid, set);
+			} else if klv::ul_matches(key, &formater::INDEX_SEGMENT, 16) {
+				index_segments.push(MxfIndexSegment::parse(&klv.read_value(&mut reader)?));
+			} else if formater::is_essence_element(key) {
+				let body_sid = partitions.last().map_or(0, |partition| partition.body_sid);
+				// a file cut short keeps what its last element still holds
+				let length = klv.length.min(file_size.saturating_sub(klv.value_offset));
+				let track_number = formater::essence_track_number(key);
+				elements.push(Element { track_number, offset: klv.value_offset, length, body_sid });
+			}
+			position = end;
+		}
+		if partitions.is_empty() {
+			return Err(error!("mxf file has no partition pack"));
+		}
+
+		// closed and complete header metadata is final; otherwise the last
+		// repetition, usually in the footer, is the most up to date
+		let chosen = header_metadata
+			.iter()
+			.find(|(partition, _)| partitions[*partition].closed && partitions[*partition].complete)
+			.or(header_metadata.last())
+			.map(|(_, sets)| sets);
+		let Some(sets) = chosen else {
+			return Err(error!("mxf file has no header metadata"));
+		};
+
+		let mut demuxer = Self {
+			reader,
+			partitions,
+			primer,
+			operational_pattern: None,
+			tracks: Vec::new(),
+			stream_tracks: Vec::new(),
+			timecode: None,
+			index_segments,
+			metadata: Metadata::new(),
+			streams: stream::Streams::new_empty(),
+			samples: Vec::new(),
+			next: 0,
+		};
+		let outputs = demuxer.resolve_packages(sets);
+		if outputs.is_empty() {
+			return Err(error!("mxf file has no supported essence track"));
+		}
+		for (index, output) in outputs.iter().enumerate() {
+			let stream = Stream::new(
+				index as u32,
+				index,
+				output.track.kind().unwrap_or(StreamKind::Video),
+				output.codec.to_string(),
+				Self::time(output),
+			);
+			demuxer.streams.add(stream);
+			demuxer.stream_tracks.push(output.track_index);
+		}
+		demuxer.samples = demuxer.build_samples(&outputs, &elements);
+		Ok(demuxer)
+	}
+
+	fn find_header_partition(reader: &mut R, file_size: u64) -> Result<u64> {
+		reader.seek(SeekFrom::Start(0))?;
+		let mut probe = vec![0u8; (MAX_RUN_IN + 16).min(file_size) as usize];
+		reader.read_exact(&mut probe)?;
+		probe
+			.windows(16)
+			.position(|window| {
+				window.try_into().is_ok_and(|key: &Ul| formater::is_partition_pack(key))
+					&& window[13] == 0x02
+			})
+			.map(|position| position as u64)
+			.ok_or_else(|| error!("not an mxf file"))
+	}
+
+	fn resolve_packages(&mut self, sets: &SetsByUid) -> Vec<Output> {
+		let preface = sets.values().find(|set| set.kind == formater::PREFACE);
+		let resolve =
+			|uids: Vec<Ul>| -> Vec<&MxfSet> { uids.iter().filter_map(|uid| sets.get(uid)).collect() };
+
+		// the last identification is the most recent modification
+		let identifications =
+			preface.map(|preface| resolve(preface.references(formater::IDENTIFICATIONS)));
+		if let Some(identification) = identifications.unwrap_or_default().last() {
+			let product = identification.text(formater::PRODUCT_NAME);
+			let version = identification.text(formater::VERSION_STRING);
+			if let Some(company) = identification.text(formater::COMPANY_NAME) {
+				self.metadata.set("company", company);
+			}
+			match (product, version) {
+				(Some(product), Some(version)) => {
+					self.metadata.set("encoder", format!("{} {}", product, version))
+				}
+				(Some(product), None) => self.metadata.set("encoder", product),
+				_ => {}
+			}
+		}
+		self.operational_pattern =
+			preface.and_then(|preface| preface.ul(formater::OPERATIONAL_PATTERN_ITEM));
+
+		// packages in content storage order, or in label order without one
+		let storage = preface
+			.and_then(|preface| sets.get(&preface.ul(formater::CONTENT_STORAGE_ITEM)?))
+			.or_else(|| sets.values().find(|set| set.kind == formater::CONTENT_STORAGE));
+		let mut packages =
+			storage.map(|storage| resolve(storage.references(formater::PACKAGES))).unwrap_or_default();
+		if packages.is_empty() {
+			packages = sets.values().collect();
+			packages.sort_by_key(|set| set.instance_uid);
+		}
+		let of_kind = |kind: u16| packages.iter().copied().filter(move |set| set.kind == kind);
+
+		// essence container data ties a file package to its body and index
+		let containers: HashMap<[u8; 32], (Option<u32>, Option<u32>)> = storage
+			.map(|storage| resolve(storage.references(formater::ESSENCE_CONTAINER_DATA_ITEM)))
+			.filter(|data| !data.is_empty())
+			.unwrap_or_else(|| {
+				sets.values().filter(|set| set.kind == formater::ESSENCE_CONTAINER_DATA).collect()
+			})
+			.into_iter()
+			.filter_map(|set| {
+				let package = set.umid(formater::LINKED_PACKAGE_UID)?;
+				Some((package, (set.u32(formater::BODY_SID), set.u32(formater::INDEX_SID))))
+			})
+			.collect();
+
+		let material = of_kind(formater::MATERIAL_PACKAGE).next();
+		if let Some(name) = material.and_then(|package| package.text(formater::PACKAGE_NAME)) {
+			self.metadata.set("title", name);
+		}
+		let file_packages: Vec<&MxfSet> = of_kind(formater::SOURCE_PACKAGE)
+			.filter(|package| {
+				let linked =
+					package.umid(formater::PACKAGE_UID).is_some_and(|uid| containers.contains_key(&uid));
+				linked || (containers.is_empty() && Self::has_essence_descriptor(sets, package))
+			})
+			.collect();
+
+		self.timecode = material
+			.into_iter()
+			.chain(file_packages.iter().copied())
+			.find_map(|package| Self::package_timecode(sets, package));
+		if let Some(timecode) = &self.timecode {
+			self.metadata.set("timecode", timecode.to_string());
+		}
+
+		let mut outputs = Vec::new();
+		for package in file_packages {
+			let (body_sid, index_sid) = package
+				.umid(formater::PACKAGE_UID)
+				.and_then(|uid| containers.get(&uid).copied())
+				.unwrap_or((None, None));
+			let descriptors = Self::package_descriptors(sets, package);
+			for track_set in package.references(formater::TRACKS).iter().filter_map(|uid| sets.get(uid)) {
+				let Some(track) = Self::parse_track(sets, track_set, &descriptors) else {
+					continue;
+				};
+				let track_index = self.tracks.len();
+				self.tracks.push(track.clone());
+				if let Some(codec) = track.codec() {
+					outputs.push(Output { track, track_index, codec, body_sid, index_sid });
+				}
+			}
+		}
+		outputs
+	}
+
+	fn has_essence_descriptor(sets: &SetsByUid, package: &MxfSet) -> bool {
+		package
+			.ul(formater::DESCRIPTOR)
+			.and_then(|uid| sets.get(&uid))
+			.is_some_and(MxfSet::is_descriptor)
+	}
+
+	fn package_descriptors(sets: &SetsByUid, package: &MxfSet) -> Vec<MxfDescriptor> {
+		let Some(descriptor) = package.ul(formater::DESCRIPTOR).and_then(|uid| sets.get(&uid)) else {
+			return Vec::new();
+		};
+		match descriptor.kind {
+			formater::MULTIPLE_DESCRIPTOR => descriptor
+				.references(formater::SUB_DESCRIPTORS)
+				.iter()
+				.filter_map(|uid| sets.get(uid))
+				.filter_map(MxfDescriptor::parse)
+				.collect(),
+			_ => MxfDescriptor::parse(descriptor).into_iter().collect(),
+		}
+	}
+
+	fn parse_track(
+		sets: &SetsByUid,
+		track: &MxfSet,
+		descriptors: &[MxfDescriptor],
+	) -> Option<MxfTrack> {
+		if track.kind != formater::TIMELINE_TRACK {
+			return None;
+		}
+		let number = track.u32(formater::TRACK_NUMBER).filter(|number| *number != 0)?;
+		let id = track.u32(formater::TRACK_ID).unwrap_or(0);
+		let sequence = track.ul(formater::TRACK_SEQUENCE).and_then(|uid| sets.get(&uid));
+		// a lone descriptor may leave out the track it describes
+		let descriptor = descriptors
+			.iter()
+			.find(|descriptor| descriptor.linked_track_id == Some(id))
+			.or_else(|| descriptors.first().filter(|_| descriptors.len() == 1))
+			.cloned();
+		Some(MxfTrack {
+			id,
+			number,
+			name: track.text(formater::TRACK_NAME),
+			edit_rate: track.rational(formater::EDIT_RATE).unwrap_or((25, 1)),
+			origin: track.i64(formater::ORIGIN).unwrap_or(0),
+			duration: sequence.and_then(|sequence| sequence.i64(formater::DURATION)),
+			descriptor,
+		})
+	}
+
+	fn package_timecode(sets: &SetsByUid, package: &MxfSet) -> Option<MxfTimecode> {
+		package.references(formater::TRACKS).iter().filter_map(|uid| sets.get(uid)).find_map(|track| {
+			let sequence = sets.get(&track.ul(formater::TRACK_SEQUENCE)?)?;
+			let components = match sequence.kind {
+				formater::SEQUENCE => sequence.references(formater::STRUCTURAL_COMPONENTS),
+				_ => vec![sequence.instance_uid],
+			};
+			components
+				.iter()
+				.filter_map(|uid| sets.get(uid))
+				.filter(|component| component.kind == formater::TIMECODE_COMPONENT)
+				.find_map(MxfTimecode::parse)
+		})
+	}
+
+	/// Sound counts samples; pictures count edit units.
+	fn time(output: &Output) -> Time {
+		let descriptor = output.track.descriptor.as_ref();
+		match descriptor.and_then(|descriptor| descriptor.audio_sampling_rate) {
+			Some((num, den))
+				if descriptor.is_some_and(|descriptor| descriptor.kind == StreamKind::Audio) =>
+			{
+				Time::new(den as u32, num as u32).simplify()
+			}
+			_ => {
+				let (num, den) = output.track.edit_rate;
+				Time::new(den as u32, num as u32)
+			}
+		}
+	}
+
+	fn index_for(
+		&self,
+		index_sid: Option<u32>,
+		body_sid: Option<u32>,
+	) -> (Vec<formater::MxfIndexEntry>, u32) {
+		let mut segments: Vec<&MxfIndexSegment> = self
+			.index_segments
+			.iter()
+			.filter(|segment| match (index_sid, body_sid) {
+				(Some(index_sid), _) => segment.index_sid == index_sid,
+				(None, Some(body_sid)) => segment.body_sid == body_sid,
+				(None, None) => true,
+			})
+			.collect();
+		segments.sort_by_key(|segment| segment.start_position);
+		let mut entries = Vec::new();
+		let mut edit_unit_byte_count = 0;
+		for segment in segments {
+			// repeated segments cover edit units already known
+			let start = segment.start_position.max(0) as usize;
+			if start + segment.entries.len() > entries.len() {
+				entries.truncate(start.min(entries.len()));
+				let skip = entries.len().saturating_sub(start);
+				entries.extend(segment.entries.iter().skip(skip).copied());
+			}
+			if segment.edit_unit_byte_count > 0 {
+				edit_unit_byte_count = segment.edit_unit_byte_count;
+			}
+		}
+		(entries, edit_unit_byte_count)
+	}
+
+	fn build_samples(&self, outputs: &[Output], elements: &[Element]) -> Vec<MxfSample> {
+		let mut samples = Vec::new();
+		for (stream, output) in outputs.iter().enumerate() {
+			let (entries, edit_unit_byte_count) = self.index_for(output.index_sid, output.body_sid);
+			let mut units = 0i64;
+			let elements = elements.iter().filter(|element| {
+				element.track_number == output.track.number
+					&& output.body_sid.is_none_or(|body_sid| body_sid == element.body_sid)
+			});
+			let kind = output.track.kind();
+			let wrapping = output.track.wrapping();
+			for element in elements {
+				match (kind, wrapping) {
+					(Some(StreamKind::Audio), Wrapping::Clip) => {
+						Self::split_sound(stream, output, element, &mut units, &mut samples)
+					}
+					(Some(StreamKind::Audio), _) => {
+						let block_align = Self::block_align(output);
+						let (size, pts) = (element.length as u32, units);
+						units += element.length as i64 / block_align as i64;
+						samples.push(MxfSample {
+							stream,
+							offset: element.offset,
+							size,
+							pts,
+							dts: pts,
+							keyframe: Some(true),
+						});
+					}
+					(_, Wrapping::Clip) => Self::split_pictures(
+						stream,
+						element,
+						&entries,
+						edit_unit_byte_count,
+						&mut units,
+						&mut samples,
+					),
+					_ => {
+						let keyframe =
+							entries.get(units as usize).map(|entry| entry.flags & formater::RANDOM_ACCESS != 0);
+						samples.push(MxfSample {
+							stream,
+							offset: element.offset,
+							size: element.length as u32,
+							pts: units,
+							dts: units,
+							keyframe,
+						});
+						units += 1;
+					}
+				}
+			}
+			if kind == Some(StreamKind::Video) {
+				Self::reorder(stream, &entries, &mut samples);
+			}
+		}
+		samples.sort_by_key(|sample| sample.offset);
+		samples
+	}
+
+	fn block_align(output: &Output) -> u32 {
+		output.track.descriptor.as_ref().map_or(0, |descriptor| descriptor.block_align).max(1)
+	}
+
+	fn split_sound(
+		stream: usize,
+		output: &Output,
+		element: &Element,
+		units: &mut i64,
+		samples: &mut Vec<MxfSample>,
+	) {
+		let block_align = Self::block_align(output) as u64;
+		let descriptor = output.track.descriptor.as_ref();
+		let rate =
+			descriptor.and_then(|descriptor| descriptor.audio_sampling_rate).unwrap_or((48000, 1));
+		let (num, den) = output.track.edit_rate;
+		let per_unit = (rate.0 as i64 * den as i64 / (rate.1 as i64 * num as i64).max(1)).max(1) as u64;
+		let packet_size = per_unit * block_align;
+		let mut offset = 0;
+		while offset < element.length {
+			let size = packet_size.min(element.length - offset);
+			let pts = *units;
+			samples.push(MxfSample {
+				stream,
+				offset: element.offset + offset,
+				size: size as u32,
+				pts,
+				dts: pts,
+				keyframe: Some(true),
+			});
+			*units += (size / block_align) as i64;
+			offset += size;
+		}
+	}
+
+	/// Clip-wrapped pictures, split along the index; without an index the clip
+	/// is one packet.
+	fn split_pictures(
+		stream: usize,
+		element: &Element,
+		entries: &[formater::MxfIndexEntry],
+		edit_unit_byte_count: u32,
+		units: &mut i64,
+		samples: &mut Vec<MxfSample>,
+	) {
+		let mut push = |offset: u64, end: u64, keyframe: Option<bool>, units: &mut i64| {
+			let pts = *units;
+			let size = (end.min(element.length) - offset) as u32;
+			samples.push(MxfSample {
+				stream,
+				offset: element.offset + offset,
+				size,
+				pts,
+				dts: pts,
+				keyframe,
+			});
+			*units += 1;
+		};
+		if edit_unit_byte_count > 0 {
+			let mut offset = 0;
+			while offset < element.length {
+				push(offset, offset + edit_unit_byte_count as u64, None, units);
+				offset += edit_unit_byte_count as u64;
+			}
+		} else if entries.is_empty() {
+			push(0, element.length, None, units);
+		} else {
+			for (index, entry) in entries.iter().enumerate() {
+				let end = entries.get(index + 1).map_or(element.length, |next| next.stream_offset);
+				if entry.stream_offset >= element.length || end < entry.stream_offset {
+					break;
+				}
+				push(entry.stream_offset, end, Some(entry.flags & formater::RANDOM_ACCESS != 0), units);
+			}
+		}
+	}
+
+	/// Presentation times from the index temporal offsets: display unit `x`
+	/// is stored at `x + offset`. Decode times are held back by the largest
+	/// reordering so that they never pass presentation times.
+	fn reorder(stream: usize, entries: &[formater::MxfIndexEntry], samples: &mut [MxfSample]) {
+		if entries.iter().all(|entry| entry.temporal_offset == 0) {
+			return;
+		}
+		let mut display = vec![None; entries.len()];
+		for (position, entry) in entries.iter().enumerate() {
+			let stored = position as i64 + entry.temporal_offset as i64;
+			if let Some(slot) = display.get_mut(stored.max(0) as usize) {
+				*slot = Some(position as i64);
+			}
+		}
+		let stream_samples = || samples.iter().filter(|sample| sample.stream == stream);
+		let delay = stream_samples()
+			.filter_map(|sample| Some(sample.dts - display.get(sample.dts as usize).copied().flatten()?))
+			.max()
+			.unwrap_or(0)
+			.max(0);
+		for sample in samples.iter_mut().filter(|sample| sample.stream == stream) {
+			if let Some(pts) = display.get(sample.dts as usize).copied().flatten() {
+				sample.pts = pts;
+			}
+			sample.dts -= delay;
+		}
+	}
+
+	/// Keep the samples of one stream only, so that it can be read on its own
+	/// without reading the others.
+	pub fn select_stream(&mut self, id: u32) -> Result<()> {
+		if self.streams.get(id).is_none() {
+			return Err(error!("mxf file has no stream {}", id));
+		}
+		self.samples.retain(|sample| sample.stream as u32 == id);
+		self.next = 0;
+		Ok(())
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		let Some(sample) = self.samples.get(self.next).copied() else {
+			return Ok(None);
+		};
+		self.next += 1;
+		self.reader.seek(SeekFrom::Start(sample.offset))?;
+		let mut data = vec![0u8; sample.size as usize];
+		self.reader.read_exact(&mut data)?;
+
+		let stream = &self.streams.all()[sample.stream];
+		let keyframe = sample.keyframe.unwrap_or_else(|| formater::is_keyframe(&stream.codec, &data));
+		let packet = Packet::new(data, stream.id, stream.time)
+			.with_pts(sample.pts)
+			.with_dts(sample.dts)
+			.with_keyframe(keyframe);
+		Ok(Some(packet))
+	}
+
+	pub fn partitions(&self) -> &[MxfPartition] {
+		&self.partitions
+	}
+
+	pub fn primer(&self) -> &MxfPrimer {
+		&self.primer
+	}
+
+	pub fn operational_pattern(&self) -> Option<&Ul> {
+		self.operational_pattern.as_ref()
+	}
+
+	pub fn is_op1a(&self) -> bool {
+		self.operational_pattern.as_ref().is_some_and(|pattern| {
+			klv::ul_matches(pattern, &formater::OPERATIONAL_PATTERN, 12)
+				&& pattern[12] == 0x01
+				&& pattern[13] == 0x01
+		})
+	}
+
+	pub fn tracks(&self) -> &[MxfTrack] {
+		&self.tracks
+	}
+
+	pub fn stream_track(&self, id: u32) -> Option<&MxfTrack> {
+		self.tracks.get(*self.stream_tracks.get(id as usize)?)
+	}
+
+	pub fn timecode(&self) -> Option<&MxfTimecode> {
+		self.timecode.as_ref()
+	}
+
+	pub fn index_segments(&self) -> &[MxfIndexSegment] {
+		&self.index_segments
+	}
+
+	pub fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+}
+
+impl<R: MediaRead + MediaSeek> Demuxer for MxfDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
@@ -0,0 +1,524 @@
+use super::klv::{self, LocalSet, Ul};
+use crate::codecs::{audio, video};
+use crate::core::stream::StreamKind;
+use crate::{error, message::Result};
+use std::collections::HashMap;
+
+/// Partition packs share these 13 bytes; the 14th is the partition kind and
+/// the 15th its status.
+pub const PARTITION_PACK: [u8; 13] =
+	[0x06, 0x0E, 0x2B, 0x34, 0x02, 0x05, 0x01, 0x01, 0x0D, 0x01, 0x02, 0x01, 0x01];
+pub const PRIMER_PACK: Ul =
+	[0x06, 0x0E, 0x2B, 0x34, 0x02, 0x05, 0x01, 0x01, 0x0D, 0x01, 0x02, 0x01, 0x01, 0x05, 0x01, 0x00];
+/// Header metadata sets share these 13 bytes; the next two give the set kind.
+pub const METADATA_SET: [u8; 13] =
+	[0x06, 0x0E, 0x2B, 0x34, 0x02, 0x53, 0x01, 0x01, 0x0D, 0x01, 0x01, 0x01, 0x01];
+pub const INDEX_SEGMENT: Ul =
+	[0x06, 0x0E, 0x2B, 0x34, 0x02, 0x53, 0x01, 0x01, 0x0D, 0x01, 0x02, 0x01, 0x01, 0x10, 0x01, 0x00];
+/// Generic container essence elements; the last four bytes are the track number.
+pub const ESSENCE_ELEMENT: [u8; 12] =
+	[0x06, 0x0E, 0x2B, 0x34, 0x01, 0x02, 0x01, 0x01, 0x0D, 0x01, 0x03, 0x01];
+/// Generic container essence container labels; the 14th byte is the mapping.
+pub const ESSENCE_CONTAINER: [u8; 13] =
+	[0x06, 0x0E, 0x2B, 0x34, 0x04, 0x01, 0x01, 0x01, 0x0D, 0x01, 0x03, 0x01, 0x02];
+pub const OPERATIONAL_PATTERN: [u8; 12] =
+	[0x06, 0x0E, 0x2B, 0x34, 0x04, 0x01, 0x01, 0x01, 0x0D, 0x01, 0x02, 0x01];
+const PICTURE_CODING: [u8; 13] =
+	[0x06, 0x0E, 0x2B, 0x34, 0x04, 0x01, 0x01, 0x01, 0x04, 0x01, 0x02, 0x02, 0x01];
+
+// header metadata set kinds
+pub const SEQUENCE: u16 = 0x010F;
+pub const SOURCE_CLIP: u16 = 0x0111;
+pub const TIMECODE_COMPONENT: u16 = 0x0114;
+pub const CONTENT_STORAGE: u16 = 0x0118;
+pub const ESSENCE_CONTAINER_DATA: u16 = 0x0123;
+pub const GENERIC_PICTURE_DESCRIPTOR: u16 = 0x0127;
+pub const CDCI_DESCRIPTOR: u16 = 0x0128;
+pub const RGBA_DESCRIPTOR: u16 = 0x0129;
+pub const PREFACE: u16 = 0x012F;
+pub const IDENTIFICATION: u16 = 0x0130;
+pub const MATERIAL_PACKAGE: u16 = 0x0136;
+pub const SOURCE_PACKAGE: u16 = 0x0137;
+pub const TIMELINE_TRACK: u16 = 0x013B;
+pub const GENERIC_SOUND_DESCRIPTOR: u16 = 0x0142;
+pub const MULTIPLE_DESCRIPTOR: u16 = 0x0144;
+pub const AES3_PCM_DESCRIPTOR: u16 = 0x0147;
+pub const WAVE_PCM_DESCRIPTOR: u16 = 0x0148;
+pub const MPEG2_VIDEO_DESCRIPTOR: u16 = 0x0151;
+
+// local tags of the items read
+pub const INSTANCE_UID: u16 = 0x3C0A;
+pub const COMPANY_NAME: u16 = 0x3C01;
+pub const PRODUCT_NAME: u16 = 0x3C02;
+pub const VERSION_STRING: u16 = 0x3C04;
+pub const CONTENT_STORAGE_ITEM: u16 = 0x3B03;
+pub const IDENTIFICATIONS: u16 = 0x3B06;
+pub const OPERATIONAL_PATTERN_ITEM: u16 = 0x3B09;
+pub const PACKAGES: u16 = 0x1901;
+pub const ESSENCE_CONTAINER_DATA_ITEM: u16 = 0x1902;
+pub const LINKED_PACKAGE_UID: u16 = 0x2701;
+pub const INDEX_SID: u16 = 0x3F06;
+pub const BODY_SID: u16 = 0x3F07;
+pub const PACKAGE_UID: u16 = 0x4401;
+pub const PACKAGE_NAME: u16 = 0x4402;
+pub const TRACKS: u16 = 0x4403;
+pub const DESCRIPTOR: u16 = 0x4701;
+pub const TRACK_ID: u16 = 0x4801;
+pub const TRACK_NAME: u16 = 0x4802;
+pub const TRACK_SEQUENCE: u16 = 0x4803;
+pub const TRACK_NUMBER: u16 = 0x4804;
+pub const EDIT_RATE: u16 = 0x4B01;
+pub const ORIGIN: u16 = 0x4B02;
+pub const DATA_DEFINITION: u16 = 0x0201;
+pub const DURATION: u16 = 0x0202;
+pub const STRUCTURAL_COMPONENTS: u16 = 0x1001;
+pub const START_TIMECODE: u16 = 0x1501;
+pub const ROUNDED_TIMECODE_BASE: u16 = 0x1502;
+pub const DROP_FRAME: u16 = 0x1503;
+pub const SUB_DESCRIPTORS: u16 = 0x3F01;
+pub const LINKED_TRACK_ID: u16 = 0x3006;
+pub const SAMPLE_RATE: u16 = 0x3001;
+pub const CONTAINER_DURATION: u16 = 0x3002;
+pub const ESSENCE_CONTAINER_ITEM: u16 = 0x3004;
+pub const PICTURE_ESSENCE_CODING: u16 = 0x3201;
+pub const STORED_HEIGHT: u16 = 0x3202;
+pub const STORED_WIDTH: u16 = 0x3203;
+pub const COMPONENT_DEPTH: u16 = 0x3301;
+pub const AUDIO_SAMPLING_RATE: u16 = 0x3D03;
+pub const QUANTIZATION_BITS: u16 = 0x3D01;
+pub const CHANNEL_COUNT: u16 = 0x3D07;
+pub const BLOCK_ALIGN: u16 = 0x3D0A;
+
+// index table segment items
+pub const INDEX_EDIT_RATE: u16 = 0x3F0B;
+pub const INDEX_START_POSITION: u16 = 0x3F0C;
+pub const INDEX_DURATION: u16 = 0x3F0D;
+pub const EDIT_UNIT_BYTE_COUNT: u16 = 0x3F05;
+pub const INDEX_ENTRY_ARRAY: u16 = 0x3F0A;
+
+pub const RANDOM_ACCESS: u8 = 0x80;
+
+pub fn is_partition_pack(key: &Ul) -> bool {
+	klv::ul_matches(key, &PARTITION_PACK, 13) && (0x02..=0x04).contains(&key[13])
+}
+
+pub fn is_metadata_set(key: &Ul) -> bool {
+	klv::ul_matches(key, &METADATA_SET, 13) && key[15] == 0x00
+}
+
+pub fn is_essence_element(key: &Ul) -> bool {
+	klv::ul_matches(key, &ESSENCE_ELEMENT, 12)
+}
+
+pub fn essence_track_number(key: &Ul) -> u32 {
+	u32::from_be_bytes([key[12], key[13], key[14], key[15]])
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionKind {
+	Header,
+	Body,
+	Footer,
+}
+
+#[derive(Debug, Clone)]
+pub struct MxfPartition {
+	pub kind: PartitionKind,
+	pub closed: bool,
+	pub complete: bool,
+	pub major_version: u16,
+	pub minor_version: u16,
+	pub kag_size: u32,
+	pub this_partition: u64,
+	pub previous_partition: u64,
+	pub footer_partition: u64,
+	pub header_byte_count: u64,
+	pub index_byte_count: u64,
+	pub index_sid: u32,
+	pub body_offset: u64,
+	pub body_sid: u32,
+	pub operational_pattern: Ul,
+	pub essence_containers: Vec<Ul>,
+}
+
+impl MxfPartition {
+	pub fn parse(key: &Ul, value: &[u8]) -> Result<Self> {
+		if value.len() < 88 {
+			return Err(error!("mxf partition pack is truncated"));
+		}
+		let kind = match key[13] {
+			0x02 => PartitionKind::Header,
+			0x03 => PartitionKind::Body,
+			_ => PartitionKind::Footer,
+		};
+		let u32_at = |at: usize| klv::read_u32(&value[at..]).unwrap_or(0);
+		let u64_at = |at: usize| klv::read_u64(&value[at..]).unwrap_or(0);
+		Ok(Self {
+			kind,
+			// status 1 and 3 are open, 3 and 4 complete
+			closed: key[14].is_multiple_of(2),
+			complete: key[14] >= 3,
+			major_version: klv::read_u16(value).unwrap_or(0),
+			minor_version: klv::read_u16(&value[2..]).unwrap_or(0),
+			kag_size: u32_at(4),
+			this_partition: u64_at(8),
+			previous_partition: u64_at(16),
+			footer_partition: u64_at(24),
+			header_byte_count: u64_at(32),
+			index_byte_count: u64_at(40),
+			index_sid: u32_at(48),
+			body_offset: u64_at(52),
+			body_sid: u32_at(60),
+			operational_pattern: klv::read_ul(&value[64..]).unwrap_or_default(),
+			essence_containers: klv::batch(&value[80..]).filter_map(klv::read_ul).collect(),
+		})
+	}
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MxfPrimer {
+	pub labels: HashMap<u16, Ul>,
+}
+
+impl MxfPrimer {
+	pub fn parse(value: &[u8]) -> Self {
+		let labels = klv::batch(value)
+			.filter_map(|entry| Some((klv::read_u16(entry)?, klv::read_ul(entry.get(2..)?)?)))
+			.collect();
+		Self { labels }
+	}
+
+	pub fn label(&self, tag: u16) -> Option<&Ul> {
+		self.labels.get(&tag)
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct MxfSet {
+	pub kind: u16,
+	pub instance_uid: Ul,
+	pub items: Vec<(u16, Vec<u8>)>,
+}
+
+impl MxfSet {
+	pub fn parse(key: &Ul, value: &[u8]) -> Option<Self> {
+		let items: Vec<(u16, Vec<u8>)> =
+			LocalSet::new(value).map(|(tag, item)| (tag, item.to_vec())).collect();
+		let instance_uid = items
+			.iter()
+			.find(|(tag, _)| *tag == INSTANCE_UID)
+			.and_then(|(_, item)| klv::read_ul(item))?;
+		Some(Self { kind: u16::from_be_bytes([key[13], key[14]]), instance_uid, items })
+	}
+
+	pub fn item(&self, tag: u16) -> Option<&[u8]> {
+		self.items.iter().find(|(item_tag, _)| *item_tag == tag).map(|(_, item)| item.as_slice())
+	}
+
+	pub fn u32(&self, tag: u16) -> Option<u32> {
+		klv::read_u32(self.item(tag)?)
+	}
+
+	pub fn i64(&self, tag: u16) -> Option<i64> {
+		klv::read_u64(self.item(tag)?).map(|value| value as i64)
+	}
+
+	pub fn ul(&self, tag: u16) -> Option<Ul> {
+		klv::read_ul(self.item(tag)?)
+	}
+
+	pub fn rational(&self, tag: u16) -> Option<(i32, i32)> {
+		klv::read_rational(self.item(tag)?).filter(|(num, den)| *num > 0 && *den > 0)
+	}
+
+	pub fn text(&self, tag: u16) -> Option<String> {
+		self.item(tag).map(klv::read_utf16).filter(|text| !text.is_empty())
+	}
+
+	pub fn references(&self, tag: u16) -> Vec<Ul> {
+		self
+			.item(tag)
+			.map(|item| klv::batch(item).filter_map(klv::read_ul).collect())
+			.unwrap_or_default()
+	}
+
+	pub fn umid(&self, tag: u16) -> Option<[u8; 32]> {
+		self.item(tag)?.get(..32)?.try_into().ok()
+	}
+
+	pub fn is_descriptor(&self) -> bool {
+		self.kind == MULTIPLE_DESCRIPTOR || self.essence_kind().is_some()
+	}
+
+	pub fn essence_kind(&self) -> Option<StreamKind> {
+		match self.kind {
+			GENERIC_PICTURE_DESCRIPTOR | CDCI_DESCRIPTOR | RGBA_DESCRIPTOR | MPEG2_VIDEO_DESCRIPTOR => {
+				Some(StreamKind::Video)
+			}
+			GENERIC_SOUND_DESCRIPTOR | AES3_PCM_DESCRIPTOR | WAVE_PCM_DESCRIPTOR => {
+				Some(StreamKind::Audio)
+			}
+			_ => None,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrapping {
+	Frame,
+	Clip,
+	Unknown,
+}
+
+impl Wrapping {
+	/// From a generic container label: the mapping kind picks the byte that
+	/// tells frame from clip wrapping.
+	pub fn from_container(container: &Ul) -> Self {
+		if !klv::ul_matches(container, &ESSENCE_CONTAINER, 13) {
+			return Self::Unknown;
+		}
+		let value = match container[13] {
+			// D-10, always one content package per frame
+			0x01 => 0x01,
+			// uncompressed pictures, where 3 is line wrapping
+			0x05 => container[15] % 4,
+			// BWF and AES3: 1 and 3 frame, 2 and 4 clip
+			0x06 => match container[14] {
+				0x03 | 0x04 => container[14] - 0x02,
+				value => value,
+			},
+			_ => container[15],
+		};
+		match value {
+			0x01 => Self::Frame,
+			0x02 => Self::Clip,
+			_ => Self::Unknown,
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct MxfDescriptor {
+	pub kind: StreamKind,
+	pub linked_track_id: Option<u32>,
+	pub sample_rate: Option<(i32, i32)>,
+	pub container_duration: Option<i64>,
+	pub essence_container: Option<Ul>,
+	pub picture_coding: Option<Ul>,
+	pub width: u32,
+	pub height: u32,
+	pub component_depth: u32,
+	pub audio_sampling_rate: Option<(i32, i32)>,
+	pub channels: u32,
+	pub quantization_bits: u32,
+	pub block_align: u32,
+}
+
+impl MxfDescriptor {
+	pub fn parse(set: &MxfSet) -> Option<Self> {
+		let kind = set.essence_kind()?;
+		let channels = set.u32(CHANNEL_COUNT).unwrap_or(0);
+		let quantization_bits = set.u32(QUANTIZATION_BITS).unwrap_or(0);
+		let block_align = match set.item(BLOCK_ALIGN).and_then(klv::read_u16) {
+			Some(block_align) if block_align > 0 => block_align as u32,
+			_ => channels * quantization_bits.div_ceil(8),
+		};
+		Some(Self {
+			kind,
+			linked_track_id: set.u32(LINKED_TRACK_ID),
+			sample_rate: set.rational(SAMPLE_RATE),
+			container_duration: set.i64(CONTAINER_DURATION),
+			essence_container: set.ul(ESSENCE_CONTAINER_ITEM),
+			picture_coding: set.ul(PICTURE_ESSENCE_CODING),
+			width: set.u32(STORED_WIDTH).unwrap_or(0),
+			height: set.u32(STORED_HEIGHT).unwrap_or(0),
+			component_depth: set.u32(COMPONENT_DEPTH).unwrap_or(0),
+			audio_sampling_rate: set.rational(AUDIO_SAMPLING_RATE),
+			channels,
+			quantization_bits,
+			block_align,
+		})
+	}
+
+	pub fn wrapping(&self) -> Wrapping {
+		self.essence_container.as_ref().map_or(Wrapping::Unknown, Wrapping::from_container)
+	}
+
+	pub fn codec(&self) -> Option<&'static str> {
+		match self.kind {
+			StreamKind::Audio => match self.quantization_bits {
+				16 => Some(audio::PCM_S16LE),
+				24 => Some(audio::PCM_S24LE),
+				_ => None,
+			},
+			_ => {
+				if let Some(coding) =
+					self.picture_coding.filter(|coding| klv::ul_matches(coding, &PICTURE_CODING, 13))
+				{
+					return match coding[13] {
+						0x01..=0x0F => Some(video::MPEG2),
+						0x31 | 0x32 => Some(video::H264),
+						_ => None,
+					};
+				}
+				let container = self
+					.essence_container
+					.filter(|container| klv::ul_matches(container, &ESSENCE_CONTAINER, 13))?;
+				match container[13] {
+					0x01 | 0x04 => Some(video::MPEG2),
+					0x05 => Some(video::RAWVIDEO),
+					0x10 => Some(video::H264),
+					_ => None,
+				}
+			}
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MxfTimecode {
+	/// Frames since midnight.
+	pub start: i64,
+	pub rounded_base: u16,
+	pub drop_frame: bool,
+}
+
+impl MxfTimecode {
+	pub fn parse(set: &MxfSet) -> Option<Self> {
+		Some(Self {
+			start: set.i64(START_TIMECODE)?,
+			rounded_base: set
+				.item(ROUNDED_TIMECODE_BASE)
+				.and_then(klv::read_u16)
+				.filter(|base| *base > 0)?,
+			drop_frame: set.item(DROP_FRAME).and_then(|item| item.first().copied()).unwrap_or(0) != 0,
+		})
+	}
+}
+
+impl std::fmt::Display for MxfTimecode {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let base = self.rounded_base as i64;
+		let mut frames = self.start.max(0);
+		if self.drop_frame {
+			// two frame numbers skipped each minute at 30 fps, but every tenth
+			let dropped = base / 15;
+			let per_ten_minutes = base * 600 - dropped * 9;
+			let per_minute = base * 60 - dropped;
+			let tens = frames / per_ten_minutes;
+			let rest = frames % per_ten_minutes;
+			frames += dropped * 9 * tens;
+			if rest > dropped {
+				frames += dropped * ((rest - dropped) / per_minute);
+			}
+		}
+		let separator = if self.drop_frame { ';' } else { ':' };
+		write!(
+			f,
+			"{:02}:{:02}:{:02}{}{:02}",
+			frames / (base * 3600) % 24,
+			frames / (base * 60) % 60,
+			frames / base % 60,
+			separator,
+			frames % base
+		)
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct MxfTrack {
+	pub id: u32,
+	pub number: u32,
+	pub name: Option<String>,
+	pub edit_rate: (i32, i32),
+	pub origin: i64,
+	pub duration: Option<i64>,
+	pub descriptor: Option<MxfDescriptor>,
+}
+
+impl MxfTrack {
+	pub fn kind(&self) -> Option<StreamKind> {
+		self.descriptor.as_ref().map(|descriptor| descriptor.kind)
+	}
+
+	pub fn codec(&self) -> Option<&'static str> {
+		self.descriptor.as_ref()?.codec()
+	}
+
+	pub fn wrapping(&self) -> Wrapping {
+		self.descriptor.as_ref().map_or(Wrapping::Unknown, MxfDescriptor::wrapping)
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MxfIndexEntry {
+	pub temporal_offset: i8,
+	pub key_frame_offset: i8,
+	pub flags: u8,
+	pub stream_offset: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct MxfIndexSegment {
+	pub edit_rate: Option<(i32, i32)>,
+	pub start_position: i64,
+	pub duration: i64,
+	/// Size of every edit unit when constant, zero when the entries give them.
+	pub edit_unit_byte_count: u32,
+	pub index_sid: u32,
+	pub body_sid: u32,
+	pub entries: Vec<MxfIndexEntry>,
+}
+
+impl MxfIndexSegment {
+	pub fn parse(value: &[u8]) -> Self {
+		let mut segment = Self {
+			edit_rate: None,
+			start_position: 0,
+			duration: 0,
+			edit_unit_byte_count: 0,
+			index_sid: 0,
+			body_sid: 0,
+			entries: Vec::new(),
+		};
+		for (tag, item) in LocalSet::new(value) {
+			match tag {
+				INDEX_EDIT_RATE => segment.edit_rate = klv::read_rational(item),
+				INDEX_START_POSITION => segment.start_position = klv::read_u64(item).unwrap_or(0) as i64,
+				INDEX_DURATION => segment.duration = klv::read_u64(item).unwrap_or(0) as i64,
+				EDIT_UNIT_BYTE_COUNT => segment.edit_unit_byte_count = klv::read_u32(item).unwrap_or(0),
+				INDEX_SID => segment.index_sid = klv::read_u32(item).unwrap_or(0),
+				BODY_SID => segment.body_sid = klv::read_u32(item).unwrap_or(0),
+				INDEX_ENTRY_ARRAY => {
+					segment.entries = klv::batch(item)
+						.filter(|entry| entry.len() >= 11)
+						.map(|entry| MxfIndexEntry {
+							temporal_offset: entry[0] as i8,
+							key_frame_offset: entry[1] as i8,
+							flags: entry[2],
+							stream_offset: klv::read_u64(&entry[3..]).unwrap_or(0),
+						})
+						.collect();
+				}
+				_ => {}
+			}
+		}
+		segment
+	}
+}
+
+/// Whether a coded picture can be decoded on its own, for files without an
+/// index table to say so.
+pub fn is_keyframe(codec: &str, data: &[u8]) -> bool {
+	let mut start_codes = data.windows(4).enumerate().filter(|(_, window)| window[..3] == [0, 0, 1]);
+	match codec {
+		// the picture header's coding type, 1 for intra pictures
+		video::MPEG2 => start_codes
+			.filter(|(_, window)| window[3] == 0x00)
+			.find_map(|(at, _)| data.get(at + 5))
+			.is_some_and(|byte| (byte >> 3) & 0x07 == 1),
+		// an IDR slice
+		video::H264 => start_codes.any(|(_, window)| window[3] & 0x1F == 5),
+		_ => true,
+	}
+}
@@ -0,0 +1,141 @@
+use crate::io::{MediaRead, ReadPrimitives};
+use crate::{error, message::Result};
+
+pub type Ul = [u8; 16];
+
+pub const UL_PREFIX: [u8; 4] = [0x06, 0x0E, 0x2B, 0x34];
+
+pub const MAX_LOADED_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Whether two labels match over their first `length` bytes, ignoring the
+/// registry version byte, which writers fill in differently.
+pub fn ul_matches(ul: &Ul, expected: &[u8], length: usize) -> bool {
+	ul.iter()
+		.zip(expected)
+		.take(length)
+		.enumerate()
+		.all(|(index, (byte, expected))| index == 7 || byte == expected)
+}
+
+pub fn ul_string(ul: &Ul) -> String {
+	ul.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(".")
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Klv {
+	pub key: Ul,
+	pub offset: u64,
+	pub value_offset: u64,
+	pub length: u64,
+}
+
+impl Klv {
+	pub fn read<R: MediaRead>(reader: &mut R, offset: u64) -> Result<Self> {
+		let mut key = [0u8; 16];
+		reader.read_exact(&mut key)?;
+		let (length, size) = read_ber_length(reader)?;
+		Ok(Self { key, offset, value_offset: offset + 16 + size, length })
+	}
+
+	pub fn end(&self) -> Result<u64> {
+		self
+			.value_offset
+			.checked_add(self.length)
+			.ok_or_else(|| error!("mxf klv at offset {} has an invalid length", self.offset))
+	}
+
+	pub fn read_value<R: MediaRead>(&self, reader: &mut R) -> Result<Vec<u8>> {
+		if self.length > MAX_LOADED_SIZE {
+			return Err(error!("mxf klv of {} bytes is too large to load", self.length));
+		}
+		let mut value = vec![0u8; self.length as usize];
+		reader.read_exact(&mut value)?;
+		Ok(value)
+	}
+}
+
+fn read_ber_length<R: MediaRead>(reader: &mut R) -> Result<(u64, u64)> {
+	let first = reader.read_u8()?;
+	if first < 0x80 {
+		return Ok((first as u64, 1));
+	}
+	let size = first & 0x7F;
+	if size == 0 || size > 8 {
+		return Err(error!("mxf ber length of {} bytes is not supported", size));
+	}
+	let mut length = 0u64;
+	for _ in 0..size {
+		length = (length << 8) | reader.read_u8()? as u64;
+	}
+	Ok((length, 1 + size as u64))
+}
+
+/// Iterates the items of a local set value. A truncated last item ends it.
+pub struct LocalSet<'a> {
+	data: &'a [u8],
+}
+
+impl<'a> LocalSet<'a> {
+	pub fn new(data: &'a [u8]) -> Self {
+		Self { data }
+	}
+}
+
+impl<'a> Iterator for LocalSet<'a> {
+	type Item = (u16, &'a [u8]);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.data.len() < 4 {
+			return None;
+		}
+		let tag = u16::from_be_bytes([self.data[0], self.data[1]]);
+		let size = u16::from_be_bytes([self.data[2], self.data[3]]) as usize;
+		let item = self.data.get(4..4 + size)?;
+		self.data = &self.data[4 + size..];
+		Some((tag, item))
+	}
+}
+
+pub fn batch(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+	let (count, size) = match data.get(..8) {
+		Some(header) => (
+			u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize,
+			u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize,
+		),
+		None => (0, 0),
+	};
+	let elements = if size == 0 { &[][..] } else { &data[8..] };
+	elements.chunks_exact(size.max(1)).take(count)
+}
+
+pub fn read_u16(data: &[u8]) -> Option<u16> {
+	Some(u16::from_be_bytes(data.get(..2)?.try_into().ok()?))
+}
+
+pub fn read_u32(data: &[u8]) -> Option<u32> {
+	Some(u32::from_be_bytes(data.get(..4)?.try_into().ok()?))
+}
+
+pub fn read_u64(data: &[u8]) -> Option<u64> {
+	Some(u64::from_be_bytes(data.get(..8)?.try_into().ok()?))
+}
+
+pub fn read_ul(data: &[u8]) -> Option<Ul> {
+	data.get(..16)?.try_into().ok()
+}
+
+pub fn read_rational(data: &[u8]) -> Option<(i32, i32)> {
+	let numerator = read_u32(data)? as i32;
+	let denominator = read_u32(data.get(4..)?)? as i32;
+	Some((numerator, denominator))
+}
+
+/// UTF-16 big-endian text, up to the first nul.
+pub fn read_utf16(data: &[u8]) -> String {
+	let units: Vec<u16> = data
+		.chunks_exact(2)
+		.map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+		.take_while(|unit| *unit != 0)
+		.collect();
+	String::from_utf16_lossy(&units)
+}
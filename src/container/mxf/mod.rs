@@ -0,0 +1,7 @@
+pub mod demuxer;
+pub mod formater;
+pub mod klv;
+pub use demuxer::MxfDemuxer;
+pub use formater::{
+	MxfDescriptor, MxfIndexSegment, MxfPartition, MxfPrimer, MxfTimecode, MxfTrack, Wrapping,
+};
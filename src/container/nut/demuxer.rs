@@ -0,0 +1,276 @@
+use super::formater::{self, NutInfo, NutMainHeader, NutStream, NutSyncpoint};
+use super::vlc::{NutRead, RecordingReader};
+use crate::container::Metadata;
+use crate::core::crc;
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream};
+use crate::io::{MediaRead, ReadPrimitives};
+use crate::{error, message::Result};
+
+pub struct NutDemuxer<R: MediaRead> {
+	reader: R,
+	header: NutMainHeader,
+	nut_streams: Vec<NutStream>,
+	streams: stream::Streams,
+	infos: Vec<NutInfo>,
+	metadata: Metadata,
+	/// Last pts of each stream, that frame headers code theirs against.
+	last_pts: Vec<i64>,
+	selected: Option<u32>,
+}
+
+impl<R: MediaRead> NutDemuxer<R> {
+	pub fn new(mut reader: R) -> Result<Self> {
+		let mut file_id = [0u8; formater::FILE_ID.len()];
+		reader.read_exact(&mut file_id)?;
+		if file_id != formater::FILE_ID {
+			return Err(error!("not a nut file"));
+		}
+
+		let startcode = Self::read_startcode(&mut reader)?;
+		if startcode != formater::MAIN_STARTCODE {
+			return Err(error!("nut file does not start with a main header"));
+		}
+		let header = NutMainHeader::parse(&Self::read_packet_body(&mut reader, startcode)?)?;
+		if header.stream_count == 0 || header.stream_count > u16::MAX as u64 {
+			return Err(error!("nut file has {} streams", header.stream_count));
+		}
+
+		let mut nut_streams: Vec<Option<NutStream>> = vec![None; header.stream_count as usize];
+		let mut infos = Vec::new();
+		loop {
+			let startcode = Self::read_startcode(&mut reader)?;
+			if startcode == formater::SYNCPOINT_STARTCODE {
+				break;
+			}
+			if (startcode >> 56) as u8 != formater::STARTCODE_BYTE {
+				return Err(error!("nut frame before the first syncpoint"));
+			}
+			let body = Self::read_packet_body(&mut reader, startcode)?;
+			match startcode {
+				formater::STREAM_STARTCODE => {
+					let stream = NutStream::parse(&body, &header.time_bases)?;
+					let Some(slot) = nut_streams.get_mut(stream.id as usize) else {
+						return Err(error!("nut stream id {} is out of range", stream.id));
+					};
+					*slot = Some(stream);
+				}
+				formater::INFO_STARTCODE => infos.push(NutInfo::parse(&body, header.time_bases.len())?),
+				_ => {}
+			}
+		}
+		let nut_streams = nut_streams
+			.into_iter()
+			.enumerate()
+			.map(|(id, stream)| stream.ok_or_else(|| error!("nut stream {} has no header", id)))
+			.collect::<Result<Vec<_>>>()?;
+
+		let mut streams = stream::Streams::new_empty();
+		for nut_stream in &nut_streams {
+			if let Some(stream) = nut_stream.to_stream(streams.all().len()) {
+				streams.add(stream);
+			}
+		}
+		let mut metadata = Metadata::new();
+		for info in infos.iter().filter(|info| info.stream_id.is_none() && info.chapter_id == 0) {
+			info.to_metadata(&mut metadata);
+		}
+
+		let mut demuxer = Self {
+			reader,
+			last_pts: vec![0; nut_streams.len()],
+			header,
+			nut_streams,
+			streams,
+			infos,
+			metadata,
+			selected: None,
+		};
+		let body = Self::read_packet_body(&mut demuxer.reader, formater::SYNCPOINT_STARTCODE)?;
+		demuxer.apply_syncpoint(&body)?;
+		Ok(demuxer)
+	}
+
+	pub fn select_stream(&mut self, id: u32) -> Result<()> {
+		if !self.streams.all().iter().any(|stream| stream.id == id) {
+			return Err(error!("nut file has no stream {}", id));
+		}
+		self.selected = Some(id);
+		Ok(())
+	}
+
+	fn read_startcode(reader: &mut R) -> Result<u64> {
+		reader.read_u64_be()
+	}
+
+	fn read_packet_body(reader: &mut R, startcode: u64) -> Result<Vec<u8>> {
+		let mut header = RecordingReader::new(reader, startcode.to_be_bytes().to_vec());
+		let forward_ptr = header.read_v()?;
+		if forward_ptr > formater::HEADER_CHECKSUM_THRESHOLD {
+			let expected = crc::crc32_nut(&header.bytes);
+			if header.read_u32()? != expected {
+				return Err(error!("nut packet header {:016x} fails its checksum", startcode));
+			}
+		}
+		if !(4..=formater::MAX_PACKET_SIZE).contains(&forward_ptr) {
+			return Err(error!("nut packet of {} bytes is not supported", forward_ptr));
+		}
+
+		let mut body = vec![0u8; forward_ptr as usize];
+		reader.read_exact(&mut body)?;
+		let checksum = body.split_off(body.len() - 4);
+		if crc::crc32_nut(&body).to_be_bytes() != checksum[..] {
+			return Err(error!("nut packet {:016x} fails its checksum", startcode));
+		}
+		Ok(body)
+	}
+
+	/// Every stream's last pts becomes the syncpoint's timestamp.
+	fn apply_syncpoint(&mut self, body: &[u8]) -> Result<()> {
+		let syncpoint = NutSyncpoint::parse(body)?;
+		let (pts, time_base_id) = syncpoint.timestamp(self.header.time_bases.len());
+		let time = self.header.time_bases[time_base_id];
+		for (last_pts, stream) in self.last_pts.iter_mut().zip(&self.nut_streams) {
+			*last_pts = formater::convert_ts(pts, time, stream.time);
+		}
+		Ok(())
+	}
+
+	fn read_frame(&mut self, code: u8) -> Result<Option<Packet>> {
+		let frame_code = self.header.frame_codes[code as usize];
+		if frame_code.flags & formater::FLAG_INVALID != 0 {
+			return Err(error!("nut frame code {} is invalid", code));
+		}
+		let mut reader = RecordingReader::new(&mut self.reader, vec![code]);
+		let mut flags = frame_code.flags;
+		if flags & formater::FLAG_CODED != 0 {
+			flags ^= reader.read_v()?;
+		}
+		let stream_id = match flags & formater::FLAG_STREAM_ID {
+			0 => frame_code.stream_id,
+			_ => reader.read_v()?,
+		};
+		let Some(stream) = self.nut_streams.get(stream_id as usize) else {
+			return Err(error!("nut frame of missing stream {}", stream_id));
+		};
+		let last_pts = self.last_pts[stream_id as usize];
+		let pts = match flags & formater::FLAG_CODED_PTS {
+			0 => last_pts + frame_code.pts_delta,
+			_ => {
+				let coded = reader.read_v()?;
+				let msb = 1u64 << stream.msb_pts_shift;
+				match coded.checked_sub(msb) {
+					Some(full) => full as i64,
+					None => formater::lsb_to_full(last_pts, coded, stream.msb_pts_shift),
+				}
+			}
+		};
+		let size_msb = match flags & formater::FLAG_SIZE_MSB {
+			0 => 0,
+			_ => reader.read_v()?,
+		};
+		if flags & formater::FLAG_MATCH_TIME != 0 {
+			reader.read_s()?;
+		}
+		let header_index = match flags & formater::FLAG_HEADER_IDX {
+			0 => frame_code.header_index,
+			_ => reader.read_v()?,
+		};
+		let reserved_count = match flags & formater::FLAG_RESERVED {
+			0 => frame_code.reserved_count,
+			_ => reader.read_v()?,
+		};
+		for _ in 0..reserved_count {
+			reader.read_v()?;
+		}
+		if flags & formater::FLAG_CHECKSUM != 0 {
+			let expected = crc::crc32_nut(&reader.bytes);
+			if reader.read_u32()? != expected {
+				return Err(error!("nut frame header of stream {} fails its checksum", stream_id));
+			}
+		}
+		if flags & formater::FLAG_SM_DATA != 0 {
+			return Err(error!("nut frames with side data are not supported"));
+		}
+
+		let size = size_msb
+			.checked_mul(frame_code.size_mul)
+			.and_then(|size| size.checked_add(frame_code.size_lsb))
+			.filter(|size| *size <= formater::MAX_PACKET_SIZE)
+			.ok_or_else(|| error!("nut frame of stream {} is too large", stream_id))?;
+		// the coded size counts the elided bytes, and larger frames never elide
+		let header_index = if size > 4096 { 0 } else { header_index };
+		let Some(elision_header) = self.header.elision_headers.get(header_index as usize) else {
+			return Err(error!("nut frame uses missing elision header {}", header_index));
+		};
+		let Some(stored) = size.checked_sub(elision_header.len() as u64) else {
+			return Err(error!("nut frame of stream {} is smaller than its elision header", stream_id));
+		};
+		let mut data = elision_header.clone();
+		let start = data.len();
+		data.resize(start + stored as usize, 0);
+		self.reader.read_exact(&mut data[start..])?;
+		self.last_pts[stream_id as usize] = pts;
+
+		let stream_id = stream_id as u32;
+		let returned = self.selected.is_none_or(|selected| selected == stream_id);
+		if flags & formater::FLAG_EOR != 0 || stream.kind().is_none() || !returned {
+			return Ok(None);
+		}
+		let packet = Packet::new(data, stream_id, stream.time)
+			.with_pts(pts)
+			.with_dts(pts)
+			.with_keyframe(flags & formater::FLAG_KEY != 0);
+		Ok(Some(packet))
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		loop {
+			let mut byte = [0u8; 1];
+			if self.reader.read(&mut byte)? == 0 {
+				return Ok(None);
+			}
+			if byte[0] != formater::STARTCODE_BYTE {
+				match self.read_frame(byte[0])? {
+					Some(packet) => return Ok(Some(packet)),
+					None => continue,
+				}
+			}
+
+			let mut rest = [0u8; 7];
+			self.reader.read_exact(&mut rest)?;
+			let mut startcode = [byte[0]; 8];
+			startcode[1..].copy_from_slice(&rest);
+			let startcode = u64::from_be_bytes(startcode);
+			let body = Self::read_packet_body(&mut self.reader, startcode)?;
+			if startcode == formater::SYNCPOINT_STARTCODE {
+				self.apply_syncpoint(&body)?;
+			}
+		}
+	}
+
+	pub fn main_header(&self) -> &NutMainHeader {
+		&self.header
+	}
+
+	pub fn nut_streams(&self) -> &[NutStream] {
+		&self.nut_streams
+	}
+
+	pub fn infos(&self) -> &[NutInfo] {
+		&self.infos
+	}
+
+	pub fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+}
+
+impl<R: MediaRead> Demuxer for NutDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
@@ -0,0 +1,687 @@
+use super::vlc::{self, NutRead, PacketReader};
+use crate::codecs::{self, audio, subtitle, video};
+use crate::container::Metadata;
+use crate::core::crc;
+use crate::core::frame::VideoFormat;
+use crate::core::stream::{Stream, StreamKind};
+use crate::core::time::Time;
+use crate::{error, message::Result};
+
+pub const FILE_ID: &[u8] = b"nut/multimedia container\0";
+
+const fn startcode(code: u64, tag: [u8; 2]) -> u64 {
+	code + (((tag[0] as u64) << 8 | tag[1] as u64) << 48)
+}
+
+pub const MAIN_STARTCODE: u64 = startcode(0x7A56_1F5F_04AD, *b"NM");
+pub const STREAM_STARTCODE: u64 = startcode(0x1140_5BF2_F9DB, *b"NS");
+pub const SYNCPOINT_STARTCODE: u64 = startcode(0xE4AD_EECA_4569, *b"NK");
+pub const INDEX_STARTCODE: u64 = startcode(0xDD67_2F23_E64E, *b"NX");
+pub const INFO_STARTCODE: u64 = startcode(0xAB68_B596_BA78, *b"NI");
+
+/// First byte of every startcode; the frame code of the same value is invalid.
+pub const STARTCODE_BYTE: u8 = b'N';
+
+/// Packets with a larger forward pointer also checksum their header.
+pub const HEADER_CHECKSUM_THRESHOLD: u64 = 4096;
+pub const MAX_PACKET_SIZE: u64 = 64 * 1024 * 1024;
+
+pub const VERSION: u64 = 3;
+pub const MAX_VERSION: u64 = 4;
+
+pub const FLAG_KEY: u64 = 1;
+pub const FLAG_EOR: u64 = 2;
+pub const FLAG_CODED_PTS: u64 = 8;
+pub const FLAG_STREAM_ID: u64 = 16;
+pub const FLAG_SIZE_MSB: u64 = 32;
+pub const FLAG_CHECKSUM: u64 = 64;
+pub const FLAG_RESERVED: u64 = 128;
+pub const FLAG_SM_DATA: u64 = 256;
+pub const FLAG_HEADER_IDX: u64 = 1024;
+pub const FLAG_MATCH_TIME: u64 = 2048;
+pub const FLAG_CODED: u64 = 4096;
+pub const FLAG_INVALID: u64 = 8192;
+
+pub const STREAM_FLAG_FIXED_FPS: u64 = 1;
+
+pub const CLASS_VIDEO: u64 = 0;
+pub const CLASS_AUDIO: u64 = 1;
+pub const CLASS_SUBTITLE: u64 = 2;
+pub const CLASS_USER_DATA: u64 = 3;
+
+pub const MAX_DISTANCE: u64 = 32768;
+pub const MSB_PTS_SHIFT: u64 = 7;
+
+const STREAM_FRAME_CODES: u64 = 16;
+
+/// Uncompressed pictures, named after their layout: plane count, chroma
+/// subsampling as log2 shifts, then bits per component.
+const VIDEO_FOURCCS: [(VideoFormat, [u8; 4]); 15] = [
+	(VideoFormat::YUV420, [b'Y', b'3', 11, 8]),
+	(VideoFormat::YUV422, [b'Y', b'3', 10, 8]),
+	(VideoFormat::YUV444, [b'Y', b'3', 0, 8]),
+	(VideoFormat::GRAY8, [b'Y', b'1', 0, 8]),
+	(VideoFormat::YUV420P10, [b'Y', b'3', 11, 10]),
+	(VideoFormat::YUV422P10, [b'Y', b'3', 10, 10]),
+	(VideoFormat::YUV444P10, [b'Y', b'3', 0, 10]),
+	(VideoFormat::YUV420P12, [b'Y', b'3', 11, 12]),
+	(VideoFormat::YUV422P12, [b'Y', b'3', 10, 12]),
+	(VideoFormat::YUV444P12, [b'Y', b'3', 0, 12]),
+	(VideoFormat::YUV420P16, [b'Y', b'3', 11, 16]),
+	(VideoFormat::YUV422P16, [b'Y', b'3', 10, 16]),
+	(VideoFormat::YUV444P16, [b'Y', b'3', 0, 16]),
+	(VideoFormat::GRAY16, [b'Y', b'1', 0, 16]),
+	(VideoFormat::RGB24, [b'R', b'G', b'B', 24]),
+];
+
+const AUDIO_FOURCCS: [(&str, [u8; 4]); 3] = [
+	(audio::PCM_S16LE, [b'P', b'S', b'D', 16]),
+	(audio::PCM_S24LE, [b'P', b'S', b'D', 24]),
+	(audio::PCM_F32LE, [b'P', b'F', b'D', 32]),
+];
+
+/// Written with the first fourcc of a codec, read with any.
+const SUBTITLE_FOURCCS: [(&str, [u8; 4]); 3] =
+	[(subtitle::SRT, *b"UTF8"), (subtitle::ASS, *b"SSA\0"), (subtitle::ASS, *b"ASS\0")];
+
+const INFO_NAMES: [(&str, &str); 5] = [
+	("title", "Title"),
+	("artist", "Author"),
+	("copyright", "Copyright"),
+	("comment", "Description"),
+	("encoder", "Encoder"),
+];
+
+pub fn packet(startcode: u64, body: &[u8]) -> Vec<u8> {
+	let forward_ptr = body.len() as u64 + 4;
+	let mut out = Vec::with_capacity(body.len() + 24);
+	out.extend_from_slice(&startcode.to_be_bytes());
+	vlc::write_v(&mut out, forward_ptr);
+	if forward_ptr > HEADER_CHECKSUM_THRESHOLD {
+		let checksum = crc::crc32_nut(&out);
+		out.extend_from_slice(&checksum.to_be_bytes());
+	}
+	out.extend_from_slice(body);
+	out.extend_from_slice(&crc::crc32_nut(body).to_be_bytes());
+	out
+}
+
+/// The full pts nearest `last` whose lowest `msb_pts_shift` bits are `coded`.
+pub fn lsb_to_full(last: i64, coded: u64, msb_pts_shift: u64) -> i64 {
+	let mask = (1i64 << msb_pts_shift) - 1;
+	let delta = last - mask / 2;
+	((coded as i64 - delta) & mask) + delta
+}
+
+pub fn convert_ts(value: i64, from: Time, to: Time) -> i64 {
+	let numerator = value as i128 * from.num as i128 * to.den as i128;
+	let denominator = from.den as i128 * to.num as i128;
+	numerator.div_euclid(denominator) as i64
+}
+
+fn read_time_base<R: NutRead>(reader: &mut R) -> Result<Time> {
+	let num = reader.read_v()?;
+	let den = reader.read_v()?;
+	if num == 0 || den == 0 || num > u32::MAX as u64 || den > u32::MAX as u64 {
+		return Err(error!("nut time base {}/{} is invalid", num, den));
+	}
+	Ok(Time::new(num as u32, den as u32))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameCode {
+	pub flags: u64,
+	pub stream_id: u64,
+	pub size_mul: u64,
+	pub size_lsb: u64,
+	pub pts_delta: i64,
+	pub reserved_count: u64,
+	pub header_index: u64,
+}
+
+impl FrameCode {
+	const INVALID: Self = Self {
+		flags: FLAG_INVALID,
+		stream_id: 0,
+		size_mul: 1,
+		size_lsb: 0,
+		pts_delta: 0,
+		reserved_count: 0,
+		header_index: 0,
+	};
+
+	const CODED: Self =
+		Self { flags: FLAG_CODED | FLAG_STREAM_ID | FLAG_CODED_PTS | FLAG_SIZE_MSB, ..Self::INVALID };
+
+	/// Whether `next` continues a run of codes that only count up the size.
+	fn follows(&self, next: &Self, count: u64) -> bool {
+		Self { size_lsb: self.size_lsb + count, ..*self } == *next
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct NutMainHeader {
+	pub version: u64,
+	pub minor_version: u64,
+	pub stream_count: u64,
+	pub max_distance: u64,
+	pub time_bases: Vec<Time>,
+	pub frame_codes: Vec<FrameCode>,
+	/// Bytes stripped from the front of frames; the first is always empty.
+	pub elision_headers: Vec<Vec<u8>>,
+	pub flags: u64,
+}
+
+impl NutMainHeader {
+	/// The header the muxer writes: one time base per distinct stream time
+	/// base, frame codes for each stream's keyframes with sizes coded in two
+	/// parts, and codes that code everything for all other frames.
+	pub fn new(streams: &[NutStream]) -> Self {
+		let mut time_bases: Vec<Time> = Vec::new();
+		for stream in streams {
+			if !time_bases.contains(&stream.time) {
+				time_bases.push(stream.time);
+			}
+		}
+
+		let mut frame_codes = vec![FrameCode::CODED; 256];
+		frame_codes[STARTCODE_BYTE as usize] = FrameCode::INVALID;
+		let mut code = 1;
+		for stream in streams {
+			// fixed frame rate pictures step their pts by one
+			let (flags, pts_delta) = match stream.flags & STREAM_FLAG_FIXED_FPS {
+				0 => (FLAG_KEY | FLAG_SIZE_MSB | FLAG_CODED_PTS, 0),
+				_ => (FLAG_KEY | FLAG_SIZE_MSB, 1),
+			};
+			for size_lsb in 0..STREAM_FRAME_CODES {
+				if code == STARTCODE_BYTE as usize {
+					code += 1;
+				}
+				if code == 256 {
+					break;
+				}
+				frame_codes[code] = FrameCode {
+					flags,
+					stream_id: stream.id as u64,
+					size_mul: STREAM_FRAME_CODES,
+					size_lsb,
+					pts_delta,
+					reserved_count: 0,
+					header_index: 0,
+				};
+				code += 1;
+			}
+		}
+		// the codes left over code any frame, their sizes spread like above
+		let rest: Vec<usize> = (code..256).filter(|code| *code != STARTCODE_BYTE as usize).collect();
+		for (size_lsb, code) in rest.iter().enumerate() {
+			frame_codes[*code] =
+				FrameCode { size_mul: rest.len() as u64, size_lsb: size_lsb as u64, ..FrameCode::CODED };
+		}
+
+		Self {
+			version: VERSION,
+			minor_version: 0,
+			stream_count: streams.len() as u64,
+			max_distance: MAX_DISTANCE,
+			time_bases,
+			frame_codes,
+			elision_headers: vec![Vec::new()],
+			flags: 0,
+		}
+	}
+
+	pub fn parse(body: &[u8]) -> Result<Self> {
+		let mut reader = PacketReader::new(body);
+		let version = reader.read_v()?;
+		if !(2..=MAX_VERSION).contains(&version) {
+			return Err(error!("nut version {} is not supported", version));
+		}
+		let minor_version = if version > 3 { reader.read_v()? } else { 0 };
+		let stream_count = reader.read_v()?;
+		let max_distance = reader.read_v()?;
+		let time_base_count = reader.read_v()?;
+		if time_base_count == 0 || time_base_count > u16::MAX as u64 {
+			return Err(error!("nut main header has {} time bases", time_base_count));
+		}
+		let time_bases =
+			(0..time_base_count).map(|_| read_time_base(&mut reader)).collect::<Result<Vec<_>>>()?;
+
+		let mut frame_codes = vec![FrameCode::INVALID; 256];
+		let mut template = FrameCode::INVALID;
+		let mut code = 0;
+		while code < 256 {
+			template.flags = reader.read_v()?;
+			let fields = reader.read_v()?;
+			if fields > 0 {
+				template.pts_delta = reader.read_s()?;
+			}
+			if fields > 1 {
+				template.size_mul = reader.read_v()?;
+			}
+			if fields > 2 {
+				template.stream_id = reader.read_v()?;
+			}
+			template.size_lsb = if fields > 3 { reader.read_v()? } else { 0 };
+			template.reserved_count = if fields > 4 { reader.read_v()? } else { 0 };
+			let count = match fields {
+				0..=5 => template.size_mul.saturating_sub(template.size_lsb),
+				_ => reader.read_v()?,
+			};
+			if fields > 6 {
+				// match time deltas only matter to seeking
+				reader.read_s()?;
+			}
+			if fields > 7 {
+				template.header_index = reader.read_v()?;
+			}
+			for _ in 8..fields {
+				reader.read_v()?;
+			}
+			if count == 0 || template.size_mul == 0 {
+				return Err(error!("nut frame code table is invalid at code {}", code));
+			}
+
+			let mut index = 0;
+			while index < count && code < 256 {
+				if code == STARTCODE_BYTE as usize {
+					code += 1;
+					continue;
+				}
+				frame_codes[code] = FrameCode { size_lsb: template.size_lsb + index, ..template };
+				index += 1;
+				code += 1;
+			}
+		}
+
+		let mut elision_headers = vec![Vec::new()];
+		let mut flags = 0;
+		if version > 3 {
+			let header_count = reader.read_v()? + 1;
+			if header_count > 128 {
+				return Err(error!("nut main header has {} elision headers", header_count));
+			}
+			for _ in 1..header_count {
+				let size = reader.read_v()?;
+				if size > 255 {
+					return Err(error!("nut elision header of {} bytes is too long", size));
+				}
+				elision_headers.push((0..size).map(|_| reader.read_byte()).collect::<Result<_>>()?);
+			}
+			flags = reader.read_v()?;
+		}
+		for (code, frame_code) in frame_codes.iter().enumerate() {
+			if frame_code.header_index as usize >= elision_headers.len() {
+				return Err(error!("nut frame code {} uses a missing elision header", code));
+			}
+		}
+
+		Ok(Self {
+			version,
+			minor_version,
+			stream_count,
+			max_distance,
+			time_bases,
+			frame_codes,
+			elision_headers,
+			flags,
+		})
+	}
+
+	pub fn write(&self) -> Vec<u8> {
+		let mut out = Vec::new();
+		vlc::write_v(&mut out, self.version);
+		vlc::write_v(&mut out, self.stream_count);
+		vlc::write_v(&mut out, self.max_distance);
+		vlc::write_v(&mut out, self.time_bases.len() as u64);
+		for time in &self.time_bases {
+			vlc::write_v(&mut out, time.num as u64);
+			vlc::write_v(&mut out, time.den as u64);
+		}
+
+		let codes: Vec<&FrameCode> = self
+			.frame_codes
+			.iter()
+			.enumerate()
+			.filter(|(code, _)| *code != STARTCODE_BYTE as usize)
+			.map(|(_, frame_code)| frame_code)
+			.collect();
+		let mut start = 0;
+		while start < codes.len() {
+			let first = codes[start];
+			let count = codes[start..]
+				.iter()
+				.enumerate()
+				.take_while(|(offset, next)| first.follows(next, *offset as u64))
+				.count();
+			vlc::write_v(&mut out, first.flags);
+			vlc::write_v(&mut out, 6);
+			vlc::write_s(&mut out, first.pts_delta);
+			vlc::write_v(&mut out, first.size_mul);
+			vlc::write_v(&mut out, first.stream_id);
+			vlc::write_v(&mut out, first.size_lsb);
+			vlc::write_v(&mut out, first.reserved_count);
+			vlc::write_v(&mut out, count as u64);
+			start += count;
+		}
+		out
+	}
+
+	pub fn time_base_id(&self, time: Time) -> Option<usize> {
+		self.time_bases.iter().position(|base| *base == time)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NutVideo {
+	pub width: u64,
+	pub height: u64,
+	pub sample_width: u64,
+	pub sample_height: u64,
+	pub colorspace: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NutAudio {
+	pub sample_rate_num: u64,
+	pub sample_rate_den: u64,
+	pub channels: u64,
+}
+
+impl NutAudio {
+	pub fn sample_rate(&self) -> u32 {
+		(self.sample_rate_num / self.sample_rate_den.max(1)) as u32
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct NutStream {
+	pub id: u32,
+	pub class: u64,
+	pub fourcc: Vec<u8>,
+	pub time: Time,
+	pub msb_pts_shift: u64,
+	pub max_pts_distance: u64,
+	pub decode_delay: u64,
+	pub flags: u64,
+	pub codec_private: Vec<u8>,
+	pub video: Option<NutVideo>,
+	pub audio: Option<NutAudio>,
+}
+
+impl NutStream {
+	fn new(class: u64, fourcc: &[u8], time: Time) -> Self {
+		Self {
+			id: 0,
+			class,
+			fourcc: fourcc.to_vec(),
+			time,
+			msb_pts_shift: MSB_PTS_SHIFT,
+			// about a second
+			max_pts_distance: (time.den / time.num).max(1) as u64,
+			decode_delay: 0,
+			flags: 0,
+			codec_private: Vec::new(),
+			video: None,
+			audio: None,
+		}
+	}
+
+	pub fn raw_video(format: VideoFormat, width: u32, height: u32, time: Time) -> Result<Self> {
+		let Some((_, fourcc)) = VIDEO_FOURCCS.iter().find(|(known, _)| *known == format) else {
+			return Err(error!("'{}' pixel format cannot be written to nut", format.name()));
+		};
+		let mut stream = Self::new(CLASS_VIDEO, fourcc, time);
+		stream.flags = STREAM_FLAG_FIXED_FPS;
+		stream.video = Some(NutVideo {
+			width: width as u64,
+			height: height as u64,
+			sample_width: 0,
+			sample_height: 0,
+			colorspace: 0,
+		});
+		Ok(stream)
+	}
+
+	pub fn audio(codec: &str, channels: u8, sample_rate: u32) -> Result<Self> {
+		let Some((_, fourcc)) = AUDIO_FOURCCS.iter().find(|(known, _)| *known == codec) else {
+			return Err(error!("'{}' audio cannot be written to nut", codec));
+		};
+		if channels == 0 || sample_rate == 0 {
+			return Err(error!("nut audio needs channels and a sample rate"));
+		}
+		let mut stream = Self::new(CLASS_AUDIO, fourcc, Time::new(1, sample_rate));
+		stream.audio = Some(NutAudio {
+			sample_rate_num: sample_rate as u64,
+			sample_rate_den: 1,
+			channels: channels as u64,
+		});
+		Ok(stream)
+	}
+
+	pub fn subtitle(codec: &str, time: Time, codec_private: Vec<u8>) -> Result<Self> {
+		let Some((_, fourcc)) = SUBTITLE_FOURCCS.iter().find(|(known, _)| *known == codec) else {
+			return Err(error!("'{}' subtitles cannot be written to nut", codec));
+		};
+		let mut stream = Self::new(CLASS_SUBTITLE, fourcc, time);
+		stream.codec_private = codec_private;
+		Ok(stream)
+	}
+
+	pub fn parse(body: &[u8], time_bases: &[Time]) -> Result<Self> {
+		let mut reader = PacketReader::new(body);
+		let id = reader.read_v()?;
+		let class = reader.read_v()?;
+		let fourcc = reader.read_vb()?;
+		let time_base_id = reader.read_v()?;
+		let Some(time) = time_bases.get(time_base_id as usize).copied() else {
+			return Err(error!("nut stream {} uses missing time base {}", id, time_base_id));
+		};
+		let msb_pts_shift = reader.read_v()?;
+		if msb_pts_shift >= 62 {
+			return Err(error!("nut stream {} has msb_pts_shift {}", id, msb_pts_shift));
+		}
+		let max_pts_distance = reader.read_v()?;
+		let decode_delay = reader.read_v()?;
+		let flags = reader.read_v()?;
+		let codec_private = reader.read_vb()?;
+
+		let mut stream = Self {
+			id: id as u32,
+			class,
+			fourcc,
+			time,
+			msb_pts_shift,
+			max_pts_distance,
+			decode_delay,
+			flags,
+			codec_private,
+			video: None,
+			audio: None,
+		};
+		match class {
+			CLASS_VIDEO => {
+				stream.video = Some(NutVideo {
+					width: reader.read_v()?,
+					height: reader.read_v()?,
+					sample_width: reader.read_v()?,
+					sample_height: reader.read_v()?,
+					colorspace: reader.read_v()?,
+				});
+			}
+			CLASS_AUDIO => {
+				stream.audio = Some(NutAudio {
+					sample_rate_num: reader.read_v()?,
+					sample_rate_den: reader.read_v()?,
+					channels: reader.read_v()?,
+				});
+			}
+			_ => {}
+		}
+		Ok(stream)
+	}
+
+	pub fn write(&self, time_base_id: usize) -> Vec<u8> {
+		let mut out = Vec::new();
+		vlc::write_v(&mut out, self.id as u64);
+		vlc::write_v(&mut out, self.class);
+		vlc::write_vb(&mut out, &self.fourcc);
+		vlc::write_v(&mut out, time_base_id as u64);
+		vlc::write_v(&mut out, self.msb_pts_shift);
+		vlc::write_v(&mut out, self.max_pts_distance);
+		vlc::write_v(&mut out, self.decode_delay);
+		vlc::write_v(&mut out, self.flags);
+		vlc::write_vb(&mut out, &self.codec_private);
+		if let Some(video) = &self.video {
+			for value in
+				[video.width, video.height, video.sample_width, video.sample_height, video.colorspace]
+			{
+				vlc::write_v(&mut out, value);
+			}
+		}
+		if let Some(audio) = &self.audio {
+			for value in [audio.sample_rate_num, audio.sample_rate_den, audio.channels] {
+				vlc::write_v(&mut out, value);
+			}
+		}
+		out
+	}
+
+	pub fn kind(&self) -> Option<StreamKind> {
+		match self.class {
+			CLASS_VIDEO => Some(StreamKind::Video),
+			CLASS_AUDIO => Some(StreamKind::Audio),
+			CLASS_SUBTITLE => Some(StreamKind::Subtitle),
+			_ => None,
+		}
+	}
+
+	pub fn video_format(&self) -> Option<VideoFormat> {
+		VIDEO_FOURCCS.iter().find(|(_, fourcc)| self.fourcc == fourcc).map(|(format, _)| *format)
+	}
+
+	pub fn codec(&self) -> &'static str {
+		let known = match self.class {
+			CLASS_VIDEO => self.video_format().map(|_| video::RAWVIDEO),
+			CLASS_AUDIO => {
+				AUDIO_FOURCCS.iter().find(|(_, fourcc)| self.fourcc == fourcc).map(|(codec, _)| *codec)
+			}
+			CLASS_SUBTITLE => {
+				SUBTITLE_FOURCCS.iter().find(|(_, fourcc)| self.fourcc == fourcc).map(|(codec, _)| *codec)
+			}
+			_ => None,
+		};
+		known.unwrap_or(codecs::UNKNOWN)
+	}
+
+	pub fn to_stream(&self, index: usize) -> Option<Stream> {
+		let stream = Stream::new(self.id, index, self.kind()?, self.codec().to_string(), self.time);
+		Some(stream.with_codec_private(self.codec_private.clone()))
+	}
+}
+
+/// Global, stream or chapter information; values other than text are kept
+/// as their decimal or `num/den` form.
+#[derive(Debug, Clone)]
+pub struct NutInfo {
+	pub stream_id: Option<u64>,
+	pub chapter_id: i64,
+	pub chapter_start: u64,
+	pub chapter_length: u64,
+	pub fields: Vec<(String, String)>,
+}
+
+impl NutInfo {
+	pub fn global(metadata: &Metadata) -> Self {
+		let fields = metadata
+			.iter()
+			.map(|(key, value)| {
+				let name = INFO_NAMES.iter().find(|(known, _)| *known == key).map_or(key, |(_, name)| name);
+				(name.to_string(), value.to_string())
+			})
+			.collect();
+		Self { stream_id: None, chapter_id: 0, chapter_start: 0, chapter_length: 0, fields }
+	}
+
+	pub fn parse(body: &[u8], time_base_count: usize) -> Result<Self> {
+		let mut reader = PacketReader::new(body);
+		let stream_id = reader.read_v()?.checked_sub(1);
+		let chapter_id = reader.read_s()?;
+		let chapter_start = reader.read_v()?;
+		let chapter_length = reader.read_v()?;
+		let count = reader.read_v()?;
+
+		let mut fields = Vec::new();
+		for _ in 0..count {
+			let name = String::from_utf8_lossy(&reader.read_vb()?).into_owned();
+			let value = match reader.read_s()? {
+				-1 => Some(String::from_utf8_lossy(&reader.read_vb()?).into_owned()),
+				-2 => {
+					let kind = reader.read_vb()?;
+					let value = reader.read_vb()?;
+					// binary values, cover art and the like, are dropped
+					(kind == b"UTF-8").then(|| String::from_utf8_lossy(&value).into_owned())
+				}
+				-3 => Some(reader.read_s()?.to_string()),
+				-4 => Some((reader.read_v()? / time_base_count as u64).to_string()),
+				denominator if denominator < -4 => {
+					Some(format!("{}/{}", reader.read_s()?, -denominator - 4))
+				}
+				value => Some(value.to_string()),
+			};
+			if let Some(value) = value {
+				fields.push((name, value));
+			}
+		}
+		Ok(Self { stream_id, chapter_id, chapter_start, chapter_length, fields })
+	}
+
+	pub fn write(&self) -> Vec<u8> {
+		let mut out = Vec::new();
+		vlc::write_v(&mut out, self.stream_id.map_or(0, |id| id + 1));
+		vlc::write_s(&mut out, self.chapter_id);
+		vlc::write_v(&mut out, self.chapter_start);
+		vlc::write_v(&mut out, self.chapter_length);
+		vlc::write_v(&mut out, self.fields.len() as u64);
+		for (name, value) in &self.fields {
+			vlc::write_vb(&mut out, name.as_bytes());
+			vlc::write_s(&mut out, -1);
+			vlc::write_vb(&mut out, value.as_bytes());
+		}
+		out
+	}
+
+	pub fn to_metadata(&self, metadata: &mut Metadata) {
+		for (name, value) in &self.fields {
+			let key =
+				INFO_NAMES.iter().find(|(_, known)| known == name).map_or(name.as_str(), |(key, _)| key);
+			metadata.set(key, value.clone());
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NutSyncpoint {
+	pub global_key_pts: u64,
+	/// Distance back to the syncpoint to start decoding from, in 16 bytes.
+	pub back_ptr_div16: u64,
+}
+
+impl NutSyncpoint {
+	pub fn parse(body: &[u8]) -> Result<Self> {
+		let mut reader = PacketReader::new(body);
+		Ok(Self { global_key_pts: reader.read_v()?, back_ptr_div16: reader.read_v()? })
+	}
+
+	pub fn write(&self) -> Vec<u8> {
+		let mut out = Vec::new();
+		vlc::write_v(&mut out, self.global_key_pts);
+		vlc::write_v(&mut out, self.back_ptr_div16);
+		out
+	}
+
+	pub fn timestamp(&self, time_base_count: usize) -> (i64, usize) {
+		let count = time_base_count as u64;
+		((self.global_key_pts / count) as i64, (self.global_key_pts % count) as usize)
+	}
+}
@@ -0,0 +1,7 @@
+pub mod demuxer;
+pub mod formater;
+pub mod muxer;
+pub mod vlc;
+pub use demuxer::NutDemuxer;
+pub use formater::{NutAudio, NutInfo, NutMainHeader, NutStream, NutSyncpoint, NutVideo};
+pub use muxer::NutMuxer;
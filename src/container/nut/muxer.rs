@@ -0,0 +1,267 @@
+use super::formater::{self, NutInfo, NutMainHeader, NutStream, NutSyncpoint};
+use super::vlc;
+use crate::container::{self, Metadata};
+use crate::core::Muxer;
+use crate::core::compatible::Compatible;
+use crate::core::crc;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, StreamKind};
+use crate::io::{MediaWrite, WritePrimitives};
+use crate::{error, message::Result};
+
+struct Output {
+	last_pts: i64,
+	keyframe_syncpoint: Option<u64>,
+}
+
+/// Writes front to back, without seeking, so it works on pipes. The headers
+/// go out again at 8 MiB, 64 MiB and so on, so a reader joining late finds
+/// them; there is no index.
+pub struct NutMuxer<W: MediaWrite> {
+	writer: W,
+	streams: stream::Streams,
+	nut_streams: Vec<NutStream>,
+	header: NutMainHeader,
+	metadata: Metadata,
+	outputs: Vec<Output>,
+	position: u64,
+	header_count: u32,
+	/// Position of the last syncpoint, `None` when the next frame needs one.
+	syncpoint: Option<u64>,
+}
+
+impl<W: MediaWrite> NutMuxer<W> {
+	/// Streams are numbered in order; their ids are replaced.
+	pub fn new(writer: W, mut nut_streams: Vec<NutStream>) -> Result<Self> {
+		if nut_streams.is_empty() {
+			return Err(error!("nut muxer needs at least one stream"));
+		}
+		let compatible = Compatible::new();
+		let Some(entry) = compatible.container(container::NUT) else {
+			return Err(error!("nut is not a known container"));
+		};
+
+		let mut streams = stream::Streams::new_empty();
+		for (index, nut_stream) in nut_streams.iter_mut().enumerate() {
+			nut_stream.id = index as u32;
+			let Some(stream) = nut_stream.to_stream(index) else {
+				return Err(error!("nut muxer cannot write user data streams"));
+			};
+			match stream.kind {
+				StreamKind::Video => entry.assert_video_codec(&stream.codec)?,
+				StreamKind::Audio => entry.assert_audio_codec(&stream.codec)?,
+				StreamKind::Subtitle => entry.assert_subtitle_format(&stream.codec)?,
+			}
+			streams.add(stream);
+		}
+		let header = NutMainHeader::new(&nut_streams);
+		let outputs =
+			nut_streams.iter().map(|_| Output { last_pts: 0, keyframe_syncpoint: None }).collect();
+
+		Ok(Self {
+			writer,
+			streams,
+			nut_streams,
+			header,
+			metadata: Metadata::new(),
+			outputs,
+			position: 0,
+			header_count: 0,
+			syncpoint: None,
+		})
+	}
+
+	pub fn with_metadata(&mut self, metadata: Option<Metadata>) {
+		self.metadata = metadata.unwrap_or_default();
+	}
+
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+		self.writer.write_all(bytes)?;
+		self.position += bytes.len() as u64;
+		Ok(())
+	}
+
+	fn write_headers(&mut self) -> Result<()> {
+		let mut out = Vec::new();
+		if self.header_count == 0 {
+			out.extend_from_slice(formater::FILE_ID);
+		}
+		out.extend(formater::packet(formater::MAIN_STARTCODE, &self.header.write()));
+		for stream in &self.nut_streams {
+			let time_base_id = self.header.time_base_id(stream.time).unwrap_or(0);
+			out.extend(formater::packet(formater::STREAM_STARTCODE, &stream.write(time_base_id)));
+		}
+		if !self.metadata.is_empty() {
+			let info = NutInfo::global(&self.metadata);
+			out.extend(formater::packet(formater::INFO_STARTCODE, &info.write()));
+		}
+		self.write_bytes(&out)?;
+		self.header_count += 1;
+		// frames after repeated headers start at a syncpoint
+		self.syncpoint = None;
+		Ok(())
+	}
+
+	/// A syncpoint at the pts of the frame about to be written, pointing back
+	/// to where every stream's latest keyframe can be decoded from.
+	fn write_syncpoint(&mut self, index: usize, pts: i64) -> Result<()> {
+		let time = self.nut_streams[index].time;
+		let time_base_id = self.header.time_base_id(time).unwrap_or(0);
+		let back_target = self.outputs.iter().filter_map(|output| output.keyframe_syncpoint).min();
+		let syncpoint = NutSyncpoint {
+			global_key_pts: pts as u64 * self.header.time_bases.len() as u64 + time_base_id as u64,
+			back_ptr_div16: back_target.map_or(0, |target| (self.position - target) / 16),
+		};
+		self.syncpoint = Some(self.position);
+		self.write_bytes(&formater::packet(formater::SYNCPOINT_STARTCODE, &syncpoint.write()))?;
+		for (output, stream) in self.outputs.iter_mut().zip(&self.nut_streams) {
+			output.last_pts = formater::convert_ts(pts, time, stream.time);
+		}
+		Ok(())
+	}
+
+	fn frame_header(&self, index: usize, pts: i64, size: u64, keyframe: bool) -> Result<Vec<u8>> {
+		let stream = &self.nut_streams[index];
+		let last_pts = self.outputs[index].last_pts;
+		let needs_checksum =
+			size > 2 * self.header.max_distance || pts.abs_diff(last_pts) > stream.max_pts_distance;
+		let msb = 1i64 << stream.msb_pts_shift;
+		let lsb = pts & (msb - 1);
+		let coded_pts = match formater::lsb_to_full(last_pts, lsb as u64, stream.msb_pts_shift) {
+			full if full == pts => lsb as u64,
+			_ => (pts + msb) as u64,
+		};
+
+		let mut best: Option<Vec<u8>> = None;
+		for (code, frame_code) in self.header.frame_codes.iter().enumerate() {
+			let mut flags = frame_code.flags;
+			if flags & formater::FLAG_CODED != 0 {
+				flags = formater::FLAG_CODED
+					| formater::FLAG_STREAM_ID
+					| formater::FLAG_CODED_PTS
+					| formater::FLAG_SIZE_MSB;
+				if keyframe {
+					flags |= formater::FLAG_KEY;
+				}
+				if needs_checksum {
+					flags |= formater::FLAG_CHECKSUM;
+				}
+			} else {
+				let unsupported = formater::FLAG_INVALID
+					| formater::FLAG_EOR
+					| formater::FLAG_RESERVED
+					| formater::FLAG_HEADER_IDX
+					| formater::FLAG_MATCH_TIME
+					| formater::FLAG_SM_DATA;
+				if flags & unsupported != 0
+					|| (flags & formater::FLAG_KEY != 0) != keyframe
+					|| (needs_checksum && flags & formater::FLAG_CHECKSUM == 0)
+				{
+					continue;
+				}
+			}
+			if frame_code.header_index != 0 || frame_code.reserved_count != 0 {
+				continue;
+			}
+			if flags & formater::FLAG_STREAM_ID == 0 && frame_code.stream_id != index as u64 {
+				continue;
+			}
+			if flags & formater::FLAG_CODED_PTS == 0 && last_pts + frame_code.pts_delta != pts {
+				continue;
+			}
+			let size_msb = match flags & formater::FLAG_SIZE_MSB {
+				0 if size == frame_code.size_lsb => 0,
+				0 => continue,
+				_ => match size.checked_sub(frame_code.size_lsb) {
+					Some(rest) if rest % frame_code.size_mul == 0 => rest / frame_code.size_mul,
+					_ => continue,
+				},
+			};
+
+			let mut out = vec![code as u8];
+			if frame_code.flags & formater::FLAG_CODED != 0 {
+				vlc::write_v(&mut out, flags ^ frame_code.flags);
+			}
+			if flags & formater::FLAG_STREAM_ID != 0 {
+				vlc::write_v(&mut out, index as u64);
+			}
+			if flags & formater::FLAG_CODED_PTS != 0 {
+				vlc::write_v(&mut out, coded_pts);
+			}
+			if flags & formater::FLAG_SIZE_MSB != 0 {
+				vlc::write_v(&mut out, size_msb);
+			}
+			if flags & formater::FLAG_CHECKSUM != 0 {
+				let checksum = crc::crc32_nut(&out);
+				out.extend_from_slice(&checksum.to_be_bytes());
+			}
+			if best.as_ref().is_none_or(|best| out.len() < best.len()) {
+				best = Some(out);
+			}
+		}
+		best.ok_or_else(|| error!("nut frame of stream {} fits no frame code", index))
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		if self.header_count == 0 || self.position >= 1 << (20 + 3 * self.header_count) {
+			self.write_headers()?;
+		}
+		let index = self
+			.streams
+			.all()
+			.iter()
+			.position(|stream| stream.id == packet.stream_id)
+			.ok_or_else(|| error!("nut muxer has no stream {}", packet.stream_id))?;
+
+		let time = self.nut_streams[index].time;
+		let pts = match packet.time == time {
+			true => packet.pts,
+			false => formater::convert_ts(packet.pts, packet.time, time),
+		};
+		if pts < 0 {
+			return Err(error!("nut timestamps cannot be negative, stream {} has {}", index, pts));
+		}
+		// the codecs written all code each frame on its own, so every frame is
+		// a keyframe and syncpoints only keep their distance
+		let keyframe = true;
+		let size = packet.data.len() as u64;
+
+		let needs_syncpoint = match self.syncpoint {
+			None => true,
+			Some(position) => self.position + size + 32 >= position + self.header.max_distance,
+		};
+		if needs_syncpoint {
+			self.write_syncpoint(index, pts)?;
+		}
+
+		let mut frame = self.frame_header(index, pts, size, keyframe)?;
+		frame.extend_from_slice(&packet.data);
+		self.write_bytes(&frame)?;
+
+		let output = &mut self.outputs[index];
+		output.last_pts = pts;
+		if keyframe {
+			output.keyframe_syncpoint = self.syncpoint;
+		}
+		Ok(())
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		if self.header_count == 0 {
+			self.write_headers()?;
+		}
+		self.writer.flush()
+	}
+}
+
+impl<W: MediaWrite> Muxer for NutMuxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+}
@@ -0,0 +1,115 @@
+use crate::io::{MediaRead, ReadPrimitives};
+use crate::{error, message::Result};
+
+const MAX_V_SIZE: usize = 10;
+const MAX_VB_SIZE: u64 = 1 << 24;
+
+pub trait NutRead {
+	fn read_byte(&mut self) -> Result<u8>;
+
+	fn read_v(&mut self) -> Result<u64> {
+		let mut value = 0u64;
+		for _ in 0..MAX_V_SIZE {
+			let byte = self.read_byte()?;
+			value = (value << 7) | (byte & 0x7F) as u64;
+			if byte & 0x80 == 0 {
+				return Ok(value);
+			}
+		}
+		Err(error!("nut variable length number is too long"))
+	}
+
+	fn read_s(&mut self) -> Result<i64> {
+		let value = self.read_v()?.wrapping_add(1);
+		if value & 1 != 0 { Ok(-((value >> 1) as i64)) } else { Ok((value >> 1) as i64) }
+	}
+
+	fn read_u32(&mut self) -> Result<u32> {
+		let mut value = 0u32;
+		for _ in 0..4 {
+			value = (value << 8) | self.read_byte()? as u32;
+		}
+		Ok(value)
+	}
+
+	fn read_u64(&mut self) -> Result<u64> {
+		let mut value = 0u64;
+		for _ in 0..8 {
+			value = (value << 8) | self.read_byte()? as u64;
+		}
+		Ok(value)
+	}
+
+	fn read_vb(&mut self) -> Result<Vec<u8>> {
+		let length = self.read_v()?;
+		if length > MAX_VB_SIZE {
+			return Err(error!("nut byte string of {} bytes is too long", length));
+		}
+		(0..length).map(|_| self.read_byte()).collect()
+	}
+}
+
+pub struct PacketReader<'a> {
+	data: &'a [u8],
+	position: usize,
+}
+
+impl<'a> PacketReader<'a> {
+	pub fn new(data: &'a [u8]) -> Self {
+		Self { data, position: 0 }
+	}
+
+	pub fn remaining(&self) -> usize {
+		self.data.len() - self.position
+	}
+}
+
+impl NutRead for PacketReader<'_> {
+	fn read_byte(&mut self) -> Result<u8> {
+		let byte = self.data.get(self.position).copied();
+		let byte = byte.ok_or_else(|| error!("nut packet is truncated"))?;
+		self.position += 1;
+		Ok(byte)
+	}
+}
+
+pub struct RecordingReader<'r, R: MediaRead> {
+	reader: &'r mut R,
+	pub bytes: Vec<u8>,
+}
+
+impl<'r, R: MediaRead> RecordingReader<'r, R> {
+	pub fn new(reader: &'r mut R, bytes: Vec<u8>) -> Self {
+		Self { reader, bytes }
+	}
+}
+
+impl<R: MediaRead> NutRead for RecordingReader<'_, R> {
+	fn read_byte(&mut self) -> Result<u8> {
+		let byte = self.reader.read_u8()?;
+		self.bytes.push(byte);
+		Ok(byte)
+	}
+}
+
+pub fn write_v(out: &mut Vec<u8>, value: u64) {
+	let groups = (64 - value.leading_zeros()).div_ceil(7).max(1);
+	for group in (1..groups).rev() {
+		out.push(0x80 | ((value >> (7 * group)) as u8 & 0x7F));
+	}
+	out.push(value as u8 & 0x7F);
+}
+
+pub fn write_s(out: &mut Vec<u8>, value: i64) {
+	let folded = if value > 0 { 2 * value as u64 - 1 } else { value.unsigned_abs() * 2 };
+	write_v(out, folded);
+}
+
+pub fn write_vb(out: &mut Vec<u8>, data: &[u8]) {
+	write_v(out, data.len() as u64);
+	out.extend_from_slice(data);
+}
+
+pub fn write_t(out: &mut Vec<u8>, value: u64, time_base_id: usize, time_base_count: usize) {
+	write_v(out, value * time_base_count as u64 + time_base_id as u64);
+}
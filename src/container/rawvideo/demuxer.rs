@@ -0,0 +1,65 @@
+use super::RawVideoFormat;
+use crate::codecs;
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream};
+use crate::io::MediaRead;
+use crate::{error, message::Result};
+
+/// Demuxer for headerless raw video: back to back pictures of
+/// `format.frame_size()` bytes, one per packet.
+pub struct RawVideoDemuxer<R: MediaRead> {
+	reader: R,
+	format: RawVideoFormat,
+	streams: stream::Streams,
+	frame_index: u64,
+}
+
+impl<R: MediaRead> RawVideoDemuxer<R> {
+	pub fn new(reader: R, format: RawVideoFormat) -> Result<Self> {
+		format.validate()?;
+		let codec_name = codecs::video::RAWVIDEO.to_string();
+		let stream = stream::Stream::new(0, 0, stream::StreamKind::Video, codec_name, format.time());
+		let streams = stream::Streams::new(vec![stream]);
+
+		Ok(Self { reader, format, streams, frame_index: 0 })
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		let frame_size = self.format.frame_size();
+		let mut data = vec![0u8; frame_size];
+		let mut filled = 0;
+		while filled < frame_size {
+			match self.reader.read(&mut data[filled..])? {
+				0 => break,
+				read => filled += read,
+			}
+		}
+
+		if filled == 0 {
+			return Ok(None);
+		}
+		if filled < frame_size {
+			let index = self.frame_index;
+			return Err(error!("raw video frame {} has {} of {} bytes", index, filled, frame_size));
+		}
+
+		let pts = self.frame_index as i64;
+		self.frame_index += 1;
+
+		let packet = Packet::new(data, 0, self.format.time());
+		Ok(Some(packet.with_pts(pts).with_dts(pts).with_keyframe(true)))
+	}
+
+	pub fn format(&self) -> RawVideoFormat {
+		self.format
+	}
+}
+
+impl<R: MediaRead> Demuxer for RawVideoDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
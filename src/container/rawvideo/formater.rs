@@ -0,0 +1,62 @@
+use crate::codecs::video::raw::{RawVideoDecoder, RawVideoEncoder};
+use crate::container;
+use crate::core::frame::VideoFormat;
+use crate::core::time::Time;
+use crate::{error, message::Result};
+
+/// Geometry of a headerless `.yuv`/`.rgb` stream. Nothing in the file
+/// describes it, so it comes from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawVideoFormat {
+	pub width: u32,
+	pub height: u32,
+	pub format: VideoFormat,
+	pub framerate_num: u32,
+	pub framerate_den: u32,
+}
+
+impl RawVideoFormat {
+	pub fn new(width: u32, height: u32, format: VideoFormat) -> Self {
+		Self { width, height, format, framerate_num: 25, framerate_den: 1 }
+	}
+
+	/// Pixel format assumed for an extension when none is given.
+	pub fn default_format(extension: &str) -> VideoFormat {
+		match extension {
+			container::RGB => VideoFormat::RGB24,
+			_ => VideoFormat::YUV420,
+		}
+	}
+
+	pub fn with_frame_rate(mut self, num: u32, den: u32) -> Self {
+		self.framerate_num = num;
+		self.framerate_den = den;
+		self
+	}
+
+	pub fn time(&self) -> Time {
+		Time::new(self.framerate_den, self.framerate_num)
+	}
+
+	pub fn frame_size(&self) -> usize {
+		self.format.expected_size(self.width, self.height)
+	}
+
+	pub fn create_decoder(&self) -> RawVideoDecoder {
+		RawVideoDecoder::new(self.width, self.height, self.format)
+	}
+
+	pub fn create_encoder(&self) -> RawVideoEncoder {
+		RawVideoEncoder::new(self.width, self.height, self.format, self.time())
+	}
+
+	pub fn validate(&self) -> Result<()> {
+		if self.width == 0 || self.height == 0 {
+			return Err(error!("raw video size {}x{} is invalid", self.width, self.height));
+		}
+		if self.framerate_num == 0 || self.framerate_den == 0 {
+			return Err(error!("raw video frame rate must be non-zero"));
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,7 @@
+pub mod demuxer;
+pub mod formater;
+pub mod muxer;
+
+pub use demuxer::RawVideoDemuxer;
+pub use formater::RawVideoFormat;
+pub use muxer::RawVideoMuxer;
@@ -0,0 +1,48 @@
+use super::RawVideoFormat;
+use crate::codecs;
+use crate::core::Muxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream, StreamKind};
+use crate::io::{MediaWrite, WritePrimitives};
+use crate::{error, message::Result};
+
+pub struct RawVideoMuxer<W: MediaWrite> {
+	writer: W,
+	format: RawVideoFormat,
+	streams: stream::Streams,
+}
+
+impl<W: MediaWrite> RawVideoMuxer<W> {
+	pub fn new(writer: W, format: RawVideoFormat) -> Result<Self> {
+		format.validate()?;
+		let codec_name = codecs::video::RAWVIDEO.to_string();
+		let mut streams = stream::Streams::new_empty();
+		streams.add(Stream::new(0, 0, StreamKind::Video, codec_name, format.time()));
+
+		Ok(Self { writer, format, streams })
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		if packet.data.len() != self.format.frame_size() {
+			let (expected, actual) = (self.format.frame_size(), packet.data.len());
+			return Err(error!("raw video frame has {} bytes, expected {}", actual, expected));
+		}
+		self.writer.write_all(&packet.data)
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		self.writer.flush()
+	}
+}
+
+impl<W: MediaWrite> Muxer for RawVideoMuxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+}
@@ -0,0 +1,173 @@
+use crate::io::{MediaRead, MediaSeek, MediaWrite, ReadPrimitives, SeekFrom, WritePrimitives};
+use crate::{error, message::Result};
+
+pub type FourCC = [u8; 4];
+
+pub const RIFF: FourCC = *b"RIFF";
+pub const LIST: FourCC = *b"LIST";
+
+pub const MAX_LOADED_SIZE: u32 = 256 * 1024 * 1024;
+
+pub fn fourcc_string(fourcc: &FourCC) -> String {
+	String::from_utf8_lossy(fourcc).to_string()
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkHeader {
+	pub id: FourCC,
+	pub size: u32,
+}
+
+impl ChunkHeader {
+	pub fn read<R: MediaRead>(reader: &mut R) -> Result<Self> {
+		let mut id = [0u8; 4];
+		reader.read_exact(&mut id)?;
+		let size = reader.read_u32_le()?;
+		Ok(Self { id, size })
+	}
+
+	pub fn padded_size(&self) -> u64 {
+		self.size as u64 + (self.size % 2) as u64
+	}
+
+	pub fn is_list(&self) -> bool {
+		self.id == LIST || self.id == RIFF
+	}
+}
+
+pub fn read_form<R: MediaRead>(reader: &mut R, form: FourCC) -> Result<u32> {
+	let header = ChunkHeader::read(reader)?;
+	if header.id != RIFF {
+		return Err(error!("expected RIFF, found {}", fourcc_string(&header.id)));
+	}
+	let actual = read_fourcc(reader)?;
+	if actual != form {
+		return Err(error!("expected {}, found {}", fourcc_string(&form), fourcc_string(&actual)));
+	}
+	Ok(header.size.saturating_sub(4))
+}
+
+pub fn read_fourcc<R: MediaRead>(reader: &mut R) -> Result<FourCC> {
+	let mut fourcc = [0u8; 4];
+	reader.read_exact(&mut fourcc)?;
+	Ok(fourcc)
+}
+
+pub fn read_body<R: MediaRead>(reader: &mut R, header: &ChunkHeader) -> Result<Vec<u8>> {
+	if header.size > MAX_LOADED_SIZE {
+		return Err(error!("riff chunk '{}' is too large to load", fourcc_string(&header.id)));
+	}
+	let mut body = vec![0u8; header.size as usize];
+	reader.read_exact(&mut body)?;
+	if header.size % 2 == 1 {
+		reader.read_u8()?;
+	}
+	Ok(body)
+}
+
+pub fn skip_body<R: MediaRead>(reader: &mut R, header: &ChunkHeader) -> Result<()> {
+	let mut remaining = header.padded_size();
+	let mut buffer = vec![0u8; remaining.min(65536) as usize];
+	while remaining > 0 {
+		let size = remaining.min(buffer.len() as u64) as usize;
+		reader.read_exact(&mut buffer[..size])?;
+		remaining -= size as u64;
+	}
+	Ok(())
+}
+
+/// Iterates the chunks of a body held in memory. A truncated last chunk is
+/// cut short to the data available.
+pub struct Chunks<'a> {
+	data: &'a [u8],
+}
+
+impl<'a> Chunks<'a> {
+	pub fn new(data: &'a [u8]) -> Self {
+		Self { data }
+	}
+}
+
+impl<'a> Iterator for Chunks<'a> {
+	type Item = (FourCC, &'a [u8]);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.data.len() < 8 {
+			return None;
+		}
+		let id = [self.data[0], self.data[1], self.data[2], self.data[3]];
+		let size =
+			u32::from_le_bytes([self.data[4], self.data[5], self.data[6], self.data[7]]) as usize;
+		let end = (8 + size).min(self.data.len());
+		let body = &self.data[8..end];
+		self.data = &self.data[(end + size % 2).min(self.data.len())..];
+		Some((id, body))
+	}
+}
+
+pub fn split_list(body: &[u8]) -> Option<(FourCC, &[u8])> {
+	let form = body.get(..4)?;
+	Some(([form[0], form[1], form[2], form[3]], &body[4..]))
+}
+
+pub fn find(data: &[u8], id: FourCC) -> Option<&[u8]> {
+	Chunks::new(data).find(|(chunk_id, _)| *chunk_id == id).map(|(_, body)| body)
+}
+
+pub fn write_chunk(out: &mut Vec<u8>, id: FourCC, body: &[u8]) {
+	out.extend_from_slice(&id);
+	out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+	out.extend_from_slice(body);
+	if body.len() % 2 == 1 {
+		out.push(0);
+	}
+}
+
+pub fn write_list(out: &mut Vec<u8>, form: FourCC, chunks: &[u8]) {
+	let mut body = Vec::with_capacity(chunks.len() + 4);
+	body.extend_from_slice(&form);
+	body.extend_from_slice(chunks);
+	write_chunk(out, LIST, &body);
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct OpenChunk {
+	pub start: u64,
+}
+
+impl OpenChunk {
+	pub fn begin<W: MediaWrite + MediaSeek>(
+		writer: &mut W,
+		id: FourCC,
+		form: Option<FourCC>,
+	) -> Result<Self> {
+		let start = writer.stream_position()?;
+		writer.write_all(&id)?;
+		writer.write_u32_le(0)?;
+		if let Some(form) = form {
+			writer.write_all(&form)?;
+		}
+		Ok(Self { start })
+	}
+
+	pub fn body(&self) -> u64 {
+		self.start + 8
+	}
+
+	/// Pad the body to an even length and patch the size; the writer is left at
+	/// the end of the chunk. Returns the body size.
+	pub fn end<W: MediaWrite + MediaSeek>(self, writer: &mut W) -> Result<u32> {
+		let end = writer.stream_position()?;
+		let size = end - self.body();
+		if size > u32::MAX as u64 {
+			return Err(error!("riff chunk of {} bytes does not fit a 32-bit size", size));
+		}
+		if size % 2 == 1 {
+			writer.write_u8(0)?;
+		}
+		writer.seek(SeekFrom::Start(self.start + 4))?;
+		writer.write_u32_le(size as u32)?;
+		writer.seek(SeekFrom::Start(end + size % 2))?;
+		Ok(size as u32)
+	}
+}
@@ -0,0 +1,415 @@
+use super::formater::{self, TsPacketHeader, TsPacketSize, TsProgram, TsStream};
+use super::pes::PesHeader;
+use super::psi::{self, Section, SectionBuffer};
+use crate::core::Demuxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, StreamKind};
+use crate::io::MediaRead;
+use crate::{error, message::Result};
+use std::collections::{HashMap, VecDeque};
+
+/// Bytes looked at to find the packet size.
+const PROBE_SIZE: usize = 204 * 8;
+/// Packets read while looking for the program tables before giving up.
+const PROBE_PACKETS: usize = 50_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Continuity {
+	Ok,
+	Duplicate,
+	Lost,
+}
+
+struct Elementary {
+	stream: TsStream,
+	data: Vec<u8>,
+	/// Size the PES header announces, if bounded.
+	expected: Option<usize>,
+	random_access: bool,
+	/// Set from a payload start until the PES is emitted or loses packets.
+	valid: bool,
+	last_dts: Option<i64>,
+}
+
+pub struct TsDemuxer<R: MediaRead> {
+	reader: R,
+	packet_size: TsPacketSize,
+	/// Bytes read ahead while probing or resyncing, consumed before the reader.
+	pending: VecDeque<u8>,
+	programs: Vec<TsProgram>,
+	elementary: Vec<Elementary>,
+	streams: stream::Streams,
+	sections: HashMap<u16, SectionBuffer>,
+	continuity: HashMap<u16, u8>,
+	/// Last unwrapped timestamp, the reference for 33-bit wraparound.
+	clock: Option<i64>,
+	queue: VecDeque<Packet>,
+	pat_seen: bool,
+	probing: bool,
+	eof: bool,
+}
+
+impl<R: MediaRead> TsDemuxer<R> {
+	pub fn new(reader: R) -> Result<Self> {
+		let mut demuxer = Self {
+			reader,
+			packet_size: TsPacketSize::Ts,
+			pending: VecDeque::new(),
+			programs: Vec::new(),
+			elementary: Vec::new(),
+			streams: stream::Streams::new_empty(),
+			sections: HashMap::new(),
+			continuity: HashMap::new(),
+			clock: None,
+			queue: VecDeque::new(),
+			pat_seen: false,
+			probing: true,
+			eof: false,
+		};
+
+		let mut probe = vec![0u8; PROBE_SIZE];
+		let length = demuxer.fill(&mut probe)?;
+		probe.truncate(length);
+		let (packet_size, start) = Self::detect_packet_size(&probe)?;
+		demuxer.packet_size = packet_size;
+		demuxer.pending.extend(&probe[start..]);
+
+		// streams come from the PMTs, which may sit anywhere in the first packets
+		let mut count = 0;
+		while !demuxer.programs_ready() && count < PROBE_PACKETS {
+			let Some(packet) = demuxer.read_transport_packet()? else {
+				demuxer.finish_all();
+				break;
+			};
+			demuxer.handle_packet(&packet)?;
+			count += 1;
+		}
+		if !demuxer.pat_seen {
+			return Err(error!("ts stream has no program association table"));
+		}
+		if demuxer.elementary.is_empty() {
+			return Err(error!("ts stream has no program with supported streams"));
+		}
+		demuxer.probing = false;
+		Ok(demuxer)
+	}
+
+	fn detect_packet_size(probe: &[u8]) -> Result<(TsPacketSize, usize)> {
+		for start in 0..probe.len().min(TsPacketSize::Fec.size()) {
+			for size in TsPacketSize::ALL {
+				let syncs: Vec<usize> = (0..5)
+					.map(|index| start + size.sync_offset() + index * size.size())
+					.filter(|position| *position < probe.len())
+					.collect();
+				let needed = (probe.len() / size.size()).clamp(1, 3);
+				if syncs.len() >= needed
+					&& syncs.iter().all(|position| probe[*position] == formater::SYNC_BYTE)
+				{
+					return Ok((size, start));
+				}
+			}
+		}
+		Err(error!("no ts sync bytes found"))
+	}
+
+	fn programs_ready(&self) -> bool {
+		self.pat_seen && self.programs.iter().all(|program| program.version.is_some())
+	}
+
+	fn fill(&mut self, buf: &mut [u8]) -> Result<usize> {
+		let mut filled = 0;
+		while filled < buf.len() {
+			match self.pending.pop_front() {
+				Some(byte) => {
+					buf[filled] = byte;
+					filled += 1;
+				}
+				None => break,
+			}
+		}
+		while filled < buf.len() {
+			match self.reader.read(&mut buf[filled..])? {
+				0 => break,
+				read => filled += read,
+			}
+		}
+		Ok(filled)
+	}
+
+	/// The next 188-byte packet, resyncing on the next sync byte when lost.
+	/// A truncated last packet ends the stream.
+	fn read_transport_packet(&mut self) -> Result<Option<[u8; formater::PACKET_SIZE]>> {
+		let offset = self.packet_size.sync_offset();
+		let mut unit = vec![0u8; self.packet_size.size()];
+		loop {
+			if self.fill(&mut unit)? < unit.len() {
+				return Ok(None);
+			}
+			if unit[offset] == formater::SYNC_BYTE {
+				let mut packet = [0u8; formater::PACKET_SIZE];
+				packet.copy_from_slice(&unit[offset..offset + formater::PACKET_SIZE]);
+				return Ok(Some(packet));
+			}
+			let next = unit[offset + 1..].iter().position(|byte| *byte == formater::SYNC_BYTE);
+			if let Some(position) = next {
+				for byte in unit[position + 1..].iter().rev() {
+					self.pending.push_front(*byte);
+				}
+			}
+		}
+	}
+
+	fn handle_packet(&mut self, packet: &[u8]) -> Result<()> {
+		// packets flagged as damaged in transmission are dropped
+		let Ok(header) = TsPacketHeader::parse(packet) else {
+			return Ok(());
+		};
+		if header.discontinuity {
+			self.clock = None;
+		}
+		let continuity = self.check_continuity(&header);
+		if continuity == Continuity::Duplicate || header.scrambled || !header.has_payload {
+			return Ok(());
+		}
+		let payload = &packet[header.payload_offset..];
+		let lost = continuity == Continuity::Lost;
+
+		let is_psi = header.pid == formater::PAT_PID
+			|| self.programs.iter().any(|program| program.pmt_pid == header.pid);
+		if is_psi {
+			return self.handle_psi(header.pid, payload, header.payload_start, lost);
+		}
+		match self.elementary.iter().position(|elementary| elementary.stream.pid == header.pid) {
+			Some(index) => self.handle_pes(index, &header, payload, lost),
+			None => Ok(()),
+		}
+	}
+
+	/// Counters advance by one per packet with payload; a repeat is a
+	/// duplicate, and a discontinuity flag allows any jump.
+	fn check_continuity(&mut self, header: &TsPacketHeader) -> Continuity {
+		if !header.has_payload || header.pid == formater::NULL_PID {
+			return Continuity::Ok;
+		}
+		match self.continuity.insert(header.pid, header.continuity) {
+			Some(_) if header.discontinuity => Continuity::Ok,
+			Some(last) if last == header.continuity => Continuity::Duplicate,
+			Some(last) if (last + 1) & 0x0F != header.continuity => Continuity::Lost,
+			_ => Continuity::Ok,
+		}
+	}
+
+	fn handle_psi(
+		&mut self,
+		pid: u16,
+		payload: &[u8],
+		payload_start: bool,
+		lost: bool,
+	) -> Result<()> {
+		let buffer = self.sections.entry(pid).or_default();
+		if lost {
+			buffer.reset();
+		}
+		for data in buffer.push(payload, payload_start) {
+			// sections with a bad crc are skipped, the tables repeat
+			let Ok(section) = Section::parse(&data) else {
+				continue;
+			};
+			if section.current {
+				self.handle_section(pid, &section)?;
+			}
+		}
+		Ok(())
+	}
+
+	fn handle_section(&mut self, pid: u16, section: &Section) -> Result<()> {
+		match section.table_id {
+			formater::TABLE_PAT if pid == formater::PAT_PID => {
+				self.pat_seen = true;
+				for (number, pmt_pid) in psi::parse_pat(section.body) {
+					if !self.programs.iter().any(|program| program.number == number) {
+						self.programs.push(TsProgram::new(number, pmt_pid));
+					}
+				}
+			}
+			formater::TABLE_PMT => {
+				let Some(program) = self
+					.programs
+					.iter_mut()
+					.find(|program| program.pmt_pid == pid && program.number == section.id)
+				else {
+					return Ok(());
+				};
+				if program.version == Some(section.version) {
+					return Ok(());
+				}
+				let (pcr_pid, streams) = psi::parse_pmt(section.body)?;
+				program.pcr_pid = pcr_pid;
+				program.version = Some(section.version);
+				program.streams = streams.clone();
+
+				// streams are fixed once reading starts; later PMT versions only update the program
+				if !self.probing {
+					return Ok(());
+				}
+				for stream in streams {
+					if self.elementary.iter().any(|known| known.stream.pid == stream.pid) {
+						continue;
+					}
+					let index = self.elementary.len();
+					let codec = stream.codec.clone();
+					self.streams.add(stream::Stream::new(
+						index as u32,
+						index,
+						stream.kind,
+						codec,
+						formater::time(),
+					));
+					self.elementary.push(Elementary {
+						stream,
+						data: Vec::new(),
+						expected: None,
+						random_access: false,
+						valid: false,
+						last_dts: None,
+					});
+				}
+			}
+			_ => {}
+		}
+		Ok(())
+	}
+
+	fn handle_pes(
+		&mut self,
+		index: usize,
+		header: &TsPacketHeader,
+		payload: &[u8],
+		lost: bool,
+	) -> Result<()> {
+		if lost {
+			let elementary = &mut self.elementary[index];
+			elementary.data.clear();
+			elementary.valid = false;
+		}
+		if header.payload_start {
+			self.finish_pes(index);
+			let elementary = &mut self.elementary[index];
+			elementary.data.extend_from_slice(payload);
+			elementary.valid = true;
+			elementary.random_access = header.random_access;
+			elementary.expected = match payload.get(4..6) {
+				Some([high, low]) if (*high, *low) != (0, 0) => {
+					Some(6 + u16::from_be_bytes([*high, *low]) as usize)
+				}
+				_ => None,
+			};
+		} else if self.elementary[index].valid {
+			self.elementary[index].data.extend_from_slice(payload);
+		}
+
+		// bounded PES packets are complete without waiting for the next start
+		let elementary = &self.elementary[index];
+		if elementary.valid && elementary.expected.is_some_and(|size| elementary.data.len() >= size) {
+			self.finish_pes(index);
+		}
+		Ok(())
+	}
+
+	/// Queue the PES packet reassembled for a stream. Damaged packets and
+	/// those before the stream's first timestamp are dropped.
+	fn finish_pes(&mut self, index: usize) {
+		let elementary = &mut self.elementary[index];
+		if !elementary.valid || elementary.data.is_empty() {
+			return;
+		}
+		elementary.valid = false;
+		let data = std::mem::take(&mut elementary.data);
+		let Ok(header) = PesHeader::parse(&data) else {
+			return;
+		};
+		let end = header.packet_size.unwrap_or(data.len()).min(data.len());
+		if header.header_size >= end {
+			return;
+		}
+
+		let pts = header.pts.map(|pts| self.unwrap_timestamp(pts));
+		let dts = header.dts.map(|dts| self.unwrap_timestamp(dts));
+		let elementary = &mut self.elementary[index];
+		let (pts, dts) = match (pts, dts, elementary.last_dts) {
+			(Some(pts), Some(dts), _) => (pts, dts),
+			(Some(pts), None, _) => (pts, pts),
+			// no timestamp: the packet follows the previous one
+			(None, _, Some(last)) => (last, last),
+			(None, _, None) => return,
+		};
+		elementary.last_dts = Some(dts);
+
+		let payload = data[header.header_size..end].to_vec();
+		let stream = &elementary.stream;
+		let keyframe = stream.kind != StreamKind::Video
+			|| elementary.random_access
+			|| formater::is_keyframe(&stream.codec, &payload);
+		let packet = Packet::new(payload, index as u32, formater::time())
+			.with_pts(pts)
+			.with_dts(dts)
+			.with_keyframe(keyframe);
+		self.queue.push_back(packet);
+	}
+
+	/// At the end of the stream, unbounded PES packets end with it.
+	fn finish_all(&mut self) {
+		self.eof = true;
+		for index in 0..self.elementary.len() {
+			self.finish_pes(index);
+		}
+	}
+
+	/// The 33-bit value closest to the last timestamp seen.
+	fn unwrap_timestamp(&mut self, raw: i64) -> i64 {
+		let value = match self.clock {
+			Some(last) => {
+				let delta = (raw - last).rem_euclid(formater::TIMESTAMP_WRAP);
+				match delta >= formater::TIMESTAMP_WRAP / 2 {
+					true => last + delta - formater::TIMESTAMP_WRAP,
+					false => last + delta,
+				}
+			}
+			None => raw,
+		};
+		self.clock = Some(value);
+		value
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		loop {
+			if let Some(packet) = self.queue.pop_front() {
+				return Ok(Some(packet));
+			}
+			if self.eof {
+				return Ok(None);
+			}
+			match self.read_transport_packet()? {
+				Some(packet) => self.handle_packet(&packet)?,
+				None => self.finish_all(),
+			}
+		}
+	}
+
+	pub fn packet_size(&self) -> TsPacketSize {
+		self.packet_size
+	}
+
+	pub fn programs(&self) -> &[TsProgram] {
+		&self.programs
+	}
+}
+
+impl<R: MediaRead> Demuxer for TsDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
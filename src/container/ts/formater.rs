@@ -0,0 +1,261 @@
+use crate::codecs::{audio, video};
+use crate::core::stream::StreamKind;
+use crate::core::time::Time;
+use crate::{error, message::Result};
+
+pub const SYNC_BYTE: u8 = 0x47;
+/// Transport packet size without the M2TS timecode or FEC parity.
+pub const PACKET_SIZE: usize = 188;
+
+pub const PAT_PID: u16 = 0x0000;
+pub const NULL_PID: u16 = 0x1FFF;
+
+pub const TABLE_PAT: u8 = 0x00;
+pub const TABLE_PMT: u8 = 0x02;
+
+pub const STREAM_TYPE_MPEG1_VIDEO: u8 = 0x01;
+pub const STREAM_TYPE_MPEG2_VIDEO: u8 = 0x02;
+pub const STREAM_TYPE_MPEG1_AUDIO: u8 = 0x03;
+pub const STREAM_TYPE_MPEG2_AUDIO: u8 = 0x04;
+pub const STREAM_TYPE_PRIVATE_DATA: u8 = 0x06;
+pub const STREAM_TYPE_AAC: u8 = 0x0F;
+pub const STREAM_TYPE_AAC_LATM: u8 = 0x11;
+pub const STREAM_TYPE_H264: u8 = 0x1B;
+pub const STREAM_TYPE_H265: u8 = 0x24;
+pub const STREAM_TYPE_AC3: u8 = 0x81;
+pub const STREAM_TYPE_EAC3: u8 = 0x87;
+
+const DESCRIPTOR_REGISTRATION: u8 = 0x05;
+pub const DESCRIPTOR_LANGUAGE: u8 = 0x0A;
+const DESCRIPTOR_AC3: u8 = 0x6A;
+const DESCRIPTOR_EAC3: u8 = 0x7A;
+
+/// PTS, DTS and PCR base tick at 90 kHz and wrap around after 33 bits.
+pub const CLOCK_RATE: u32 = 90_000;
+pub const TIMESTAMP_WRAP: i64 = 1 << 33;
+
+pub fn time() -> Time {
+	Time::new(1, CLOCK_RATE)
+}
+
+/// Transport packet layouts: plain, M2TS (4-byte timecode first) and with
+/// 16 bytes of Reed-Solomon parity after.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TsPacketSize {
+	Ts,
+	M2ts,
+	Fec,
+}
+
+impl TsPacketSize {
+	pub const ALL: [Self; 3] = [Self::Ts, Self::M2ts, Self::Fec];
+
+	pub fn size(&self) -> usize {
+		match self {
+			Self::Ts => 188,
+			Self::M2ts => 192,
+			Self::Fec => 204,
+		}
+	}
+
+	pub fn sync_offset(&self) -> usize {
+		match self {
+			Self::M2ts => 4,
+			_ => 0,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TsPacketHeader {
+	pub pid: u16,
+	pub payload_start: bool,
+	pub scrambled: bool,
+	pub continuity: u8,
+	pub has_payload: bool,
+	/// The continuity counter and time base may jump at this packet.
+	pub discontinuity: bool,
+	pub random_access: bool,
+	/// Program clock reference in 27 MHz ticks.
+	pub pcr: Option<u64>,
+	pub payload_offset: usize,
+}
+
+impl TsPacketHeader {
+	pub fn parse(packet: &[u8]) -> Result<Self> {
+		if packet.len() < PACKET_SIZE || packet[0] != SYNC_BYTE {
+			return Err(error!("ts packet has no sync byte"));
+		}
+		if packet[1] & 0x80 != 0 {
+			return Err(error!("ts packet has its transport error flag set"));
+		}
+		let control = (packet[3] >> 4) & 0x03;
+		let mut header = Self {
+			pid: u16::from_be_bytes([packet[1], packet[2]]) & 0x1FFF,
+			payload_start: packet[1] & 0x40 != 0,
+			scrambled: packet[3] & 0xC0 != 0,
+			continuity: packet[3] & 0x0F,
+			has_payload: control & 0x01 != 0,
+			discontinuity: false,
+			random_access: false,
+			pcr: None,
+			payload_offset: 4,
+		};
+
+		if control & 0x02 != 0 {
+			let length = packet[4] as usize;
+			if 5 + length > PACKET_SIZE {
+				return Err(error!("ts adaptation field of {} bytes overruns the packet", length));
+			}
+			if length > 0 {
+				let flags = packet[5];
+				header.discontinuity = flags & 0x80 != 0;
+				header.random_access = flags & 0x40 != 0;
+				if flags & 0x10 != 0 && length >= 7 {
+					header.pcr = Some(read_pcr(&packet[6..12]));
+				}
+			}
+			header.payload_offset = 5 + length;
+		}
+		Ok(header)
+	}
+}
+
+/// 33-bit base at 90 kHz and 9-bit extension, as 27 MHz ticks.
+fn read_pcr(data: &[u8]) -> u64 {
+	let base = (data[0] as u64) << 25
+		| (data[1] as u64) << 17
+		| (data[2] as u64) << 9
+		| (data[3] as u64) << 1
+		| (data[4] as u64) >> 7;
+	let extension = ((data[4] as u64 & 0x01) << 8) | data[5] as u64;
+	base * 300 + extension
+}
+
+#[derive(Debug, Clone)]
+pub struct TsProgram {
+	pub number: u16,
+	pub pmt_pid: u16,
+	pub pcr_pid: u16,
+	/// PMT version, `None` until the PMT has been read.
+	pub version: Option<u8>,
+	pub streams: Vec<TsStream>,
+}
+
+impl TsProgram {
+	pub fn new(number: u16, pmt_pid: u16) -> Self {
+		Self { number, pmt_pid, pcr_pid: NULL_PID, version: None, streams: Vec::new() }
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct TsStream {
+	pub pid: u16,
+	pub stream_type: u8,
+	pub kind: StreamKind,
+	pub codec: String,
+	pub language: Option<String>,
+}
+
+impl TsStream {
+	/// `None` for stream types this crate has no codec for.
+	pub fn from_pmt(pid: u16, stream_type: u8, descriptors: &[u8]) -> Option<Self> {
+		let mut language = None;
+		let mut private_codec = None;
+		for (tag, body) in Descriptors::new(descriptors) {
+			match (tag, body) {
+				(DESCRIPTOR_LANGUAGE, [a, b, c, ..]) => {
+					language = Some(String::from_utf8_lossy(&[*a, *b, *c]).to_string());
+				}
+				(DESCRIPTOR_AC3, _) | (DESCRIPTOR_REGISTRATION, b"AC-3") => {
+					private_codec = Some(audio::AC3);
+				}
+				(DESCRIPTOR_EAC3, _) | (DESCRIPTOR_REGISTRATION, b"EAC3") => {
+					private_codec = Some(audio::EAC3);
+				}
+				_ => {}
+			}
+		}
+
+		let (kind, codec) = match stream_type {
+			STREAM_TYPE_MPEG1_VIDEO | STREAM_TYPE_MPEG2_VIDEO => (StreamKind::Video, video::MPEG2),
+			STREAM_TYPE_H264 => (StreamKind::Video, video::H264),
+			STREAM_TYPE_H265 => (StreamKind::Video, video::H265),
+			STREAM_TYPE_MPEG1_AUDIO | STREAM_TYPE_MPEG2_AUDIO => (StreamKind::Audio, audio::MP2),
+			STREAM_TYPE_AAC | STREAM_TYPE_AAC_LATM => (StreamKind::Audio, audio::AAC),
+			STREAM_TYPE_AC3 => (StreamKind::Audio, audio::AC3),
+			STREAM_TYPE_EAC3 => (StreamKind::Audio, audio::EAC3),
+			STREAM_TYPE_PRIVATE_DATA => (StreamKind::Audio, private_codec?),
+			_ => return None,
+		};
+		Some(Self { pid, stream_type, kind, codec: codec.to_string(), language })
+	}
+
+	pub fn for_codec(pid: u16, kind: StreamKind, codec: &str) -> Result<Self> {
+		let stream_type = match codec {
+			video::H264 => STREAM_TYPE_H264,
+			video::H265 => STREAM_TYPE_H265,
+			video::MPEG2 => STREAM_TYPE_MPEG2_VIDEO,
+			audio::AAC => STREAM_TYPE_AAC,
+			audio::MP2 => STREAM_TYPE_MPEG1_AUDIO,
+			audio::AC3 => STREAM_TYPE_AC3,
+			audio::EAC3 => STREAM_TYPE_EAC3,
+			_ => return Err(error!("ts cannot carry '{}'", codec)),
+		};
+		Ok(Self { pid, stream_type, kind, codec: codec.to_string(), language: None })
+	}
+
+	pub fn pes_stream_id(&self) -> u8 {
+		match (self.kind, self.stream_type) {
+			(StreamKind::Video, _) => 0xE0,
+			// private stream 1
+			(_, STREAM_TYPE_AC3 | STREAM_TYPE_EAC3) => 0xBD,
+			_ => 0xC0,
+		}
+	}
+}
+
+pub struct Descriptors<'a> {
+	data: &'a [u8],
+}
+
+impl<'a> Descriptors<'a> {
+	pub fn new(data: &'a [u8]) -> Self {
+		Self { data }
+	}
+}
+
+impl<'a> Iterator for Descriptors<'a> {
+	type Item = (u8, &'a [u8]);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let [tag, length, rest @ ..] = self.data else {
+			return None;
+		};
+		let body = rest.get(..*length as usize)?;
+		self.data = &rest[*length as usize..];
+		Some((*tag, body))
+	}
+}
+
+/// Offsets of the byte after each `00 00 01` start code.
+pub fn start_codes(data: &[u8]) -> impl Iterator<Item = usize> + '_ {
+	(3..=data.len()).filter(move |end| data[end - 3..*end] == [0, 0, 1])
+}
+
+pub fn is_keyframe(codec: &str, data: &[u8]) -> bool {
+	start_codes(data).any(|start| {
+		let Some(&code) = data.get(start) else {
+			return false;
+		};
+		match codec {
+			video::H264 => code & 0x1F == 5,
+			video::H265 => matches!((code >> 1) & 0x3F, 16..=21),
+			// sequence header, or an intra-coded picture
+			video::MPEG2 => {
+				code == 0xB3 || (code == 0x00 && data.get(start + 2).is_some_and(|b| (b >> 3) & 0x07 == 1))
+			}
+			_ => false,
+		}
+	})
+}
@@ -0,0 +1,167 @@
+use crate::codecs::{audio, video};
+use crate::core::stream::Stream;
+use crate::{error, message::Result};
+
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+const H264_DELIMITER: [u8; 6] = [0, 0, 0, 1, 0x09, 0xF0];
+const H265_DELIMITER: [u8; 7] = [0, 0, 0, 1, 0x46, 0x01, 0x50];
+
+/// How packets from containers with out-of-band codec setup (MP4, Matroska)
+/// are rewritten for a transport stream, which carries it in band. Chosen once
+/// per stream from its codec private data.
+#[derive(Debug, Clone)]
+pub enum Framing {
+	/// No setup data: already Annex B, ADTS or a codec that needs none.
+	Passthrough,
+	/// Length-prefixed NAL units to start codes, parameter sets before keyframes.
+	AnnexB { hevc: bool, length_size: usize, parameter_sets: Vec<u8> },
+	/// Raw AAC frames to ADTS, from the AudioSpecificConfig.
+	Adts { profile: u8, sample_index: u8, channels: u8 },
+}
+
+impl Framing {
+	pub fn new(stream: &Stream) -> Result<Self> {
+		let private = stream.codec_private.as_slice();
+		match stream.codec.as_str() {
+			video::H264 if private.first() == Some(&1) => Self::from_avcc(private),
+			video::H265 if private.first() == Some(&1) => Self::from_hvcc(private),
+			audio::AAC if private.len() >= 2 => Self::from_audio_specific_config(private),
+			_ => Ok(Self::Passthrough),
+		}
+	}
+
+	fn from_avcc(data: &[u8]) -> Result<Self> {
+		let truncated = || error!("h264 avcC record is truncated");
+		let length_size = (*data.get(4).ok_or_else(truncated)? & 0x03) as usize + 1;
+		let mut position = 5;
+		let mut parameter_sets = Vec::new();
+		// sequence then picture parameter sets, with a 5-bit and an 8-bit count
+		for mask in [0x1F, 0xFF] {
+			let count = *data.get(position).ok_or_else(truncated)? & mask;
+			position += 1;
+			for _ in 0..count {
+				position = push_parameter_set(data, position, &mut parameter_sets).ok_or_else(truncated)?;
+			}
+		}
+		Ok(Self::AnnexB { hevc: false, length_size, parameter_sets })
+	}
+
+	fn from_hvcc(data: &[u8]) -> Result<Self> {
+		let truncated = || error!("h265 hvcC record is truncated");
+		let length_size = (*data.get(21).ok_or_else(truncated)? & 0x03) as usize + 1;
+		let arrays = *data.get(22).ok_or_else(truncated)?;
+		let mut position = 23;
+		let mut parameter_sets = Vec::new();
+		for _ in 0..arrays {
+			let count = data.get(position + 1..position + 3).ok_or_else(truncated)?;
+			let count = u16::from_be_bytes([count[0], count[1]]);
+			position += 3;
+			for _ in 0..count {
+				position = push_parameter_set(data, position, &mut parameter_sets).ok_or_else(truncated)?;
+			}
+		}
+		Ok(Self::AnnexB { hevc: true, length_size, parameter_sets })
+	}
+
+	fn from_audio_specific_config(data: &[u8]) -> Result<Self> {
+		let object_type = data[0] >> 3;
+		let sample_index = ((data[0] & 0x07) << 1) | (data[1] >> 7);
+		let channels = (data[1] >> 3) & 0x0F;
+		if !(1..=4).contains(&object_type) {
+			return Err(error!("aac object type {} has no adts profile", object_type));
+		}
+		if sample_index > 12 {
+			return Err(error!("aac sample rate index {} has no adts form", sample_index));
+		}
+		Ok(Self::Adts { profile: object_type - 1, sample_index, channels })
+	}
+
+	pub fn convert(&self, data: &[u8], keyframe: bool) -> Result<Vec<u8>> {
+		match self {
+			Self::Passthrough => Ok(data.to_vec()),
+			Self::AnnexB { hevc, length_size, parameter_sets } => {
+				Self::to_annex_b(data, *hevc, *length_size, parameter_sets, keyframe)
+			}
+			Self::Adts { profile, sample_index, channels } => {
+				let length = data.len() + 7;
+				if length >= 1 << 13 {
+					return Err(error!("aac frame of {} bytes is too large for adts", data.len()));
+				}
+				let mut out = Vec::with_capacity(length);
+				out.extend_from_slice(&[
+					0xFF,
+					0xF1,
+					(profile << 6) | (sample_index << 2) | (channels >> 2),
+					((channels & 0x03) << 6) | (length >> 11) as u8,
+					(length >> 3) as u8,
+					((length & 0x07) << 5) as u8 | 0x1F,
+					0xFC,
+				]);
+				out.extend_from_slice(data);
+				Ok(out)
+			}
+		}
+	}
+
+	/// Every access unit opens with a delimiter; keyframes without their own
+	/// parameter sets get the ones from the codec setup.
+	fn to_annex_b(
+		data: &[u8],
+		hevc: bool,
+		length_size: usize,
+		parameter_sets: &[u8],
+		keyframe: bool,
+	) -> Result<Vec<u8>> {
+		let mut units = Vec::new();
+		let mut rest = data;
+		while !rest.is_empty() {
+			let size = rest
+				.get(..length_size)
+				.map(|bytes| bytes.iter().fold(0usize, |acc, byte| (acc << 8) | *byte as usize))
+				.ok_or_else(|| error!("nal unit length is truncated"))?;
+			let unit = rest
+				.get(length_size..length_size + size)
+				.ok_or_else(|| error!("nal unit of {} bytes overruns its packet", size))?;
+			units.push(unit);
+			rest = &rest[length_size + size..];
+		}
+
+		let unit_type = |unit: &[u8]| match hevc {
+			true => unit.first().map(|byte| (byte >> 1) & 0x3F),
+			false => unit.first().map(|byte| byte & 0x1F),
+		};
+		let (delimiter_type, parameter_types): (u8, &[u8]) = match hevc {
+			true => (35, &[32, 33, 34]),
+			false => (9, &[7, 8]),
+		};
+		let has_parameter_sets =
+			units.iter().any(|unit| unit_type(unit).is_some_and(|kind| parameter_types.contains(&kind)));
+
+		let mut out = Vec::with_capacity(data.len() + parameter_sets.len() + 16);
+		match hevc {
+			true => out.extend_from_slice(&H265_DELIMITER),
+			false => out.extend_from_slice(&H264_DELIMITER),
+		}
+		if keyframe && !has_parameter_sets {
+			out.extend_from_slice(parameter_sets);
+		}
+		for unit in units {
+			if unit_type(unit) != Some(delimiter_type) {
+				out.extend_from_slice(&START_CODE);
+				out.extend_from_slice(unit);
+			}
+		}
+		Ok(out)
+	}
+}
+
+/// Copy one 16-bit length-prefixed parameter set as an Annex B unit; returns
+/// the position after it.
+fn push_parameter_set(data: &[u8], position: usize, out: &mut Vec<u8>) -> Option<usize> {
+	let size = data.get(position..position + 2)?;
+	let size = u16::from_be_bytes([size[0], size[1]]) as usize;
+	let unit = data.get(position + 2..position + 2 + size)?;
+	out.extend_from_slice(&START_CODE);
+	out.extend_from_slice(unit);
+	Some(position + 2 + size)
+}
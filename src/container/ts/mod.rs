@@ -0,0 +1,10 @@
+pub mod demuxer;
+pub mod formater;
+pub mod framing;
+pub mod muxer;
+pub mod pes;
+pub mod psi;
+pub use demuxer::TsDemuxer;
+pub use formater::{TsPacketHeader, TsPacketSize, TsProgram, TsStream};
+pub use framing::Framing;
+pub use muxer::TsMuxer;
@@ -0,0 +1,359 @@
+use super::formater::{self, TsProgram, TsStream};
+use super::framing::Framing;
+use super::{pes, psi};
+use crate::container;
+use crate::core::Muxer;
+use crate::core::compatible::Compatible;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream, StreamKind};
+use crate::core::time::Time;
+use crate::io::{MediaWrite, WritePrimitives};
+use crate::{error, message::Result};
+
+/// Seconds between PAT/PMT repetitions when none is asked for.
+pub const DEFAULT_PSI_INTERVAL: f64 = 0.1;
+/// How far timestamps run ahead of the PCR, the decoder buffering time: 0.7 s.
+const MUX_DELAY: i64 = 63_000;
+/// 27 MHz ticks between PCRs while a constant rate stream is padded: 20 ms.
+const PCR_INTERVAL: u64 = 540_000;
+const PCR_CLOCK: u64 = 27_000_000;
+
+const TRANSPORT_STREAM_ID: u16 = 1;
+const PROGRAM_NUMBER: u16 = 1;
+const PMT_PID: u16 = 0x1000;
+const FIRST_PID: u16 = 0x0100;
+
+struct Output {
+	stream: TsStream,
+	framing: Framing,
+	continuity: u8,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Adaptation {
+	random_access: bool,
+	pcr: Option<u64>,
+}
+
+impl Adaptation {
+	fn is_empty(&self) -> bool {
+		!self.random_access && self.pcr.is_none()
+	}
+}
+
+/// Single-program transport stream writer. The PCR rides on the first video
+/// stream, or the first stream when there is no video.
+pub struct TsMuxer<W: MediaWrite> {
+	writer: W,
+	streams: stream::Streams,
+	program: TsProgram,
+	outputs: Vec<Output>,
+	psi_interval: i64,
+	last_psi: Option<i64>,
+	pat_continuity: u8,
+	pmt_continuity: u8,
+	/// DTS of the first packet; output timestamps count from it, so remuxing a
+	/// transport stream does not add the mux delay again.
+	origin: Option<i64>,
+	/// Highest DTS written; PSI repetition follows it.
+	clock: i64,
+	last_pcr: Option<u64>,
+	/// Constant mux rate in bits per second, reached with null packets.
+	mux_rate: Option<u64>,
+	/// PCR of the first packet of a constant rate stream.
+	rate_start: Option<u64>,
+	packets: u64,
+}
+
+impl<W: MediaWrite> TsMuxer<W> {
+	pub fn new(writer: W, streams: &[Stream]) -> Result<Self> {
+		if streams.is_empty() {
+			return Err(error!("ts muxer needs at least one stream"));
+		}
+		let compatible = Compatible::new();
+		let Some(entry) = compatible.container(container::TS) else {
+			return Err(error!("ts is not a known container"));
+		};
+
+		let mut outputs = Vec::new();
+		let mut muxer_streams = stream::Streams::new_empty();
+		for (index, stream) in streams.iter().enumerate() {
+			match stream.kind {
+				StreamKind::Video => entry.assert_video_codec(&stream.codec)?,
+				StreamKind::Audio => entry.assert_audio_codec(&stream.codec)?,
+				StreamKind::Subtitle => entry.assert_subtitle_format(&stream.codec)?,
+			}
+			let pid = FIRST_PID + index as u16;
+			let ts_stream = TsStream::for_codec(pid, stream.kind, &stream.codec)?;
+			outputs.push(Output { stream: ts_stream, framing: Framing::new(stream)?, continuity: 0 });
+			let codec = stream.codec.clone();
+			muxer_streams.add(Stream::new(index as u32, index, stream.kind, codec, formater::time()));
+		}
+
+		let pcr_stream = outputs.iter().find(|output| output.stream.kind == StreamKind::Video);
+		let mut program = TsProgram::new(PROGRAM_NUMBER, PMT_PID);
+		program.pcr_pid = pcr_stream.unwrap_or(&outputs[0]).stream.pid;
+		program.version = Some(0);
+		program.streams = outputs.iter().map(|output| output.stream.clone()).collect();
+
+		Ok(Self {
+			writer,
+			streams: muxer_streams,
+			program,
+			outputs,
+			psi_interval: Self::to_clock_seconds(DEFAULT_PSI_INTERVAL),
+			last_psi: None,
+			pat_continuity: 0,
+			pmt_continuity: 0,
+			origin: None,
+			clock: 0,
+			last_pcr: None,
+			mux_rate: None,
+			rate_start: None,
+			packets: 0,
+		})
+	}
+
+	pub fn with_psi_interval(&mut self, seconds: f64) -> Result<()> {
+		if seconds.is_nan() || seconds <= 0.0 {
+			return Err(error!("ts psi interval must be positive, got {}", seconds));
+		}
+		self.psi_interval = Self::to_clock_seconds(seconds);
+		Ok(())
+	}
+
+	pub fn with_mux_rate(&mut self, mux_rate: Option<u64>) -> Result<()> {
+		if mux_rate == Some(0) {
+			return Err(error!("ts mux rate must be positive"));
+		}
+		self.mux_rate = mux_rate;
+		Ok(())
+	}
+
+	fn to_clock_seconds(seconds: f64) -> i64 {
+		(seconds * formater::CLOCK_RATE as f64).round().max(1.0) as i64
+	}
+
+	fn to_clock(value: i64, time: Time) -> i64 {
+		let scaled = value as i128 * time.num as i128 * formater::CLOCK_RATE as i128;
+		let den = time.den as i128;
+		(scaled + den / 2).div_euclid(den) as i64
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		let index = packet.stream_id as usize;
+		let Some(output) = self.outputs.get(index) else {
+			return Err(error!("ts muxer has no stream {}", packet.stream_id));
+		};
+		let origin = *self.origin.get_or_insert(Self::to_clock(packet.dts, packet.time));
+		let pts = Self::to_clock(packet.pts, packet.time) - origin + MUX_DELAY;
+		let dts = Self::to_clock(packet.dts, packet.time) - origin + MUX_DELAY;
+		if pts < 0 || dts < 0 {
+			return Err(error!("ts stream {} has a timestamp before the mux delay", packet.stream_id));
+		}
+		let data = output.framing.convert(&packet.data, packet.keyframe)?;
+
+		let target = Self::pcr_for(dts);
+		if self.mux_rate.is_some() && self.rate_start.is_none() {
+			self.rate_start = Some(target);
+		}
+		self.clock = self.clock.max(dts);
+		if self.last_psi.is_none_or(|last| self.clock - last >= self.psi_interval) {
+			self.write_psi()?;
+			self.last_psi = Some(self.clock);
+		}
+		if self.mux_rate.is_some() {
+			self.pad_to(target)?;
+		}
+		self.write_pes(index, &data, pts, dts, packet.keyframe)
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		if self.packets == 0 {
+			self.write_psi()?;
+		}
+		self.writer.flush()
+	}
+
+	/// The PCR a packet decoded at `dts` is sent at, the mux delay earlier.
+	fn pcr_for(dts: i64) -> u64 {
+		(dts - MUX_DELAY).max(0) as u64 * 300
+	}
+
+	fn rate_time(&self) -> Option<u64> {
+		let rate = self.mux_rate? as u128;
+		let sent = self.packets as u128 * formater::PACKET_SIZE as u128 * 8 * PCR_CLOCK as u128;
+		Some(self.rate_start.unwrap_or(0) + (sent / rate) as u64)
+	}
+
+	fn pcr_due(&self) -> bool {
+		match (self.rate_time(), self.last_pcr) {
+			(Some(now), Some(last)) => now.saturating_sub(last) >= PCR_INTERVAL,
+			(Some(_), None) => true,
+			(None, _) => false,
+		}
+	}
+
+	/// Null packets up to `target`, with PCR-only packets keeping the clock going.
+	fn pad_to(&mut self, target: u64) -> Result<()> {
+		while self.rate_time().is_some_and(|now| now < target) {
+			if !self.pcr_due() {
+				self.write_null_packet()?;
+				continue;
+			}
+			let pcr_pid = self.program.pcr_pid;
+			let continuity = self.outputs.iter().find(|output| output.stream.pid == pcr_pid);
+			// packets without payload repeat the counter
+			let continuity = continuity.map_or(0, |output| output.continuity.wrapping_sub(1) & 0x0F);
+			let adaptation = Adaptation { random_access: false, pcr: self.rate_time() };
+			self.write_transport_packet(pcr_pid, false, continuity, &adaptation, &[])?;
+		}
+		Ok(())
+	}
+
+	fn write_psi(&mut self) -> Result<()> {
+		let pat = psi::write_pat(TRANSPORT_STREAM_ID, &[(self.program.number, self.program.pmt_pid)]);
+		let continuity = self.pat_continuity;
+		self.pat_continuity = self.write_section(formater::PAT_PID, continuity, &pat)?;
+		let pmt = psi::write_pmt(&self.program);
+		let continuity = self.pmt_continuity;
+		self.pmt_continuity = self.write_section(self.program.pmt_pid, continuity, &pmt)?;
+		Ok(())
+	}
+
+	/// Returns the next continuity counter of the PID.
+	fn write_section(&mut self, pid: u16, continuity: u8, section: &[u8]) -> Result<u8> {
+		// pointer field first, the rest of the last packet stuffed with 0xFF
+		let mut payload = vec![0u8];
+		payload.extend_from_slice(section);
+		let padded = payload.len().div_ceil(184) * 184;
+		payload.resize(padded, 0xFF);
+
+		let mut continuity = continuity;
+		for (index, chunk) in payload.chunks(184).enumerate() {
+			self.write_transport_packet(pid, index == 0, continuity, &Adaptation::default(), chunk)?;
+			continuity = (continuity + 1) & 0x0F;
+		}
+		Ok(continuity)
+	}
+
+	fn write_pes(
+		&mut self,
+		index: usize,
+		data: &[u8],
+		pts: i64,
+		dts: i64,
+		keyframe: bool,
+	) -> Result<()> {
+		let pid = self.outputs[index].stream.pid;
+		let mut payload =
+			pes::write_header(self.outputs[index].stream.pes_stream_id(), pts, dts, data.len());
+		payload.extend_from_slice(data);
+
+		let mut position = 0;
+		while position < payload.len() {
+			let first = position == 0;
+			let carries_pcr = pid == self.program.pcr_pid && (first || self.pcr_due());
+			let pcr = match self.rate_time() {
+				Some(now) => now,
+				None => Self::pcr_for(dts).max(self.last_pcr.unwrap_or(0)),
+			};
+			let adaptation =
+				Adaptation { random_access: first && keyframe, pcr: carries_pcr.then_some(pcr) };
+			let continuity = self.outputs[index].continuity;
+			self.outputs[index].continuity = (continuity + 1) & 0x0F;
+			position +=
+				self.write_transport_packet(pid, first, continuity, &adaptation, &payload[position..])?;
+		}
+		Ok(())
+	}
+
+	/// One packet with as much of `payload` as fits after the adaptation field;
+	/// returns the payload bytes used. Short payloads are padded by stuffing the
+	/// adaptation field.
+	fn write_transport_packet(
+		&mut self,
+		pid: u16,
+		payload_start: bool,
+		continuity: u8,
+		adaptation: &Adaptation,
+		payload: &[u8],
+	) -> Result<usize> {
+		let mut field = Vec::new();
+		if !adaptation.is_empty() {
+			let flags = (adaptation.random_access as u8) << 6 | (adaptation.pcr.is_some() as u8) << 4;
+			field.push(flags);
+			if let Some(pcr) = adaptation.pcr {
+				write_pcr(&mut field, pcr);
+			}
+		}
+		let has_field = !field.is_empty() || payload.len() < 184;
+		let room = match has_field {
+			true => 183 - field.len(),
+			false => 184,
+		};
+		let used = payload.len().min(room);
+		let mut stuffing = room - used;
+		if field.is_empty() && stuffing > 0 {
+			field.push(0);
+			stuffing -= 1;
+		}
+
+		let control = match (has_field, used > 0) {
+			(true, true) => 0x30,
+			(true, false) => 0x20,
+			(false, _) => 0x10,
+		};
+		let mut packet = Vec::with_capacity(formater::PACKET_SIZE);
+		packet.push(formater::SYNC_BYTE);
+		packet.extend_from_slice(&(pid | (payload_start as u16) << 14).to_be_bytes());
+		packet.push(control | (continuity & 0x0F));
+		if has_field {
+			packet.push((field.len() + stuffing) as u8);
+			packet.extend_from_slice(&field);
+			packet.resize(packet.len() + stuffing, 0xFF);
+		}
+		packet.extend_from_slice(&payload[..used]);
+		self.writer.write_all(&packet)?;
+
+		if adaptation.pcr.is_some() {
+			self.last_pcr = adaptation.pcr;
+		}
+		self.packets += 1;
+		Ok(used)
+	}
+
+	fn write_null_packet(&mut self) -> Result<()> {
+		let mut packet = vec![0xFFu8; formater::PACKET_SIZE];
+		packet[..4].copy_from_slice(&[formater::SYNC_BYTE, 0x1F, 0xFF, 0x10]);
+		self.writer.write_all(&packet)?;
+		self.packets += 1;
+		Ok(())
+	}
+}
+
+/// 33-bit base at 90 kHz, 6 reserved bits and a 9-bit extension.
+fn write_pcr(out: &mut Vec<u8>, pcr: u64) {
+	let base = (pcr / 300) & (formater::TIMESTAMP_WRAP as u64 - 1);
+	let extension = pcr % 300;
+	out.extend_from_slice(&[
+		(base >> 25) as u8,
+		(base >> 17) as u8,
+		(base >> 9) as u8,
+		(base >> 1) as u8,
+		((base & 0x01) << 7) as u8 | 0x7E | (extension >> 8) as u8,
+		extension as u8,
+	]);
+}
+
+impl<W: MediaWrite> Muxer for TsMuxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+}
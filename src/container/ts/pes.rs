@@ -0,0 +1,93 @@
+use super::formater::TIMESTAMP_WRAP;
+use crate::{error, message::Result};
+
+/// Stream ids whose PES packets carry no optional header.
+const NO_HEADER_STREAMS: [u8; 8] = [0xBC, 0xBE, 0xBF, 0xF0, 0xF1, 0xF2, 0xF8, 0xFF];
+
+#[derive(Debug, Clone, Copy)]
+pub struct PesHeader {
+	pub stream_id: u8,
+	/// 33-bit timestamps at 90 kHz, not yet unwrapped.
+	pub pts: Option<i64>,
+	pub dts: Option<i64>,
+	pub header_size: usize,
+	/// Whole PES size, unknown (0 on the wire) for unbounded video packets.
+	pub packet_size: Option<usize>,
+}
+
+impl PesHeader {
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		if data.len() < 6 || data[..3] != [0, 0, 1] {
+			return Err(error!("ts pes packet has no start code"));
+		}
+		let stream_id = data[3];
+		let length = u16::from_be_bytes([data[4], data[5]]) as usize;
+		let packet_size = (length > 0).then_some(6 + length);
+
+		if NO_HEADER_STREAMS.contains(&stream_id) {
+			return Ok(Self { stream_id, pts: None, dts: None, header_size: 6, packet_size });
+		}
+		if data.len() < 9 || data[6] & 0xC0 != 0x80 {
+			return Err(error!("ts pes packet of stream 0x{:02X} has a bad header", stream_id));
+		}
+		let header_size = 9 + data[8] as usize;
+		let optional = data.get(9..header_size).ok_or_else(|| error!("ts pes header is truncated"))?;
+
+		let (pts, dts) = match data[7] >> 6 {
+			0b10 => (Some(read_timestamp(optional)?), None),
+			0b11 => {
+				(Some(read_timestamp(optional)?), Some(read_timestamp(optional.get(5..).unwrap_or(&[]))?))
+			}
+			_ => (None, None),
+		};
+		Ok(Self { stream_id, pts, dts, header_size, packet_size })
+	}
+}
+
+/// The 5-byte PTS/DTS layout: 33 bits split by marker bits.
+fn read_timestamp(data: &[u8]) -> Result<i64> {
+	let [a, b, c, d, e, ..] = data else {
+		return Err(error!("ts pes timestamp is truncated"));
+	};
+	let value = ((*a as i64 >> 1) & 0x07) << 30
+		| (*b as i64) << 22
+		| (*c as i64 >> 1) << 15
+		| (*d as i64) << 7
+		| *e as i64 >> 1;
+	Ok(value)
+}
+
+/// A PES header with PTS, and DTS when it differs. Timestamps wrap to 33 bits;
+/// `payload_size` over 64 KiB writes an unbounded packet.
+pub fn write_header(stream_id: u8, pts: i64, dts: i64, payload_size: usize) -> Vec<u8> {
+	let (flags, optional_size) = match pts == dts {
+		true => (0x80, 5),
+		false => (0xC0, 10),
+	};
+	let length = 3 + optional_size + payload_size;
+	let length = if length > u16::MAX as usize { 0 } else { length as u16 };
+
+	let mut header = vec![0, 0, 1, stream_id];
+	header.extend_from_slice(&length.to_be_bytes());
+	// marker bits and data alignment
+	header.extend_from_slice(&[0x84, flags, optional_size as u8]);
+	match pts == dts {
+		true => write_timestamp(&mut header, 0x2, pts),
+		false => {
+			write_timestamp(&mut header, 0x3, pts);
+			write_timestamp(&mut header, 0x1, dts);
+		}
+	}
+	header
+}
+
+fn write_timestamp(out: &mut Vec<u8>, prefix: u8, value: i64) {
+	let value = value.rem_euclid(TIMESTAMP_WRAP) as u64;
+	out.extend_from_slice(&[
+		(prefix << 4) | (((value >> 30) & 0x07) << 1) as u8 | 1,
+		(value >> 22) as u8,
+		(((value >> 15) & 0x7F) << 1) as u8 | 1,
+		(value >> 7) as u8,
+		((value & 0x7F) << 1) as u8 | 1,
+	]);
+}
@@ -0,0 +1,169 @@
+use super::formater::{self, TsProgram, TsStream};
+use crate::core::crc;
+use crate::{error, message::Result};
+
+/// Joins sections that span several transport packets of one PID.
+#[derive(Debug, Default)]
+pub struct SectionBuffer {
+	data: Vec<u8>,
+	started: bool,
+}
+
+impl SectionBuffer {
+	pub fn push(&mut self, payload: &[u8], payload_start: bool) -> Vec<Vec<u8>> {
+		let mut sections = Vec::new();
+		let mut payload = payload;
+		if payload_start {
+			let Some((&pointer, rest)) = payload.split_first() else {
+				return sections;
+			};
+			let pointer = (pointer as usize).min(rest.len());
+			// bytes before the pointer finish the previous section
+			if self.started {
+				self.data.extend_from_slice(&rest[..pointer]);
+				self.drain(&mut sections);
+			}
+			self.data.clear();
+			self.started = true;
+			payload = &rest[pointer..];
+		}
+		if self.started {
+			self.data.extend_from_slice(payload);
+			self.drain(&mut sections);
+		}
+		sections
+	}
+
+	fn drain(&mut self, sections: &mut Vec<Vec<u8>>) {
+		while self.data.len() >= 3 {
+			// the rest of the packet is stuffing
+			if self.data[0] == 0xFF {
+				self.data.clear();
+				self.started = false;
+				return;
+			}
+			let length = 3 + (u16::from_be_bytes([self.data[1], self.data[2]]) & 0x0FFF) as usize;
+			if self.data.len() < length {
+				return;
+			}
+			sections.push(self.data.drain(..length).collect());
+		}
+	}
+
+	pub fn reset(&mut self) {
+		self.data.clear();
+		self.started = false;
+	}
+}
+
+#[derive(Debug)]
+pub struct Section<'a> {
+	pub table_id: u8,
+	/// Transport stream id for a PAT, program number for a PMT.
+	pub id: u16,
+	pub version: u8,
+	pub current: bool,
+	pub body: &'a [u8],
+}
+
+impl<'a> Section<'a> {
+	pub fn parse(data: &'a [u8]) -> Result<Self> {
+		if data.len() < 12 || data[1] & 0x80 == 0 {
+			return Err(error!("ts psi section is too short or not in long form"));
+		}
+		let crc_start = data.len() - 4;
+		let stored = u32::from_be_bytes([
+			data[crc_start],
+			data[crc_start + 1],
+			data[crc_start + 2],
+			data[crc_start + 3],
+		]);
+		if crc::crc32_mpeg(&data[..crc_start]) != stored {
+			return Err(error!("ts psi section 0x{:02X} has a bad crc", data[0]));
+		}
+		Ok(Self {
+			table_id: data[0],
+			id: u16::from_be_bytes([data[3], data[4]]),
+			version: (data[5] >> 1) & 0x1F,
+			current: data[5] & 0x01 != 0,
+			body: &data[8..crc_start],
+		})
+	}
+}
+
+/// (program number, PMT PID) pairs; program 0 points at the network table.
+pub fn parse_pat(body: &[u8]) -> Vec<(u16, u16)> {
+	body
+		.chunks_exact(4)
+		.map(|entry| {
+			(u16::from_be_bytes([entry[0], entry[1]]), u16::from_be_bytes([entry[2], entry[3]]) & 0x1FFF)
+		})
+		.filter(|(program, _)| *program != 0)
+		.collect()
+}
+
+/// A program's PCR PID and the elementary streams it has codecs for.
+pub fn parse_pmt(body: &[u8]) -> Result<(u16, Vec<TsStream>)> {
+	if body.len() < 4 {
+		return Err(error!("ts pmt is too short"));
+	}
+	let pcr_pid = u16::from_be_bytes([body[0], body[1]]) & 0x1FFF;
+	let info_length = (u16::from_be_bytes([body[2], body[3]]) & 0x0FFF) as usize;
+	let mut entries =
+		body.get(4 + info_length..).ok_or_else(|| error!("ts pmt program info overruns"))?;
+
+	let mut streams = Vec::new();
+	while entries.len() >= 5 {
+		let stream_type = entries[0];
+		let pid = u16::from_be_bytes([entries[1], entries[2]]) & 0x1FFF;
+		let length = (u16::from_be_bytes([entries[3], entries[4]]) & 0x0FFF) as usize;
+		let descriptors =
+			entries.get(5..5 + length).ok_or_else(|| error!("ts pmt entry for pid {} overruns", pid))?;
+		if let Some(stream) = TsStream::from_pmt(pid, stream_type, descriptors) {
+			streams.push(stream);
+		}
+		entries = &entries[5 + length..];
+	}
+	Ok((pcr_pid, streams))
+}
+
+/// A long-form section, current and complete in one piece, with its CRC.
+pub fn write_section(table_id: u8, id: u16, version: u8, body: &[u8]) -> Vec<u8> {
+	let length = (5 + body.len() + 4) as u16;
+	let mut section = vec![table_id];
+	section.extend_from_slice(&(0xB000 | length).to_be_bytes());
+	section.extend_from_slice(&id.to_be_bytes());
+	section.extend_from_slice(&[0xC1 | ((version & 0x1F) << 1), 0, 0]);
+	section.extend_from_slice(body);
+	let crc = crc::crc32_mpeg(&section);
+	section.extend_from_slice(&crc.to_be_bytes());
+	section
+}
+
+pub fn write_pat(transport_stream_id: u16, programs: &[(u16, u16)]) -> Vec<u8> {
+	let mut body = Vec::new();
+	for (number, pmt_pid) in programs {
+		body.extend_from_slice(&number.to_be_bytes());
+		body.extend_from_slice(&(0xE000 | pmt_pid).to_be_bytes());
+	}
+	write_section(formater::TABLE_PAT, transport_stream_id, 0, &body)
+}
+
+pub fn write_pmt(program: &TsProgram) -> Vec<u8> {
+	let mut body = Vec::new();
+	body.extend_from_slice(&(0xE000 | program.pcr_pid).to_be_bytes());
+	body.extend_from_slice(&0xF000u16.to_be_bytes());
+	for stream in &program.streams {
+		let mut descriptors = Vec::new();
+		if let Some(language) = stream.language.as_ref().filter(|language| language.len() == 3) {
+			descriptors.extend_from_slice(&[formater::DESCRIPTOR_LANGUAGE, 4]);
+			descriptors.extend_from_slice(language.as_bytes());
+			descriptors.push(0);
+		}
+		body.push(stream.stream_type);
+		body.extend_from_slice(&(0xE000 | stream.pid).to_be_bytes());
+		body.extend_from_slice(&(0xF000 | descriptors.len() as u16).to_be_bytes());
+		body.extend_from_slice(&descriptors);
+	}
+	write_section(formater::TABLE_PMT, program.number, program.version.unwrap_or(0), &body)
+}
@@ -0,0 +1,130 @@
+use super::TtaFormat;
+use crate::codecs;
+use crate::container::Metadata;
+use crate::container::apev2::ApeTag;
+use crate::container::id3v2;
+use crate::core::crc;
+use crate::core::frame::Channels;
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream, time};
+use crate::io::{MediaRead, MediaSeek, ReadPrimitives, SeekFrom};
+use crate::{error, message::Result};
+
+const HEADER_SIZE: usize = 18;
+const FORMAT_SIMPLE: u16 = 1;
+const FORMAT_ENCRYPTED: u16 = 2;
+
+/// Demuxer for TTA (`.tta`) files. A leading ID3v2 tag is skipped and a
+/// trailing APEv2 tag provides the metadata. Each packet is one frame,
+/// including its CRC-32, located through the seek table.
+pub struct TtaDemuxer<R: MediaRead + MediaSeek> {
+	reader: R,
+	format: TtaFormat,
+	streams: stream::Streams,
+	metadata: Metadata,
+	frame_sizes: Vec<u32>,
+	frame_index: usize,
+}
+
+impl<R: MediaRead + MediaSeek> TtaDemuxer<R> {
+	pub fn new(mut reader: R) -> Result<Self> {
+		let metadata = match ApeTag::read(&mut reader)? {
+			Some(tag) => tag.metadata,
+			None => Metadata::new(),
+		};
+
+		let skip = id3v2::tag_size(&mut reader)?;
+		reader.seek(SeekFrom::Start(skip))?;
+
+		let mut header = [0u8; HEADER_SIZE];
+		reader.read_exact(&mut header)?;
+		if &header[..4] != b"TTA1" {
+			return Err(error!("missing 'TTA1' header"));
+		}
+		let stored_crc = reader.read_u32_le()?;
+		if crc::crc32(&header) != stored_crc {
+			return Err(error!("tta header crc mismatch"));
+		}
+
+		let mut fields = &header[4..];
+		let format_code = fields.read_u16_le()?;
+		let channels = fields.read_u16_le()?;
+		let bits_per_sample = fields.read_u16_le()?;
+		let sample_rate = fields.read_u32_le()?;
+		let total_samples = fields.read_u32_le()? as u64;
+
+		match format_code {
+			FORMAT_SIMPLE => {}
+			FORMAT_ENCRYPTED => return Err(error!("encrypted tta files are not supported")),
+			code => return Err(error!("tta format {} is not supported", code)),
+		}
+		let channels = u8::try_from(channels)
+			.map_err(|_| error!("tta channel count {} is not supported", channels))?;
+
+		let format = TtaFormat {
+			channels: Channels::from_count(channels),
+			sample_rate,
+			bits_per_sample,
+			total_samples,
+		};
+		format.validate()?;
+
+		let frame_sizes = Self::read_seek_table(&mut reader, format.frame_count())?;
+
+		let time = time::Time::new(1, sample_rate);
+		let codec_name = codecs::audio::TTA.to_string();
+		let stream = stream::Stream::new(0, 0, stream::StreamKind::Audio, codec_name, time);
+		let streams = stream::Streams::new(vec![stream]);
+
+		Ok(Self { reader, format, streams, metadata, frame_sizes, frame_index: 0 })
+	}
+
+	fn read_seek_table(reader: &mut R, frame_count: u64) -> Result<Vec<u32>> {
+		let mut table = vec![0u8; frame_count as usize * 4];
+		reader.read_exact(&mut table)?;
+		let stored_crc = reader.read_u32_le()?;
+		if crc::crc32(&table) != stored_crc {
+			return Err(error!("tta seek table crc mismatch"));
+		}
+
+		let sizes = table
+			.chunks_exact(4)
+			.map(|entry| u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]));
+		Ok(sizes.collect())
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		let Some(&size) = self.frame_sizes.get(self.frame_index) else {
+			return Ok(None);
+		};
+
+		let mut data = vec![0u8; size as usize];
+		self.reader.read_exact(&mut data)?;
+
+		let pts = self.frame_index as u64 * self.format.frame_length();
+		self.frame_index += 1;
+
+		let time = time::Time::new(1, self.format.sample_rate);
+		let mut packet = Packet::new(data, 0, time).with_pts(pts as i64);
+		packet.dts = packet.pts;
+		packet.keyframe = true;
+		Ok(Some(packet))
+	}
+
+	pub fn format(&self) -> TtaFormat {
+		self.format
+	}
+
+	pub fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+}
+
+impl<R: MediaRead + MediaSeek> Demuxer for TtaDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
@@ -0,0 +1,50 @@
+use crate::codecs::audio::tta::TtaDecoder;
+use crate::container::wav;
+use crate::core::frame::Channels;
+use crate::{error, message::Result};
+
+#[derive(Debug, Clone, Copy)]
+pub struct TtaFormat {
+	pub channels: Channels,
+	pub sample_rate: u32,
+	pub bits_per_sample: u16,
+	pub total_samples: u64,
+}
+
+impl TtaFormat {
+	/// PCM format the decoded stream is written with by default. 8-bit
+	/// streams widen to 16 bits.
+	pub fn to_wav_format(&self) -> wav::WavFormat {
+		wav::WavFormat {
+			channels: self.channels,
+			sample_rate: self.sample_rate,
+			bit_depth: self.bits_per_sample.max(16),
+			format_code: 1,
+		}
+	}
+
+	pub fn frame_length(&self) -> u64 {
+		TtaDecoder::frame_length(self.sample_rate)
+	}
+
+	pub fn frame_count(&self) -> u64 {
+		self.total_samples.div_ceil(self.frame_length())
+	}
+
+	pub fn create_decoder(&self) -> Result<TtaDecoder> {
+		TtaDecoder::new(self.sample_rate, self.channels, self.bits_per_sample, self.total_samples)
+	}
+
+	pub fn validate(&self) -> Result<()> {
+		if self.channels.count() == 0 {
+			return Err(error!("channels must be non-zero"));
+		}
+		if self.sample_rate == 0 {
+			return Err(error!("sample rate must be non-zero"));
+		}
+		if !matches!(self.bits_per_sample, 8 | 16 | 24) {
+			return Err(error!("tta with {} bits per sample is not supported", self.bits_per_sample));
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,159 @@
+use super::guid::{self, Guid};
+use crate::container::wav::header::WavHeader;
+use crate::container::wav::{WavFormat, WavMetadata};
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream, time};
+use crate::io::{MediaRead, ReadPrimitives};
+use crate::{error, message::Result};
+
+pub struct W64Demuxer<R: MediaRead> {
+	reader: R,
+	format: WavFormat,
+	streams: stream::Streams,
+	metadata: WavMetadata,
+	data_remaining: u64,
+	packet_count: u64,
+	sample_position: u64,
+}
+
+impl<R: MediaRead> W64Demuxer<R> {
+	const CHUNK_SIZE_LIMIT: usize = 65536;
+	const CHUNK_HEADER_SIZE: u64 = 24;
+
+	pub fn new(mut reader: R) -> Result<Self> {
+		let (header, metadata, data_size) = Self::read_w64_and_find_data(&mut reader)?;
+		header.validate()?;
+
+		let format = header.to_format();
+
+		let codec_name = format.to_codec_string().to_string();
+		let time = time::Time::new(1, header.sample_rate);
+		let stream = stream::Stream::new(0, 0, stream::StreamKind::Audio, codec_name, time);
+		let streams = stream::Streams::new(vec![stream]);
+
+		Ok(Self {
+			reader,
+			format,
+			streams,
+			metadata,
+			data_remaining: data_size,
+			packet_count: 0,
+			sample_position: 0,
+		})
+	}
+
+	fn read_w64_and_find_data(reader: &mut R) -> Result<(WavHeader, WavMetadata, u64)> {
+		Self::check_guid(reader, &guid::RIFF)?;
+		let _file_size = reader.read_u64_le()?;
+		Self::check_guid(reader, &guid::WAVE)?;
+
+		let mut header = None;
+		let mut metadata = WavMetadata::new();
+
+		loop {
+			let chunk_id = Self::read_guid(reader)?;
+			let chunk_size = reader.read_u64_le()?;
+			if chunk_size < Self::CHUNK_HEADER_SIZE {
+				return Err(error!("w64 chunk '{}' has invalid size", guid::name(&chunk_id)));
+			}
+
+			let body_size = chunk_size - Self::CHUNK_HEADER_SIZE;
+			let padding = Self::padding(chunk_size);
+
+			match chunk_id {
+				guid::FMT => {
+					header = Some(WavHeader::read_fmt(reader, body_size)?);
+					Self::skip_bytes(reader, padding)?;
+				}
+				guid::LIST => {
+					let data = Self::read_bytes(reader, body_size)?;
+					Self::skip_bytes(reader, padding)?;
+					metadata.parse_list(&data);
+				}
+				guid::DATA => match header {
+					Some(header) => return Ok((header, metadata, body_size)),
+					None => return Err(error!("w64 data chunk before fmt chunk")),
+				},
+				_ => Self::skip_bytes(reader, body_size + padding)?,
+			}
+		}
+	}
+
+	fn padding(chunk_size: u64) -> u64 {
+		(8 - chunk_size % 8) % 8
+	}
+
+	fn read_guid(reader: &mut R) -> Result<Guid> {
+		let mut buf = [0u8; 16];
+		reader.read_exact(&mut buf)?;
+		Ok(buf)
+	}
+
+	fn check_guid(reader: &mut R, expected: &Guid) -> Result<()> {
+		let actual = Self::read_guid(reader)?;
+		if &actual != expected {
+			return Err(error!("expected {}, found {}", guid::name(expected), guid::name(&actual)));
+		}
+		Ok(())
+	}
+
+	fn read_bytes(reader: &mut R, size: u64) -> Result<Vec<u8>> {
+		let mut buf = vec![0u8; size as usize];
+		reader.read_exact(&mut buf)?;
+		Ok(buf)
+	}
+
+	fn skip_bytes(reader: &mut R, size: u64) -> Result<()> {
+		let mut buf = vec![0u8; size as usize];
+		reader.read_exact(&mut buf)?;
+		Ok(())
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		if self.data_remaining == 0 {
+			return Ok(None);
+		}
+
+		let block_align = self.format.block_align() as u64;
+		let max_chunk = (Self::CHUNK_SIZE_LIMIT as u64 / block_align) * block_align;
+		let chunk_size = std::cmp::min(self.data_remaining, max_chunk) as usize;
+		let mut data = vec![0u8; chunk_size];
+		let bytes_read = self.reader.read(&mut data)?;
+
+		if bytes_read == 0 {
+			return Ok(None);
+		}
+
+		data.truncate(bytes_read);
+		self.data_remaining -= bytes_read as u64;
+
+		let time = time::Time::new(1, self.format.sample_rate);
+		let packet = Packet::new(data, 0, time).with_pts(self.sample_position as i64);
+
+		self.sample_position += (bytes_read / self.format.bytes_per_frame()) as u64;
+		self.packet_count += 1;
+
+		Ok(Some(packet))
+	}
+
+	pub fn read_audio_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+
+	pub fn format(&self) -> WavFormat {
+		self.format
+	}
+
+	pub fn metadata(&self) -> &WavMetadata {
+		&self.metadata
+	}
+}
+
+impl<R: MediaRead> Demuxer for W64Demuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
@@ -0,0 +1,33 @@
+/// Wave64 replaces RIFF fourccs with 16-byte GUIDs. The RIFF-level ids keep
+/// the lowercase fourcc in their first four bytes, the rest share a suffix.
+pub type Guid = [u8; 16];
+
+pub const RIFF: Guid =
+	guid(*b"riff", [0x2E, 0x91, 0xCF, 0x11, 0xA5, 0xD6, 0x28, 0xDB, 0x04, 0xC1, 0x00, 0x00]);
+pub const LIST: Guid =
+	guid(*b"list", [0x2F, 0x91, 0xCF, 0x11, 0xA5, 0xD6, 0x28, 0xDB, 0x04, 0xC1, 0x00, 0x00]);
+pub const WAVE: Guid = guid(*b"wave", WAVE_SUFFIX);
+pub const FMT: Guid = guid(*b"fmt ", WAVE_SUFFIX);
+pub const FACT: Guid = guid(*b"fact", WAVE_SUFFIX);
+pub const DATA: Guid = guid(*b"data", WAVE_SUFFIX);
+
+const WAVE_SUFFIX: [u8; 12] =
+	[0xF3, 0xAC, 0xD3, 0x11, 0x8C, 0xD1, 0x00, 0xC0, 0x4F, 0x8E, 0xDB, 0x8A];
+
+const fn guid(fourcc: [u8; 4], suffix: [u8; 12]) -> Guid {
+	let mut id = [0u8; 16];
+	let mut i = 0;
+	while i < 4 {
+		id[i] = fourcc[i];
+		i += 1;
+	}
+	while i < 16 {
+		id[i] = suffix[i - 4];
+		i += 1;
+	}
+	id
+}
+
+pub fn name(id: &Guid) -> String {
+	String::from_utf8_lossy(&id[..4]).to_string()
+}
@@ -0,0 +1,5 @@
+pub mod demuxer;
+pub mod guid;
+pub mod muxer;
+pub use demuxer::W64Demuxer;
+pub use muxer::W64Muxer;
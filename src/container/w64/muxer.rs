@@ -0,0 +1,103 @@
+use super::guid;
+use crate::container::wav::header::WavHeader;
+use crate::container::wav::{WavFormat, WavMetadata};
+use crate::core::Muxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream, StreamKind};
+use crate::core::time::Time;
+use crate::io::{MediaSeek, MediaWrite, SeekFrom, WritePrimitives};
+use crate::message::Result;
+
+pub struct W64Muxer<W: MediaWrite + MediaSeek> {
+	writer: W,
+	streams: stream::Streams,
+	metadata: Option<WavMetadata>,
+	data_size: u64,
+	data_size_pos: u64,
+	file_size_pos: u64,
+}
+
+impl<W: MediaWrite + MediaSeek> W64Muxer<W> {
+	const CHUNK_HEADER_SIZE: u64 = 24;
+
+	pub fn new(mut writer: W, format: WavFormat) -> Result<Self> {
+		let (file_size_pos, data_size_pos) = Self::write_header(&mut writer, &format)?;
+		writer.flush()?;
+
+		let codec_name = format.to_codec_string().to_string();
+		let time = Time::new(1, format.sample_rate);
+		let mut streams = stream::Streams::new_empty();
+		let stream = Stream::new(0, 0, StreamKind::Audio, codec_name, time);
+
+		streams.add(stream);
+
+		Ok(Self { writer, streams, metadata: None, data_size: 0, data_size_pos, file_size_pos })
+	}
+
+	pub fn with_metadata(&mut self, metadata: Option<WavMetadata>) {
+		self.metadata = metadata;
+	}
+
+	fn write_header(writer: &mut W, format: &WavFormat) -> Result<(u64, u64)> {
+		writer.write_all(&guid::RIFF)?;
+		let file_size_pos = writer.stream_position()?;
+		writer.write_u64_le(0)?;
+		writer.write_all(&guid::WAVE)?;
+
+		let fmt_size = Self::CHUNK_HEADER_SIZE + WavHeader::fmt_size(format) as u64;
+		writer.write_all(&guid::FMT)?;
+		writer.write_u64_le(fmt_size)?;
+		WavHeader::write_fmt(writer, format)?;
+		Self::write_padding(writer, fmt_size)?;
+
+		writer.write_all(&guid::DATA)?;
+		let data_size_pos = writer.stream_position()?;
+		writer.write_u64_le(Self::CHUNK_HEADER_SIZE)?;
+		Ok((file_size_pos, data_size_pos))
+	}
+
+	fn write_padding(writer: &mut W, chunk_size: u64) -> Result<()> {
+		let padding = (8 - chunk_size % 8) % 8;
+		writer.write_all(&[0u8; 8][..padding as usize])
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		self.writer.write_all(&packet.data)?;
+		self.data_size += packet.data.len() as u64;
+		Ok(())
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		let data_chunk_size = Self::CHUNK_HEADER_SIZE + self.data_size;
+		Self::write_padding(&mut self.writer, data_chunk_size)?;
+
+		if let Some(meta) = self.metadata.as_ref().filter(|meta| !meta.is_empty()) {
+			let list_size = Self::CHUNK_HEADER_SIZE + meta.list_size();
+			self.writer.write_all(&guid::LIST)?;
+			self.writer.write_u64_le(list_size)?;
+			meta.write_list(&mut self.writer)?;
+			Self::write_padding(&mut self.writer, list_size)?;
+		}
+
+		let file_size = self.writer.stream_position()?;
+		self.writer.seek(SeekFrom::Start(self.data_size_pos))?;
+		self.writer.write_u64_le(data_chunk_size)?;
+
+		self.writer.seek(SeekFrom::Start(self.file_size_pos))?;
+		self.writer.write_u64_le(file_size)?;
+		self.writer.flush()?;
+		Ok(())
+	}
+}
+
+impl<W: MediaWrite + MediaSeek> Muxer for W64Muxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+}
@@ -1,10 +1,10 @@
 use super::header::WavHeader;
 use super::{WavFormat, WavMetadata};
-use crate::core::frame::Channels;
+use crate::container::riff;
 use crate::core::packet::Packet;
 use crate::core::{Demuxer, stream, time};
 use crate::io::{MediaRead, ReadPrimitives};
-use crate::{error, message::Result};
+use crate::message::Result;
 
 pub struct WavDemuxer<R: MediaRead> {
 	reader: R,
@@ -42,119 +42,27 @@ impl<R: MediaRead> WavDemuxer<R> {
 	}
 
 	fn read_wav_and_find_data(reader: &mut R) -> Result<(WavHeader, WavMetadata, u64)> {
-		Self::check_fourcc(reader, "RIFF")?;
-		let _file_size = reader.read_u32_le()?;
-		Self::check_fourcc(reader, "WAVE")?;
-
-		let mut header = WavHeader {
-			channels: Channels::Mono,
-			sample_rate: 0,
-			byte_rate: 0,
-			block_align: 0,
-			bits_per_sample: 0,
-			format_code: 0,
-		};
+		riff::read_form(reader, *b"WAVE")?;
+
+		let mut header = WavHeader::default();
 		let mut metadata = WavMetadata::new();
 
 		loop {
-			let chunk_id = Self::read_fourcc(reader)?;
-			let chunk_size = reader.read_u32_le()? as u64;
-
-			match chunk_id.as_str() {
-				"fmt " => Self::read_fmt_chunk(reader, chunk_size, &mut header)?,
-				"LIST" => Self::read_list_chunk(reader, chunk_size, &mut metadata)?,
-				"data" => return Ok((header, metadata, chunk_size)),
-				_ => Self::skip_bytes(reader, chunk_size)?,
+			let chunk = riff::ChunkHeader::read(reader)?;
+			match &chunk.id {
+				b"fmt " => {
+					header = WavHeader::read_fmt(reader, chunk.size as u64)?;
+					if chunk.size % 2 == 1 {
+						reader.read_u8()?;
+					}
+				}
+				b"LIST" => metadata.parse_list(&riff::read_body(reader, &chunk)?),
+				b"data" => return Ok((header, metadata, chunk.size as u64)),
+				_ => riff::skip_body(reader, &chunk)?,
 			}
 		}
 	}
 
-	fn read_fmt_chunk(reader: &mut R, chunk_size: u64, header: &mut WavHeader) -> Result<()> {
-		if chunk_size < 16 {
-			return Err(error!("fmt chunk too small"));
-		}
-
-		header.format_code = reader.read_u16_le()?;
-		let channel_count = reader.read_u16_le()? as u8;
-		header.channels = Channels::from_count(channel_count);
-		header.sample_rate = reader.read_u32_le()?;
-		header.byte_rate = reader.read_u32_le()?;
-		header.block_align = reader.read_u16_le()?;
-		header.bits_per_sample = reader.read_u16_le()?;
-
-		let remaining = chunk_size - 16;
-		if remaining > 0 {
-			Self::skip_bytes(reader, remaining)?;
-		}
-		Ok(())
-	}
-
-	fn read_list_chunk(reader: &mut R, chunk_size: u64, metadata: &mut WavMetadata) -> Result<()> {
-		if chunk_size < 4 {
-			return Ok(());
-		}
-
-		let form_type = Self::read_fourcc(reader)?;
-		if form_type != "INFO" {
-			return Self::skip_bytes(reader, chunk_size - 4);
-		}
-
-		let mut position = 4u64;
-		while position + 8 <= chunk_size {
-			let id = Self::read_fourcc(reader)?;
-			let size = reader.read_u32_le()? as u64;
-			position += 8;
-
-			let data = Self::read_bytes(reader, size)?;
-			position += size;
-
-			let value = String::from_utf8_lossy(&data).trim_end_matches('\0').to_string();
-
-			match id.as_str() {
-				"IART" => metadata.set("artist", value),
-				"INAM" => metadata.set("title", value),
-				"ICOM" => metadata.set("comment", value),
-				"ICOP" => metadata.set("copyright", value),
-				"ISFT" => metadata.set("software", value),
-				"IGNR" => metadata.set("genre", value),
-				"ITRK" => metadata.set("track", value),
-				_ => {}
-			}
-
-			if size % 2 == 1 {
-				reader.read_u8()?;
-				position += 1;
-			}
-		}
-		Ok(())
-	}
-
-	fn read_fourcc(reader: &mut R) -> Result<String> {
-		let mut buf = [0u8; 4];
-		reader.read_exact(&mut buf)?;
-		Ok(String::from_utf8_lossy(&buf).to_string())
-	}
-
-	fn check_fourcc(reader: &mut R, expected: &str) -> Result<()> {
-		let actual = Self::read_fourcc(reader)?;
-		if actual != expected {
-			return Err(error!("expected {}, found {}", expected, actual));
-		}
-		Ok(())
-	}
-
-	fn read_bytes(reader: &mut R, size: u64) -> Result<Vec<u8>> {
-		let mut buf = vec![0u8; size as usize];
-		reader.read_exact(&mut buf)?;
-		Ok(buf)
-	}
-
-	fn skip_bytes(reader: &mut R, size: u64) -> Result<()> {
-		let mut buf = vec![0u8; size as usize];
-		reader.read_exact(&mut buf)?;
-		Ok(())
-	}
-
 	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
 		if self.data_remaining == 0 {
 			return Ok(None);
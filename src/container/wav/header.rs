@@ -1,5 +1,8 @@
+use crate::io::{MediaRead, MediaWrite, ReadPrimitives, WritePrimitives};
 use crate::{container::wav::WavFormat, core::frame::Channels, error, message::Result};
 
+pub const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
 #[derive(Debug)]
 pub struct WavHeader {
 	pub channels: Channels,
@@ -10,7 +13,83 @@ pub struct WavHeader {
 	pub format_code: u16,
 }
 
+impl Default for WavHeader {
+	fn default() -> Self {
+		Self {
+			channels: Channels::Mono,
+			sample_rate: 0,
+			byte_rate: 0,
+			block_align: 0,
+			bits_per_sample: 0,
+			format_code: 0,
+		}
+	}
+}
+
 impl WavHeader {
+	/// Read the body of a `fmt ` chunk, resolving `WAVE_FORMAT_EXTENSIBLE` to the
+	/// format code of its sub-format.
+	pub fn read_fmt<R: MediaRead>(reader: &mut R, chunk_size: u64) -> Result<Self> {
+		if chunk_size < 16 {
+			return Err(error!("fmt chunk too small"));
+		}
+
+		let format_code = reader.read_u16_le()?;
+		let channel_count = reader.read_u16_le()? as u8;
+		let mut header = Self {
+			format_code,
+			channels: Channels::from_count(channel_count),
+			sample_rate: reader.read_u32_le()?,
+			byte_rate: reader.read_u32_le()?,
+			block_align: reader.read_u16_le()?,
+			bits_per_sample: reader.read_u16_le()?,
+		};
+
+		let mut remaining = chunk_size - 16;
+		if header.format_code == FORMAT_EXTENSIBLE && remaining >= 24 {
+			let _extra_size = reader.read_u16_le()?;
+			let _valid_bits = reader.read_u16_le()?;
+			let _channel_mask = reader.read_u32_le()?;
+			let mut sub_format = [0u8; 16];
+			reader.read_exact(&mut sub_format)?;
+			header.format_code = u16::from_le_bytes([sub_format[0], sub_format[1]]);
+			remaining -= 24;
+		}
+
+		if remaining > 0 {
+			let mut buf = vec![0u8; remaining as usize];
+			reader.read_exact(&mut buf)?;
+		}
+		Ok(header)
+	}
+
+	/// Size of the `fmt ` chunk body written by [`WavHeader::write_fmt`].
+	pub fn fmt_size(format: &WavFormat) -> u32 {
+		match format.format_code {
+			3 => 18,
+			0x11 => 20,
+			_ => 16,
+		}
+	}
+
+	pub fn write_fmt<W: MediaWrite>(writer: &mut W, format: &WavFormat) -> Result<()> {
+		writer.write_u16_le(format.format_code)?;
+		writer.write_u16_le(format.channels.count() as u16)?;
+		writer.write_u32_le(format.sample_rate)?;
+		writer.write_u32_le(format.byte_rate())?;
+		writer.write_u16_le(format.block_align())?;
+		writer.write_u16_le(format.bit_depth)?;
+
+		if format.format_code == 3 {
+			writer.write_u16_le(0)?;
+		} else if format.format_code == 0x11 {
+			writer.write_u16_le(4)?;
+			let spb = ((512 - 4 * format.channels.count() as usize) * 2 + 1) as u16;
+			writer.write_u16_le(spb)?;
+		}
+		Ok(())
+	}
+
 	pub fn to_format(&self) -> WavFormat {
 		WavFormat {
 			channels: self.channels,
@@ -1,5 +1,9 @@
 use std::collections::HashMap;
 
+use crate::container::riff;
+use crate::io::{MediaWrite, WritePrimitives};
+use crate::message::Result;
+
 #[derive(Debug, Clone)]
 pub struct WavMetadata {
 	pub fields: HashMap<String, String>,
@@ -41,6 +45,72 @@ impl WavMetadata {
 	pub fn is_empty(&self) -> bool {
 		self.fields.is_empty()
 	}
+
+	/// Parse the body of a `LIST` chunk, keeping the known `INFO` entries.
+	pub fn parse_list(&mut self, data: &[u8]) {
+		let Some((_, chunks)) = riff::split_list(data).filter(|(form, _)| form == b"INFO") else {
+			return;
+		};
+		for (id, body) in riff::Chunks::new(chunks) {
+			let value = String::from_utf8_lossy(body).trim_end_matches('\0').to_string();
+			if let Some(key) = Self::info_key(&id) {
+				self.set(key, value);
+			}
+		}
+	}
+
+	fn info_key(id: &[u8]) -> Option<&'static str> {
+		match id {
+			b"IART" => Some("artist"),
+			b"INAM" => Some("title"),
+			b"ICOM" => Some("comment"),
+			b"ICOP" => Some("copyright"),
+			b"ISFT" => Some("software"),
+			b"IGNR" => Some("genre"),
+			b"ITRK" => Some("track"),
+			_ => None,
+		}
+	}
+
+	fn info_id(key: &str) -> Option<&'static [u8; 4]> {
+		match key {
+			"artist" => Some(b"IART"),
+			"title" => Some(b"INAM"),
+			"comment" => Some(b"ICOM"),
+			"copyright" => Some(b"ICOP"),
+			"software" => Some(b"ISFT"),
+			"genre" => Some(b"IGNR"),
+			"track" => Some(b"ITRK"),
+			_ => None,
+		}
+	}
+
+	/// Size of the `LIST` chunk body written by [`WavMetadata::write_list`].
+	pub fn list_size(&self) -> u64 {
+		let fields = self.fields.iter().filter(|(key, _)| Self::info_id(key).is_some());
+		fields.fold(4, |acc, (_, value)| {
+			let size = value.len() as u64 + 1;
+			acc + 8 + size + size % 2
+		})
+	}
+
+	/// Write the `INFO` form of a `LIST` chunk body, without the chunk header.
+	pub fn write_list<W: MediaWrite>(&self, writer: &mut W) -> Result<()> {
+		writer.write_all(b"INFO")?;
+		for (field, value) in &self.fields {
+			let Some(id) = Self::info_id(field) else {
+				continue;
+			};
+			let data = format!("{}\0", value);
+			writer.write_all(id)?;
+			writer.write_u32_le(data.len() as u32)?;
+			writer.write_all(data.as_bytes())?;
+			if data.len() % 2 == 1 {
+				writer.write_u8(0)?;
+			}
+		}
+		Ok(())
+	}
 }
 
 impl Default for WavMetadata {
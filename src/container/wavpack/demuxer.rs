@@ -0,0 +1,160 @@
+use super::WavPackFormat;
+use crate::codecs;
+use crate::codecs::audio::wavpack::{BlockHeader, block};
+use crate::container::Metadata;
+use crate::container::apev2::ApeTag;
+use crate::core::frame::Channels;
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream, time};
+use crate::io::{MediaRead, MediaSeek, ReadPrimitives, SeekFrom};
+use crate::{error, message::Result};
+
+/// Demuxer for WavPack (`.wv`) files. Each packet holds all blocks of one
+/// frame, from the block flagged initial to the one flagged final, headers
+/// included. Blocks without samples are skipped.
+pub struct WavPackDemuxer<R: MediaRead + MediaSeek> {
+	reader: R,
+	format: WavPackFormat,
+	streams: stream::Streams,
+	metadata: Metadata,
+	/// Offset of the trailing APEv2 tag, or the file size.
+	data_end: u64,
+	position: u64,
+	pending: Option<Packet>,
+}
+
+impl<R: MediaRead + MediaSeek> WavPackDemuxer<R> {
+	pub fn new(mut reader: R) -> Result<Self> {
+		let file_size = reader.seek(SeekFrom::End(0))?;
+		let (metadata, data_end) = match ApeTag::read(&mut reader)? {
+			Some(tag) => (tag.metadata, tag.start),
+			None => (Metadata::new(), file_size),
+		};
+
+		let mut position = 0;
+		let first = Self::read_frame(&mut reader, &mut position, data_end)?;
+		let (header, frame) = first.ok_or_else(|| error!("wavpack file has no audio blocks"))?;
+
+		let body = &frame[block::HEADER_SIZE..header.block_size()];
+		let (sample_rate, channel_count) = block::stream_info(&header, body)?;
+		let channel_count = match channel_count {
+			Some(count) => count,
+			None => Self::count_channels(&frame)?,
+		};
+
+		let format = WavPackFormat {
+			channels: Channels::from_count(channel_count),
+			sample_rate,
+			bytes_per_sample: header.bytes_stored(),
+			float: header.is_float(),
+			total_samples: header.total_samples,
+		};
+		format.validate()?;
+
+		let time = time::Time::new(1, sample_rate);
+		let codec_name = codecs::audio::WAVPACK.to_string();
+		let stream = stream::Stream::new(0, 0, stream::StreamKind::Audio, codec_name, time);
+		let streams = stream::Streams::new(vec![stream]);
+
+		let pending = Some(Self::make_packet(header, frame, sample_rate));
+		Ok(Self { reader, format, streams, metadata, data_end, position, pending })
+	}
+
+	/// Without a channel info sub-block each block adds one or two channels.
+	fn count_channels(frame: &[u8]) -> Result<u8> {
+		let mut data = frame;
+		let mut count = 0;
+		while !data.is_empty() {
+			let header = BlockHeader::parse(data)?;
+			count += header.channel_count();
+			data = &data[header.block_size()..];
+		}
+		u8::try_from(count).map_err(|_| error!("wavpack channel count {} is not supported", count))
+	}
+
+	fn read_block(
+		reader: &mut R,
+		position: &mut u64,
+		end: u64,
+	) -> Result<Option<(BlockHeader, Vec<u8>)>> {
+		if *position + block::HEADER_SIZE as u64 > end {
+			return Ok(None);
+		}
+
+		let mut data = vec![0u8; block::HEADER_SIZE];
+		reader.read_exact(&mut data)?;
+		let header = BlockHeader::parse(&data)?;
+		if *position + header.block_size() as u64 > end {
+			return Err(error!("wavpack block at offset {} is truncated", position));
+		}
+
+		data.resize(header.block_size(), 0);
+		reader.read_exact(&mut data[block::HEADER_SIZE..])?;
+		*position += header.block_size() as u64;
+		Ok(Some((header, data)))
+	}
+
+	fn read_frame(
+		reader: &mut R,
+		position: &mut u64,
+		end: u64,
+	) -> Result<Option<(BlockHeader, Vec<u8>)>> {
+		loop {
+			let Some((header, mut frame)) = Self::read_block(reader, position, end)? else {
+				return Ok(None);
+			};
+			if !header.is_initial() {
+				return Err(error!("wavpack frame does not start with an initial block"));
+			}
+
+			let mut last = header;
+			while !last.is_final() {
+				let Some((next, data)) = Self::read_block(reader, position, end)? else {
+					return Err(error!("wavpack frame ends without a final block"));
+				};
+				frame.extend_from_slice(&data);
+				last = next;
+			}
+
+			if header.block_samples > 0 {
+				return Ok(Some((header, frame)));
+			}
+		}
+	}
+
+	fn make_packet(header: BlockHeader, frame: Vec<u8>, sample_rate: u32) -> Packet {
+		let time = time::Time::new(1, sample_rate);
+		let mut packet = Packet::new(frame, 0, time).with_pts(header.block_index as i64);
+		packet.dts = packet.pts;
+		packet.keyframe = true;
+		packet
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		if let Some(packet) = self.pending.take() {
+			return Ok(Some(packet));
+		}
+
+		match Self::read_frame(&mut self.reader, &mut self.position, self.data_end)? {
+			Some((header, frame)) => Ok(Some(Self::make_packet(header, frame, self.format.sample_rate))),
+			None => Ok(None),
+		}
+	}
+
+	pub fn format(&self) -> WavPackFormat {
+		self.format
+	}
+
+	pub fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+}
+
+impl<R: MediaRead + MediaSeek> Demuxer for WavPackDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
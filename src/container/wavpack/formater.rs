@@ -0,0 +1,45 @@
+use crate::codecs::audio::wavpack::WavPackDecoder;
+use crate::container::wav;
+use crate::core::frame::Channels;
+use crate::{error, message::Result};
+
+#[derive(Debug, Clone, Copy)]
+pub struct WavPackFormat {
+	pub channels: Channels,
+	pub sample_rate: u32,
+	pub bytes_per_sample: usize,
+	pub float: bool,
+	pub total_samples: Option<u64>,
+}
+
+impl WavPackFormat {
+	/// PCM format the decoded stream is written with by default. 8-bit
+	/// streams widen to 16 bits and 32-bit integers become float.
+	pub fn to_wav_format(&self) -> wav::WavFormat {
+		let (bit_depth, format_code) = match (self.bytes_per_sample, self.float) {
+			(1 | 2, _) => (16, 1),
+			(3, _) => (24, 1),
+			_ => (32, 3),
+		};
+		wav::WavFormat {
+			channels: self.channels,
+			sample_rate: self.sample_rate,
+			bit_depth,
+			format_code,
+		}
+	}
+
+	pub fn create_decoder(&self) -> Result<WavPackDecoder> {
+		WavPackDecoder::new(self.sample_rate, self.channels, self.bytes_per_sample, self.float)
+	}
+
+	pub fn validate(&self) -> Result<()> {
+		if self.channels.count() == 0 {
+			return Err(error!("channels must be non-zero"));
+		}
+		if self.sample_rate == 0 {
+			return Err(error!("sample rate must be non-zero"));
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,4 @@
+pub mod demuxer;
+pub mod formater;
+pub use demuxer::WavPackDemuxer;
+pub use formater::WavPackFormat;
@@ -0,0 +1,13 @@
+use super::mkv::{MkvMuxer, MkvProfile, MkvTrack};
+use crate::io::{MediaSeek, MediaWrite};
+use crate::message::Result;
+
+pub use super::mkv::MkvDemuxer as WebmDemuxer;
+
+/// Matroska muxer restricted to the WebM codec set.
+pub fn create_muxer<W: MediaWrite + MediaSeek>(
+	writer: W,
+	tracks: Vec<MkvTrack>,
+) -> Result<MkvMuxer<W>> {
+	MkvMuxer::new(writer, tracks, MkvProfile::WebM)
+}
@@ -0,0 +1,86 @@
+use super::Y4mFormat;
+use super::formater::FRAME_TAG;
+use crate::codecs;
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream};
+use crate::io::{MediaRead, ReadPrimitives};
+use crate::{error, message::Result};
+
+/// Header lines are short; anything longer is not a y4m stream.
+const MAX_LINE_SIZE: usize = 4096;
+
+/// Demuxer for YUV4MPEG2 (`.y4m`) streams. Each packet holds one picture
+/// and its pts counts frames in the stream frame rate.
+pub struct Y4mDemuxer<R: MediaRead> {
+	reader: R,
+	format: Y4mFormat,
+	streams: stream::Streams,
+	frame_index: u64,
+}
+
+impl<R: MediaRead> Y4mDemuxer<R> {
+	pub fn new(mut reader: R) -> Result<Self> {
+		let line = Self::read_line(&mut reader)?.ok_or_else(|| error!("y4m stream is empty"))?;
+		let format = Y4mFormat::parse_header(&line)?;
+
+		let codec_name = codecs::video::RAWVIDEO.to_string();
+		let stream = stream::Stream::new(0, 0, stream::StreamKind::Video, codec_name, format.time());
+		let streams = stream::Streams::new(vec![stream]);
+
+		Ok(Self { reader, format, streams, frame_index: 0 })
+	}
+
+	fn read_line(reader: &mut R) -> Result<Option<String>> {
+		let mut line = Vec::new();
+		let mut byte = [0u8; 1];
+		loop {
+			if reader.read(&mut byte)? == 0 {
+				if line.is_empty() {
+					return Ok(None);
+				}
+				return Err(error!("y4m header line is truncated"));
+			}
+			if byte[0] == b'\n' {
+				break;
+			}
+			if line.len() == MAX_LINE_SIZE {
+				return Err(error!("y4m header line is too long"));
+			}
+			line.push(byte[0]);
+		}
+		let line = String::from_utf8(line).map_err(|_| error!("y4m header line is not text"))?;
+		Ok(Some(line))
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		let Some(line) = Self::read_line(&mut self.reader)? else {
+			return Ok(None);
+		};
+		// frame parameters are allowed after the tag but carry nothing we use
+		if line.split(' ').next() != Some(FRAME_TAG) {
+			return Err(error!("y4m frame {} has no '{}' header", self.frame_index, FRAME_TAG));
+		}
+
+		let mut data = vec![0u8; self.format.frame_size()];
+		self.reader.read_exact(&mut data)?;
+
+		let pts = self.frame_index as i64;
+		self.frame_index += 1;
+
+		let packet = Packet::new(data, 0, self.format.time());
+		Ok(Some(packet.with_pts(pts).with_dts(pts).with_keyframe(true)))
+	}
+
+	pub fn format(&self) -> &Y4mFormat {
+		&self.format
+	}
+}
+
+impl<R: MediaRead> Demuxer for Y4mDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
@@ -0,0 +1,221 @@
+use crate::codecs::video::raw::{RawVideoDecoder, RawVideoEncoder};
+use crate::core::frame::{ChromaLocation, VideoFormat};
+use crate::core::time::Time;
+use crate::{error, message::Result};
+
+pub const SIGNATURE: &str = "YUV4MPEG2";
+pub const FRAME_TAG: &str = "FRAME";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interlace {
+	#[default]
+	Progressive,
+	TopFirst,
+	BottomFirst,
+	/// Signalled per frame.
+	Mixed,
+	Unknown,
+}
+
+impl Interlace {
+	fn from_tag(tag: &str) -> Result<Self> {
+		match tag {
+			"p" => Ok(Self::Progressive),
+			"t" => Ok(Self::TopFirst),
+			"b" => Ok(Self::BottomFirst),
+			"m" => Ok(Self::Mixed),
+			"?" => Ok(Self::Unknown),
+			_ => Err(error!("y4m interlace mode '{}' is invalid", tag)),
+		}
+	}
+
+	fn tag(&self) -> &'static str {
+		match self {
+			Self::Progressive => "p",
+			Self::TopFirst => "t",
+			Self::BottomFirst => "b",
+			Self::Mixed => "m",
+			Self::Unknown => "?",
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Y4mFormat {
+	pub width: u32,
+	pub height: u32,
+	pub framerate_num: u32,
+	pub framerate_den: u32,
+	/// Pixel aspect ratio; 0:0 when unknown.
+	pub aspect_num: u32,
+	pub aspect_den: u32,
+	pub interlace: Interlace,
+	pub colorspace: VideoFormat,
+	/// Chroma siting of 4:2:0 streams.
+	pub chroma_location: ChromaLocation,
+	/// `X` extension tags, without the leading `X`, kept for round trips.
+	pub extensions: Vec<String>,
+}
+
+impl Default for Y4mFormat {
+	fn default() -> Self {
+		Self {
+			width: 0,
+			height: 0,
+			framerate_num: 25,
+			framerate_den: 1,
+			aspect_num: 0,
+			aspect_den: 0,
+			interlace: Interlace::Progressive,
+			colorspace: VideoFormat::YUV420,
+			chroma_location: ChromaLocation::Center,
+			extensions: Vec::new(),
+		}
+	}
+}
+
+impl Y4mFormat {
+	pub fn new(width: u32, height: u32, colorspace: VideoFormat, time: Time) -> Self {
+		Self {
+			width,
+			height,
+			framerate_num: time.den,
+			framerate_den: time.num,
+			colorspace,
+			..Self::default()
+		}
+	}
+
+	pub fn parse_header(line: &str) -> Result<Self> {
+		let mut tokens = line.split(' ').filter(|token| !token.is_empty());
+		if tokens.next() != Some(SIGNATURE) {
+			return Err(error!("missing '{}' header", SIGNATURE));
+		}
+
+		let mut format = Self::default();
+		for token in tokens {
+			let tag_len = token.chars().next().map_or(0, char::len_utf8);
+			let (tag, value) = token.split_at(tag_len);
+			match tag {
+				"W" => format.width = parse_number(tag, value)?,
+				"H" => format.height = parse_number(tag, value)?,
+				"F" => (format.framerate_num, format.framerate_den) = parse_ratio(tag, value)?,
+				"A" => (format.aspect_num, format.aspect_den) = parse_ratio(tag, value)?,
+				"I" => format.interlace = Interlace::from_tag(value)?,
+				"C" => (format.colorspace, format.chroma_location) = parse_colorspace(value)?,
+				"X" => format.extensions.push(value.to_string()),
+				// unknown tags are ignored as the specification asks
+				_ => {}
+			}
+		}
+		format.validate()?;
+		Ok(format)
+	}
+
+	pub fn header_line(&self) -> String {
+		let mut line = format!(
+			"{} W{} H{} F{}:{} I{} A{}:{} C{}",
+			SIGNATURE,
+			self.width,
+			self.height,
+			self.framerate_num,
+			self.framerate_den,
+			self.interlace.tag(),
+			self.aspect_num,
+			self.aspect_den,
+			self.colorspace_tag(),
+		);
+		for extension in &self.extensions {
+			line.push_str(" X");
+			line.push_str(extension);
+		}
+		line.push('\n');
+		line
+	}
+
+	fn colorspace_tag(&self) -> &'static str {
+		match (self.colorspace, self.chroma_location) {
+			(VideoFormat::YUV420, ChromaLocation::Left) => "420mpeg2",
+			(VideoFormat::YUV420, ChromaLocation::TopLeft) => "420paldv",
+			(VideoFormat::YUV420, _) => "420jpeg",
+			(VideoFormat::YUV422, _) => "422",
+			(VideoFormat::YUV444, _) => "444",
+			(VideoFormat::GRAY8, _) => "mono",
+			(VideoFormat::YUV420P10, _) => "420p10",
+			(VideoFormat::YUV422P10, _) => "422p10",
+			(VideoFormat::YUV444P10, _) => "444p10",
+			(VideoFormat::YUV420P12, _) => "420p12",
+			(VideoFormat::YUV422P12, _) => "422p12",
+			(VideoFormat::YUV444P12, _) => "444p12",
+			(VideoFormat::YUV420P16, _) => "420p16",
+			(VideoFormat::YUV422P16, _) => "422p16",
+			(VideoFormat::YUV444P16, _) => "444p16",
+			(VideoFormat::GRAY16, _) => "mono16",
+			// rejected by validate
+			(VideoFormat::RGB24 | VideoFormat::RGBA32, _) => "444",
+		}
+	}
+
+	pub fn time(&self) -> Time {
+		Time::new(self.framerate_den, self.framerate_num)
+	}
+
+	pub fn frame_size(&self) -> usize {
+		self.colorspace.expected_size(self.width, self.height)
+	}
+
+	pub fn create_decoder(&self) -> RawVideoDecoder {
+		RawVideoDecoder::new(self.width, self.height, self.colorspace)
+	}
+
+	pub fn create_encoder(&self) -> RawVideoEncoder {
+		RawVideoEncoder::new(self.width, self.height, self.colorspace, self.time())
+	}
+
+	pub fn validate(&self) -> Result<()> {
+		if self.width == 0 || self.height == 0 {
+			return Err(error!("y4m frame size {}x{} is invalid", self.width, self.height));
+		}
+		if self.framerate_num == 0 || self.framerate_den == 0 {
+			let (num, den) = (self.framerate_num, self.framerate_den);
+			return Err(error!("y4m frame rate {}:{} is invalid", num, den));
+		}
+		if self.colorspace.is_rgb() {
+			return Err(error!("y4m cannot carry {:?} pictures", self.colorspace));
+		}
+		Ok(())
+	}
+}
+
+fn parse_number(tag: &str, value: &str) -> Result<u32> {
+	value.parse().map_err(|_| error!("y4m tag '{}' has invalid value '{}'", tag, value))
+}
+
+fn parse_ratio(tag: &str, value: &str) -> Result<(u32, u32)> {
+	let (num, den) =
+		value.split_once(':').ok_or_else(|| error!("y4m tag '{}' is not a ratio", tag))?;
+	Ok((parse_number(tag, num)?, parse_number(tag, den)?))
+}
+
+fn parse_colorspace(value: &str) -> Result<(VideoFormat, ChromaLocation)> {
+	let colorspace = match value {
+		"420" | "420jpeg" => (VideoFormat::YUV420, ChromaLocation::Center),
+		"420mpeg2" => (VideoFormat::YUV420, ChromaLocation::Left),
+		"420paldv" => (VideoFormat::YUV420, ChromaLocation::TopLeft),
+		"422" => (VideoFormat::YUV422, ChromaLocation::Center),
+		"444" => (VideoFormat::YUV444, ChromaLocation::Center),
+		"mono" => (VideoFormat::GRAY8, ChromaLocation::Center),
+		"420p10" => (VideoFormat::YUV420P10, ChromaLocation::Center),
+		"422p10" => (VideoFormat::YUV422P10, ChromaLocation::Center),
+		"444p10" => (VideoFormat::YUV444P10, ChromaLocation::Center),
+		"420p12" => (VideoFormat::YUV420P12, ChromaLocation::Center),
+		"422p12" => (VideoFormat::YUV422P12, ChromaLocation::Center),
+		"444p12" => (VideoFormat::YUV444P12, ChromaLocation::Center),
+		"420p16" => (VideoFormat::YUV420P16, ChromaLocation::Center),
+		"422p16" => (VideoFormat::YUV422P16, ChromaLocation::Center),
+		"444p16" => (VideoFormat::YUV444P16, ChromaLocation::Center),
+		"mono16" => (VideoFormat::GRAY16, ChromaLocation::Center),
+		_ => return Err(error!("y4m colorspace '{}' is not supported", value)),
+	};
+	Ok(colorspace)
+}
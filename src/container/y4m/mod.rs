@@ -0,0 +1,10 @@
+pub mod demuxer;
+pub mod formater;
+pub mod muxer;
+
+pub use demuxer::Y4mDemuxer;
+pub use formater::{Interlace, Y4mFormat};
+pub use muxer::Y4mMuxer;
+
+pub type Y4mReader<R> = Y4mDemuxer<R>;
+pub type Y4mWriter<W> = Y4mMuxer<W>;
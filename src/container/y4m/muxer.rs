@@ -0,0 +1,59 @@
+use super::Y4mFormat;
+use super::formater::FRAME_TAG;
+use crate::codecs;
+use crate::core::Muxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream, StreamKind};
+use crate::io::{MediaWrite, WritePrimitives};
+use crate::{error, message::Result};
+
+/// Muxer for YUV4MPEG2 (`.y4m`) streams. The header is written up front and
+/// every packet must hold exactly one picture.
+pub struct Y4mMuxer<W: MediaWrite> {
+	writer: W,
+	format: Y4mFormat,
+	streams: stream::Streams,
+}
+
+impl<W: MediaWrite> Y4mMuxer<W> {
+	pub fn new(mut writer: W, format: Y4mFormat) -> Result<Self> {
+		format.validate()?;
+		writer.write_all(format.header_line().as_bytes())?;
+
+		let codec_name = codecs::video::RAWVIDEO.to_string();
+		let mut streams = stream::Streams::new_empty();
+		streams.add(Stream::new(0, 0, StreamKind::Video, codec_name, format.time()));
+
+		Ok(Self { writer, format, streams })
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		if packet.data.len() != self.format.frame_size() {
+			let (expected, actual) = (self.format.frame_size(), packet.data.len());
+			return Err(error!("y4m frame has {} bytes, expected {}", actual, expected));
+		}
+		self.writer.write_all(FRAME_TAG.as_bytes())?;
+		self.writer.write_all(b"\n")?;
+		self.writer.write_all(&packet.data)
+	}
+
+	pub fn format(&self) -> &Y4mFormat {
+		&self.format
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		self.writer.flush()
+	}
+}
+
+impl<W: MediaWrite> Muxer for Y4mMuxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+}
@@ -70,7 +70,12 @@ impl Compatible {
 		// video container
 		let mut mp4 = ContainerCompatible::new(container::MP4);
 		mp4.supports_video([codecs::video::H264, codecs::video::H265]);
-		mp4.supports_audio([codecs::audio::AAC, codecs::audio::MP3]);
+		mp4.supports_audio([
+			codecs::audio::AAC,
+			codecs::audio::MP3,
+			codecs::audio::ALAC,
+			codecs::audio::OPUS,
+		]);
 		mp4.supports_subtitles([codecs::subtitle::MOV_TEXT]);
 		graph.insert(container::MP4, mp4);
 
@@ -78,6 +83,7 @@ impl Compatible {
 		mkv.supports_video([
 			codecs::video::H264,
 			codecs::video::H265,
+			codecs::video::VP8,
 			codecs::video::VP9,
 			codecs::video::AV1,
 		]);
@@ -86,6 +92,10 @@ impl Compatible {
 			codecs::audio::VORBIS,
 			codecs::audio::OPUS,
 			codecs::audio::MP3,
+			codecs::audio::FLAC,
+			codecs::audio::PCM_S16LE,
+			codecs::audio::PCM_S24LE,
+			codecs::audio::PCM_F32LE,
 		]);
 
 		mkv.supports_subtitles([codecs::subtitle::SRT, codecs::subtitle::ASS, codecs::subtitle::VTT]);
@@ -93,7 +103,7 @@ impl Compatible {
 
 		let mut mov = ContainerCompatible::new(container::MOV);
 		mov.supports_video([codecs::video::H264, codecs::video::H265]);
-		mov.supports_audio([codecs::audio::AAC, codecs::audio::MP3]);
+		mov.supports_audio([codecs::audio::AAC, codecs::audio::MP3, codecs::audio::ALAC]);
 		mov.supports_subtitles([codecs::subtitle::MOV_TEXT]);
 		graph.insert(container::MOV, mov);
 
@@ -104,8 +114,21 @@ impl Compatible {
 		graph.insert(container::WEBM, webm);
 
 		let mut avi = ContainerCompatible::new(container::AVI);
-		avi.supports_video([codecs::video::MPEG4, codecs::video::H264]);
-		avi.supports_audio([codecs::audio::MP3, codecs::audio::AAC]);
+		avi.supports_video([
+			codecs::video::MPEG4,
+			codecs::video::H264,
+			codecs::video::MJPEG,
+			codecs::video::RAWVIDEO,
+		]);
+		avi.supports_audio([
+			codecs::audio::MP3,
+			codecs::audio::MP2,
+			codecs::audio::AAC,
+			codecs::audio::AC3,
+			codecs::audio::PCM_S16LE,
+			codecs::audio::PCM_S24LE,
+			codecs::audio::PCM_F32LE,
+		]);
 		graph.insert(container::AVI, avi);
 
 		let mut ogv = ContainerCompatible::new(container::OGV);
@@ -115,19 +138,50 @@ impl Compatible {
 
 		let mut flv = ContainerCompatible::new(container::FLV);
 		flv.supports_video([codecs::video::H264, codecs::video::VP6]);
-		flv.supports_audio([codecs::audio::MP3]);
+		flv.supports_audio([codecs::audio::MP3, codecs::audio::AAC]);
 		graph.insert(container::FLV, flv);
 
 		let mut mxf = ContainerCompatible::new(container::MXF);
-		mxf.supports_video([codecs::video::MPEG2, codecs::video::H264, codecs::video::H265]);
-		mxf.supports_audio([codecs::audio::PCM_S16LE, codecs::audio::AAC]);
+		mxf.supports_video([
+			codecs::video::MPEG2,
+			codecs::video::H264,
+			codecs::video::H265,
+			codecs::video::RAWVIDEO,
+		]);
+		mxf.supports_audio([codecs::audio::PCM_S16LE, codecs::audio::PCM_S24LE, codecs::audio::AAC]);
 		graph.insert(container::MXF, mxf);
 
 		let mut ts = ContainerCompatible::new(container::TS);
 		ts.supports_video([codecs::video::H264, codecs::video::H265, codecs::video::MPEG2]);
 		ts.supports_audio([codecs::audio::AAC, codecs::audio::MP2]);
+		// blu-ray and avchd files, read with their 192-byte packets
+		for name in [container::M2TS, container::MTS] {
+			graph.insert(name, ContainerCompatible { name: name.into(), ..ts.clone() });
+		}
 		graph.insert(container::TS, ts);
 
+		let mut nut = ContainerCompatible::new(container::NUT);
+		nut.supports_video([codecs::video::RAWVIDEO]);
+		nut.supports_audio([
+			codecs::audio::PCM_S16LE,
+			codecs::audio::PCM_S24LE,
+			codecs::audio::PCM_F32LE,
+		]);
+		nut.supports_subtitles([codecs::subtitle::SRT, codecs::subtitle::ASS]);
+		graph.insert(container::NUT, nut);
+
+		let mut y4m = ContainerCompatible::new(container::Y4M);
+		y4m.supports_video([codecs::video::RAWVIDEO]);
+		graph.insert(container::Y4M, y4m);
+
+		let mut yuv = ContainerCompatible::new(container::YUV);
+		yuv.supports_video([codecs::video::RAWVIDEO]);
+		graph.insert(container::YUV, yuv);
+
+		let mut rgb = ContainerCompatible::new(container::RGB);
+		rgb.supports_video([codecs::video::RAWVIDEO]);
+		graph.insert(container::RGB, rgb);
+
 		// audio container
 		let mut mp3 = ContainerCompatible::new(container::MP3);
 		mp3.supports_audio([codecs::audio::MP3]);
@@ -153,6 +207,14 @@ impl Compatible {
 		]);
 		graph.insert(container::WAV, wav);
 
+		let mut w64 = ContainerCompatible::new(container::W64);
+		w64.supports_audio([
+			codecs::audio::PCM_S16LE,
+			codecs::audio::PCM_S24LE,
+			codecs::audio::PCM_F32LE,
+		]);
+		graph.insert(container::W64, w64);
+
 		let mut m4a = ContainerCompatible::new(container::M4A);
 		m4a.supports_audio([codecs::audio::AAC, codecs::audio::ALAC]);
 		graph.insert(container::M4A, m4a);
@@ -165,6 +227,36 @@ impl Compatible {
 		ogg_audio.supports_audio([codecs::audio::VORBIS, codecs::audio::OPUS]);
 		graph.insert(container::OGG, ogg_audio);
 
+		let mut caf = ContainerCompatible::new(container::CAF);
+		caf.supports_audio([
+			codecs::audio::PCM_S16LE,
+			codecs::audio::PCM_S24LE,
+			codecs::audio::PCM_F32LE,
+			codecs::audio::AAC,
+			codecs::audio::ALAC,
+		]);
+		graph.insert(container::CAF, caf);
+
+		let mut dsf = ContainerCompatible::new(container::DSF);
+		dsf.supports_audio([codecs::audio::DSD_LSBF_PLANAR, codecs::audio::DSD_MSBF_PLANAR]);
+		graph.insert(container::DSF, dsf);
+
+		let mut dff = ContainerCompatible::new(container::DFF);
+		dff.supports_audio([codecs::audio::DSD_MSBF]);
+		graph.insert(container::DFF, dff);
+
+		let mut wv = ContainerCompatible::new(container::WV);
+		wv.supports_audio([codecs::audio::WAVPACK]);
+		graph.insert(container::WV, wv);
+
+		let mut tta = ContainerCompatible::new(container::TTA);
+		tta.supports_audio([codecs::audio::TTA]);
+		graph.insert(container::TTA, tta);
+
+		let mut ape = ContainerCompatible::new(container::APE);
+		ape.supports_audio([codecs::audio::APE]);
+		graph.insert(container::APE, ape);
+
 		let mut raw = ContainerCompatible::new(container::RAW);
 		raw.supports_audio([
 			codecs::audio::PCM_S16LE,
@@ -0,0 +1,85 @@
+/// CRC-32 as used by zip and PNG (reflected polynomial 0xEDB88320).
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32 {
+	value: u32,
+}
+
+impl Default for Crc32 {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Crc32 {
+	const TABLE: [u32; 256] = Self::table();
+
+	const fn table() -> [u32; 256] {
+		let mut table = [0u32; 256];
+		let mut index = 0;
+		while index < 256 {
+			let mut value = index as u32;
+			let mut bit = 0;
+			while bit < 8 {
+				value = if value & 1 != 0 { (value >> 1) ^ 0xEDB8_8320 } else { value >> 1 };
+				bit += 1;
+			}
+			table[index] = value;
+			index += 1;
+		}
+		table
+	}
+
+	pub fn new() -> Self {
+		Self { value: u32::MAX }
+	}
+
+	pub fn update(&mut self, data: &[u8]) {
+		for byte in data {
+			let index = (self.value ^ *byte as u32) & 0xff;
+			self.value = (self.value >> 8) ^ Self::TABLE[index as usize];
+		}
+	}
+
+	pub fn finish(&self) -> u32 {
+		!self.value
+	}
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+	let mut crc = Crc32::new();
+	crc.update(data);
+	crc.finish()
+}
+
+/// CRC-32 of MPEG-2 PSI sections: polynomial 0x04C11DB7, most significant bit
+/// first and no final inversion.
+pub fn crc32_mpeg(data: &[u8]) -> u32 {
+	crc32_msb_first(u32::MAX, data)
+}
+
+/// CRC-32 of NUT packets and frame headers: the MPEG-2 polynomial, starting
+/// from zero.
+pub fn crc32_nut(data: &[u8]) -> u32 {
+	crc32_msb_first(0, data)
+}
+
+fn crc32_msb_first(initial: u32, data: &[u8]) -> u32 {
+	const TABLE: [u32; 256] = mpeg_table();
+	data.iter().fold(initial, |crc, byte| (crc << 8) ^ TABLE[((crc >> 24) ^ *byte as u32) as usize])
+}
+
+const fn mpeg_table() -> [u32; 256] {
+	let mut table = [0u32; 256];
+	let mut index = 0;
+	while index < 256 {
+		let mut value = (index as u32) << 24;
+		let mut bit = 0;
+		while bit < 8 {
+			value = if value & 0x8000_0000 != 0 { (value << 1) ^ 0x04C1_1DB7 } else { value << 1 };
+			bit += 1;
+		}
+		table[index] = value;
+		index += 1;
+	}
+	table
+}
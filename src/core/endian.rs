@@ -1 +1,9 @@
-
+/// Reverse the byte order of every `width`-byte sample in `data`.
+pub fn swap_samples(data: &mut [u8], width: usize) {
+	if width < 2 {
+		return;
+	}
+	for sample in data.chunks_exact_mut(width) {
+		sample.reverse();
+	}
+}
@@ -24,21 +24,23 @@ pub enum FrameData {
 pub struct Frame {
 	pub pts: i64,
 	pub dts: Option<i64>,
+	/// Display duration in stream ticks, when known.
+	pub duration: Option<i64>,
 	pub stream_id: u32,
 	pub data: FrameData,
 }
 
 impl Frame {
 	pub fn new_audio(audio: FrameAudio, stream_id: u32) -> Self {
-		Self { pts: 0, dts: None, data: FrameData::Audio(audio), stream_id }
+		Self { pts: 0, dts: None, duration: None, data: FrameData::Audio(audio), stream_id }
 	}
 
 	pub fn new_video(video: FrameVideo, stream_id: u32) -> Self {
-		Self { pts: 0, dts: None, data: FrameData::Video(video), stream_id }
+		Self { pts: 0, dts: None, duration: None, data: FrameData::Video(video), stream_id }
 	}
 
 	pub fn new_subtitle(subtitle: FrameSubtitle, stream_id: u32) -> Self {
-		Self { pts: 0, dts: None, data: FrameData::Subtitle(subtitle), stream_id }
+		Self { pts: 0, dts: None, duration: None, data: FrameData::Subtitle(subtitle), stream_id }
 	}
 
 	pub fn with_pts(mut self, pts: i64) -> Self {
@@ -46,6 +48,11 @@ impl Frame {
 		self
 	}
 
+	pub fn with_duration(mut self, duration: i64) -> Self {
+		self.duration = Some(duration);
+		self
+	}
+
 	#[inline]
 	pub fn audio(&self) -> Option<&FrameAudio> {
 		match &self.data {
@@ -6,6 +6,30 @@ pub enum VideoFormat {
 	YUV422,
 	YUV444,
 	GRAY8,
+	// planar formats with 16-bit little-endian samples holding `bit_depth` bits
+	YUV420P10,
+	YUV422P10,
+	YUV444P10,
+	YUV420P12,
+	YUV422P12,
+	YUV444P12,
+	YUV420P16,
+	YUV422P16,
+	YUV444P16,
+	GRAY16,
+}
+
+/// Where subsampled chroma samples sit relative to the luma grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChromaLocation {
+	/// Horizontally co-sited with the left luma sample, vertically centered
+	/// (MPEG-2 and later).
+	Left,
+	/// Centered between luma samples (JPEG, MPEG-1).
+	#[default]
+	Center,
+	/// Co-sited with the top-left luma sample (PAL DV).
+	TopLeft,
 }
 
 impl VideoFormat {
@@ -15,20 +39,119 @@ impl VideoFormat {
 			VideoFormat::RGBA32 => Some(4),
 			VideoFormat::YUV444 => Some(3),
 			VideoFormat::GRAY8 => Some(1),
-			VideoFormat::YUV420 | VideoFormat::YUV422 => None,
+			VideoFormat::YUV444P10 | VideoFormat::YUV444P12 | VideoFormat::YUV444P16 => Some(6),
+			VideoFormat::GRAY16 => Some(2),
+			_ => None,
 		}
 	}
 
-	pub fn expected_size(&self, width: u32, height: u32) -> usize {
+	/// Name as used by `--video pix_fmt=`.
+	pub fn name(&self) -> &'static str {
 		match self {
-			VideoFormat::RGB24 => (width * height * 3) as usize,
-			VideoFormat::RGBA32 => (width * height * 4) as usize,
-			VideoFormat::YUV444 => (width * height * 3) as usize,
-			VideoFormat::GRAY8 => (width * height) as usize,
-			VideoFormat::YUV420 => ((width * height * 3) / 2) as usize,
-			VideoFormat::YUV422 => (width * height * 2) as usize,
+			VideoFormat::RGB24 => "rgb24",
+			VideoFormat::RGBA32 => "rgba",
+			VideoFormat::YUV420 => "yuv420p",
+			VideoFormat::YUV422 => "yuv422p",
+			VideoFormat::YUV444 => "yuv444p",
+			VideoFormat::GRAY8 => "gray",
+			VideoFormat::YUV420P10 => "yuv420p10le",
+			VideoFormat::YUV422P10 => "yuv422p10le",
+			VideoFormat::YUV444P10 => "yuv444p10le",
+			VideoFormat::YUV420P12 => "yuv420p12le",
+			VideoFormat::YUV422P12 => "yuv422p12le",
+			VideoFormat::YUV444P12 => "yuv444p12le",
+			VideoFormat::YUV420P16 => "yuv420p16le",
+			VideoFormat::YUV422P16 => "yuv422p16le",
+			VideoFormat::YUV444P16 => "yuv444p16le",
+			VideoFormat::GRAY16 => "gray16le",
 		}
 	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		const ALL: [VideoFormat; 16] = [
+			VideoFormat::RGB24,
+			VideoFormat::RGBA32,
+			VideoFormat::YUV420,
+			VideoFormat::YUV422,
+			VideoFormat::YUV444,
+			VideoFormat::GRAY8,
+			VideoFormat::YUV420P10,
+			VideoFormat::YUV422P10,
+			VideoFormat::YUV444P10,
+			VideoFormat::YUV420P12,
+			VideoFormat::YUV422P12,
+			VideoFormat::YUV444P12,
+			VideoFormat::YUV420P16,
+			VideoFormat::YUV422P16,
+			VideoFormat::YUV444P16,
+			VideoFormat::GRAY16,
+		];
+		let name = name.to_lowercase();
+		ALL.into_iter().find(|format| format.name() == name)
+	}
+
+	/// Significant bits per component.
+	pub fn bit_depth(&self) -> u32 {
+		match self {
+			VideoFormat::YUV420P10 | VideoFormat::YUV422P10 | VideoFormat::YUV444P10 => 10,
+			VideoFormat::YUV420P12 | VideoFormat::YUV422P12 | VideoFormat::YUV444P12 => 12,
+			VideoFormat::YUV420P16 | VideoFormat::YUV422P16 | VideoFormat::YUV444P16 => 16,
+			VideoFormat::GRAY16 => 16,
+			_ => 8,
+		}
+	}
+
+	/// Bytes each stored component takes (1 or 2).
+	pub fn bytes_per_component(&self) -> usize {
+		if self.bit_depth() > 8 { 2 } else { 1 }
+	}
+
+	pub fn is_rgb(&self) -> bool {
+		matches!(self, VideoFormat::RGB24 | VideoFormat::RGBA32)
+	}
+
+	pub fn is_gray(&self) -> bool {
+		matches!(self, VideoFormat::GRAY8 | VideoFormat::GRAY16)
+	}
+
+	/// Horizontal and vertical chroma subsampling as log2 factors.
+	pub fn chroma_shift(&self) -> (u32, u32) {
+		match self {
+			VideoFormat::YUV420 | VideoFormat::YUV420P10 | VideoFormat::YUV420P12 => (1, 1),
+			VideoFormat::YUV420P16 => (1, 1),
+			VideoFormat::YUV422 | VideoFormat::YUV422P10 | VideoFormat::YUV422P12 => (1, 0),
+			VideoFormat::YUV422P16 => (1, 0),
+			_ => (0, 0),
+		}
+	}
+
+	/// Width and height in samples of every plane. Packed RGB formats have
+	/// a single plane whose width counts pixels.
+	pub fn planes(&self, width: u32, height: u32) -> Vec<(u32, u32)> {
+		if self.is_rgb() || self.is_gray() {
+			return vec![(width, height)];
+		}
+		let (shift_x, shift_y) = self.chroma_shift();
+		let chroma = (width.div_ceil(1 << shift_x), height.div_ceil(1 << shift_y));
+		vec![(width, height), chroma, chroma]
+	}
+
+	/// Bytes in one row of a plane of the given sample width.
+	pub fn row_size(&self, plane_width: u32) -> usize {
+		match self {
+			VideoFormat::RGB24 => plane_width as usize * 3,
+			VideoFormat::RGBA32 => plane_width as usize * 4,
+			_ => plane_width as usize * self.bytes_per_component(),
+		}
+	}
+
+	pub fn expected_size(&self, width: u32, height: u32) -> usize {
+		let planes = self.planes(width, height);
+		planes
+			.iter()
+			.map(|(plane_width, plane_height)| self.row_size(*plane_width) * *plane_height as usize)
+			.sum()
+	}
 }
 
 #[derive(Debug, Clone)]
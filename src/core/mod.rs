@@ -1,4 +1,6 @@
 pub mod compatible;
+pub mod crc;
+pub mod endian;
 pub mod frame;
 pub mod packet;
 pub mod stream;
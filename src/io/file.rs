@@ -13,8 +13,11 @@ impl File {
 		Ok(Self { file })
 	}
 
+	/// Opened for reading too, so muxers can move data they already wrote.
 	pub fn create(path: &str) -> Result<Self> {
-		let file = mapper_error(std::fs::File::create(path), path)?;
+		let mut options = std::fs::OpenOptions::new();
+		options.read(true).write(true).create(true).truncate(true);
+		let file = mapper_error(options.open(path), path)?;
 		Ok(Self { file })
 	}
 }
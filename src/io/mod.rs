@@ -6,7 +6,7 @@ pub mod stdio;
 mod writer;
 
 pub use cursor::Cursor;
-pub use file::File;
+pub use file::{File, mapper_error};
 pub use reader::{
 	BufferedReader, BufferedWriter, DEFAULT_BUFFER_SIZE, MediaRead, ReadPrimitives, StdReadAdapter,
 };
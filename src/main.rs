@@ -1,13 +1,17 @@
 use clap::Parser;
 use ffmpreg::EXIT_SUCCESS;
 use ffmpreg::cli::color;
-use ffmpreg::cli::{Cli, executor};
+use ffmpreg::cli::{Cli, STDIO_PATH, executor};
 
 fn main() {
 	let cli = Cli::parse();
+	// nothing but the stream may go to standard output
+	let piped = cli.output == STDIO_PATH;
 	if let Err(message) = executor::execute(cli) {
 		message.render_and_exit();
 	}
-	color::print_success(None);
+	if !piped {
+		color::print_success(None);
+	}
 	std::process::exit(EXIT_SUCCESS);
 }
@@ -57,7 +57,8 @@ impl Message {
 
 	pub fn render(&self) {
 		match self.kind {
-			MessageKind::Error => println!("{}{}:{} {}", RED, self.kind, RESET, self.text),
+			// standard output may be carrying a stream
+			MessageKind::Error => eprintln!("{}{}:{} {}", RED, self.kind, RESET, self.text),
 			MessageKind::Warning => println!("{}{}:{} {}", YELLOW, self.kind, RESET, self.text),
 			MessageKind::Info => println!("{}{}:{} {}", BLUE, self.kind, RESET, self.text),
 		}
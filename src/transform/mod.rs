@@ -1,5 +1,7 @@
 pub mod normalize;
+pub mod video;
 pub mod volume;
 
 pub use normalize::Normalize;
+pub use video::{PixelConverter, Scaler};
 pub use volume::Volume;
@@ -0,0 +1,159 @@
+use crate::core::frame::{ChromaLocation, VideoFormat};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMatrix {
+	#[default]
+	Bt601,
+	Bt709,
+	Bt2020,
+}
+
+impl ColorMatrix {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name.to_lowercase().as_str() {
+			"bt601" | "601" | "smpte170m" | "bt470bg" => Some(Self::Bt601),
+			"bt709" | "709" => Some(Self::Bt709),
+			"bt2020" | "2020" | "bt2020nc" => Some(Self::Bt2020),
+			_ => None,
+		}
+	}
+
+	/// Red and blue luma weights (Kr, Kb).
+	pub fn coefficients(&self) -> (f32, f32) {
+		match self {
+			Self::Bt601 => (0.299, 0.114),
+			Self::Bt709 => (0.2126, 0.0722),
+			Self::Bt2020 => (0.2627, 0.0593),
+		}
+	}
+
+	/// Normalized R'G'B' to Y' in 0..1 and Cb/Cr in -0.5..0.5.
+	pub fn to_ycbcr(&self, r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+		let (kr, kb) = self.coefficients();
+		let y = kr * r + (1.0 - kr - kb) * g + kb * b;
+		let cb = (b - y) / (2.0 * (1.0 - kb));
+		let cr = (r - y) / (2.0 * (1.0 - kr));
+		(y, cb, cr)
+	}
+
+	pub fn to_rgb(&self, y: f32, cb: f32, cr: f32) -> (f32, f32, f32) {
+		let (kr, kb) = self.coefficients();
+		let r = y + 2.0 * (1.0 - kr) * cr;
+		let b = y + 2.0 * (1.0 - kb) * cb;
+		let g = (y - kr * r - kb * b) / (1.0 - kr - kb);
+		(r, g, b)
+	}
+}
+
+/// Quantization range of luma and chroma codes. RGB is always full range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorRange {
+	/// Luma 16..235 and chroma 16..240, scaled to the bit depth.
+	#[default]
+	Limited,
+	Full,
+}
+
+impl ColorRange {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name.to_lowercase().as_str() {
+			"limited" | "tv" | "mpeg" => Some(Self::Limited),
+			"full" | "pc" | "jpeg" => Some(Self::Full),
+			_ => None,
+		}
+	}
+
+	pub fn luma(&self, bit_depth: u32) -> (f32, f32) {
+		let shift = (1u32 << (bit_depth - 8)) as f32;
+		match self {
+			Self::Limited => (219.0 * shift, 16.0 * shift),
+			Self::Full => (((1u64 << bit_depth) - 1) as f32, 0.0),
+		}
+	}
+
+	pub fn chroma(&self, bit_depth: u32) -> (f32, f32) {
+		let center = (1u32 << (bit_depth - 1)) as f32;
+		match self {
+			Self::Limited => (224.0 * (1u32 << (bit_depth - 8)) as f32, center),
+			Self::Full => (((1u64 << bit_depth) - 1) as f32, center),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ColorSpace {
+	pub matrix: ColorMatrix,
+	pub range: ColorRange,
+	pub chroma_location: ChromaLocation,
+}
+
+impl ColorSpace {
+	pub fn new(matrix: ColorMatrix, range: ColorRange) -> Self {
+		Self { matrix, range, chroma_location: ChromaLocation::default() }
+	}
+
+	pub fn with_chroma_location(mut self, chroma_location: ChromaLocation) -> Self {
+		self.chroma_location = chroma_location;
+		self
+	}
+
+	/// Position of the first chroma sample on the luma grid, in luma samples,
+	/// for the given log2 subsampling.
+	pub fn chroma_offset(&self, shift_x: u32, shift_y: u32) -> (f32, f32) {
+		let half = |shift: u32| ((1u32 << shift) as f32 - 1.0) / 2.0;
+		let x = match self.chroma_location {
+			ChromaLocation::Center => half(shift_x),
+			ChromaLocation::Left | ChromaLocation::TopLeft => 0.0,
+		};
+		let y = match self.chroma_location {
+			ChromaLocation::TopLeft => 0.0,
+			ChromaLocation::Left | ChromaLocation::Center => half(shift_y),
+		};
+		(x, y)
+	}
+
+	/// An RGB colour as code values of `format`, per packed channel for RGB
+	/// and per plane otherwise.
+	pub fn fill_codes(&self, format: VideoFormat, rgb: [u8; 3]) -> [u16; 4] {
+		let [r, g, b] = rgb;
+		if format.is_rgb() {
+			return [r as u16, g as u16, b as u16, 255];
+		}
+		let depth = format.bit_depth();
+		let (y, cb, cr) = self.matrix.to_ycbcr(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+		let (luma_scale, luma_offset) = self.range.luma(depth);
+		let (chroma_scale, chroma_center) = self.range.chroma(depth);
+		let max = ((1u64 << depth) - 1) as f32;
+		let code = |value: f32| value.round().clamp(0.0, max) as u16;
+		[
+			code(y * luma_scale + luma_offset),
+			code(cb * chroma_scale + chroma_center),
+			code(cr * chroma_scale + chroma_center),
+			0,
+		]
+	}
+}
+
+/// Parses `black`, `white`, `gray`, `red`, `green`, `blue` or a `#rrggbb` /
+/// `0xrrggbb` hex triplet.
+pub fn parse_rgb(name: &str) -> Option<[u8; 3]> {
+	let name = name.to_lowercase();
+	let named = match name.as_str() {
+		"black" => Some([0, 0, 0]),
+		"white" => Some([255, 255, 255]),
+		"gray" | "grey" => Some([128, 128, 128]),
+		"red" => Some([255, 0, 0]),
+		"green" => Some([0, 255, 0]),
+		"blue" => Some([0, 0, 255]),
+		_ => None,
+	};
+	if named.is_some() {
+		return named;
+	}
+	let hex = name.strip_prefix('#').or_else(|| name.strip_prefix("0x")).unwrap_or(&name);
+	if hex.len() != 6 {
+		return None;
+	}
+	let value = u32::from_str_radix(hex, 16).ok()?;
+	Some([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
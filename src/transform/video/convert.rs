@@ -0,0 +1,370 @@
+use super::color::ColorSpace;
+use super::resample::{self, Taps};
+use crate::core::Transform;
+use crate::core::frame::{Frame, FrameVideo, VideoFormat};
+use crate::{error, message::Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaneLayout {
+	pub offset: usize,
+	pub stride: usize,
+}
+
+impl PlaneLayout {
+	/// Planes stored back to back without row padding, as in `FrameVideo::data`.
+	pub fn packed(format: VideoFormat, width: u32, height: u32) -> Vec<PlaneLayout> {
+		let mut offset = 0;
+		let planes = format.planes(width, height);
+		planes
+			.into_iter()
+			.map(|(plane_width, plane_height)| {
+				let stride = format.row_size(plane_width);
+				let layout = PlaneLayout { offset, stride };
+				offset += stride * plane_height as usize;
+				layout
+			})
+			.collect()
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Model {
+	Rgb,
+	YCbCr,
+}
+
+/// Normalized picture: RGB in 0..1, or Y' in 0..1 with Cb/Cr in -0.5..0.5
+/// and chroma planes subsampled by `shift`. Gray pictures carry one plane.
+struct Picture {
+	width: usize,
+	height: usize,
+	model: Model,
+	shift: (u32, u32),
+	planes: Vec<Vec<f32>>,
+	alpha: Option<Vec<f32>>,
+}
+
+pub struct PixelConverter {
+	target: VideoFormat,
+	source_color: ColorSpace,
+	target_color: ColorSpace,
+}
+
+impl PixelConverter {
+	pub fn new(target: VideoFormat) -> Self {
+		Self { target, source_color: ColorSpace::default(), target_color: ColorSpace::default() }
+	}
+
+	pub fn with_color(mut self, color: ColorSpace) -> Self {
+		self.source_color = color;
+		self.target_color = color;
+		self
+	}
+
+	pub fn with_source_color(mut self, color: ColorSpace) -> Self {
+		self.source_color = color;
+		self
+	}
+
+	pub fn with_target_color(mut self, color: ColorSpace) -> Self {
+		self.target_color = color;
+		self
+	}
+
+	pub fn target(&self) -> VideoFormat {
+		self.target
+	}
+
+	pub fn convert(&self, video: &FrameVideo) -> Result<FrameVideo> {
+		let layout = PlaneLayout::packed(video.format, video.width, video.height);
+		let data =
+			self.convert_strided(&video.data, &layout, video.width, video.height, video.format)?;
+		Ok(FrameVideo::new(data, video.width, video.height, self.target, video.keyframe))
+	}
+
+	/// Converts a picture whose planes are described by `layout` into a tightly
+	/// packed buffer in the target format.
+	pub fn convert_strided(
+		&self,
+		data: &[u8],
+		layout: &[PlaneLayout],
+		width: u32,
+		height: u32,
+		format: VideoFormat,
+	) -> Result<Vec<u8>> {
+		if width == 0 || height == 0 {
+			return Err(error!("cannot convert a {}x{} picture", width, height));
+		}
+		validate_layout(data, layout, width, height, format)?;
+
+		let mut picture = read_picture(data, layout, width, height, format, &self.source_color);
+		let target = self.target;
+
+		if target.is_rgb() {
+			if picture.model == Model::YCbCr {
+				picture = self.to_rgb(picture);
+			}
+			return Ok(write_picture(&picture, target, &self.target_color));
+		}
+
+		let matrix_changes = self.source_color.matrix != self.target_color.matrix;
+		if picture.model == Model::Rgb || (matrix_changes && picture.planes.len() == 3) {
+			if picture.model == Model::YCbCr {
+				picture = self.to_rgb(picture);
+			}
+			picture = self.to_ycbcr(picture);
+		}
+
+		if target.is_gray() {
+			picture.planes.truncate(1);
+		} else if picture.planes.len() == 1 {
+			let (shift_x, shift_y) = target.chroma_shift();
+			let chroma_width = picture.width.div_ceil(1 << shift_x);
+			let chroma_height = picture.height.div_ceil(1 << shift_y);
+			let neutral = vec![0.0; chroma_width * chroma_height];
+			picture.planes.push(neutral.clone());
+			picture.planes.push(neutral);
+			picture.shift = (shift_x, shift_y);
+		} else {
+			let siting_changes = self.source_color.chroma_location != self.target_color.chroma_location;
+			let shift = target.chroma_shift();
+			if picture.shift != shift || (shift != (0, 0) && siting_changes) {
+				self.upsample_chroma(&mut picture);
+				self.downsample_chroma(&mut picture, shift);
+			}
+		}
+
+		Ok(write_picture(&picture, target, &self.target_color))
+	}
+
+	fn to_rgb(&self, mut picture: Picture) -> Picture {
+		if picture.planes.len() == 1 {
+			let luma = picture.planes.pop().unwrap_or_default();
+			picture.planes = vec![luma.clone(), luma.clone(), luma];
+		} else {
+			self.upsample_chroma(&mut picture);
+			let matrix = self.source_color.matrix;
+			let count = picture.width * picture.height;
+			for index in 0..count {
+				let y = picture.planes[0][index];
+				let (cb, cr) = (picture.planes[1][index], picture.planes[2][index]);
+				let (r, g, b) = matrix.to_rgb(y, cb, cr);
+				picture.planes[0][index] = r;
+				picture.planes[1][index] = g;
+				picture.planes[2][index] = b;
+			}
+		}
+		picture.model = Model::Rgb;
+		picture
+	}
+
+	fn to_ycbcr(&self, mut picture: Picture) -> Picture {
+		let matrix = self.target_color.matrix;
+		let count = picture.width * picture.height;
+		for index in 0..count {
+			let (r, g, b) =
+				(picture.planes[0][index], picture.planes[1][index], picture.planes[2][index]);
+			let (y, cb, cr) = matrix.to_ycbcr(r, g, b);
+			picture.planes[0][index] = y;
+			picture.planes[1][index] = cb;
+			picture.planes[2][index] = cr;
+		}
+		picture.model = Model::YCbCr;
+		picture.shift = (0, 0);
+		picture
+	}
+
+	/// Interpolates chroma back onto the luma grid using the source siting.
+	fn upsample_chroma(&self, picture: &mut Picture) {
+		let (shift_x, shift_y) = picture.shift;
+		if (shift_x, shift_y) == (0, 0) {
+			return;
+		}
+		let (width, height) = (picture.width, picture.height);
+		let (chroma_width, chroma_height) =
+			(width.div_ceil(1 << shift_x), height.div_ceil(1 << shift_y));
+		let (offset_x, offset_y) = self.source_color.chroma_offset(shift_x, shift_y);
+		let (factor_x, factor_y) = ((1u32 << shift_x) as f32, (1u32 << shift_y) as f32);
+
+		let horizontal = Taps::linear(chroma_width, width, 1.0 / factor_x, -offset_x / factor_x);
+		let vertical = Taps::linear(chroma_height, height, 1.0 / factor_y, -offset_y / factor_y);
+		for plane in &mut picture.planes[1..] {
+			*plane = resample::resample(plane, chroma_width, chroma_height, &horizontal, &vertical);
+		}
+		picture.shift = (0, 0);
+	}
+
+	/// Filters full resolution chroma down to `shift` at the target siting.
+	fn downsample_chroma(&self, picture: &mut Picture, shift: (u32, u32)) {
+		let (shift_x, shift_y) = shift;
+		if shift == (0, 0) {
+			return;
+		}
+		let (width, height) = (picture.width, picture.height);
+		let (chroma_width, chroma_height) =
+			(width.div_ceil(1 << shift_x), height.div_ceil(1 << shift_y));
+		let (offset_x, offset_y) = self.target_color.chroma_offset(shift_x, shift_y);
+		let (factor_x, factor_y) = ((1u32 << shift_x) as f32, (1u32 << shift_y) as f32);
+
+		let center_x = |index: usize| index as f32 * factor_x + offset_x;
+		let center_y = |index: usize| index as f32 * factor_y + offset_y;
+		let horizontal =
+			Taps::new(width, chroma_width, center_x, factor_x, factor_x, resample::triangle);
+		let vertical =
+			Taps::new(height, chroma_height, center_y, factor_y, factor_y, resample::triangle);
+		for plane in &mut picture.planes[1..] {
+			*plane = resample::resample(plane, width, height, &horizontal, &vertical);
+		}
+		picture.shift = shift;
+	}
+}
+
+impl Transform for PixelConverter {
+	fn apply(&mut self, mut frame: Frame) -> Result<Frame> {
+		let recolor = self.source_color != self.target_color;
+		let target = self.target;
+		if let Some(video) = frame.video_mut().filter(|video| video.format != target || recolor) {
+			*video = self.convert(video)?;
+		}
+		Ok(frame)
+	}
+
+	fn name(&self) -> &'static str {
+		"format"
+	}
+}
+
+fn validate_layout(
+	data: &[u8],
+	layout: &[PlaneLayout],
+	width: u32,
+	height: u32,
+	format: VideoFormat,
+) -> Result<()> {
+	let planes = format.planes(width, height);
+	if layout.len() != planes.len() {
+		return Err(error!("'{}' needs {} planes, got {}", format.name(), planes.len(), layout.len()));
+	}
+	for (index, (plane, (plane_width, plane_height))) in layout.iter().zip(planes).enumerate() {
+		let row_size = format.row_size(plane_width);
+		if plane.stride < row_size {
+			return Err(error!(
+				"plane {} stride {} is below its row size {}",
+				index, plane.stride, row_size
+			));
+		}
+		let end = plane.offset + plane.stride * (plane_height as usize - 1) + row_size;
+		if end > data.len() {
+			return Err(error!(
+				"plane {} ends at byte {} past the {} byte buffer",
+				index,
+				end,
+				data.len()
+			));
+		}
+	}
+	Ok(())
+}
+
+fn read_sample(data: &[u8], position: usize, bytes: usize) -> f32 {
+	if bytes == 2 {
+		u16::from_le_bytes([data[position], data[position + 1]]) as f32
+	} else {
+		data[position] as f32
+	}
+}
+
+fn read_picture(
+	data: &[u8],
+	layout: &[PlaneLayout],
+	width: u32,
+	height: u32,
+	format: VideoFormat,
+	color: &ColorSpace,
+) -> Picture {
+	let (width, height) = (width as usize, height as usize);
+
+	if format.is_rgb() {
+		let channels = if format == VideoFormat::RGBA32 { 4 } else { 3 };
+		let count = width * height;
+		let mut planes: Vec<Vec<f32>> = (0..3).map(|_| Vec::with_capacity(count)).collect();
+		let mut alpha = (channels == 4).then(|| Vec::with_capacity(count));
+		for row in 0..height {
+			let start = layout[0].offset + row * layout[0].stride;
+			for pixel in data[start..start + width * channels].chunks_exact(channels) {
+				for (plane, value) in planes.iter_mut().zip(pixel) {
+					plane.push(*value as f32 / 255.0);
+				}
+				if let Some(alpha) = &mut alpha {
+					alpha.push(pixel[3] as f32 / 255.0);
+				}
+			}
+		}
+		return Picture { width, height, model: Model::Rgb, shift: (0, 0), planes, alpha };
+	}
+
+	let depth = format.bit_depth();
+	let bytes = format.bytes_per_component();
+	let (luma_scale, luma_offset) = color.range.luma(depth);
+	let (chroma_scale, chroma_center) = color.range.chroma(depth);
+
+	let plane_sizes = format.planes(width as u32, height as u32);
+	let mut planes = Vec::with_capacity(plane_sizes.len());
+	for (index, (plane, (plane_width, plane_height))) in layout.iter().zip(plane_sizes).enumerate() {
+		let (scale, offset) =
+			if index == 0 { (luma_scale, luma_offset) } else { (chroma_scale, chroma_center) };
+		let mut samples = Vec::with_capacity(plane_width as usize * plane_height as usize);
+		for row in 0..plane_height as usize {
+			let start = plane.offset + row * plane.stride;
+			for column in 0..plane_width as usize {
+				let value = read_sample(data, start + column * bytes, bytes);
+				samples.push((value - offset) / scale);
+			}
+		}
+		planes.push(samples);
+	}
+	let shift = format.chroma_shift();
+	Picture { width, height, model: Model::YCbCr, shift, planes, alpha: None }
+}
+
+fn write_picture(picture: &Picture, format: VideoFormat, color: &ColorSpace) -> Vec<u8> {
+	let count = picture.width * picture.height;
+
+	if format.is_rgb() {
+		let channels = if format == VideoFormat::RGBA32 { 4 } else { 3 };
+		let quantize = |value: f32| (value * 255.0).round().clamp(0.0, 255.0) as u8;
+		let mut data = Vec::with_capacity(count * channels);
+		for index in 0..count {
+			for plane in &picture.planes {
+				data.push(quantize(plane[index]));
+			}
+			if channels == 4 {
+				let alpha = picture.alpha.as_ref().map_or(1.0, |alpha| alpha[index]);
+				data.push(quantize(alpha));
+			}
+		}
+		return data;
+	}
+
+	let depth = format.bit_depth();
+	let bytes = format.bytes_per_component();
+	let max = ((1u64 << depth) - 1) as f32;
+	let (luma_scale, luma_offset) = color.range.luma(depth);
+	let (chroma_scale, chroma_center) = color.range.chroma(depth);
+
+	let mut data =
+		Vec::with_capacity(format.expected_size(picture.width as u32, picture.height as u32));
+	for (index, plane) in picture.planes.iter().enumerate() {
+		let (scale, offset) =
+			if index == 0 { (luma_scale, luma_offset) } else { (chroma_scale, chroma_center) };
+		for sample in plane {
+			let code = (sample * scale + offset).round().clamp(0.0, max) as u16;
+			if bytes == 2 {
+				data.extend_from_slice(&code.to_le_bytes());
+			} else {
+				data.push(code as u8);
+			}
+		}
+	}
+	data
+}
@@ -0,0 +1,164 @@
+use super::color::ColorRange;
+use super::rotate::sample_layout;
+use crate::core::Transform;
+use crate::core::frame::{Frame, FrameVideo, VideoFormat};
+use crate::{error, message::Result};
+
+/// Keeps a rectangle of each frame. The rectangle is snapped to the chroma
+/// subsampling of the frame format so chroma planes crop exactly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crop {
+	pub x: u32,
+	pub y: u32,
+	pub width: u32,
+	pub height: u32,
+}
+
+impl Crop {
+	pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+		Self { x, y, width, height }
+	}
+
+	/// The rectangle actually cut from a `width` x `height` frame: origin
+	/// rounded down and size rounded down to the subsampling, kept inside.
+	pub fn aligned(&self, width: u32, height: u32, format: VideoFormat) -> Crop {
+		let (shift_x, shift_y) = format.chroma_shift();
+		let (step_x, step_y) = (1u32 << shift_x, 1u32 << shift_y);
+		let x = (self.x.min(width.saturating_sub(1)) / step_x) * step_x;
+		let y = (self.y.min(height.saturating_sub(1)) / step_y) * step_y;
+		let crop_width = (self.width.min(width - x) / step_x * step_x).max(step_x.min(width - x));
+		let crop_height = (self.height.min(height - y) / step_y * step_y).max(step_y.min(height - y));
+		Crop { x, y, width: crop_width, height: crop_height }
+	}
+
+	pub fn output_size(&self, width: u32, height: u32, format: VideoFormat) -> (u32, u32) {
+		let aligned = self.aligned(width, height, format);
+		(aligned.width, aligned.height)
+	}
+
+	pub fn crop(&self, video: &FrameVideo) -> Result<FrameVideo> {
+		if !video.is_valid() {
+			let (expected, actual) = (video.expected_size(), video.data.len());
+			return Err(error!("video frame has {} bytes, expected {}", actual, expected));
+		}
+		let format = video.format;
+		let rect = self.aligned(video.width, video.height, format);
+		if (rect.x, rect.y, rect.width, rect.height) == (0, 0, video.width, video.height) {
+			return Ok(video.clone());
+		}
+
+		let (channels, bytes) = sample_layout(format);
+		let element = channels * bytes;
+		let (shift_x, shift_y) = format.chroma_shift();
+		let mut data = Vec::with_capacity(format.expected_size(rect.width, rect.height));
+		let mut offset = 0;
+		let output_planes = format.planes(rect.width, rect.height);
+		for (index, ((width, height), (out_width, out_height))) in
+			format.planes(video.width, video.height).into_iter().zip(output_planes).enumerate()
+		{
+			let (x, y) =
+				if index == 0 { (rect.x, rect.y) } else { (rect.x >> shift_x, rect.y >> shift_y) };
+			let stride = width as usize * element;
+			for row in 0..out_height as usize {
+				let start = offset + (y as usize + row) * stride + x as usize * element;
+				data.extend_from_slice(&video.data[start..start + out_width as usize * element]);
+			}
+			offset += stride * height as usize;
+		}
+		Ok(FrameVideo::new(data, rect.width, rect.height, format, video.keyframe))
+	}
+}
+
+impl Transform for Crop {
+	fn apply(&mut self, mut frame: Frame) -> Result<Frame> {
+		if let Some(video) = frame.video_mut() {
+			*video = self.crop(video)?;
+		}
+		Ok(frame)
+	}
+
+	fn name(&self) -> &'static str {
+		"crop"
+	}
+}
+
+/// Finds the black borders of a picture from sample frames. A row or
+/// column counts as black when its mean luma stays within `limit` of black;
+/// the detected area is the union of the content of every frame seen.
+pub struct CropDetector {
+	limit: f32,
+	range: ColorRange,
+	bounds: Option<(u32, u32, u32, u32)>,
+	size: Option<(u32, u32)>,
+}
+
+impl CropDetector {
+	/// `limit` is a 0..1 fraction of the luma range above black.
+	pub fn new(limit: f32, range: ColorRange) -> Self {
+		Self { limit, range, bounds: None, size: None }
+	}
+
+	pub fn push(&mut self, video: &FrameVideo) {
+		let (width, height) = (video.width as usize, video.height as usize);
+		if !video.is_valid() || self.size.is_some_and(|size| size != (video.width, video.height)) {
+			return;
+		}
+		self.size = Some((video.width, video.height));
+
+		let luma = luma_plane(video, self.range);
+		let threshold = self.limit;
+		let row_is_black = |row: usize| {
+			luma[row * width..(row + 1) * width].iter().sum::<f32>() / width as f32 <= threshold
+		};
+		let column_is_black = |column: usize| {
+			(0..height).map(|row| luma[row * width + column]).sum::<f32>() / height as f32 <= threshold
+		};
+
+		let Some(top) = (0..height).find(|&row| !row_is_black(row)) else {
+			return;
+		};
+		let bottom = (0..height).rev().find(|&row| !row_is_black(row)).unwrap_or(top);
+		let left = (0..width).find(|&column| !column_is_black(column)).unwrap_or(0);
+		let right = (0..width).rev().find(|&column| !column_is_black(column)).unwrap_or(width - 1);
+
+		let (left, top, right, bottom) = (left as u32, top as u32, right as u32, bottom as u32);
+		self.bounds = Some(match self.bounds {
+			Some((l, t, r, b)) => (l.min(left), t.min(top), r.max(right), b.max(bottom)),
+			None => (left, top, right, bottom),
+		});
+	}
+
+	/// The content rectangle, or `None` when every frame was black.
+	pub fn result(&self) -> Option<Crop> {
+		let (left, top, right, bottom) = self.bounds?;
+		Some(Crop::new(left, top, right - left + 1, bottom - top + 1))
+	}
+}
+
+fn luma_plane(video: &FrameVideo, range: ColorRange) -> Vec<f32> {
+	let count = video.width as usize * video.height as usize;
+	let format = video.format;
+	if format.is_rgb() {
+		let (channels, _) = sample_layout(format);
+		return video.data[..count * channels]
+			.chunks_exact(channels)
+			.map(|pixel| {
+				(pixel[0] as f32 * 0.299 + pixel[1] as f32 * 0.587 + pixel[2] as f32 * 0.114) / 255.0
+			})
+			.collect();
+	}
+	let depth = format.bit_depth();
+	let (scale, offset) = range.luma(depth);
+	let bytes = format.bytes_per_component();
+	video.data[..count * bytes]
+		.chunks_exact(bytes)
+		.map(|sample| {
+			let code = if bytes == 2 {
+				u16::from_le_bytes([sample[0], sample[1]]) as f32
+			} else {
+				sample[0] as f32
+			};
+			(code - offset) / scale
+		})
+		.collect()
+}
@@ -0,0 +1,213 @@
+use super::color::ColorSpace;
+use crate::core::Transform;
+use crate::core::frame::{Frame, FrameVideo, VideoFormat};
+use crate::{error, message::Result};
+
+/// Brightness, contrast, saturation, hue and gamma adjustment. YCbCr and
+/// gray frames are adjusted in place, luma through a lookup table and
+/// chroma as a rotation and scale around neutral; RGB frames go through
+/// YCbCr only when saturation or hue change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Equalizer {
+	/// Added to normalized luma, -1..1.
+	pub brightness: f32,
+	/// Luma gain around mid grey; 1 is unchanged.
+	pub contrast: f32,
+	/// Chroma gain; 0 is grayscale, 1 is unchanged.
+	pub saturation: f32,
+	pub hue: f32,
+	/// Luma gamma; 1 is unchanged.
+	pub gamma: f32,
+	color: ColorSpace,
+}
+
+impl Default for Equalizer {
+	fn default() -> Self {
+		Self {
+			brightness: 0.0,
+			contrast: 1.0,
+			saturation: 1.0,
+			hue: 0.0,
+			gamma: 1.0,
+			color: ColorSpace::default(),
+		}
+	}
+}
+
+impl Equalizer {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn with_brightness(mut self, brightness: f32) -> Self {
+		self.brightness = brightness;
+		self
+	}
+
+	pub fn with_contrast(mut self, contrast: f32) -> Self {
+		self.contrast = contrast;
+		self
+	}
+
+	pub fn with_saturation(mut self, saturation: f32) -> Self {
+		self.saturation = saturation;
+		self
+	}
+
+	pub fn with_hue(mut self, hue: f32) -> Self {
+		self.hue = hue;
+		self
+	}
+
+	pub fn with_gamma(mut self, gamma: f32) -> Self {
+		self.gamma = gamma;
+		self
+	}
+
+	pub fn with_color(mut self, color: ColorSpace) -> Self {
+		self.color = color;
+		self
+	}
+
+	pub fn is_identity(&self) -> bool {
+		!self.changes_luma() && !self.changes_chroma()
+	}
+
+	fn changes_luma(&self) -> bool {
+		self.brightness != 0.0 || self.contrast != 1.0 || self.gamma != 1.0
+	}
+
+	fn changes_chroma(&self) -> bool {
+		self.saturation != 1.0 || self.hue != 0.0
+	}
+
+	/// Brightness and contrast, then gamma, on a 0..1 value.
+	fn adjust_luma(&self, value: f32) -> f32 {
+		let value = ((value - 0.5) * self.contrast + 0.5 + self.brightness).clamp(0.0, 1.0);
+		if self.gamma == 1.0 { value } else { value.powf(1.0 / self.gamma) }
+	}
+
+	fn adjust_chroma(&self, cb: f32, cr: f32) -> (f32, f32) {
+		let (sin, cos) = self.hue.to_radians().sin_cos();
+		let cb_rotated = cb * cos - cr * sin;
+		let cr_rotated = cb * sin + cr * cos;
+		(cb_rotated * self.saturation, cr_rotated * self.saturation)
+	}
+
+	pub fn adjust(&self, video: &mut FrameVideo) -> Result<()> {
+		if !video.is_valid() {
+			let (expected, actual) = (video.expected_size(), video.data.len());
+			return Err(error!("video frame has {} bytes, expected {}", actual, expected));
+		}
+		if self.is_identity() {
+			return Ok(());
+		}
+		if video.format.is_rgb() {
+			self.adjust_rgb(video);
+		} else {
+			self.adjust_ycbcr(video);
+		}
+		Ok(())
+	}
+
+	fn adjust_ycbcr(&self, video: &mut FrameVideo) {
+		let format = video.format;
+		let depth = format.bit_depth();
+		let bytes = format.bytes_per_component();
+		let max = ((1u64 << depth) - 1) as f32;
+		let (luma_scale, luma_offset) = self.color.range.luma(depth);
+		let (chroma_scale, chroma_center) = self.color.range.chroma(depth);
+
+		let planes = format.planes(video.width, video.height);
+		let luma_size = planes[0].0 as usize * planes[0].1 as usize * bytes;
+		let (luma, chroma) = video.data.split_at_mut(luma_size);
+
+		if self.changes_luma() {
+			let table: Vec<u16> = (0..=max as u32)
+				.map(|code| {
+					let value = self.adjust_luma((code as f32 - luma_offset) / luma_scale);
+					(value * luma_scale + luma_offset).round().clamp(0.0, max) as u16
+				})
+				.collect();
+			map_samples(luma, bytes, |code| table[code.min(max as u16) as usize]);
+		}
+
+		if self.changes_chroma() && planes.len() == 3 {
+			let (cb_plane, cr_plane) = chroma.split_at_mut(chroma.len() / 2);
+			let read = |plane: &[u8], index: usize| -> f32 {
+				let code = if bytes == 2 {
+					u16::from_le_bytes([plane[index], plane[index + 1]])
+				} else {
+					plane[index] as u16
+				};
+				(code as f32 - chroma_center) / chroma_scale
+			};
+			let code = |value: f32| (value * chroma_scale + chroma_center).round().clamp(0.0, max) as u16;
+			for index in (0..cb_plane.len()).step_by(bytes) {
+				let (cb, cr) = self.adjust_chroma(read(cb_plane, index), read(cr_plane, index));
+				write_sample(cb_plane, index, bytes, code(cb));
+				write_sample(cr_plane, index, bytes, code(cr));
+			}
+		}
+	}
+
+	fn adjust_rgb(&self, video: &mut FrameVideo) {
+		let channels = if video.format == VideoFormat::RGBA32 { 4 } else { 3 };
+		let luma_table: Vec<f32> =
+			(0..=255).map(|code| self.adjust_luma(code as f32 / 255.0)).collect();
+		let matrix = self.color.matrix;
+		for pixel in video.data.chunks_exact_mut(channels) {
+			let (mut r, mut g, mut b) =
+				(pixel[0] as f32 / 255.0, pixel[1] as f32 / 255.0, pixel[2] as f32 / 255.0);
+			if self.changes_luma() {
+				(r, g, b) = (
+					luma_table[pixel[0] as usize],
+					luma_table[pixel[1] as usize],
+					luma_table[pixel[2] as usize],
+				);
+			}
+			if self.changes_chroma() {
+				let (y, cb, cr) = matrix.to_ycbcr(r, g, b);
+				let (cb, cr) = self.adjust_chroma(cb, cr);
+				(r, g, b) = matrix.to_rgb(y, cb, cr);
+			}
+			for (sample, value) in pixel.iter_mut().zip([r, g, b]) {
+				*sample = (value * 255.0).round().clamp(0.0, 255.0) as u8;
+			}
+		}
+	}
+}
+
+impl Transform for Equalizer {
+	fn apply(&mut self, mut frame: Frame) -> Result<Frame> {
+		if let Some(video) = frame.video_mut() {
+			self.adjust(video)?;
+		}
+		Ok(frame)
+	}
+
+	fn name(&self) -> &'static str {
+		"eq"
+	}
+}
+
+fn map_samples(plane: &mut [u8], bytes: usize, map: impl Fn(u16) -> u16) {
+	if bytes == 2 {
+		for sample in plane.chunks_exact_mut(2) {
+			let code = map(u16::from_le_bytes([sample[0], sample[1]]));
+			sample.copy_from_slice(&code.to_le_bytes());
+		}
+	} else {
+		for sample in plane.iter_mut() {
+			*sample = map(*sample as u16) as u8;
+		}
+	}
+}
+
+fn write_sample(plane: &mut [u8], index: usize, bytes: usize, code: u16) {
+	if bytes == 2 {
+		plane[index..index + 2].copy_from_slice(&code.to_le_bytes());
+	} else {
+		plane[index] = code as u8;
+	}
+}
@@ -0,0 +1,144 @@
+use crate::core::frame::{Frame, FrameData, FrameVideo};
+use crate::core::time::Time;
+use crate::{error, message::Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameRateMode {
+	/// Repeat or drop whole frames, taking the input closest in time.
+	#[default]
+	Nearest,
+	/// Mix the surrounding input frames by their distance in time.
+	Blend,
+}
+
+impl FrameRateMode {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name.to_lowercase().as_str() {
+			"nearest" | "drop" | "dup" => Some(Self::Nearest),
+			"blend" => Some(Self::Blend),
+			_ => None,
+		}
+	}
+}
+
+/// Unlike a `Transform` one input may yield no or several outputs, so
+/// frames go through `push` and the last one is released by `flush`.
+pub struct FrameRateConverter {
+	source: Time,
+	target: Time,
+	mode: FrameRateMode,
+	/// Time of output 0 in `source.num * target.den` units of a second.
+	start: Option<i128>,
+	next_index: i64,
+	previous: Option<(i128, Frame)>,
+}
+
+impl FrameRateConverter {
+	pub fn new(source: Time, target: Time) -> Self {
+		Self {
+			source,
+			target,
+			mode: FrameRateMode::default(),
+			start: None,
+			next_index: 0,
+			previous: None,
+		}
+	}
+
+	pub fn with_mode(mut self, mode: FrameRateMode) -> Self {
+		self.mode = mode;
+		self
+	}
+
+	fn input_time(&self, ticks: i64) -> i128 {
+		ticks as i128 * self.source.num as i128 * self.target.den as i128
+	}
+
+	fn output_time(&self, index: i64) -> i128 {
+		self.start.unwrap_or(0) + index as i128 * self.target.num as i128 * self.source.den as i128
+	}
+
+	pub fn push(&mut self, frame: Frame) -> Result<Vec<Frame>> {
+		let time = self.input_time(frame.pts);
+		if self.start.is_none() {
+			self.start = Some(time);
+		}
+
+		let Some((previous_time, previous)) = self.previous.take() else {
+			self.previous = Some((time, frame));
+			return Ok(Vec::new());
+		};
+		if time <= previous_time {
+			// out of order or repeated timestamps: keep the newest picture
+			self.previous = Some((previous_time, frame));
+			return Ok(Vec::new());
+		}
+
+		let mut frames = Vec::new();
+		while self.output_time(self.next_index) < time {
+			let output_time = self.output_time(self.next_index);
+			let output = match self.mode {
+				FrameRateMode::Blend if output_time > previous_time => {
+					let weight = (output_time - previous_time) as f64 / (time - previous_time) as f64;
+					blend(&previous, &frame, weight)?
+				}
+				_ if 2 * output_time < previous_time + time => previous.clone(),
+				_ => frame.clone(),
+			};
+			frames.push(self.stamp(output));
+		}
+		self.previous = Some((time, frame));
+		Ok(frames)
+	}
+
+	/// Emits the last input frame for the slots it covers, one source tick
+	/// long unless it carries its own duration.
+	pub fn flush(&mut self) -> Result<Vec<Frame>> {
+		let Some((time, frame)) = self.previous.take() else {
+			return Ok(Vec::new());
+		};
+		let end = time + self.input_time(frame.duration.unwrap_or(1));
+		let mut frames = Vec::new();
+		while self.output_time(self.next_index) < end {
+			frames.push(self.stamp(frame.clone()));
+		}
+		Ok(frames)
+	}
+
+	fn stamp(&mut self, mut frame: Frame) -> Frame {
+		frame.pts = self.next_index;
+		frame.dts = Some(self.next_index);
+		frame.duration = Some(1);
+		self.next_index += 1;
+		frame
+	}
+}
+
+fn blend(previous: &Frame, next: &Frame, weight: f64) -> Result<Frame> {
+	let (FrameData::Video(a), FrameData::Video(b)) = (&previous.data, &next.data) else {
+		return Ok(if weight < 0.5 { previous.clone() } else { next.clone() });
+	};
+	if (a.width, a.height, a.format) != (b.width, b.height, b.format) || a.data.len() != b.data.len()
+	{
+		return Err(error!(
+			"cannot blend a {}x{} frame with a {}x{} frame",
+			a.width, a.height, b.width, b.height
+		));
+	}
+
+	let mix = |x: f64, y: f64| x + (y - x) * weight;
+	let data = if a.format.bytes_per_component() == 2 {
+		let mut data = Vec::with_capacity(a.data.len());
+		for (x, y) in a.data.chunks_exact(2).zip(b.data.chunks_exact(2)) {
+			let x = u16::from_le_bytes([x[0], x[1]]) as f64;
+			let y = u16::from_le_bytes([y[0], y[1]]) as f64;
+			data.extend_from_slice(&(mix(x, y).round() as u16).to_le_bytes());
+		}
+		data
+	} else {
+		a.data.iter().zip(&b.data).map(|(&x, &y)| mix(x as f64, y as f64).round() as u8).collect()
+	};
+
+	let video = FrameVideo::new(data, a.width, a.height, a.format, true);
+	Ok(Frame::new_video(video, previous.stream_id).with_pts(previous.pts))
+}
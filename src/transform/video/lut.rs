@@ -0,0 +1,402 @@
+use super::color::ColorSpace;
+use crate::core::Transform;
+use crate::core::frame::{Frame, FrameVideo, VideoFormat};
+use crate::io::{File, MediaRead};
+use crate::{error, message::Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LutInterpolation {
+	Trilinear,
+	/// Interpolates inside one of six tetrahedra of the cell; keeps neutral
+	/// greys on the cube diagonal and is what most grading tools use.
+	#[default]
+	Tetrahedral,
+}
+
+impl LutInterpolation {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name.to_lowercase().as_str() {
+			"trilinear" => Some(Self::Trilinear),
+			"tetrahedral" => Some(Self::Tetrahedral),
+			_ => None,
+		}
+	}
+}
+
+/// A 1D curve, a 3D lattice or a 1D shaper followed by a 3D lattice, as
+/// read from an Adobe/Resolve `.cube` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CubeLut {
+	pub title: Option<String>,
+	shaper: Option<Table1d>,
+	lattice: Option<Table3d>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Table1d {
+	min: [f32; 3],
+	max: [f32; 3],
+	entries: Vec<[f32; 3]>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Table3d {
+	min: [f32; 3],
+	max: [f32; 3],
+	size: usize,
+	/// Red varies fastest, then green, then blue.
+	entries: Vec<[f32; 3]>,
+}
+
+/// Largest accepted `LUT_3D_SIZE`, as allowed by the format.
+const MAX_3D_SIZE: usize = 256;
+const MAX_1D_SIZE: usize = 65536;
+
+impl CubeLut {
+	pub fn load(path: &str) -> Result<Self> {
+		let mut file = File::open(path)?;
+		let mut bytes = Vec::new();
+		let mut buffer = [0u8; 8192];
+		loop {
+			match file.read(&mut buffer)? {
+				0 => break,
+				read => bytes.extend_from_slice(&buffer[..read]),
+			}
+		}
+		let text =
+			String::from_utf8(bytes).map_err(|_| error!("'{}' is not a text .cube file", path))?;
+		Self::parse(&text)
+	}
+
+	pub fn parse(text: &str) -> Result<Self> {
+		let mut title = None;
+		let (mut size_1d, mut size_3d) = (None, None);
+		let (mut domain_min, mut domain_max) = ([0.0f32; 3], [1.0f32; 3]);
+		let (mut range_1d, mut range_3d) = (None, None);
+		let mut rows: Vec<[f32; 3]> = Vec::new();
+
+		for (number, line) in text.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			let invalid = || error!("invalid .cube line {}: {}", number + 1, line);
+			let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+			let rest = rest.trim();
+			let floats = |count: usize| -> Result<Vec<f32>> {
+				let values: Vec<f32> = rest
+					.split_whitespace()
+					.map(str::parse)
+					.collect::<std::result::Result<_, _>>()
+					.map_err(|_| invalid())?;
+				if values.len() != count || values.iter().any(|value| !value.is_finite()) {
+					return Err(invalid());
+				}
+				Ok(values)
+			};
+			match keyword {
+				"TITLE" => title = Some(rest.trim_matches('"').to_string()),
+				"LUT_1D_SIZE" => size_1d = Some(rest.parse::<usize>().map_err(|_| invalid())?),
+				"LUT_3D_SIZE" => size_3d = Some(rest.parse::<usize>().map_err(|_| invalid())?),
+				"DOMAIN_MIN" => domain_min.copy_from_slice(&floats(3)?),
+				"DOMAIN_MAX" => domain_max.copy_from_slice(&floats(3)?),
+				"LUT_1D_INPUT_RANGE" => range_1d = Some(floats(2)?),
+				"LUT_3D_INPUT_RANGE" => range_3d = Some(floats(2)?),
+				_ if keyword.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.') => {
+					let values: Vec<f32> = line
+						.split_whitespace()
+						.map(str::parse)
+						.collect::<std::result::Result<_, _>>()
+						.map_err(|_| invalid())?;
+					match values[..] {
+						[r, g, b] if values.iter().all(|value| value.is_finite()) => rows.push([r, g, b]),
+						_ => return Err(invalid()),
+					}
+				}
+				// unknown keywords are vendor extensions
+				_ => {}
+			}
+		}
+
+		let expected_1d = size_1d.unwrap_or(0);
+		let expected_3d = size_3d.map_or(0, |size| size * size * size);
+		if size_1d.is_none() && size_3d.is_none() {
+			return Err(error!(".cube file has neither LUT_1D_SIZE nor LUT_3D_SIZE"));
+		}
+		if size_1d.is_some_and(|size| !(2..=MAX_1D_SIZE).contains(&size)) {
+			return Err(error!(".cube LUT_1D_SIZE {} is out of range", expected_1d));
+		}
+		if size_3d.is_some_and(|size| !(2..=MAX_3D_SIZE).contains(&size)) {
+			return Err(error!(".cube LUT_3D_SIZE {} is out of range", size_3d.unwrap_or(0)));
+		}
+		if rows.len() != expected_1d + expected_3d {
+			return Err(error!(
+				".cube file has {} entries, expected {}",
+				rows.len(),
+				expected_1d + expected_3d
+			));
+		}
+
+		let domain = |range: Option<Vec<f32>>| match range {
+			Some(range) => ([range[0]; 3], [range[1]; 3]),
+			None => (domain_min, domain_max),
+		};
+		let shaper = size_1d.map(|_| {
+			let (min, max) = domain(range_1d.clone());
+			Table1d { min, max, entries: rows[..expected_1d].to_vec() }
+		});
+		let lattice = size_3d.map(|size| {
+			// a shaper feeds the lattice values already in its output range
+			let (min, max) = if shaper.is_some() && range_3d.is_none() {
+				([0.0; 3], [1.0; 3])
+			} else {
+				domain(range_3d.clone())
+			};
+			Table3d { min, max, size, entries: rows[expected_1d..].to_vec() }
+		});
+
+		for (min, max) in shaper
+			.iter()
+			.map(|table| (table.min, table.max))
+			.chain(lattice.iter().map(|table| (table.min, table.max)))
+		{
+			if (0..3).any(|channel| max[channel] <= min[channel]) {
+				return Err(error!(".cube domain maximum must exceed its minimum"));
+			}
+		}
+
+		Ok(Self { title, shaper, lattice })
+	}
+
+	pub fn apply(&self, rgb: [f32; 3], interpolation: LutInterpolation) -> [f32; 3] {
+		let mut rgb = rgb;
+		if let Some(shaper) = &self.shaper {
+			rgb = shaper.sample(rgb);
+		}
+		if let Some(lattice) = &self.lattice {
+			rgb = lattice.sample(rgb, interpolation);
+		}
+		rgb
+	}
+}
+
+impl Table1d {
+	fn sample(&self, rgb: [f32; 3]) -> [f32; 3] {
+		let last = self.entries.len() - 1;
+		let mut output = [0.0; 3];
+		for channel in 0..3 {
+			let position = normalize(rgb[channel], self.min[channel], self.max[channel]) * last as f32;
+			let index = (position.floor() as usize).min(last - 1);
+			let fraction = position - index as f32;
+			let (low, high) = (self.entries[index][channel], self.entries[index + 1][channel]);
+			output[channel] = low + (high - low) * fraction;
+		}
+		output
+	}
+}
+
+impl Table3d {
+	fn sample(&self, rgb: [f32; 3], interpolation: LutInterpolation) -> [f32; 3] {
+		let last = self.size - 1;
+		let mut base = [0usize; 3];
+		let mut fraction = [0.0f32; 3];
+		for channel in 0..3 {
+			let position = normalize(rgb[channel], self.min[channel], self.max[channel]) * last as f32;
+			base[channel] = (position.floor() as usize).min(last - 1);
+			fraction[channel] = position - base[channel] as f32;
+		}
+		let corner = |r: usize, g: usize, b: usize| {
+			let index = (base[0] + r) + (base[1] + g) * self.size + (base[2] + b) * self.size * self.size;
+			self.entries[index]
+		};
+		let [fr, fg, fb] = fraction;
+
+		let mut output = [0.0; 3];
+		match interpolation {
+			LutInterpolation::Trilinear => {
+				let lerp = |a: [f32; 3], b: [f32; 3], t: f32| [0, 1, 2].map(|c| a[c] + (b[c] - a[c]) * t);
+				let c00 = lerp(corner(0, 0, 0), corner(1, 0, 0), fr);
+				let c10 = lerp(corner(0, 1, 0), corner(1, 1, 0), fr);
+				let c01 = lerp(corner(0, 0, 1), corner(1, 0, 1), fr);
+				let c11 = lerp(corner(0, 1, 1), corner(1, 1, 1), fr);
+				output = lerp(lerp(c00, c10, fg), lerp(c01, c11, fg), fb);
+			}
+			LutInterpolation::Tetrahedral => {
+				let c000 = corner(0, 0, 0);
+				let c111 = corner(1, 1, 1);
+				// walk from c000 to c111 along the edges of the tetrahedron
+				// holding the point, largest fraction first
+				let (first, second, weights) = if fr > fg {
+					if fg > fb {
+						(corner(1, 0, 0), corner(1, 1, 0), [fr, fg, fb])
+					} else if fr > fb {
+						(corner(1, 0, 0), corner(1, 0, 1), [fr, fb, fg])
+					} else {
+						(corner(0, 0, 1), corner(1, 0, 1), [fb, fr, fg])
+					}
+				} else if fb > fg {
+					(corner(0, 0, 1), corner(0, 1, 1), [fb, fg, fr])
+				} else if fb > fr {
+					(corner(0, 1, 0), corner(0, 1, 1), [fg, fb, fr])
+				} else {
+					(corner(0, 1, 0), corner(1, 1, 0), [fg, fr, fb])
+				};
+				for channel in 0..3 {
+					output[channel] = c000[channel]
+						+ weights[0] * (first[channel] - c000[channel])
+						+ weights[1] * (second[channel] - first[channel])
+						+ weights[2] * (c111[channel] - second[channel]);
+				}
+			}
+		}
+		output
+	}
+}
+
+fn normalize(value: f32, min: f32, max: f32) -> f32 {
+	((value - min) / (max - min)).clamp(0.0, 1.0)
+}
+
+/// Applies a `CubeLut` to video frames. RGB is graded directly; YCbCr and
+/// gray frames are graded in place through the matrix of `color`.
+pub struct ColorLut {
+	lut: CubeLut,
+	interpolation: LutInterpolation,
+	color: ColorSpace,
+}
+
+impl ColorLut {
+	pub fn new(lut: CubeLut) -> Self {
+		Self { lut, interpolation: LutInterpolation::default(), color: ColorSpace::default() }
+	}
+
+	pub fn with_interpolation(mut self, interpolation: LutInterpolation) -> Self {
+		self.interpolation = interpolation;
+		self
+	}
+
+	pub fn with_color(mut self, color: ColorSpace) -> Self {
+		self.color = color;
+		self
+	}
+
+	pub fn grade(&self, video: &FrameVideo) -> Result<FrameVideo> {
+		if !video.is_valid() {
+			let (expected, actual) = (video.expected_size(), video.data.len());
+			return Err(error!("video frame has {} bytes, expected {}", actual, expected));
+		}
+		let format = video.format;
+		if format.is_rgb() {
+			let mut graded = video.clone();
+			let channels = if format == VideoFormat::RGBA32 { 4 } else { 3 };
+			for pixel in graded.data.chunks_exact_mut(channels) {
+				let rgb = [pixel[0], pixel[1], pixel[2]].map(|value| value as f32 / 255.0);
+				let output = self.lut.apply(rgb, self.interpolation);
+				for (sample, value) in pixel.iter_mut().zip(output) {
+					*sample = (value * 255.0).round().clamp(0.0, 255.0) as u8;
+				}
+			}
+			return Ok(graded);
+		}
+
+		let mut graded = video.clone();
+		self.grade_ycbcr(&mut graded);
+		Ok(graded)
+	}
+
+	/// Grades every luma sample with the chroma of its block, then stores
+	/// the mean graded chroma of the block, so an identity LUT leaves the
+	/// frame untouched.
+	fn grade_ycbcr(&self, video: &mut FrameVideo) {
+		let format = video.format;
+		let depth = format.bit_depth();
+		let bytes = format.bytes_per_component();
+		let max = ((1u64 << depth) - 1) as f32;
+		let (luma_scale, luma_offset) = self.color.range.luma(depth);
+		let (chroma_scale, chroma_center) = self.color.range.chroma(depth);
+		let matrix = self.color.matrix;
+
+		let (width, height) = (video.width as usize, video.height as usize);
+		let (shift_x, shift_y) = format.chroma_shift();
+		let planes = format.planes(video.width, video.height);
+		let luma_size = width * height;
+		let (chroma_width, chroma_height) = match planes.get(1) {
+			Some(&(chroma_width, chroma_height)) => (chroma_width as usize, chroma_height as usize),
+			None => (width, height),
+		};
+		let chroma_size = chroma_width * chroma_height;
+		let has_chroma = planes.len() == 3;
+
+		let read = |data: &[u8], index: usize| -> f32 {
+			let position = index * bytes;
+			if bytes == 2 {
+				u16::from_le_bytes([data[position], data[position + 1]]) as f32
+			} else {
+				data[position] as f32
+			}
+		};
+		let write = |data: &mut [u8], index: usize, value: f32| {
+			let code = value.round().clamp(0.0, max) as u16;
+			let position = index * bytes;
+			if bytes == 2 {
+				data[position..position + 2].copy_from_slice(&code.to_le_bytes());
+			} else {
+				data[position] = code as u8;
+			}
+		};
+
+		for chroma_y in 0..chroma_height {
+			for chroma_x in 0..chroma_width {
+				let chroma_index = chroma_y * chroma_width + chroma_x;
+				let (cb, cr) = if has_chroma {
+					(
+						(read(&video.data, luma_size + chroma_index) - chroma_center) / chroma_scale,
+						(read(&video.data, luma_size + chroma_size + chroma_index) - chroma_center)
+							/ chroma_scale,
+					)
+				} else {
+					(0.0, 0.0)
+				};
+
+				let (mut cb_sum, mut cr_sum, mut count) = (0.0, 0.0, 0.0);
+				let rows = (chroma_y << shift_y)..((chroma_y + 1) << shift_y).min(height);
+				for y in rows {
+					for x in (chroma_x << shift_x)..((chroma_x + 1) << shift_x).min(width) {
+						let index = y * width + x;
+						let luma = (read(&video.data, index) - luma_offset) / luma_scale;
+						let (r, g, b) = matrix.to_rgb(luma, cb, cr);
+						let [r, g, b] = self.lut.apply([r, g, b], self.interpolation);
+						let (luma, cb, cr) = matrix.to_ycbcr(r, g, b);
+						write(&mut video.data, index, luma * luma_scale + luma_offset);
+						cb_sum += cb;
+						cr_sum += cr;
+						count += 1.0;
+					}
+				}
+
+				if has_chroma {
+					write(
+						&mut video.data,
+						luma_size + chroma_index,
+						cb_sum / count * chroma_scale + chroma_center,
+					);
+					let cr_index = luma_size + chroma_size + chroma_index;
+					write(&mut video.data, cr_index, cr_sum / count * chroma_scale + chroma_center);
+				}
+			}
+		}
+	}
+}
+
+impl Transform for ColorLut {
+	fn apply(&mut self, mut frame: Frame) -> Result<Frame> {
+		if let Some(video) = frame.video_mut() {
+			*video = self.grade(video)?;
+		}
+		Ok(frame)
+	}
+
+	fn name(&self) -> &'static str {
+		"lut"
+	}
+}
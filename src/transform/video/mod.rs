@@ -0,0 +1,20 @@
+pub mod color;
+pub mod convert;
+pub mod crop;
+pub mod eq;
+pub mod fps;
+pub mod lut;
+pub mod pad;
+pub mod resample;
+pub mod rotate;
+pub mod scale;
+
+pub use color::{ColorMatrix, ColorRange, ColorSpace, parse_rgb};
+pub use convert::{PixelConverter, PlaneLayout};
+pub use crop::{Crop, CropDetector};
+pub use eq::Equalizer;
+pub use fps::{FrameRateConverter, FrameRateMode};
+pub use lut::{ColorLut, CubeLut, LutInterpolation};
+pub use pad::Pad;
+pub use rotate::{Orientation, Rotate};
+pub use scale::{ScaleKernel, ScaleMode, Scaler};
@@ -0,0 +1,135 @@
+use super::color::ColorSpace;
+use super::rotate::sample_layout;
+use crate::core::Transform;
+use crate::core::frame::{Frame, FrameVideo, VideoFormat};
+use crate::{error, message::Result};
+
+/// Places each frame on a larger canvas painted with a fill colour. The
+/// picture is centred unless a position is given, and the position is
+/// snapped to the chroma subsampling of the format.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pad {
+	width: u32,
+	height: u32,
+	position: Option<(u32, u32)>,
+	fill: [u8; 3],
+	color: ColorSpace,
+}
+
+impl Pad {
+	pub fn new(width: u32, height: u32) -> Self {
+		Self { width, height, position: None, fill: [0, 0, 0], color: ColorSpace::default() }
+	}
+
+	/// Canvas with the display aspect ratio `aspect` that just contains a
+	/// `width` x `height` picture: bars above and below or at the sides.
+	pub fn letterbox(width: u32, height: u32, aspect: f64) -> Self {
+		let (canvas_width, canvas_height) = if width as f64 / height as f64 > aspect {
+			(width, (width as f64 / aspect).round() as u32)
+		} else {
+			((height as f64 * aspect).round() as u32, height)
+		};
+		Self::new(canvas_width.max(width), canvas_height.max(height))
+	}
+
+	pub fn with_position(mut self, x: u32, y: u32) -> Self {
+		self.position = Some((x, y));
+		self
+	}
+
+	pub fn with_fill(mut self, fill: [u8; 3]) -> Self {
+		self.fill = fill;
+		self
+	}
+
+	pub fn with_color(mut self, color: ColorSpace) -> Self {
+		self.color = color;
+		self
+	}
+
+	pub fn output_size(&self, width: u32, height: u32, format: VideoFormat) -> (u32, u32) {
+		let (shift_x, shift_y) = format.chroma_shift();
+		let canvas_width = self.width.max(width).next_multiple_of(1 << shift_x);
+		let canvas_height = self.height.max(height).next_multiple_of(1 << shift_y);
+		(canvas_width, canvas_height)
+	}
+
+	fn position(
+		&self,
+		width: u32,
+		height: u32,
+		canvas_width: u32,
+		canvas_height: u32,
+		format: VideoFormat,
+	) -> (u32, u32) {
+		let (x, y) =
+			self.position.unwrap_or(((canvas_width - width) / 2, (canvas_height - height) / 2));
+		let (shift_x, shift_y) = format.chroma_shift();
+		let x = x.min(canvas_width - width) >> shift_x << shift_x;
+		let y = y.min(canvas_height - height) >> shift_y << shift_y;
+		(x, y)
+	}
+
+	pub fn pad(&self, video: &FrameVideo) -> Result<FrameVideo> {
+		if !video.is_valid() {
+			let (expected, actual) = (video.expected_size(), video.data.len());
+			return Err(error!("video frame has {} bytes, expected {}", actual, expected));
+		}
+		let format = video.format;
+		let (canvas_width, canvas_height) = self.output_size(video.width, video.height, format);
+		if (canvas_width, canvas_height) == (video.width, video.height) {
+			return Ok(video.clone());
+		}
+		let (x, y) = self.position(video.width, video.height, canvas_width, canvas_height, format);
+
+		let (channels, bytes) = sample_layout(format);
+		let element = channels * bytes;
+		let fill = self.color.fill_codes(format, self.fill);
+		let (shift_x, shift_y) = format.chroma_shift();
+
+		let mut data = Vec::with_capacity(format.expected_size(canvas_width, canvas_height));
+		let mut offset = 0;
+		let canvas_planes = format.planes(canvas_width, canvas_height);
+		for (index, ((width, height), (plane_width, plane_height))) in
+			format.planes(video.width, video.height).into_iter().zip(canvas_planes).enumerate()
+		{
+			let mut pixel = Vec::with_capacity(element);
+			for channel in 0..channels {
+				let code = fill[if channels > 1 { channel } else { index }];
+				if bytes == 2 {
+					pixel.extend_from_slice(&code.to_le_bytes());
+				} else {
+					pixel.push(code as u8);
+				}
+			}
+			let start = data.len();
+			for _ in 0..plane_width as usize * plane_height as usize {
+				data.extend_from_slice(&pixel);
+			}
+
+			let (plane_x, plane_y) = if index == 0 { (x, y) } else { (x >> shift_x, y >> shift_y) };
+			let source_stride = width as usize * element;
+			let canvas_stride = plane_width as usize * element;
+			for row in 0..height as usize {
+				let source = &video.data[offset + row * source_stride..offset + (row + 1) * source_stride];
+				let target = start + (plane_y as usize + row) * canvas_stride + plane_x as usize * element;
+				data[target..target + source_stride].copy_from_slice(source);
+			}
+			offset += source_stride * height as usize;
+		}
+		Ok(FrameVideo::new(data, canvas_width, canvas_height, format, video.keyframe))
+	}
+}
+
+impl Transform for Pad {
+	fn apply(&mut self, mut frame: Frame) -> Result<Frame> {
+		if let Some(video) = frame.video_mut() {
+			*video = self.pad(video)?;
+		}
+		Ok(frame)
+	}
+
+	fn name(&self) -> &'static str {
+		"pad"
+	}
+}
@@ -0,0 +1,105 @@
+/// Source samples and weights contributing to each destination sample along
+/// one axis. Taps falling outside the source are clamped to the edge.
+pub struct Taps {
+	taps: Vec<Vec<(usize, f32)>>,
+}
+
+impl Taps {
+	/// `center` maps a destination index to a source coordinate, `radius` is
+	/// the kernel support in source samples and `kernel` weighs a distance
+	/// already divided by `scale`.
+	pub fn new<C, K>(
+		src_len: usize,
+		dst_len: usize,
+		center: C,
+		radius: f32,
+		scale: f32,
+		kernel: K,
+	) -> Self
+	where
+		C: Fn(usize) -> f32,
+		K: Fn(f32) -> f32,
+	{
+		let last = src_len as isize - 1;
+		let taps = (0..dst_len)
+			.map(|index| {
+				let center = center(index);
+				let start = (center - radius).floor() as isize;
+				let end = (center + radius).ceil() as isize;
+				let mut taps: Vec<(usize, f32)> = Vec::new();
+				let mut total = 0.0;
+				for position in start..=end {
+					let weight = kernel((position as f32 - center) / scale);
+					if weight == 0.0 {
+						continue;
+					}
+					total += weight;
+					let source = position.clamp(0, last) as usize;
+					match taps.iter_mut().find(|(existing, _)| *existing == source) {
+						Some((_, existing)) => *existing += weight,
+						None => taps.push((source, weight)),
+					}
+				}
+				if total == 0.0 {
+					return vec![(center.round().clamp(0.0, last as f32) as usize, 1.0)];
+				}
+				taps.iter().map(|&(source, weight)| (source, weight / total)).collect()
+			})
+			.collect();
+		Self { taps }
+	}
+
+	/// Linear interpolation with the destination sample `index` found at
+	/// `index * step + offset` in source coordinates.
+	pub fn linear(src_len: usize, dst_len: usize, step: f32, offset: f32) -> Self {
+		Self::new(src_len, dst_len, |index| index as f32 * step + offset, 1.0, 1.0, triangle)
+	}
+
+	pub fn len(&self) -> usize {
+		self.taps.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.taps.is_empty()
+	}
+
+	fn get(&self, index: usize) -> &[(usize, f32)] {
+		&self.taps[index]
+	}
+}
+
+pub fn triangle(distance: f32) -> f32 {
+	(1.0 - distance.abs()).max(0.0)
+}
+
+pub fn resample(
+	plane: &[f32],
+	width: usize,
+	height: usize,
+	horizontal: &Taps,
+	vertical: &Taps,
+) -> Vec<f32> {
+	let dst_width = horizontal.len();
+	let dst_height = vertical.len();
+
+	let mut rows = vec![0.0; dst_width * height];
+	for y in 0..height {
+		let src = &plane[y * width..(y + 1) * width];
+		let dst = &mut rows[y * dst_width..(y + 1) * dst_width];
+		for (x, sample) in dst.iter_mut().enumerate() {
+			*sample = horizontal.get(x).iter().map(|&(source, weight)| src[source] * weight).sum();
+		}
+	}
+
+	let mut output = vec![0.0; dst_width * dst_height];
+	for y in 0..dst_height {
+		let dst = &mut output[y * dst_width..(y + 1) * dst_width];
+		for &(source, weight) in vertical.get(y) {
+			let src = &rows[source * dst_width..(source + 1) * dst_width];
+			for (sample, value) in dst.iter_mut().zip(src) {
+				*sample += value * weight;
+			}
+		}
+	}
+	output
+}
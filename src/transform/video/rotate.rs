@@ -0,0 +1,261 @@
+use super::color::ColorSpace;
+use super::scale::{ScaleKernel, Scaler};
+use crate::core::Transform;
+use crate::core::frame::{Frame, FrameVideo, VideoFormat};
+use crate::{error, message::Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+	/// 90 degrees clockwise.
+	Rotate90,
+	Rotate180,
+	/// 90 degrees counter-clockwise.
+	Rotate270,
+	FlipHorizontal,
+	FlipVertical,
+	/// Mirror along the top-left to bottom-right diagonal.
+	Transpose,
+}
+
+impl Orientation {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name.to_lowercase().as_str() {
+			"90" | "cw" | "clock" => Some(Self::Rotate90),
+			"180" | "-180" => Some(Self::Rotate180),
+			"270" | "-90" | "ccw" | "cclock" => Some(Self::Rotate270),
+			"hflip" => Some(Self::FlipHorizontal),
+			"vflip" => Some(Self::FlipVertical),
+			"transpose" => Some(Self::Transpose),
+			_ => None,
+		}
+	}
+
+	pub fn swaps_dimensions(&self) -> bool {
+		matches!(self, Self::Rotate90 | Self::Rotate270 | Self::Transpose)
+	}
+
+	fn source(&self, x: usize, y: usize, width: usize, height: usize) -> (usize, usize) {
+		match self {
+			Self::Rotate90 => (y, height - 1 - x),
+			Self::Rotate180 => (width - 1 - x, height - 1 - y),
+			Self::Rotate270 => (width - 1 - y, x),
+			Self::FlipHorizontal => (width - 1 - x, y),
+			Self::FlipVertical => (x, height - 1 - y),
+			Self::Transpose => (y, x),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RotateKind {
+	Orientation(Orientation),
+	/// Clockwise degrees, not a multiple of 90.
+	Angle(f64),
+}
+
+/// Rotates or mirrors video frames. Right angles and flips move samples
+/// exactly; other angles resample bilinearly into a frame large enough for
+/// the whole picture, painting the uncovered corners with the fill colour.
+pub struct Rotate {
+	kind: RotateKind,
+	fill: [u8; 3],
+	color: ColorSpace,
+}
+
+impl Rotate {
+	pub fn new(orientation: Orientation) -> Self {
+		Self {
+			kind: RotateKind::Orientation(orientation),
+			fill: [0, 0, 0],
+			color: ColorSpace::default(),
+		}
+	}
+
+	/// Clockwise rotation by `degrees`; multiples of 90 stay lossless.
+	pub fn angle(degrees: f64) -> Self {
+		let normalized = degrees.rem_euclid(360.0);
+		let kind = match normalized {
+			90.0 => RotateKind::Orientation(Orientation::Rotate90),
+			180.0 => RotateKind::Orientation(Orientation::Rotate180),
+			270.0 => RotateKind::Orientation(Orientation::Rotate270),
+			_ => RotateKind::Angle(normalized),
+		};
+		Self { kind, fill: [0, 0, 0], color: ColorSpace::default() }
+	}
+
+	pub fn with_fill(mut self, fill: [u8; 3]) -> Self {
+		self.fill = fill;
+		self
+	}
+
+	pub fn with_color(mut self, color: ColorSpace) -> Self {
+		self.color = color;
+		self
+	}
+
+	pub fn output_size(&self, width: u32, height: u32, format: VideoFormat) -> (u32, u32) {
+		match self.kind {
+			RotateKind::Orientation(orientation) if orientation.swaps_dimensions() => (height, width),
+			RotateKind::Orientation(_) => (width, height),
+			RotateKind::Angle(0.0) => (width, height),
+			RotateKind::Angle(degrees) => {
+				let (sin, cos) = degrees.to_radians().sin_cos();
+				let (width, height) = (width as f64, height as f64);
+				// trim float noise so exact fits do not grow by a pixel
+				let bound = |size: f64| (size - 1e-6).ceil().max(1.0) as u32;
+				let out_width = bound(width * cos.abs() + height * sin.abs());
+				let out_height = bound(width * sin.abs() + height * cos.abs());
+				let (shift_x, shift_y) = format.chroma_shift();
+				(out_width.next_multiple_of(1 << shift_x), out_height.next_multiple_of(1 << shift_y))
+			}
+		}
+	}
+
+	pub fn rotate(&self, video: &FrameVideo) -> Result<FrameVideo> {
+		if !video.is_valid() {
+			let (expected, actual) = (video.expected_size(), video.data.len());
+			return Err(error!("video frame has {} bytes, expected {}", actual, expected));
+		}
+		match self.kind {
+			RotateKind::Orientation(orientation) => reorient(video, orientation),
+			RotateKind::Angle(0.0) => Ok(video.clone()),
+			RotateKind::Angle(degrees) => Ok(self.rotate_angle(video, degrees)),
+		}
+	}
+
+	fn rotate_angle(&self, video: &FrameVideo, degrees: f64) -> FrameVideo {
+		let format = video.format;
+		let (out_width, out_height) = self.output_size(video.width, video.height, format);
+		let (sin, cos) = degrees.to_radians().sin_cos();
+		let (channels, bytes) = sample_layout(format);
+		let fill = self.color.fill_codes(format, self.fill);
+		let max = ((1u64 << format.bit_depth()) - 1) as f64;
+
+		let mut data = Vec::with_capacity(format.expected_size(out_width, out_height));
+		let mut offset = 0;
+		let source_planes = format.planes(video.width, video.height);
+		let output_planes = format.planes(out_width, out_height);
+		for (index, (&(width, height), &(plane_out_width, plane_out_height))) in
+			source_planes.iter().zip(&output_planes).enumerate()
+		{
+			let (width, height) = (width as usize, height as usize);
+			let size = width * height * channels * bytes;
+			let plane = &video.data[offset..offset + size];
+			offset += size;
+
+			let ratio_x = width as f64 / video.width as f64;
+			let ratio_y = height as f64 / video.height as f64;
+			let out_ratio_x = plane_out_width as f64 / out_width as f64;
+			let out_ratio_y = plane_out_height as f64 / out_height as f64;
+			let sample = |x: usize, y: usize, channel: usize| -> f64 {
+				let position = ((y * width + x) * channels + channel) * bytes;
+				if bytes == 2 {
+					u16::from_le_bytes([plane[position], plane[position + 1]]) as f64
+				} else {
+					plane[position] as f64
+				}
+			};
+
+			for row in 0..plane_out_height as usize {
+				for column in 0..plane_out_width as usize {
+					// output position relative to the centre, in luma samples
+					let x = (column as f64 + 0.5) / out_ratio_x - out_width as f64 / 2.0;
+					let y = (row as f64 + 0.5) / out_ratio_y - out_height as f64 / 2.0;
+					let source_x = (x * cos + y * sin + video.width as f64 / 2.0) * ratio_x - 0.5;
+					let source_y = (y * cos - x * sin + video.height as f64 / 2.0) * ratio_y - 0.5;
+					let inside = source_x >= -0.5
+						&& source_y >= -0.5
+						&& source_x <= width as f64 - 0.5
+						&& source_y <= height as f64 - 0.5;
+
+					for channel in 0..channels {
+						let value = if inside {
+							let x0 = source_x.floor().clamp(0.0, (width - 1) as f64) as usize;
+							let y0 = source_y.floor().clamp(0.0, (height - 1) as f64) as usize;
+							let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+							let fx = (source_x - x0 as f64).clamp(0.0, 1.0);
+							let fy = (source_y - y0 as f64).clamp(0.0, 1.0);
+							let top = sample(x0, y0, channel) * (1.0 - fx) + sample(x1, y0, channel) * fx;
+							let bottom = sample(x0, y1, channel) * (1.0 - fx) + sample(x1, y1, channel) * fx;
+							(top * (1.0 - fy) + bottom * fy).round().clamp(0.0, max) as u16
+						} else {
+							fill[if channels > 1 { channel } else { index }]
+						};
+						if bytes == 2 {
+							data.extend_from_slice(&value.to_le_bytes());
+						} else {
+							data.push(value as u8);
+						}
+					}
+				}
+			}
+		}
+		FrameVideo::new(data, out_width, out_height, format, video.keyframe)
+	}
+}
+
+impl Transform for Rotate {
+	fn apply(&mut self, mut frame: Frame) -> Result<Frame> {
+		if let Some(video) = frame.video_mut() {
+			*video = self.rotate(video)?;
+		}
+		Ok(frame)
+	}
+
+	fn name(&self) -> &'static str {
+		"rotate"
+	}
+}
+
+pub(super) fn sample_layout(format: VideoFormat) -> (usize, usize) {
+	match format {
+		VideoFormat::RGB24 => (3, 1),
+		VideoFormat::RGBA32 => (4, 1),
+		_ => (1, format.bytes_per_component()),
+	}
+}
+
+fn reorient(video: &FrameVideo, orientation: Orientation) -> Result<FrameVideo> {
+	let format = video.format;
+	let (out_width, out_height) = if orientation.swaps_dimensions() {
+		(video.height, video.width)
+	} else {
+		(video.width, video.height)
+	};
+	let (channels, bytes) = sample_layout(format);
+	let element = channels * bytes;
+
+	let mut data = Vec::with_capacity(format.expected_size(out_width, out_height));
+	let mut offset = 0;
+	let output_planes = format.planes(out_width, out_height);
+	for ((width, height), (plane_out_width, plane_out_height)) in
+		format.planes(video.width, video.height).into_iter().zip(output_planes)
+	{
+		let (width, height) = (width as usize, height as usize);
+		let plane = &video.data[offset..offset + width * height * element];
+		offset += width * height * element;
+
+		let (moved_width, moved_height) =
+			if orientation.swaps_dimensions() { (height, width) } else { (width, height) };
+		let mut moved = Vec::with_capacity(plane.len());
+		for y in 0..moved_height {
+			for x in 0..moved_width {
+				let (source_x, source_y) = orientation.source(x, y, width, height);
+				let position = (source_y * width + source_x) * element;
+				moved.extend_from_slice(&plane[position..position + element]);
+			}
+		}
+
+		// 4:2:2 chroma turned sideways is subsampled vertically instead,
+		// so bring it back to the layout of the format
+		if (moved_width as u32, moved_height as u32) != (plane_out_width, plane_out_height) {
+			let gray = if bytes == 2 { VideoFormat::GRAY16 } else { VideoFormat::GRAY8 };
+			let plane = FrameVideo::new(moved, moved_width as u32, moved_height as u32, gray, true);
+			let scaler = Scaler::new(Some(plane_out_width), Some(plane_out_height))
+				.with_kernel(ScaleKernel::Bilinear);
+			moved = scaler.scale(&plane)?.data;
+		}
+		data.extend_from_slice(&moved);
+	}
+	Ok(FrameVideo::new(data, out_width, out_height, format, video.keyframe))
+}
@@ -0,0 +1,283 @@
+use super::resample::{self, Taps};
+use crate::core::Transform;
+use crate::core::frame::{Frame, FrameVideo, VideoFormat};
+use crate::{error, message::Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScaleKernel {
+	Nearest,
+	Bilinear,
+	#[default]
+	Bicubic,
+	Lanczos,
+}
+
+impl ScaleKernel {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name.to_lowercase().as_str() {
+			"nearest" | "point" => Some(Self::Nearest),
+			"bilinear" | "linear" => Some(Self::Bilinear),
+			"bicubic" | "cubic" => Some(Self::Bicubic),
+			"lanczos" => Some(Self::Lanczos),
+			_ => None,
+		}
+	}
+
+	/// Support in source samples when not downscaling.
+	pub fn radius(&self) -> f32 {
+		match self {
+			Self::Nearest => 0.5,
+			Self::Bilinear => 1.0,
+			Self::Bicubic => 2.0,
+			Self::Lanczos => 3.0,
+		}
+	}
+
+	pub fn weight(&self, distance: f32) -> f32 {
+		let x = distance.abs();
+		match self {
+			Self::Nearest => {
+				if (-0.5..0.5).contains(&distance) {
+					1.0
+				} else {
+					0.0
+				}
+			}
+			Self::Bilinear => resample::triangle(distance),
+			// Keys cubic with a = -0.5
+			Self::Bicubic => {
+				if x < 1.0 {
+					(1.5 * x - 2.5) * x * x + 1.0
+				} else if x < 2.0 {
+					((-0.5 * x + 2.5) * x - 4.0) * x + 2.0
+				} else {
+					0.0
+				}
+			}
+			Self::Lanczos => {
+				if x == 0.0 {
+					1.0
+				} else if x < 3.0 {
+					let pi_x = std::f32::consts::PI * x;
+					3.0 * pi_x.sin() * (pi_x / 3.0).sin() / (pi_x * pi_x)
+				} else {
+					0.0
+				}
+			}
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScaleMode {
+	/// Exactly the requested size, ignoring the aspect ratio.
+	#[default]
+	Stretch,
+	/// Largest size inside the requested box keeping the aspect ratio.
+	Fit,
+	/// Exactly the requested size keeping the aspect ratio, cropping the
+	/// source edges that do not fit.
+	Fill,
+}
+
+impl ScaleMode {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name.to_lowercase().as_str() {
+			"stretch" => Some(Self::Stretch),
+			"fit" | "contain" => Some(Self::Fit),
+			"fill" | "cover" => Some(Self::Fill),
+			_ => None,
+		}
+	}
+}
+
+/// Resizes video frames plane by plane. A missing dimension follows the
+/// aspect ratio; sizes are rounded to the chroma subsampling of the format.
+pub struct Scaler {
+	width: Option<u32>,
+	height: Option<u32>,
+	kernel: ScaleKernel,
+	mode: ScaleMode,
+	aspect_ratio: Option<f64>,
+}
+
+impl Scaler {
+	pub fn new(width: Option<u32>, height: Option<u32>) -> Self {
+		Self {
+			width,
+			height,
+			kernel: ScaleKernel::default(),
+			mode: ScaleMode::default(),
+			aspect_ratio: None,
+		}
+	}
+
+	pub fn with_kernel(mut self, kernel: ScaleKernel) -> Self {
+		self.kernel = kernel;
+		self
+	}
+
+	pub fn with_mode(mut self, mode: ScaleMode) -> Self {
+		self.mode = mode;
+		self
+	}
+
+	/// Display aspect ratio used instead of the source one to derive a
+	/// missing dimension or the fit box.
+	pub fn with_aspect_ratio(mut self, aspect_ratio: f64) -> Self {
+		self.aspect_ratio = Some(aspect_ratio);
+		self
+	}
+
+	pub fn output_size(&self, width: u32, height: u32, format: VideoFormat) -> (u32, u32) {
+		let aspect = self.aspect_ratio.unwrap_or(width as f64 / height as f64);
+		let (out_width, out_height) = match (self.width, self.height) {
+			(Some(box_width), Some(box_height)) => match self.mode {
+				ScaleMode::Fit if (box_width as f64 / box_height as f64) > aspect => {
+					((box_height as f64 * aspect).round() as u32, box_height)
+				}
+				ScaleMode::Fit => (box_width, (box_width as f64 / aspect).round() as u32),
+				ScaleMode::Stretch | ScaleMode::Fill => (box_width, box_height),
+			},
+			(Some(box_width), None) => (box_width, (box_width as f64 / aspect).round() as u32),
+			(None, Some(box_height)) => ((box_height as f64 * aspect).round() as u32, box_height),
+			(None, None) => (width, height),
+		};
+		let (shift_x, shift_y) = format.chroma_shift();
+		(round_to_multiple(out_width, 1 << shift_x), round_to_multiple(out_height, 1 << shift_y))
+	}
+
+	/// Source rectangle (x, y, width, height) mapped onto the output.
+	fn source_region(
+		&self,
+		width: u32,
+		height: u32,
+		out_width: u32,
+		out_height: u32,
+	) -> (f64, f64, f64, f64) {
+		let (width, height) = (width as f64, height as f64);
+		if self.mode != ScaleMode::Fill {
+			return (0.0, 0.0, width, height);
+		}
+		let target_aspect = out_width as f64 / out_height as f64;
+		if width / height > target_aspect {
+			let crop_width = height * target_aspect;
+			((width - crop_width) / 2.0, 0.0, crop_width, height)
+		} else {
+			let crop_height = width / target_aspect;
+			(0.0, (height - crop_height) / 2.0, width, crop_height)
+		}
+	}
+
+	pub fn scale(&self, video: &FrameVideo) -> Result<FrameVideo> {
+		if !video.is_valid() {
+			let (expected, actual) = (video.expected_size(), video.data.len());
+			return Err(error!("video frame has {} bytes, expected {}", actual, expected));
+		}
+		let format = video.format;
+		let (out_width, out_height) = self.output_size(video.width, video.height, format);
+		if out_width == 0 || out_height == 0 {
+			return Err(error!("cannot scale to {}x{}", out_width, out_height));
+		}
+		let region = self.source_region(video.width, video.height, out_width, out_height);
+		if (out_width, out_height) == (video.width, video.height) && region.2 == video.width as f64 {
+			return Ok(video.clone());
+		}
+
+		let max = ((1u64 << format.bit_depth()) - 1) as f32;
+		let data = if format.is_rgb() {
+			let channels = if format == VideoFormat::RGBA32 { 4 } else { 3 };
+			let (width, height) = (video.width as usize, video.height as usize);
+			let planes: Vec<Vec<f32>> = (0..channels)
+				.map(|channel| {
+					video.data.iter().skip(channel).step_by(channels).map(|&value| value as f32).collect()
+				})
+				.collect();
+			let horizontal = self.taps(width, out_width as usize, region.0, region.2);
+			let vertical = self.taps(height, out_height as usize, region.1, region.3);
+			let scaled: Vec<Vec<f32>> = planes
+				.iter()
+				.map(|plane| resample::resample(plane, width, height, &horizontal, &vertical))
+				.collect();
+			let count = out_width as usize * out_height as usize;
+			let mut data = Vec::with_capacity(count * channels);
+			for index in 0..count {
+				for plane in &scaled {
+					data.push(plane[index].round().clamp(0.0, max) as u8);
+				}
+			}
+			data
+		} else {
+			let bytes = format.bytes_per_component();
+			let source_planes = format.planes(video.width, video.height);
+			let output_planes = format.planes(out_width, out_height);
+			let mut data = Vec::with_capacity(format.expected_size(out_width, out_height));
+			let mut offset = 0;
+			for (&(width, height), &(plane_out_width, plane_out_height)) in
+				source_planes.iter().zip(&output_planes)
+			{
+				let (width, height) = (width as usize, height as usize);
+				let size = width * height * bytes;
+				let plane: Vec<f32> = video.data[offset..offset + size]
+					.chunks_exact(bytes)
+					.map(|sample| {
+						if bytes == 2 {
+							u16::from_le_bytes([sample[0], sample[1]]) as f32
+						} else {
+							sample[0] as f32
+						}
+					})
+					.collect();
+				offset += size;
+
+				let ratio_x = width as f64 / video.width as f64;
+				let ratio_y = height as f64 / video.height as f64;
+				let horizontal =
+					self.taps(width, plane_out_width as usize, region.0 * ratio_x, region.2 * ratio_x);
+				let vertical =
+					self.taps(height, plane_out_height as usize, region.1 * ratio_y, region.3 * ratio_y);
+				for sample in resample::resample(&plane, width, height, &horizontal, &vertical) {
+					let code = sample.round().clamp(0.0, max) as u16;
+					if bytes == 2 {
+						data.extend_from_slice(&code.to_le_bytes());
+					} else {
+						data.push(code as u8);
+					}
+				}
+			}
+			data
+		};
+
+		Ok(FrameVideo::new(data, out_width, out_height, format, video.keyframe))
+	}
+
+	fn taps(&self, src_len: usize, dst_len: usize, start: f64, length: f64) -> Taps {
+		let step = (length / dst_len as f64) as f32;
+		let start = start as f32;
+		// widen the kernel when shrinking so every source sample contributes
+		let scale = if self.kernel == ScaleKernel::Nearest { 1.0 } else { step.max(1.0) };
+		let center = |index: usize| start + (index as f32 + 0.5) * step - 0.5;
+		let kernel = self.kernel;
+		Taps::new(src_len, dst_len, center, kernel.radius() * scale, scale, |distance| {
+			kernel.weight(distance)
+		})
+	}
+}
+
+impl Transform for Scaler {
+	fn apply(&mut self, mut frame: Frame) -> Result<Frame> {
+		if let Some(video) = frame.video_mut() {
+			*video = self.scale(video)?;
+		}
+		Ok(frame)
+	}
+
+	fn name(&self) -> &'static str {
+		"scale"
+	}
+}
+
+fn round_to_multiple(value: u32, multiple: u32) -> u32 {
+	let rounded = (value + multiple / 2) / multiple * multiple;
+	rounded.max(multiple)
+}
@@ -0,0 +1,27 @@
+use ffmpreg::container::ape::ApeDemuxer;
+use ffmpreg::core::Decoder;
+use ffmpreg::io::File;
+
+// every frame carries a crc of its decoded samples, so decoding without an
+// error means the samples are exact
+fn decode(path: &str) -> Vec<u8> {
+	let mut demuxer = ApeDemuxer::new(File::open(path).unwrap()).unwrap();
+	let mut decoder = demuxer.format().create_decoder().unwrap();
+	let mut samples = Vec::new();
+	while let Some(packet) = demuxer.read_packet().unwrap() {
+		if let Some(frame) = decoder.decode(packet).unwrap() {
+			samples.extend_from_slice(&frame.audio().unwrap().data);
+		}
+	}
+	samples
+}
+
+#[test]
+fn decodes_stereo_3950() {
+	assert_eq!(decode("tests/fixtures/stereo_3950.ape").len(), 1500 * 4);
+}
+
+#[test]
+fn decodes_stereo_3980() {
+	assert_eq!(decode("tests/fixtures/stereo_3980.ape").len(), 1500 * 4);
+}